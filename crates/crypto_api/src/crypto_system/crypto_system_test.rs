@@ -1,7 +1,7 @@
 //! Expose a test suite that can exercise CryptoSystem implementations.
 //! You'll probably also need to write unit tests specific to your impl.
 
-use crate::{Buffer, CryptoError, CryptoSystem};
+use crate::{Buffer, CryptoError, CryptoSystem, SecretStreamTag};
 
 struct FullSuite {
     crypto: Box<dyn CryptoSystem>,
@@ -14,18 +14,24 @@ impl FullSuite {
 
     pub fn run(&self) {
         self.test_sec_buf();
+        self.test_scratch_buf();
         self.test_random();
         self.test_hash();
         self.test_generic_hash();
+        self.test_incremental_hash();
         self.test_pwhash();
         self.test_kdf();
         self.test_sign_keypair_sizes();
         self.test_sign_keypair_generation();
         self.test_sign();
+        self.test_sign_verify_batch();
         self.test_kx_keypair_sizes();
         self.test_kx_keypair_generation();
         self.test_kx();
         self.test_aead();
+        self.test_secretstream();
+        self.test_sealed_box();
+        self.test_sealed_box_to_sign_key();
     }
 
     fn test_sec_buf(&self) {
@@ -52,6 +58,18 @@ impl FullSuite {
         );
     }
 
+    fn test_scratch_buf(&self) {
+        for size in [1, 32, 100, 5000].iter() {
+            let mut b = self.crypto.buf_new_scratch(*size);
+            assert_eq!(*size, b.len());
+            assert!(b.read_lock().iter().all(|x| *x == 0));
+            b.write(0, &vec![42; *size]).unwrap();
+            assert!(b.read_lock().iter().all(|x| *x == 42));
+            let c = b.box_clone();
+            assert_eq!(*b.read_lock(), *c.read_lock());
+        }
+    }
+
     fn test_random(&self) {
         let mut a: Box<dyn Buffer> = Box::new(vec![0; 8]);
         let mut b: Box<dyn Buffer> = Box::new(vec![0; 8]);
@@ -87,6 +105,85 @@ impl FullSuite {
         assert_eq!(*hash1.read_lock(), *hash2.read_lock());
     }
 
+    fn test_incremental_hash(&self) {
+        let mut data: Box<dyn Buffer> = Box::new(vec![0; 300]);
+        self.crypto.randombytes_buf(&mut data).unwrap();
+        // chunks of both secure and insecure buffers
+        let mut chunk1 = self.crypto.buf_new_secure(100);
+        chunk1.write(0, &data.read_lock()[..100]).unwrap();
+        let mut chunk2 = self.crypto.buf_new_insecure(200);
+        chunk2.write(0, &data.read_lock()[100..]).unwrap();
+
+        let mut hash256 = self
+            .crypto
+            .buf_new_insecure(self.crypto.hash_sha256_bytes());
+        self.crypto.hash_sha256(&mut hash256, &data).unwrap();
+        let mut state = self.crypto.hash_sha256_init().unwrap();
+        state.update(&chunk1).unwrap();
+        state.update(&chunk2).unwrap();
+        let mut inc_hash256 = self.crypto.buf_new_secure(self.crypto.hash_sha256_bytes());
+        state.finalize(&mut inc_hash256).unwrap();
+        assert_eq!(*hash256.read_lock(), *inc_hash256.read_lock());
+
+        let mut hash512 = self
+            .crypto
+            .buf_new_insecure(self.crypto.hash_sha512_bytes());
+        self.crypto.hash_sha512(&mut hash512, &data).unwrap();
+        let mut state = self.crypto.hash_sha512_init().unwrap();
+        state.update(&chunk1).unwrap();
+        state.update(&chunk2).unwrap();
+        let mut inc_hash512 = self
+            .crypto
+            .buf_new_insecure(self.crypto.hash_sha512_bytes());
+        state.finalize(&mut inc_hash512).unwrap();
+        assert_eq!(*hash512.read_lock(), *inc_hash512.read_lock());
+
+        let mut key = self
+            .crypto
+            .buf_new_secure(self.crypto.generic_hash_key_max_bytes());
+        self.crypto.randombytes_buf(&mut key).unwrap();
+        for key in [None, Some(&key)].iter().cloned() {
+            let size = self.crypto.generic_hash_max_bytes();
+            let mut hash = self.crypto.buf_new_insecure(size);
+            self.crypto.generic_hash(&mut hash, &data, key).unwrap();
+            let mut state = self.crypto.generic_hash_init(size, key).unwrap();
+            state.update(&chunk1).unwrap();
+            state.update(&chunk2).unwrap();
+            let mut inc_hash = self.crypto.buf_new_insecure(size);
+            state.finalize(&mut inc_hash).unwrap();
+            assert_eq!(*hash.read_lock(), *inc_hash.read_lock());
+        }
+
+        let mut bad_hash = self
+            .crypto
+            .buf_new_insecure(self.crypto.hash_sha256_bytes() + 1);
+        assert_eq!(
+            CryptoError::BadHashSize,
+            self.crypto
+                .hash_sha256_init()
+                .unwrap()
+                .finalize(&mut bad_hash)
+                .unwrap_err()
+        );
+        assert_eq!(
+            CryptoError::BadHashSize,
+            self.crypto
+                .generic_hash_init(self.crypto.generic_hash_max_bytes() + 1, None)
+                .err()
+                .unwrap()
+        );
+        let bad_key = self
+            .crypto
+            .buf_new_insecure(self.crypto.generic_hash_key_max_bytes() + 1);
+        assert_eq!(
+            CryptoError::BadKeySize,
+            self.crypto
+                .generic_hash_init(self.crypto.generic_hash_max_bytes(), Some(&bad_key))
+                .err()
+                .unwrap()
+        );
+    }
+
     fn test_pwhash(&self) {
         let mut pw: Box<dyn Buffer> = Box::new(vec![0; 16]);
         self.crypto.randombytes_buf(&mut pw).unwrap();
@@ -236,6 +333,52 @@ impl FullSuite {
         assert!(!self.crypto.sign_verify(&sig, &msg, &pk).unwrap());
     }
 
+    fn test_sign_verify_batch(&self) {
+        let mut pks = Vec::new();
+        let mut msgs = Vec::new();
+        let mut sigs = Vec::new();
+        for _ in 0..8 {
+            let mut pk = self
+                .crypto
+                .buf_new_secure(self.crypto.sign_public_key_bytes());
+            let mut sk = self
+                .crypto
+                .buf_new_secure(self.crypto.sign_secret_key_bytes());
+            self.crypto.sign_keypair(&mut pk, &mut sk).unwrap();
+            // two messages per key
+            for _ in 0..2 {
+                let mut msg = self.crypto.buf_new_insecure(64);
+                self.crypto.randombytes_buf(&mut msg).unwrap();
+                let mut sig = self.crypto.buf_new_insecure(self.crypto.sign_bytes());
+                self.crypto.sign(&mut sig, &msg, &sk).unwrap();
+                msgs.push(msg);
+                sigs.push(sig);
+            }
+            pks.push(pk);
+        }
+
+        assert!(self.crypto.sign_verify_batch(&[]).is_empty());
+
+        let mut items: Vec<_> = (0..16).map(|i| (&sigs[i], &msgs[i], &pks[i / 2])).collect();
+        let results = self.crypto.sign_verify_batch(&items);
+        assert_eq!(16, results.len());
+        assert!(results.into_iter().all(|r| r.unwrap()));
+
+        // a signature of another message, and one for another key
+        items[3].0 = &sigs[2];
+        items[9].2 = &pks[0];
+        let bad_sig = self.crypto.buf_new_insecure(self.crypto.sign_bytes() + 1);
+        items[12].0 = &bad_sig;
+        let results = self.crypto.sign_verify_batch(&items);
+        for (i, result) in results.into_iter().enumerate() {
+            match i {
+                3 | 9 => assert!(!result.unwrap()),
+                12 => assert_eq!(CryptoError::BadSignatureSize, result.unwrap_err()),
+                _ => assert!(result.unwrap()),
+            }
+        }
+    }
+
     fn test_kx_keypair_sizes(&self) {
         let seed: Box<dyn Buffer> = Box::new(vec![0; self.crypto.kx_seed_bytes() + 1]);
         let mut pk: Box<dyn Buffer> = Box::new(vec![0; self.crypto.kx_public_key_bytes()]);
@@ -389,6 +532,209 @@ impl FullSuite {
 
         assert_eq!(&format!("{:?}", message), &format!("{:?}", msg_out));
     }
+
+    fn test_secretstream(&self) {
+        let mut secret: Box<dyn Buffer> =
+            Box::new(vec![0; self.crypto.secretstream_secret_bytes()]);
+        self.crypto.randombytes_buf(&mut secret).unwrap();
+        let mut adata: Box<dyn Buffer> = Box::new(vec![0; 16]);
+        self.crypto.randombytes_buf(&mut adata).unwrap();
+        let tags = [
+            SecretStreamTag::Message,
+            SecretStreamTag::Push,
+            SecretStreamTag::Rekey,
+            SecretStreamTag::Message,
+            SecretStreamTag::Final,
+        ];
+
+        let mut state = self
+            .crypto
+            .buf_new_secure(self.crypto.secretstream_state_bytes());
+        let mut header: Box<dyn Buffer> =
+            Box::new(vec![0; self.crypto.secretstream_header_bytes()]);
+        self.crypto
+            .secretstream_init_push(&mut state, &mut header, &secret)
+            .unwrap();
+
+        let mut messages = Vec::new();
+        let mut ciphers = Vec::new();
+        for (i, tag) in tags.iter().enumerate() {
+            let mut message: Box<dyn Buffer> = Box::new(vec![0; 16 + i]);
+            self.crypto.randombytes_buf(&mut message).unwrap();
+            let mut cipher: Box<dyn Buffer> =
+                Box::new(vec![
+                    0;
+                    message.len() + self.crypto.secretstream_auth_bytes()
+                ]);
+            let adata = if i == 1 { Some(&adata) } else { None };
+            self.crypto
+                .secretstream_push(&mut state, &mut cipher, &message, adata, *tag)
+                .unwrap();
+            messages.push(message);
+            ciphers.push(cipher);
+        }
+        // the same message is encrypted differently further in the stream
+        let mut cipher: Box<dyn Buffer> = Box::new(vec![
+            0;
+            messages[0].len()
+                + self.crypto.secretstream_auth_bytes()
+        ]);
+        assert_eq!(
+            Err(CryptoError::BadCipherSize),
+            self.crypto.secretstream_push(
+                &mut state,
+                &mut cipher,
+                &messages[1],
+                None,
+                SecretStreamTag::Message
+            )
+        );
+        self.crypto
+            .secretstream_push(
+                &mut state,
+                &mut cipher,
+                &messages[0],
+                None,
+                SecretStreamTag::Message,
+            )
+            .unwrap();
+        assert_ne!(&format!("{:?}", ciphers[0]), &format!("{:?}", cipher));
+
+        let mut state = self
+            .crypto
+            .buf_new_secure(self.crypto.secretstream_state_bytes());
+        self.crypto
+            .secretstream_init_pull(&mut state, &header, &secret)
+            .unwrap();
+        let mut msg_out: Box<dyn Buffer> = Box::new(vec![0; messages[1].len()]);
+        // out of order
+        assert_eq!(
+            Err(CryptoError::CouldNotDecrypt),
+            self.crypto
+                .secretstream_pull(&mut state, &mut msg_out, &ciphers[1], Some(&adata))
+        );
+        for (i, tag) in tags.iter().enumerate() {
+            let mut msg_out: Box<dyn Buffer> = Box::new(vec![0; messages[i].len()]);
+            let adata = if i == 1 { Some(&adata) } else { None };
+            assert_eq!(
+                Ok(*tag),
+                self.crypto
+                    .secretstream_pull(&mut state, &mut msg_out, &ciphers[i], adata)
+            );
+            assert_eq!(&format!("{:?}", messages[i]), &format!("{:?}", msg_out));
+        }
+
+        // another secret can't pull the stream
+        let mut other_secret: Box<dyn Buffer> =
+            Box::new(vec![0; self.crypto.secretstream_secret_bytes()]);
+        self.crypto.randombytes_buf(&mut other_secret).unwrap();
+        self.crypto
+            .secretstream_init_pull(&mut state, &header, &other_secret)
+            .unwrap();
+        let mut msg_out: Box<dyn Buffer> = Box::new(vec![0; messages[0].len()]);
+        assert_eq!(
+            Err(CryptoError::CouldNotDecrypt),
+            self.crypto
+                .secretstream_pull(&mut state, &mut msg_out, &ciphers[0], None)
+        );
+
+        let mut small_state = self.crypto.buf_new_secure(1);
+        assert_eq!(
+            Err(CryptoError::BadStateSize),
+            self.crypto
+                .secretstream_init_pull(&mut small_state, &header, &secret)
+        );
+    }
+
+    fn test_sealed_box(&self) {
+        let mut pk: Box<dyn Buffer> = Box::new(vec![0; self.crypto.kx_public_key_bytes()]);
+        let mut sk = self
+            .crypto
+            .buf_new_secure(self.crypto.kx_secret_key_bytes());
+        self.crypto.kx_keypair(&mut pk, &mut sk).unwrap();
+        let mut other_pk: Box<dyn Buffer> = Box::new(vec![0; self.crypto.kx_public_key_bytes()]);
+        let mut other_sk = self
+            .crypto
+            .buf_new_secure(self.crypto.kx_secret_key_bytes());
+        self.crypto
+            .kx_keypair(&mut other_pk, &mut other_sk)
+            .unwrap();
+
+        let mut message: Box<dyn Buffer> = Box::new(vec![0; 16]);
+        self.crypto.randombytes_buf(&mut message).unwrap();
+        let mut cipher: Box<dyn Buffer> =
+            Box::new(vec![0; 16 + self.crypto.sealed_box_auth_bytes()]);
+        self.crypto
+            .sealed_box_encrypt(&mut cipher, &message, &pk)
+            .unwrap();
+        // sealing is not deterministic
+        let mut cipher2: Box<dyn Buffer> =
+            Box::new(vec![0; 16 + self.crypto.sealed_box_auth_bytes()]);
+        self.crypto
+            .sealed_box_encrypt(&mut cipher2, &message, &pk)
+            .unwrap();
+        assert_ne!(&format!("{:?}", cipher), &format!("{:?}", cipher2));
+
+        let mut msg_out: Box<dyn Buffer> = Box::new(vec![0; 16]);
+        self.crypto
+            .sealed_box_decrypt(&mut msg_out, &cipher, &pk, &sk)
+            .unwrap();
+        assert_eq!(&format!("{:?}", message), &format!("{:?}", msg_out));
+
+        assert_eq!(
+            Err(CryptoError::CouldNotDecrypt),
+            self.crypto
+                .sealed_box_decrypt(&mut msg_out, &cipher, &other_pk, &other_sk)
+        );
+        let mut short_msg_out: Box<dyn Buffer> = Box::new(vec![0; 15]);
+        assert_eq!(
+            Err(CryptoError::BadMessageSize),
+            self.crypto
+                .sealed_box_decrypt(&mut short_msg_out, &cipher, &pk, &sk)
+        );
+    }
+
+    fn test_sealed_box_to_sign_key(&self) {
+        let mut sign_pk: Box<dyn Buffer> = Box::new(vec![0; self.crypto.sign_public_key_bytes()]);
+        let mut sign_sk = self
+            .crypto
+            .buf_new_secure(self.crypto.sign_secret_key_bytes());
+        self.crypto
+            .sign_keypair(&mut sign_pk, &mut sign_sk)
+            .unwrap();
+
+        let mut kx_pk: Box<dyn Buffer> = Box::new(vec![0; self.crypto.kx_public_key_bytes()]);
+        let mut kx_sk = self
+            .crypto
+            .buf_new_secure(self.crypto.kx_secret_key_bytes());
+        self.crypto
+            .sign_to_kx_public_key(&mut kx_pk, &sign_pk)
+            .unwrap();
+        self.crypto
+            .sign_to_kx_secret_key(&mut kx_sk, &sign_sk)
+            .unwrap();
+
+        // the converted public key is enough to seal a box
+        // the converted secret key opens
+        let mut message: Box<dyn Buffer> = Box::new(vec![0; 16]);
+        self.crypto.randombytes_buf(&mut message).unwrap();
+        let mut cipher: Box<dyn Buffer> =
+            Box::new(vec![0; 16 + self.crypto.sealed_box_auth_bytes()]);
+        self.crypto
+            .sealed_box_encrypt(&mut cipher, &message, &kx_pk)
+            .unwrap();
+        let mut msg_out: Box<dyn Buffer> = Box::new(vec![0; 16]);
+        self.crypto
+            .sealed_box_decrypt(&mut msg_out, &cipher, &kx_pk, &kx_sk)
+            .unwrap();
+        assert_eq!(&format!("{:?}", message), &format!("{:?}", msg_out));
+
+        let mut bad_kx_pk: Box<dyn Buffer> = Box::new(vec![0; 8]);
+        assert_eq!(
+            Err(CryptoError::BadPublicKeySize),
+            self.crypto.sign_to_kx_public_key(&mut bad_kx_pk, &sign_pk)
+        );
+    }
 }
 
 /// run a full suite of common CryptoSystem verification functions
@@ -399,7 +745,7 @@ pub fn full_suite(crypto: Box<dyn CryptoSystem>) {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{CryptoResult, ProtectState};
+    use crate::{CryptoResult, HashState, ProtectState};
     use rand::{Rng, SeedableRng};
     use sha2::Digest;
     use std::ops::{Deref, DerefMut};
@@ -487,6 +833,37 @@ mod test {
         }
     }
 
+    /// buffers the input, hashed in `finalize`
+    struct FakeHashState {
+        data: Vec<u8>,
+        hash_size: usize,
+        hash_fn: fn(&[u8]) -> Vec<u8>,
+    }
+
+    impl HashState for FakeHashState {
+        fn update(&mut self, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+            self.data.extend_from_slice(&data.read_lock());
+            Ok(())
+        }
+
+        fn finalize(self: Box<Self>, hash: &mut Box<dyn Buffer>) -> CryptoResult<()> {
+            if hash.len() != self.hash_size {
+                return Err(CryptoError::BadHashSize);
+            }
+
+            hash.write(0, &(self.hash_fn)(&self.data)[..self.hash_size])?;
+            Ok(())
+        }
+    }
+
+    fn fake_sha256(data: &[u8]) -> Vec<u8> {
+        sha2::Sha256::digest(data).to_vec()
+    }
+
+    fn fake_sha512(data: &[u8]) -> Vec<u8> {
+        sha2::Sha512::digest(data).to_vec()
+    }
+
     struct FakeCryptoSystem {
         seed: [u8; 32],
         rng: std::sync::RwLock<rand::rngs::StdRng>,
@@ -555,6 +932,22 @@ mod test {
             Ok(())
         }
 
+        fn hash_sha256_init(&self) -> CryptoResult<Box<dyn HashState>> {
+            Ok(Box::new(FakeHashState {
+                data: Vec::new(),
+                hash_size: self.hash_sha256_bytes(),
+                hash_fn: fake_sha256,
+            }))
+        }
+
+        fn hash_sha512_init(&self) -> CryptoResult<Box<dyn HashState>> {
+            Ok(Box::new(FakeHashState {
+                data: Vec::new(),
+                hash_size: self.hash_sha512_bytes(),
+                hash_fn: fake_sha512,
+            }))
+        }
+
         fn generic_hash_min_bytes(&self) -> usize {
             8
         }
@@ -596,6 +989,31 @@ mod test {
             Ok(())
         }
 
+        fn generic_hash_init(
+            &self,
+            hash_size: usize,
+            key: Option<&Box<dyn Buffer>>,
+        ) -> CryptoResult<Box<dyn HashState>> {
+            if hash_size < self.generic_hash_min_bytes()
+                || hash_size > self.generic_hash_max_bytes()
+            {
+                return Err(CryptoError::BadHashSize);
+            }
+
+            if key.is_some()
+                && (key.unwrap().len() < self.generic_hash_key_min_bytes()
+                    || key.unwrap().len() > self.generic_hash_key_max_bytes())
+            {
+                return Err(CryptoError::BadKeySize);
+            }
+
+            Ok(Box::new(FakeHashState {
+                data: Vec::new(),
+                hash_size,
+                hash_fn: fake_sha512,
+            }))
+        }
+
         fn pwhash_salt_bytes(&self) -> usize {
             8
         }
@@ -903,6 +1321,43 @@ mod test {
             Ok(())
         }
 
+        fn sign_to_kx_public_key(
+            &self,
+            kx_public_key: &mut Box<dyn Buffer>,
+            sign_public_key: &Box<dyn Buffer>,
+        ) -> CryptoResult<()> {
+            if kx_public_key.len() != self.kx_public_key_bytes() {
+                return Err(CryptoError::BadPublicKeySize);
+            }
+
+            if sign_public_key.len() != self.sign_public_key_bytes() {
+                return Err(CryptoError::BadPublicKeySize);
+            }
+
+            // our sign and kx keys have the same layout
+            kx_public_key.write(0, &sign_public_key.read_lock())?;
+
+            Ok(())
+        }
+
+        fn sign_to_kx_secret_key(
+            &self,
+            kx_secret_key: &mut Box<dyn Buffer>,
+            sign_secret_key: &Box<dyn Buffer>,
+        ) -> CryptoResult<()> {
+            if kx_secret_key.len() != self.kx_secret_key_bytes() {
+                return Err(CryptoError::BadSecretKeySize);
+            }
+
+            if sign_secret_key.len() != self.sign_secret_key_bytes() {
+                return Err(CryptoError::BadSecretKeySize);
+            }
+
+            kx_secret_key.write(0, &sign_secret_key.read_lock())?;
+
+            Ok(())
+        }
+
         fn aead_nonce_bytes(&self) -> usize {
             8
         }
@@ -1004,5 +1459,212 @@ mod test {
 
             Ok(())
         }
+
+        fn secretstream_header_bytes(&self) -> usize {
+            8
+        }
+
+        fn secretstream_auth_bytes(&self) -> usize {
+            8
+        }
+
+        fn secretstream_secret_bytes(&self) -> usize {
+            8
+        }
+
+        fn secretstream_state_bytes(&self) -> usize {
+            8
+        }
+
+        fn secretstream_init_push(
+            &self,
+            state: &mut Box<dyn Buffer>,
+            header: &mut Box<dyn Buffer>,
+            secret: &Box<dyn Buffer>,
+        ) -> CryptoResult<()> {
+            if header.len() != self.secretstream_header_bytes() {
+                return Err(CryptoError::BadHeaderSize);
+            }
+
+            self.randombytes_buf(header)?;
+            self.secretstream_init_pull(state, header, secret)
+        }
+
+        fn secretstream_push(
+            &self,
+            state: &mut Box<dyn Buffer>,
+            cipher: &mut Box<dyn Buffer>,
+            message: &Box<dyn Buffer>,
+            adata: Option<&Box<dyn Buffer>>,
+            tag: SecretStreamTag,
+        ) -> CryptoResult<()> {
+            if state.len() != self.secretstream_state_bytes() {
+                return Err(CryptoError::BadStateSize);
+            }
+
+            if cipher.len() != message.len() + self.secretstream_auth_bytes() {
+                return Err(CryptoError::BadCipherSize);
+            }
+
+            // like aead_encrypt, but we also store the tag and
+            // the position of the message in the stream:
+            // the state holds two bytes of the secret and of the header,
+            // then a message counter
+            cipher.zero();
+            cipher.write(0, &[tag as u8])?;
+            cipher.write(1, &[state.read_lock()[4]])?;
+            cipher.write(2, &state.read_lock()[2..4])?;
+            cipher.write(4, &state.read_lock()[..2])?;
+            if let Some(adata) = adata {
+                cipher.write(6, &adata.read_lock()[..2])?;
+            }
+            cipher.write(8, &message.read_lock())?;
+
+            let counter = state.read_lock()[4].wrapping_add(1);
+            state.write(4, &[counter])?;
+
+            Ok(())
+        }
+
+        fn secretstream_init_pull(
+            &self,
+            state: &mut Box<dyn Buffer>,
+            header: &Box<dyn Buffer>,
+            secret: &Box<dyn Buffer>,
+        ) -> CryptoResult<()> {
+            if state.len() != self.secretstream_state_bytes() {
+                return Err(CryptoError::BadStateSize);
+            }
+
+            if header.len() != self.secretstream_header_bytes() {
+                return Err(CryptoError::BadHeaderSize);
+            }
+
+            if secret.len() != self.secretstream_secret_bytes() {
+                return Err(CryptoError::BadSecretKeySize);
+            }
+
+            state.zero();
+            state.write(0, &secret.read_lock()[..2])?;
+            state.write(2, &header.read_lock()[..2])?;
+
+            Ok(())
+        }
+
+        fn secretstream_pull(
+            &self,
+            state: &mut Box<dyn Buffer>,
+            message: &mut Box<dyn Buffer>,
+            cipher: &Box<dyn Buffer>,
+            adata: Option<&Box<dyn Buffer>>,
+        ) -> CryptoResult<SecretStreamTag> {
+            if state.len() != self.secretstream_state_bytes() {
+                return Err(CryptoError::BadStateSize);
+            }
+
+            if cipher.len() < self.secretstream_auth_bytes()
+                || message.len() != cipher.len() - self.secretstream_auth_bytes()
+            {
+                return Err(CryptoError::BadMessageSize);
+            }
+
+            let cipher = cipher.read_lock();
+
+            // check that this "cipher" is the next one of this stream
+            if cipher[1] != state.read_lock()[4]
+                || &cipher[2..4] != &state.read_lock()[2..4]
+                || &cipher[4..6] != &state.read_lock()[..2]
+            {
+                return Err(CryptoError::CouldNotDecrypt);
+            }
+
+            let adata_bytes = match adata {
+                Some(adata) => adata.read_lock()[..2].to_vec(),
+                None => vec![0, 0],
+            };
+            if &cipher[6..8] != &adata_bytes[..] {
+                return Err(CryptoError::CouldNotDecrypt);
+            }
+
+            let tag = match cipher[0] {
+                0 => SecretStreamTag::Message,
+                1 => SecretStreamTag::Push,
+                2 => SecretStreamTag::Rekey,
+                3 => SecretStreamTag::Final,
+                _ => return Err(CryptoError::CouldNotDecrypt),
+            };
+
+            message.write(0, &cipher[8..])?;
+
+            let counter = state.read_lock()[4].wrapping_add(1);
+            state.write(4, &[counter])?;
+
+            Ok(tag)
+        }
+
+        fn sealed_box_auth_bytes(&self) -> usize {
+            8
+        }
+
+        fn sealed_box_encrypt(
+            &self,
+            cipher: &mut Box<dyn Buffer>,
+            message: &Box<dyn Buffer>,
+            public_key: &Box<dyn Buffer>,
+        ) -> CryptoResult<()> {
+            if cipher.len() != message.len() + self.sealed_box_auth_bytes() {
+                return Err(CryptoError::BadCipherSize);
+            }
+
+            if public_key.len() != self.kx_public_key_bytes() {
+                return Err(CryptoError::BadPublicKeySize);
+            }
+
+            // two random bytes stand for the ephemeral key,
+            // then two bytes of the recipient's public key
+            let mut ephemeral: Box<dyn Buffer> = Box::new(vec![0; 2]);
+            self.randombytes_buf(&mut ephemeral)?;
+            cipher.zero();
+            cipher.write(0, &ephemeral.read_lock())?;
+            cipher.write(2, &public_key.read_lock()[..2])?;
+            cipher.write(8, &message.read_lock())?;
+
+            Ok(())
+        }
+
+        fn sealed_box_decrypt(
+            &self,
+            message: &mut Box<dyn Buffer>,
+            cipher: &Box<dyn Buffer>,
+            public_key: &Box<dyn Buffer>,
+            secret_key: &Box<dyn Buffer>,
+        ) -> CryptoResult<()> {
+            if cipher.len() < self.sealed_box_auth_bytes()
+                || message.len() != cipher.len() - self.sealed_box_auth_bytes()
+            {
+                return Err(CryptoError::BadMessageSize);
+            }
+
+            if public_key.len() != self.kx_public_key_bytes() {
+                return Err(CryptoError::BadPublicKeySize);
+            }
+
+            if secret_key.len() != self.kx_secret_key_bytes() {
+                return Err(CryptoError::BadSecretKeySize);
+            }
+
+            let cipher = cipher.read_lock();
+
+            // check that this "cipher" was sealed to our keypair
+            if &cipher[2..4] != &public_key.read_lock()[..2]
+                || &public_key.read_lock()[..2] != &secret_key.read_lock()[..2]
+            {
+                return Err(CryptoError::CouldNotDecrypt);
+            }
+
+            message.write(0, &cipher[8..])?;
+
+            Ok(())
+        }
     }
 }
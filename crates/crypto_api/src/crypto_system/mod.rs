@@ -88,6 +88,32 @@ macro_rules! aead_decrypt {
     };
 }
 
+/// Tag attached to each message of a secretstream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SecretStreamTag {
+    /// most common tag, nothing special about this message
+    Message,
+    /// marks the end of a set of messages, not the end of the stream
+    Push,
+    /// the state is rekeyed after this message, forgetting the old key
+    Rekey,
+    /// last message of the stream, the state is rekeyed as well
+    Final,
+}
+
+/// State of an incremental hash, created by the CryptoSystem
+/// `hash_sha256_init`, `hash_sha512_init` and `generic_hash_init` methods,
+/// so large data can be hashed one chunk at a time.
+#[allow(clippy::borrowed_box)]
+pub trait HashState: Send {
+    /// add `data` to the hashed input
+    fn update(&mut self, data: &Box<dyn Buffer>) -> CryptoResult<()>;
+
+    /// store the hash of all the input in `hash`, consuming the state
+    /// `hash` must be the size the state was initialized for
+    fn finalize(self: Box<Self>, hash: &mut Box<dyn Buffer>) -> CryptoResult<()>;
+}
+
 /// A trait describing a cryptographic system implementation compatible
 /// with Lib3h and Holochain.
 #[allow(clippy::borrowed_box)]
@@ -109,6 +135,13 @@ pub trait CryptoSystem: Sync {
         Box::new(vec![0; size])
     }
 
+    /// create a memory secured buffer for short-lived scratch data,
+    /// implementations may skip the memory protection changes
+    /// of its lockers, or take it from a pool
+    fn buf_new_scratch(&self, size: usize) -> Box<dyn Buffer> {
+        self.buf_new_secure(size)
+    }
+
     // -- random methods -- //
 
     /// fill all the bytes in the buffer with secure random data
@@ -128,6 +161,12 @@ pub trait CryptoSystem: Sync {
     /// compute a sha512 hash for `data`, storing it in `hash`
     fn hash_sha512(&self, hash: &mut Box<dyn Buffer>, data: &Box<dyn Buffer>) -> CryptoResult<()>;
 
+    /// start an incremental sha256 hash
+    fn hash_sha256_init(&self) -> CryptoResult<Box<dyn HashState>>;
+
+    /// start an incremental sha512 hash
+    fn hash_sha512_init(&self) -> CryptoResult<Box<dyn HashState>>;
+
     /// min bytelength of generic hash output
     fn generic_hash_min_bytes(&self) -> usize;
 
@@ -149,6 +188,15 @@ pub trait CryptoSystem: Sync {
         key: Option<&Box<dyn Buffer>>,
     ) -> CryptoResult<()>;
 
+    /// start an incremental generic hash, with the same result as
+    /// `generic_hash` for a `hash_size` bytes hash of all the input
+    /// key can be `None`
+    fn generic_hash_init(
+        &self,
+        hash_size: usize,
+        key: Option<&Box<dyn Buffer>>,
+    ) -> CryptoResult<Box<dyn HashState>>;
+
     /// bytelength of pwhash salt
     fn pwhash_salt_bytes(&self) -> usize;
 
@@ -231,6 +279,24 @@ pub trait CryptoSystem: Sync {
         public_key: &Box<dyn Buffer>,
     ) -> CryptoResult<bool>;
 
+    /// verify many (`signature`, `message`, `public_key`) items at once,
+    /// with the result `sign_verify` would give for each of them
+    /// the default verifies them one by one. An ed25519 batch equation is
+    /// not a drop-in replacement: it accepts some invalid signatures crafted
+    /// with torsion components, which `sign_verify` rejects
+    #[allow(clippy::type_complexity)]
+    fn sign_verify_batch(
+        &self,
+        items: &[(&Box<dyn Buffer>, &Box<dyn Buffer>, &Box<dyn Buffer>)],
+    ) -> Vec<CryptoResult<bool>> {
+        items
+            .iter()
+            .map(|(signature, message, public_key)| {
+                self.sign_verify(signature, message, public_key)
+            })
+            .collect()
+    }
+
     // -- key exchange methods -- //
 
     /// bytelength of key exchange seed
@@ -285,6 +351,22 @@ pub trait CryptoSystem: Sync {
         client_pk: &Box<dyn Buffer>,
     ) -> CryptoResult<()>;
 
+    /// convert a signature public key into a key exchange public key,
+    /// e.g. to seal boxes to an agent knowing only its signing key
+    fn sign_to_kx_public_key(
+        &self,
+        kx_public_key: &mut Box<dyn Buffer>,
+        sign_public_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()>;
+
+    /// convert a signature secret key into the key exchange secret key
+    /// matching `sign_to_kx_public_key()`
+    fn sign_to_kx_secret_key(
+        &self,
+        kx_secret_key: &mut Box<dyn Buffer>,
+        sign_secret_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()>;
+
     // -- aead encryption methods -- //
 
     /// bytelength of key exchange seed
@@ -319,6 +401,83 @@ pub trait CryptoSystem: Sync {
         nonce: &Box<dyn Buffer>,
         secret: &Box<dyn Buffer>,
     ) -> CryptoResult<()>;
+
+    // -- secretstream encryption methods -- //
+
+    /// bytelength of the header starting a secretstream
+    fn secretstream_header_bytes(&self) -> usize;
+
+    /// bytelength added to each secretstream message (tag and authentication)
+    fn secretstream_auth_bytes(&self) -> usize;
+
+    /// bytelength of secretstream symmetric key
+    fn secretstream_secret_bytes(&self) -> usize;
+
+    /// bytelength of the state of a secretstream,
+    /// which should be kept in a secure buffer
+    fn secretstream_state_bytes(&self) -> usize;
+
+    /// start encrypting a stream: initialize `state` and write the `header`
+    /// the puller will need
+    fn secretstream_init_push(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        header: &mut Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()>;
+
+    /// encrypt the next `message` of the stream into buffer `cipher`
+    fn secretstream_push(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        cipher: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+        tag: SecretStreamTag,
+    ) -> CryptoResult<()>;
+
+    /// start decrypting a stream from the `header` written by the pusher
+    fn secretstream_init_pull(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        header: &Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()>;
+
+    /// decrypt the next `cipher` of the stream into buffer `message`,
+    /// returning the tag it was pushed with.
+    /// Fails on messages that are out of order, tampered with or from
+    /// another stream
+    fn secretstream_pull(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        message: &mut Box<dyn Buffer>,
+        cipher: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+    ) -> CryptoResult<SecretStreamTag>;
+
+    // -- sealed box methods -- //
+
+    /// bytelength added to a message by sealing it
+    fn sealed_box_auth_bytes(&self) -> usize;
+
+    /// anonymously encrypt `message` into buffer `cipher`, so that only the
+    /// owner of the key exchange `public_key` can open it
+    fn sealed_box_encrypt(
+        &self,
+        cipher: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        public_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()>;
+
+    /// open a `cipher` sealed to our key exchange keypair into buffer `message`
+    fn sealed_box_decrypt(
+        &self,
+        message: &mut Box<dyn Buffer>,
+        cipher: &Box<dyn Buffer>,
+        public_key: &Box<dyn Buffer>,
+        secret_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()>;
 }
 
 pub mod crypto_system_test;
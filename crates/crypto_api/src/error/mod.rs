@@ -22,6 +22,8 @@ pub enum CryptoError {
     BadCipherSize,
     BadNonceSize,
     BadMessageSize,
+    BadHeaderSize,
+    BadStateSize,
     CouldNotDecrypt,
 }
 
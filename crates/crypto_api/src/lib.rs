@@ -21,4 +21,4 @@ mod buffer;
 pub use buffer::{Buffer, ProtectState, ReadLocker, WriteLocker};
 
 mod crypto_system;
-pub use crypto_system::{crypto_system_test, CryptoSystem};
+pub use crypto_system::{crypto_system_test, CryptoSystem, HashState, SecretStreamTag};
@@ -4,7 +4,7 @@ extern crate detach;
 use detach::Detach;
 use holochain_tracing::*;
 use lib3h::{
-    dht::mirror_dht::MirrorDht,
+    dht::{dht_config::DEFAULT_TOMBSTONE_RETENTION_MS, mirror_dht::MirrorDht},
     engine::{engine_actor::*, *},
     error::*,
     transport::websocket::tls::TlsConfig,
@@ -67,9 +67,17 @@ impl<'lt> EngineContainer<GhostEngine<'lt>> {
             work_dir: std::path::PathBuf::new(),
             log_level: 'd',
             bind_url: bind_url,
+            extra_bind_urls: Vec::new(),
             dht_gossip_interval: 100,
             dht_timeout_threshold: 1000,
+            dht_tombstone_retention: DEFAULT_TOMBSTONE_RETENTION_MS,
             dht_custom_config: vec![],
+            network_policy: NetworkPolicyData::default(),
+            publish: PublishConfig::default(),
+            direct_message: DirectMessageConfig::default(),
+            stream: StreamConfig::default(),
+            transport_seed: None,
+            transport_rotations: Vec::new(),
         };
 
         let dht_factory = MirrorDht::new_with_config;
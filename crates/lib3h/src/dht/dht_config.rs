@@ -1,7 +1,13 @@
+use crate::peer_policy::PeerPolicy;
 use lib3h_protocol::uri::Lib3hUri;
 
 pub const DEFAULT_GOSSIP_INTERVAL_MS: u64 = 2000;
 pub const DEFAULT_TIMEOUT_THRESHOLD_MS: u64 = 60000;
+pub const DEFAULT_TOMBSTONE_RETENTION_MS: u64 = 7 * 24 * 3600 * 1000;
+
+pub(crate) fn default_tombstone_retention() -> u64 {
+    DEFAULT_TOMBSTONE_RETENTION_MS
+}
 
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct DhtConfig {
@@ -9,6 +15,10 @@ pub struct DhtConfig {
     custom: Vec<u8>,
     gossip_interval: u64,
     timeout_threshold: u64,
+    #[serde(default = "default_tombstone_retention")]
+    tombstone_retention: u64,
+    #[serde(default)]
+    peer_policy: PeerPolicy,
 }
 
 impl DhtConfig {
@@ -18,6 +28,8 @@ impl DhtConfig {
             custom: vec![],
             gossip_interval: DEFAULT_GOSSIP_INTERVAL_MS,
             timeout_threshold: DEFAULT_TIMEOUT_THRESHOLD_MS,
+            tombstone_retention: DEFAULT_TOMBSTONE_RETENTION_MS,
+            peer_policy: PeerPolicy::default(),
         }
     }
 
@@ -27,6 +39,8 @@ impl DhtConfig {
             custom: config.clone().dht_custom_config,
             gossip_interval: config.dht_gossip_interval,
             timeout_threshold: config.dht_timeout_threshold,
+            tombstone_retention: config.dht_tombstone_retention,
+            peer_policy: PeerPolicy::default(),
         }
     }
 
@@ -38,7 +52,28 @@ impl DhtConfig {
         self.gossip_interval
     }
 
+    /// How long tombstones are kept, in ms after they were issued
+    pub fn tombstone_retention(&self) -> u64 {
+        self.tombstone_retention
+    }
+
+    pub fn set_tombstone_retention(&mut self, tombstone_retention: u64) {
+        self.tombstone_retention = tombstone_retention;
+    }
+
     pub fn this_peer_name(&self) -> Lib3hUri {
         self.this_peer_name.clone()
     }
+
+    pub fn set_this_peer_name(&mut self, peer_name: &Lib3hUri) {
+        self.this_peer_name = peer_name.to_owned();
+    }
+
+    pub fn peer_policy(&self) -> &PeerPolicy {
+        &self.peer_policy
+    }
+
+    pub fn set_peer_policy(&mut self, peer_policy: PeerPolicy) {
+        self.peer_policy = peer_policy;
+    }
 }
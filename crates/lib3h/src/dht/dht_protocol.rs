@@ -1,11 +1,14 @@
 use lib3h_protocol::{
-    data_types::{EntryData, Opaque},
+    data_types::{EntryData, Opaque, PeerStateData},
     types::*,
     uri::Lib3hUri,
+    Address,
 };
 
-use crate::{dht::dht_config::DhtConfig, error::*};
+use crate::{dht::dht_config::DhtConfig, error::*, peer_policy::PeerPolicy};
 use lib3h_ghost_actor::prelude::*;
+use rmp_serde::Serializer;
+use serde::Serialize;
 
 pub type FromPeerName = Lib3hUri;
 
@@ -63,9 +66,24 @@ pub enum DhtRequestToChild {
     BroadcastEntry(EntryData),
     /// Parent notifies us that is is not holding an entry anymore.
     DropEntryAddress(EntryHash),
+    /// Parent verified a tombstone. We drop the aspects it deletes, refuse
+    /// them until it expires, and gossip it to every known peer
+    HoldTombstone(TombstoneData),
+    /// Parent notifies us that a peer announced it left at `timestamp`.
+    /// We forget it, unless we heard from it since.
+    /// With a `location`, only a peer we hold at that location is dropped.
+    DropPeer {
+        peer_name: Lib3hUri,
+        timestamp: u64,
+        location: Option<Lib3hUri>,
+    },
 
     /// Parent notifies us that the binding changed
     UpdateAdvertise(Lib3hUri),
+    /// Parent notifies us that our name changed, after a TransportId rotation
+    UpdateThisPeerName(Lib3hUri),
+    /// Parent notifies us that the allow/block lists for peers changed
+    UpdatePeerPolicy(PeerPolicy),
 
     /// Requests
     /// Parent wants PeerData for a specific Peer
@@ -80,6 +98,8 @@ pub enum DhtRequestToChild {
     RequestAspectsOf(EntryHash),
     /// Parent wants a specific entry.
     RequestEntry(EntryHash),
+    /// Parent wants a summary of the peers and entries we are holding
+    RequestState,
 }
 
 #[derive(Debug, Clone)]
@@ -90,6 +110,7 @@ pub enum DhtRequestToChildResponse {
     RequestEntryAddressList(Vec<EntryHash>),
     RequestAspectsOf(Option<Vec<AspectHash>>),
     RequestEntry(EntryData),
+    RequestState(DhtStateData),
 }
 
 #[derive(Debug, Clone)]
@@ -106,11 +127,16 @@ pub enum DhtRequestToParent {
     HoldPeerRequested(PeerData),
     /// Notify owner that we believe a peer has dropped
     PeerTimedOut(Lib3hUri),
+    /// Notify owner that we forgot a peer after its departure
+    PeerLeft(Lib3hUri),
     /// Notify owner that gossip is requesting we hold an entry.
     HoldEntryRequested {
         from_peer_name: Lib3hUri,
         entry: EntryData,
     },
+    /// Notify owner that gossip brought a tombstone we are not holding.
+    /// Owner should check its signature before asking us to hold it.
+    HoldTombstoneRequested(TombstoneData),
     /// Notify owner that we are no longer tracking this entry internally.
     /// Owner should purge this address from storage, but they can, of course, choose not to.
     EntryPruned(EntryHash),
@@ -148,6 +174,75 @@ pub struct PeerData {
     pub timestamp: u64,
 }
 
+/// What a DHT is holding, in response to `DhtRequestToChild::RequestState`
+#[derive(Debug, PartialEq, Clone)]
+pub struct DhtStateData {
+    pub peer_list: Vec<PeerData>,
+    /// peers in `peer_list` we haven't heard from within the timeout threshold
+    pub timed_out_peers: Vec<Lib3hUri>,
+    pub entry_count: u64,
+    pub aspect_count: u64,
+}
+
+impl DhtStateData {
+    /// Held peers, as reported to clients
+    pub fn peer_states(&self) -> Vec<PeerStateData> {
+        let mut peer_states: Vec<PeerStateData> = self
+            .peer_list
+            .iter()
+            .map(|peer| PeerStateData {
+                peer_name: peer.peer_name.clone(),
+                peer_location: peer.peer_location.clone(),
+                timestamp: peer.timestamp,
+                is_alive: !self.timed_out_peers.contains(&peer.peer_name),
+            })
+            .collect();
+        peer_states.sort_by_key(|peer| peer.peer_name.to_string());
+        peer_states
+    }
+}
+
+/// Signed deletion of some aspects of an entry, or of the whole entry
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct TombstoneData {
+    pub space_address: SpaceHash,
+    pub entry_address: EntryHash,
+    /// Aspects deleted, the whole entry if None
+    pub aspect_address_list: Option<Vec<AspectHash>>,
+    /// Agent that asked for the deletion
+    pub provider_agent_id: Address,
+    /// When the deletion was asked, in ms since epoch
+    pub timestamp: u64,
+    /// Network peer name of the node that signed it
+    pub signer: Lib3hUri,
+    pub signature: Opaque,
+}
+
+impl TombstoneData {
+    /// The bytes covered by `signature`
+    pub fn signed_content(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        (
+            &self.space_address,
+            &self.entry_address,
+            &self.aspect_address_list,
+            &self.provider_agent_id,
+            self.timestamp,
+        )
+            .serialize(&mut Serializer::new(&mut out))
+            .unwrap();
+        out
+    }
+
+    /// true if this tombstone deletes that aspect of its entry
+    pub fn deletes(&self, aspect_address: &AspectHash) -> bool {
+        match &self.aspect_address_list {
+            None => true,
+            Some(aspect_address_list) => aspect_address_list.contains(aspect_address),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub struct FetchDhtEntryData {
     pub msg_id: String,
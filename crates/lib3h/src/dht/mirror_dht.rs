@@ -18,19 +18,24 @@ type HasTimedOut = bool;
 enum MirrorGossip {
     Entry(EntryData),
     Peer(PeerData),
+    Tombstone(TombstoneData),
 }
 
 /// Mirror DHT implementation: Holds and reflect everything back to other nodes (fullsync)
 ///  - On *HoldRequest, store and gossip data back to every known peer.
 ///  - Gossip can only be a *HoldRequest
-///  - Monotonic data
+///  - Monotonic data, except for what tombstones delete
 pub struct MirrorDht {
     /// Storage of EntryData with empty aspect content?
     entry_list: HashMap<EntryHash, HashSet<AspectHash>>,
+    /// Tombstones held until they expire, per entry
+    tombstone_map: HashMap<EntryHash, Vec<TombstoneData>>,
     /// Monotonic Storage of PeerData
     peer_map: HashMap<Lib3hUri, PeerData>,
     /// Track if peer timed out
     timed_out_map: HashMap<Lib3hUri, HasTimedOut>,
+    /// Peers that announced they left, with the timestamp of their departure
+    departed_map: HashMap<Lib3hUri, u64>,
     /// PeerData of this peer
     this_peer: PeerData,
     /// Keep track of last time this peer gossiped self to others
@@ -78,7 +83,9 @@ impl MirrorDht {
         let this = MirrorDht {
             peer_map: HashMap::new(),
             timed_out_map: HashMap::new(),
+            departed_map: HashMap::new(),
             entry_list: HashMap::new(),
+            tombstone_map: HashMap::new(),
             this_peer,
             last_gossip_of_self: timestamp,
             config: config.clone(),
@@ -129,6 +136,20 @@ impl MirrorDht {
         }
     }
 
+    fn get_state(&self) -> DhtStateData {
+        DhtStateData {
+            peer_list: self.get_peer_list(),
+            timed_out_peers: self
+                .timed_out_map
+                .iter()
+                .filter(|(_, has_timed_out)| **has_timed_out)
+                .map(|(peer_name, _)| peer_name.clone())
+                .collect(),
+            entry_count: self.entry_list.len() as u64,
+            aspect_count: self.entry_list.values().map(|set| set.len() as u64).sum(),
+        }
+    }
+
     // -- Processing -- //
 
     /// Serve each item in inbox
@@ -170,6 +191,18 @@ impl MirrorDht {
         for peer_name in timed_out_list {
             self.timed_out_map.insert(peer_name, true);
         }
+        // Forget expired tombstones
+        let retention = self.config.tombstone_retention();
+        for tombstone_list in self.tombstone_map.values_mut() {
+            tombstone_list.retain(|tombstone| !is_expired(tombstone, retention, now));
+        }
+        self.tombstone_map
+            .retain(|_, tombstone_list| !tombstone_list.is_empty());
+        // Forget old departures, gossip of what a peer was before them
+        // would time out anyway
+        let timeout_threshold = self.config.timeout_threshold();
+        self.departed_map
+            .retain(|_, departed_at| now.saturating_sub(*departed_at) <= timeout_threshold);
         // Check if must gossip self
         /*trace!(
             "@MirrorDht@ now: {} ; last_gossip: {} ({})",
@@ -219,12 +252,26 @@ impl MirrorDht {
         }
     }
 
+    /// Return true if `peer_info` predates the departure its peer announced
+    fn has_departed_since(&self, peer_info: &PeerData) -> bool {
+        self.departed_map
+            .get(&peer_info.peer_name)
+            .map(|departed_at| peer_info.timestamp <= *departed_at)
+            .unwrap_or(false)
+    }
+
     /// Return true if new peer or updated peer
     fn add_peer(&mut self, peer_info: &PeerData) -> bool {
         debug!(
             "@MirrorDht@ {:?} Adding peer: {:?}",
             self.this_peer, peer_info
         );
+        if self.has_departed_since(peer_info) {
+            debug!("@MirrorDht@ Adding peer - BAD it left since");
+            return false;
+        }
+        // it came back
+        self.departed_map.remove(&peer_info.peer_name);
         let maybe_peer = self.peer_map.get_mut(&peer_info.peer_name);
         match maybe_peer {
             None => {
@@ -258,12 +305,57 @@ impl MirrorDht {
         }
     }
 
+    /// Forget a peer that left at `timestamp`, unless we heard from it since,
+    /// or it is not at `location`. Return true if it was removed.
+    fn remove_departed_peer(
+        &mut self,
+        peer_name: &Lib3hUri,
+        timestamp: u64,
+        location: Option<&Lib3hUri>,
+    ) -> bool {
+        if peer_name == &self.this_peer.peer_name {
+            return false;
+        }
+        match (self.peer_map.get(peer_name), location) {
+            (Some(peer), _) if peer.timestamp > timestamp => return false,
+            (Some(peer), Some(location)) if &peer.peer_location != location => {
+                warn!(
+                    "@MirrorDht@ ignoring departure of {} from {}, it is at {}",
+                    peer_name, location, peer.peer_location
+                );
+                return false;
+            }
+            // can't tell where it was
+            (None, Some(_)) => return false,
+            _ => (),
+        }
+        self.departed_map.insert(peer_name.clone(), timestamp);
+        self.timed_out_map.remove(peer_name);
+        self.peer_map.remove(peer_name).is_some()
+    }
+
+    /// Forget every held peer that our peer policy no longer allows
+    fn remove_filtered_peers(&mut self) {
+        let filtered_list: Vec<Lib3hUri> = self
+            .peer_map
+            .values()
+            .filter(|peer| !self.config.peer_policy().allows(peer))
+            .map(|peer| peer.peer_name.clone())
+            .collect();
+        for peer_name in filtered_list {
+            debug!("@MirrorDht@ removing filtered peer: {}", peer_name);
+            self.peer_map.remove(&peer_name);
+            self.timed_out_map.remove(&peer_name);
+        }
+    }
+
     /// Return aspect addresses diff between
     /// known aspects and aspects in the entry argument
     fn diff_aspects(&self, entry: &EntryData) -> HashSet<AspectHash> {
         let aspect_address_set: HashSet<_> = entry
             .aspect_list
             .iter()
+            .filter(|aspect| !self.is_deleted(&entry.entry_address, &aspect.aspect_address))
             .map(|aspect| aspect.aspect_address.clone())
             .collect();
         let maybe_aspects = self.entry_list.get(&entry.entry_address);
@@ -300,6 +392,65 @@ impl MirrorDht {
         true
     }
 
+    /// Return true if a held tombstone deletes that aspect
+    fn is_deleted(&self, entry_address: &EntryHash, aspect_address: &AspectHash) -> bool {
+        self.tombstone_map
+            .get(entry_address)
+            .map(|tombstone_list| {
+                tombstone_list
+                    .iter()
+                    .any(|tombstone| tombstone.deletes(aspect_address))
+            })
+            .unwrap_or(false)
+    }
+
+    /// Return true if we hold that tombstone or it expired already
+    fn is_known_tombstone(&self, tombstone: &TombstoneData) -> bool {
+        let retention = self.config.tombstone_retention();
+        is_expired(tombstone, retention, time::since_epoch_ms())
+            || self
+                .tombstone_map
+                .get(&tombstone.entry_address)
+                .map(|tombstone_list| tombstone_list.contains(tombstone))
+                .unwrap_or(false)
+    }
+
+    /// Hold a tombstone and drop the aspects it deletes.
+    /// Return true if it was new.
+    fn add_tombstone(&mut self, tombstone: &TombstoneData) -> bool {
+        if self.is_known_tombstone(tombstone) {
+            return false;
+        }
+        self.tombstone_map
+            .entry(tombstone.entry_address.clone())
+            .or_default()
+            .push(tombstone.clone());
+        if let Some(aspect_set) = self.entry_list.get_mut(&tombstone.entry_address) {
+            aspect_set.retain(|aspect_address| !tombstone.deletes(aspect_address));
+            if aspect_set.is_empty() {
+                self.entry_list.remove(&tombstone.entry_address);
+            }
+        }
+        true
+    }
+
+    /// Create GossipTo event for a tombstone to the listed peers
+    fn gossip_tombstone(
+        &self,
+        tombstone: &TombstoneData,
+        peer_name_list: Vec<Lib3hUri>,
+    ) -> DhtRequestToParent {
+        let tombstone_gossip = MirrorGossip::Tombstone(tombstone.clone());
+        let mut buf = Vec::new();
+        tombstone_gossip
+            .serialize(&mut Serializer::new(&mut buf))
+            .unwrap();
+        DhtRequestToParent::GossipTo(GossipToData {
+            peer_name_list,
+            bundle: buf.into(),
+        })
+    }
+
     /// Create GossipTo event for entry to all known peers
     fn gossip_entry(&self, entry: &EntryData) -> DhtRequestToParent {
         let entry_gossip = MirrorGossip::Entry(entry.clone());
@@ -319,6 +470,11 @@ impl MirrorDht {
     }
 }
 
+/// Return true if the tombstone was issued longer than `retention` ms ago
+fn is_expired(tombstone: &TombstoneData, retention: u64, now: u64) -> bool {
+    tombstone.timestamp.saturating_add(retention) < now
+}
+
 /// Impl DhtActor interface
 impl
     GhostActor<
@@ -382,11 +538,44 @@ impl MirrorDht {
                             )?;
                         }
                     }
+                    MirrorGossip::Tombstone(tombstone) => {
+                        trace!(
+                            "DhtRequestToChild::HandleGossip: Tombstone = {:?}",
+                            tombstone
+                        );
+                        let latest = time::since_epoch_ms() + time::MAX_CLOCK_SKEW_MS;
+                        if tombstone.timestamp > latest {
+                            // would outlive its retention
+                            warn!(
+                                "@MirrorDht@ ignoring tombstone from the future: {:?}",
+                                tombstone
+                            );
+                        } else if !self.is_known_tombstone(&tombstone) {
+                            self.endpoint_self.publish(
+                                span,
+                                DhtRequestToParent::HoldTombstoneRequested(tombstone),
+                            )?;
+                        }
+                    }
                     MirrorGossip::Peer(gossiped_peer) => {
                         trace!(
                             "DhtRequestToChild::HandleGossip: Peer = {:?}",
                             gossiped_peer
                         );
+                        if !self.config.peer_policy().allows(&gossiped_peer) {
+                            debug!(
+                                "@MirrorDht@ ignoring gossip of filtered peer: {}",
+                                gossiped_peer.peer_name
+                            );
+                            return Ok(());
+                        }
+                        if self.has_departed_since(&gossiped_peer) {
+                            debug!(
+                                "@MirrorDht@ ignoring stale gossip of departed peer: {}",
+                                gossiped_peer.peer_name
+                            );
+                            return Ok(());
+                        }
                         let maybe_known_peer = self.get_peer(&gossiped_peer.peer_name);
                         match maybe_known_peer {
                             None => {
@@ -409,8 +598,20 @@ impl MirrorDht {
             // Owner is asking us to hold a peer info
             DhtRequestToChild::HoldPeer(new_peer_data) => {
                 trace!("DhtRequestToChild::HoldPeer: {:?}", new_peer_data);
+                // Bail if our peer policy does not let us hold it
+                if new_peer_data.peer_name != self.this_peer.peer_name
+                    && !self.config.peer_policy().allows(&new_peer_data)
+                {
+                    debug!(
+                        "@MirrorDht@ refusing to hold filtered peer: {}",
+                        new_peer_data.peer_name
+                    );
+                    return Ok(());
+                }
                 // Get peer_list before adding new peer (to use when doing gossipTo)
                 let others_list = self.get_other_peer_list();
+                let is_new_peer = new_peer_data.peer_name != self.this_peer.peer_name
+                    && !self.peer_map.contains_key(&new_peer_data.peer_name);
                 // Store it
                 let received_new_content = self.add_peer(&new_peer_data);
                 // Bail if peer is known and up to date.
@@ -451,6 +652,20 @@ impl MirrorDht {
                         )?;
                     }
                 }
+
+                // A new peer may still hold what our tombstones deleted
+                if is_new_peer {
+                    let tombstone_list: Vec<TombstoneData> =
+                        self.tombstone_map.values().flatten().cloned().collect();
+                    for tombstone in tombstone_list {
+                        let gossip_evt = self
+                            .gossip_tombstone(&tombstone, vec![new_peer_data.peer_name.clone()]);
+                        self.endpoint_self.publish(
+                            span.follower("TODO-name DhtRequestToChild::HoldPeer"),
+                            gossip_evt,
+                        )?;
+                    }
+                }
             }
 
             // Owner is holding some entry. Store its address for bookkeeping.
@@ -499,6 +714,34 @@ impl MirrorDht {
             // N/A. Do nothing since this is a monotonic fullsync dht
             DhtRequestToChild::DropEntryAddress(_) => (),
 
+            // Drop what it deletes and gossip it to every known peer
+            DhtRequestToChild::HoldTombstone(tombstone) => {
+                trace!("DhtRequestToChild::HoldTombstone: {:?}", tombstone);
+                if !self.add_tombstone(&tombstone) {
+                    return Ok(());
+                }
+                let gossip_evt = self.gossip_tombstone(&tombstone, self.get_other_peer_list());
+                self.endpoint_self.publish(
+                    span.follower("DhtRequestToChild::HoldTombstone"),
+                    gossip_evt,
+                )?;
+            }
+
+            DhtRequestToChild::DropPeer {
+                peer_name,
+                timestamp,
+                location,
+            } => {
+                trace!("DhtRequestToChild::DropPeer: {} ({})", peer_name, timestamp);
+                if self.remove_departed_peer(&peer_name, timestamp, location.as_ref()) {
+                    debug!("@MirrorDht@ peer {} left", peer_name);
+                    self.endpoint_self.publish(
+                        span.follower("DhtRequestToChild::DropPeer"),
+                        DhtRequestToParent::PeerLeft(peer_name),
+                    )?;
+                }
+            }
+
             DhtRequestToChild::UpdateAdvertise(peer_location) => {
                 trace!(
                     "({}).DhtRequestToChild::UpdateAdvertise: {:?}",
@@ -508,6 +751,26 @@ impl MirrorDht {
                 self.this_peer.peer_location = peer_location;
             }
 
+            DhtRequestToChild::UpdateThisPeerName(peer_name) => {
+                trace!(
+                    "({}).DhtRequestToChild::UpdateThisPeerName: {}",
+                    self.config.this_peer_name(),
+                    peer_name
+                );
+                self.peer_map.remove(&peer_name);
+                self.config.set_this_peer_name(&peer_name);
+                self.this_peer.peer_name = peer_name;
+                self.this_peer.timestamp = time::since_epoch_ms();
+                // gossip our new name right away
+                self.last_gossip_of_self = 0;
+            }
+
+            DhtRequestToChild::UpdatePeerPolicy(peer_policy) => {
+                trace!("DhtRequestToChild::UpdatePeerPolicy: {:?}", peer_policy);
+                self.config.set_peer_policy(peer_policy);
+                self.remove_filtered_peers();
+            }
+
             DhtRequestToChild::RequestPeer(peer_name) => {
                 trace!("DhtRequestToChild::RequestPeer: {:?}", peer_name);
                 let maybe_peer = self.get_peer(&peer_name);
@@ -544,6 +807,11 @@ impl MirrorDht {
                 request.respond(payload)?;
             }
 
+            DhtRequestToChild::RequestState => {
+                let payload = Ok(DhtRequestToChildResponse::RequestState(self.get_state()));
+                request.respond(payload)?;
+            }
+
             // Ask owner to respond to self
             DhtRequestToChild::RequestEntry(entry_address) => {
                 trace!("DhtRequestToChild::RequestEntry: {:?}", entry_address);
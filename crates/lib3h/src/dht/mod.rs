@@ -7,7 +7,12 @@ pub mod mirror_dht;
 #[cfg(test)]
 pub mod tests {
     use crate::{
-        dht::{dht_protocol::*, mirror_dht::MirrorDht},
+        dht::{
+            dht_config::{DhtConfig, DEFAULT_TIMEOUT_THRESHOLD_MS},
+            dht_protocol::*,
+            mirror_dht::MirrorDht,
+        },
+        peer_policy::PeerPolicy,
         tests::enable_logging_for_test,
     };
     use detach::prelude::*;
@@ -15,7 +20,9 @@ pub mod tests {
     use holochain_tracing::test_span;
     use lib3h_ghost_actor::prelude::*;
     use lib3h_protocol::{
-        data_types::{EntryAspectData, EntryData},
+        data_types::{
+            EntryAspectData, EntryData, NetworkPolicyData, PeerFilterData, SpacePolicyData,
+        },
         types::*,
         uri::Lib3hUri,
     };
@@ -53,6 +60,7 @@ pub mod tests {
         peer_list: Vec<PeerData>,
         entry_list: Vec<EntryHash>,
         maybe_aspect_list: Option<Vec<AspectHash>>,
+        maybe_state: Option<DhtStateData>,
     }
 
     impl DhtData {
@@ -67,6 +75,7 @@ pub mod tests {
                 peer_list: Vec::new(),
                 entry_list: Vec::new(),
                 maybe_aspect_list: None,
+                maybe_state: None,
             }
         }
     }
@@ -190,6 +199,16 @@ pub mod tests {
         ud.maybe_peer
     }
 
+    /// Drain the DHT's messages, true if one says it forgot that peer
+    fn has_peer_left(dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>, peer_name: &Lib3hUri) -> bool {
+        dht.drain_messages()
+            .iter_mut()
+            .any(|msg| match msg.take_message() {
+                Some(DhtRequestToParent::PeerLeft(left)) => left == *peer_name,
+                _ => false,
+            })
+    }
+
     fn get_peer_list(dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>) -> Vec<PeerData> {
         let mut ud = DhtData::new();
         dht.request(
@@ -281,6 +300,26 @@ pub mod tests {
         ud.maybe_aspect_list
     }
 
+    fn get_state(dht: &mut Detach<ChildDhtWrapperDyn<DhtData>>) -> DhtStateData {
+        let mut ud = DhtData::new();
+        dht.request(
+            test_span(""),
+            DhtRequestToChild::RequestState,
+            Box::new(|mut ud, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(DhtRequestToChildResponse::RequestState(
+                        state,
+                    ))) => ud.maybe_state = Some(state),
+                    _ => panic!("bad response to RequestState: {:?}", response),
+                }
+                Ok(())
+            }),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        ud.maybe_state.expect("RequestState should be answered")
+    }
+
     #[test]
     fn test_this_peer() {
         enable_logging_for_test(true);
@@ -405,6 +444,38 @@ pub mod tests {
         dht.process(&mut ud).unwrap();
     }
 
+    #[test]
+    fn test_dht_state() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(true, &*PEER_A);
+        let mut ud = DhtData::new();
+        let state = get_state(&mut dht);
+        assert_eq!(state.peer_list.len(), 0);
+        assert_eq!(state.entry_count, 0);
+        // Hold a peer and two aspects of an entry
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_B)),
+        )
+        .unwrap();
+        let mut entry = create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        entry.aspect_list.append(
+            &mut create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_2, &*ASPECT_CONTENT_2)
+                .aspect_list,
+        );
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::HoldEntryAspectAddress(entry),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        let state = get_state(&mut dht);
+        assert_eq!(state.peer_list.len(), 1);
+        assert!(state.timed_out_peers.is_empty());
+        assert_eq!(state.entry_count, 1);
+        assert_eq!(state.aspect_count, 2);
+    }
+
     #[test]
     fn test_update_peer() {
         enable_logging_for_test(true);
@@ -454,6 +525,159 @@ pub mod tests {
         assert!(peer.timestamp > ref_time);
     }
 
+    #[test]
+    fn test_drop_peer() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(true, &*PEER_A);
+        let mut ud = DhtData::new();
+        // leave room for newer timestamps that are still in the past
+        let mut peer_b_data = create_PeerData(&*PEER_B);
+        peer_b_data.timestamp -= 100;
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::HoldPeer(peer_b_data.clone()),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_some());
+        // A departure older than what we heard should be ignored
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::DropPeer {
+                peer_name: PEER_B.clone(),
+                timestamp: peer_b_data.timestamp - 1,
+                location: None,
+            },
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_some());
+        assert!(!has_peer_left(&mut dht, &*PEER_B));
+        // Otherwise the peer is forgotten
+        let departed_at = peer_b_data.timestamp + 1;
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::DropPeer {
+                peer_name: PEER_B.clone(),
+                timestamp: departed_at,
+                location: None,
+            },
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_none());
+        assert!(has_peer_left(&mut dht, &*PEER_B));
+        // Stale peer data should not bring it back, nor any from when it left
+        for timestamp in &[peer_b_data.timestamp, departed_at] {
+            peer_b_data.timestamp = *timestamp;
+            dht.publish(
+                test_span(""),
+                DhtRequestToChild::HoldPeer(peer_b_data.clone()),
+            )
+            .unwrap();
+            dht.process(&mut ud).unwrap();
+            assert!(get_peer(&mut dht, &*PEER_B).is_none());
+        }
+        // Newer peer data should
+        peer_b_data.timestamp = departed_at + 1;
+        dht.publish(test_span(""), DhtRequestToChild::HoldPeer(peer_b_data))
+            .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_some());
+    }
+
+    #[test]
+    fn test_drop_peer_at_location() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(true, &*PEER_A);
+        let mut ud = DhtData::new();
+        let mut peer_b_data = create_PeerData(&*PEER_B);
+        peer_b_data.timestamp -= 100;
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::HoldPeer(peer_b_data.clone()),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        // A departure announced from another location should be ignored
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::DropPeer {
+                peer_name: PEER_B.clone(),
+                timestamp: peer_b_data.timestamp + 1,
+                location: Some(Lib3hUri::with_transport_id(&HashString::from("elsewhere"))),
+            },
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_some());
+        // And should not keep out its peer data
+        peer_b_data.timestamp += 2;
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::HoldPeer(peer_b_data.clone()),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert_eq!(
+            get_peer(&mut dht, &*PEER_B).unwrap().timestamp,
+            peer_b_data.timestamp
+        );
+        // Nor should one for a peer we don't hold
+        let peer_c_data = create_PeerData(&*PEER_C);
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::DropPeer {
+                peer_name: PEER_C.clone(),
+                timestamp: peer_c_data.timestamp + 1,
+                location: Some(create_test_uri()),
+            },
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        dht.publish(test_span(""), DhtRequestToChild::HoldPeer(peer_c_data))
+            .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_C).is_some());
+        // From the right location, it is forgotten
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::DropPeer {
+                peer_name: PEER_B.clone(),
+                timestamp: peer_b_data.timestamp + 1,
+                location: Some(create_test_uri()),
+            },
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_none());
+    }
+
+    #[test]
+    fn test_drop_peer_departures_are_pruned() {
+        enable_logging_for_test(true);
+        let mut dht = new_dht_wrapper(true, &*PEER_A);
+        let mut ud = DhtData::new();
+        // a departure older than the timeout threshold
+        let mut peer_b_data = create_PeerData(&*PEER_B);
+        peer_b_data.timestamp -= 2 * DEFAULT_TIMEOUT_THRESHOLD_MS;
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::DropPeer {
+                peer_name: PEER_B.clone(),
+                timestamp: peer_b_data.timestamp + 1,
+                location: None,
+            },
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        // Once forgotten, earlier peer data is no longer held back
+        dht.publish(test_span(""), DhtRequestToChild::HoldPeer(peer_b_data))
+            .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_some());
+    }
+
     #[test]
     fn test_mirror_broadcast_entry() {
         enable_logging_for_test(true);
@@ -637,4 +861,252 @@ pub mod tests {
         let peer_info = get_peer(&mut dht_b, &*PEER_C).unwrap();
         assert_eq!(peer_info, peer_c_data);
     }
+
+    #[test]
+    fn test_peer_policy() {
+        enable_logging_for_test(true);
+        let mut config = DhtConfig::new(&*PEER_A);
+        config.set_peer_policy(
+            PeerPolicy::for_space(
+                &NetworkPolicyData {
+                    spaces: vec![SpacePolicyData {
+                        space_address: "space".into(),
+                        agent_ids: PeerFilterData {
+                            allow: vec![],
+                            block: vec![PEER_B_STR.to_string()],
+                        },
+                    }],
+                    ..Default::default()
+                },
+                &"space".into(),
+            )
+            .unwrap(),
+        );
+        let dht = MirrorDht::new_with_config(&config, None).unwrap();
+        let mut dht = Detach::new(ChildDhtWrapperDyn::new(dht, "dht_parent_"));
+        let mut ud = DhtData::new();
+        // Blocked peer should not be held
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_B)),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert!(get_peer(&mut dht, &*PEER_B).is_none());
+        // Others should
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_C)),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert_eq!(get_peer_list(&mut dht).len(), 1);
+        // Blocking a held peer at runtime should drop it
+        let peer_policy = PeerPolicy::for_network(&NetworkPolicyData {
+            transport_ids: PeerFilterData {
+                allow: vec![],
+                block: vec![PEER_C_STR.to_string()],
+            },
+            ..Default::default()
+        })
+        .unwrap();
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::UpdatePeerPolicy(peer_policy),
+        )
+        .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert_eq!(get_peer_list(&mut dht).len(), 0);
+    }
+
+    fn create_tombstone(aspect_address_list: Option<Vec<AspectHash>>) -> TombstoneData {
+        TombstoneData {
+            space_address: "space".into(),
+            entry_address: ENTRY_ADDRESS_1.clone(),
+            aspect_address_list,
+            provider_agent_id: PEER_A_STR.into(),
+            timestamp: crate::time::since_epoch_ms(),
+            signer: create_test_uri(),
+            signature: Vec::new().into(),
+        }
+    }
+
+    #[test]
+    fn test_mirror_tombstone() {
+        enable_logging_for_test(true);
+        let mut dht_a = new_dht_wrapper(true, &*PEER_A);
+        let mut ud = DhtData::new();
+        dht_a
+            .publish(
+                test_span(""),
+                DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_B)),
+            )
+            .unwrap();
+        let mut entry_data =
+            create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_1, &*ASPECT_CONTENT_1);
+        entry_data.aspect_list.append(
+            &mut create_EntryData(&*ENTRY_ADDRESS_1, &*ASPECT_ADDRESS_2, &*ASPECT_CONTENT_2)
+                .aspect_list,
+        );
+        dht_a
+            .publish(
+                test_span(""),
+                DhtRequestToChild::BroadcastEntry(entry_data.clone()),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        dht_a.drain_messages();
+
+        // Holding a tombstone drops the aspect and gossips the tombstone
+        let tombstone = create_tombstone(Some(vec![ASPECT_ADDRESS_1.clone()]));
+        dht_a
+            .publish(
+                test_span(""),
+                DhtRequestToChild::HoldTombstone(tombstone.clone()),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        let mut request_list = dht_a.drain_messages();
+        assert_eq!(request_list.len(), 1);
+        let bundle = match request_list[0].take_message().expect("exists") {
+            DhtRequestToParent::GossipTo(gossip_data) => {
+                assert_eq!(gossip_data.peer_name_list, vec![PEER_B.clone()]);
+                gossip_data.bundle
+            }
+            payload => panic!("Expecting a GossipTo, got {:?}", payload),
+        };
+        assert_eq!(
+            get_aspects_of(&mut dht_a, &*ENTRY_ADDRESS_1),
+            Some(vec![ASPECT_ADDRESS_2.clone()])
+        );
+
+        // The deleted aspect is not held again
+        dht_a
+            .publish(
+                test_span(""),
+                DhtRequestToChild::BroadcastEntry(entry_data.clone()),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        assert_eq!(dht_a.drain_messages().len(), 0);
+        dht_a
+            .publish(
+                test_span(""),
+                DhtRequestToChild::HandleGossip(RemoteGossipBundleData {
+                    from_peer_name: PEER_B.clone(),
+                    bundle: bundle.clone(),
+                }),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        assert_eq!(dht_a.drain_messages().len(), 0);
+
+        // Gossip of it asks the owner to check it first
+        let mut dht_b = new_dht_wrapper(true, &*PEER_B);
+        dht_b
+            .publish(
+                test_span(""),
+                DhtRequestToChild::HandleGossip(RemoteGossipBundleData {
+                    from_peer_name: PEER_A.clone(),
+                    bundle: bundle.clone(),
+                }),
+            )
+            .unwrap();
+        dht_b.process(&mut ud).unwrap();
+        let mut request_list = dht_b.drain_messages();
+        assert_eq!(request_list.len(), 1);
+        match request_list[0].take_message().expect("exists") {
+            DhtRequestToParent::HoldTombstoneRequested(gossiped) => {
+                assert_eq!(gossiped, tombstone)
+            }
+            payload => panic!("Expecting a HoldTombstoneRequested, got {:?}", payload),
+        }
+
+        // New peers get our tombstones
+        dht_a
+            .publish(
+                test_span(""),
+                DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_C)),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        let has_tombstone_gossip = dht_a.drain_messages().iter_mut().any(|request| {
+            match request.take_message().expect("exists") {
+                DhtRequestToParent::GossipTo(gossip_data) => {
+                    gossip_data.peer_name_list == vec![PEER_C.clone()]
+                        && gossip_data.bundle == bundle
+                }
+                _ => false,
+            }
+        });
+        assert!(has_tombstone_gossip);
+    }
+
+    #[test]
+    fn test_mirror_tombstone_from_the_future() {
+        enable_logging_for_test(true);
+        let mut dht_a = new_dht_wrapper(true, &*PEER_A);
+        let mut dht_b = new_dht_wrapper(true, &*PEER_B);
+        let mut ud = DhtData::new();
+        dht_a
+            .publish(
+                test_span(""),
+                DhtRequestToChild::HoldPeer(create_PeerData(&*PEER_B)),
+            )
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        dht_a.drain_messages();
+        let mut tombstone = create_tombstone(None);
+        tombstone.timestamp += 2 * crate::time::MAX_CLOCK_SKEW_MS;
+        dht_a
+            .publish(test_span(""), DhtRequestToChild::HoldTombstone(tombstone))
+            .unwrap();
+        dht_a.process(&mut ud).unwrap();
+        let mut request_list = dht_a.drain_messages();
+        assert_eq!(request_list.len(), 1);
+        let bundle = match request_list[0].take_message().expect("exists") {
+            DhtRequestToParent::GossipTo(gossip_data) => gossip_data.bundle,
+            payload => panic!("Expecting a GossipTo, got {:?}", payload),
+        };
+        // ignored on receipt
+        dht_b
+            .publish(
+                test_span(""),
+                DhtRequestToChild::HandleGossip(RemoteGossipBundleData {
+                    from_peer_name: PEER_A.clone(),
+                    bundle,
+                }),
+            )
+            .unwrap();
+        dht_b.process(&mut ud).unwrap();
+        assert_eq!(dht_b.drain_messages().len(), 0);
+    }
+
+    #[test]
+    fn test_mirror_tombstone_expired() {
+        enable_logging_for_test(true);
+        let mut config = DhtConfig::new(&*PEER_A);
+        config.set_tombstone_retention(10);
+        let dht = MirrorDht::new_with_config(&config, None).unwrap();
+        let mut dht = Detach::new(ChildDhtWrapperDyn::new(dht, "dht_parent_"));
+        let mut ud = DhtData::new();
+        dht.publish(
+            test_span(""),
+            DhtRequestToChild::BroadcastEntry(create_EntryData(
+                &*ENTRY_ADDRESS_1,
+                &*ASPECT_ADDRESS_1,
+                &*ASPECT_CONTENT_1,
+            )),
+        )
+        .unwrap();
+        let mut tombstone = create_tombstone(None);
+        tombstone.timestamp -= 100;
+        dht.publish(test_span(""), DhtRequestToChild::HoldTombstone(tombstone))
+            .unwrap();
+        dht.process(&mut ud).unwrap();
+        assert_eq!(
+            get_entry_address_list(&mut dht),
+            vec![ENTRY_ADDRESS_1.clone()]
+        );
+    }
 }
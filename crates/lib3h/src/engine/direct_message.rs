@@ -0,0 +1,321 @@
+use crate::{
+    engine::{
+        engine_actor::ClientToLib3hMessage,
+        p2p_protocol::{DirectMessageRejectedData, P2pProtocol},
+        GhostEngine,
+    },
+    error::{ErrorKind, Lib3hError, Lib3hResult},
+    gateway::protocol::*,
+    transport,
+};
+
+use holochain_tracing::Span;
+use lib3h_ghost_actor::{prelude::*, RequestId};
+use lib3h_protocol::{
+    data_types::DirectMessageData,
+    protocol::{ClientToLib3hResponse, Lib3hToClient, Lib3hToClientResponse},
+    uri::Lib3hUri,
+    DidWork,
+};
+
+/// A SendDirectMessage waiting for the recipient's answer
+pub(crate) struct PendingDirectMessage {
+    /// the client's message, answered with the recipient's answer or an error
+    msg: ClientToLib3hMessage,
+    data: DirectMessageData,
+    attempts: u32,
+    /// when the current attempt stops waiting, in ms since epoch
+    attempt_deadline: u64,
+    /// why the transport could not send the last attempt, if it could not
+    send_error: Option<String>,
+}
+
+/// A DirectMessage from a remote agent, kept to recognize its retries
+pub(crate) struct ReceivedDirectMessage {
+    /// our answer, sent again to retries, or None while our client is on it
+    answer: Option<DirectMessageAnswer>,
+    /// when we first got it, in ms since epoch
+    received_at: u64,
+}
+
+#[cfg(test)]
+impl ReceivedDirectMessage {
+    pub(crate) fn is_answered(&self) -> bool {
+        self.answer.is_some()
+    }
+}
+
+/// What we answered to a DirectMessage
+#[derive(Clone)]
+enum DirectMessageAnswer {
+    Result(DirectMessageData),
+    Rejected(DirectMessageRejectedData),
+}
+
+/// Direct message related private methods
+impl<'engine> GhostEngine<'engine> {
+    /// Send a direct message to a remote agent. `client_msg` is answered when
+    /// the remote answers, or by `process_direct_messages()` with an error
+    /// once every attempt timed out.
+    pub(crate) fn handle_direct_message(
+        &mut self,
+        span: Span,
+        client_msg: ClientToLib3hMessage,
+        mut data: DirectMessageData,
+    ) -> Lib3hResult<()> {
+        // Generate a new request_id for the network exchange:
+        // the client's one is not unique among the messages we are waiting on
+        let request_id: String = RequestId::new().into();
+        trace!(
+            "GhostEngine: mutating request id from {:?} to {:?} for {:?}",
+            data.request_id,
+            request_id,
+            data
+        );
+        data.request_id = request_id.clone();
+        self.pending_client_direct_messages.insert(
+            request_id.clone(),
+            PendingDirectMessage {
+                msg: client_msg,
+                data,
+                attempts: 0,
+                attempt_deadline: 0,
+                send_error: None,
+            },
+        );
+        self.send_direct_message(span, &request_id)
+    }
+
+    /// Send again the direct messages that timed out,
+    /// and answer with an error the ones out of retries
+    pub(crate) fn process_direct_messages(&mut self) -> Lib3hResult<DidWork> {
+        let max_attempts = self.config.direct_message.max_retries + 1;
+        let now = crate::time::since_epoch_ms();
+        // senders stop retrying after as many attempts as we would
+        let retention = self.config.direct_message.timeout_ms * u64::from(max_attempts);
+        self.received_direct_messages
+            .retain(|_, received| now.saturating_sub(received.received_at) <= retention);
+        let request_id_list: Vec<String> = self
+            .pending_client_direct_messages
+            .iter()
+            .filter(|(_, pending)| now >= pending.attempt_deadline)
+            .map(|(request_id, _)| request_id.clone())
+            .collect();
+        let did_work = !request_id_list.is_empty();
+        for request_id in request_id_list {
+            if self.pending_client_direct_messages[&request_id].attempts < max_attempts {
+                debug!("GhostEngine: resending direct message {}", request_id);
+                self.send_direct_message(Span::fixme(), &request_id)?;
+                continue;
+            }
+            let pending = self
+                .pending_client_direct_messages
+                .remove(&request_id)
+                .expect("Should have pending direct message");
+            let kind = match pending.send_error {
+                Some(e) => ErrorKind::DirectMessageUnreachable(format!(
+                    "{}: {}",
+                    pending.data.to_agent_id, e
+                )),
+                None => ErrorKind::DirectMessageTimeout(pending.data.to_agent_id.to_string()),
+            };
+            pending.msg.respond(Err(Lib3hError::new(kind)))?;
+        }
+        Ok(did_work)
+    }
+
+    /// Send an attempt of a pending direct message to its recipient
+    fn send_direct_message(&mut self, span: Span, request_id: &str) -> Lib3hResult<()> {
+        let timeout_ms = self.config.direct_message.timeout_ms;
+        let data = {
+            let pending = self
+                .pending_client_direct_messages
+                .get_mut(request_id)
+                .expect("Should have pending direct message");
+            pending.attempts += 1;
+            pending.attempt_deadline = crate::time::since_epoch_ms() + timeout_ms;
+            pending.data.clone()
+        };
+        let to_agent_id = data.to_agent_id.clone();
+        let (space_gateway, payload) = match self.prepare_direct_peer_msg(
+            data.space_address.clone(),
+            data.from_agent_id.clone(),
+            data.to_agent_id.clone(),
+            P2pProtocol::DirectMessage(data),
+        ) {
+            Ok(r) => r,
+            Err(e) => {
+                // not or no longer part of that space
+                let pending = self
+                    .pending_client_direct_messages
+                    .remove(request_id)
+                    .expect("Should have pending direct message");
+                return Ok(pending.msg.respond(Err(e))?);
+            }
+        };
+        let request_id = request_id.to_string();
+        space_gateway.request(
+            span,
+            GatewayRequestToChild::Transport(
+                transport::protocol::RequestToChild::create_send_message(
+                    Lib3hUri::with_agent_id(&to_agent_id),
+                    payload,
+                ),
+            ),
+            Box::new(move |me, response| {
+                debug!(
+                    "GhostEngine: response to send direct message {}: {:?}",
+                    request_id, response
+                );
+                let send_error = match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Transport(
+                        transport::protocol::RequestToChildResponse::SendMessageSuccess,
+                    ))) => None,
+                    _ => Some(format!("{:?}", response)),
+                };
+                if let Some(pending) = me.pending_client_direct_messages.get_mut(&request_id) {
+                    pending.send_error = send_error;
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// The recipient answered one of our direct messages
+    pub(crate) fn handle_direct_message_result(
+        &mut self,
+        data: DirectMessageData,
+    ) -> Lib3hResult<()> {
+        match self.pending_client_direct_messages.remove(&data.request_id) {
+            Some(pending) => {
+                trace!("GhostEngine: direct message result: {:?}", data);
+                pending
+                    .msg
+                    .respond(Ok(ClientToLib3hResponse::SendDirectMessageResult(data)))?;
+            }
+            // answer to an attempt we already gave up on, or already got
+            None => debug!(
+                "GhostEngine: ignoring late direct message result {:?}",
+                data
+            ),
+        }
+        Ok(())
+    }
+
+    /// The recipient's client refused one of our direct messages
+    pub(crate) fn handle_direct_message_rejected(
+        &mut self,
+        data: DirectMessageRejectedData,
+    ) -> Lib3hResult<()> {
+        match self.pending_client_direct_messages.remove(&data.request_id) {
+            Some(pending) => {
+                pending
+                    .msg
+                    .respond(Err(Lib3hError::new(ErrorKind::DirectMessageRejected(
+                        format!("{}: {}", data.from_agent_id, data.reason),
+                    ))))?
+            }
+            None => debug!(
+                "GhostEngine: ignoring late direct message rejection {:?}",
+                data
+            ),
+        }
+        Ok(())
+    }
+
+    /// A remote agent sent a direct message to one of our agents.
+    /// Its retries are not handed to our client again: they get our answer,
+    /// once our client gave it.
+    pub(crate) fn handle_remote_direct_message(
+        &mut self,
+        span: Span,
+        data: DirectMessageData,
+    ) -> Lib3hResult<()> {
+        let key = (data.from_agent_id.clone(), data.request_id.clone());
+        if let Some(received) = self.received_direct_messages.get(&key) {
+            match received.answer.clone() {
+                Some(answer) => {
+                    debug!("GhostEngine: answering again direct message {:?}", key);
+                    self.send_direct_message_answer(answer)?;
+                }
+                None => debug!("GhostEngine: ignoring retried direct message {:?}", key),
+            }
+            return Ok(());
+        }
+        self.received_direct_messages.insert(
+            key.clone(),
+            ReceivedDirectMessage {
+                answer: None,
+                received_at: crate::time::since_epoch_ms(),
+            },
+        );
+        let request = data.clone();
+        self.lib3h_endpoint.request(
+            span,
+            Lib3hToClient::HandleSendDirectMessage(data),
+            Box::new(move |me, response| {
+                let answer = match response {
+                    GhostCallbackData::Response(Ok(
+                        Lib3hToClientResponse::HandleSendDirectMessageResult(data),
+                    )) => DirectMessageAnswer::Result(data),
+                    _ => {
+                        debug!("Client did not answer direct message: {:?}", response);
+                        DirectMessageAnswer::Rejected(DirectMessageRejectedData {
+                            space_address: request.space_address.clone(),
+                            request_id: request.request_id.clone(),
+                            from_agent_id: request.to_agent_id.clone(),
+                            to_agent_id: request.from_agent_id.clone(),
+                            reason: format!("{:?}", response),
+                        })
+                    }
+                };
+                if let Some(received) = me.received_direct_messages.get_mut(&key) {
+                    received.answer = Some(answer.clone());
+                }
+                me.send_direct_message_answer(answer)?;
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Send our answer to a direct message back to its sender
+    fn send_direct_message_answer(&mut self, answer: DirectMessageAnswer) -> Lib3hResult<()> {
+        let (space_address, from_agent_id, to_agent_id, p2p_msg) = match answer {
+            DirectMessageAnswer::Result(data) => (
+                data.space_address.clone(),
+                data.from_agent_id.clone(),
+                data.to_agent_id.clone(),
+                P2pProtocol::DirectMessageResult(data),
+            ),
+            DirectMessageAnswer::Rejected(data) => (
+                data.space_address.clone(),
+                data.from_agent_id.clone(),
+                data.to_agent_id.clone(),
+                P2pProtocol::DirectMessageRejected(data),
+            ),
+        };
+        trace!(
+            "GhostEngine: answering direct message to {}: {:?}",
+            to_agent_id,
+            p2p_msg
+        );
+        let (space_gateway, payload) = self.prepare_direct_peer_msg(
+            space_address,
+            from_agent_id,
+            to_agent_id.clone(),
+            p2p_msg,
+        )?;
+        space_gateway.publish(
+            Span::fixme(),
+            GatewayRequestToChild::Transport(
+                transport::protocol::RequestToChild::create_send_message(
+                    Lib3hUri::with_agent_id(&to_agent_id),
+                    payload,
+                ),
+            ),
+        )?;
+        Ok(())
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{engine::GhostEngine, error::Lib3hError};
+use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::protocol::*;
 
@@ -46,9 +47,13 @@ impl<'engine>
     fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
         // always run the endpoint process loop
         detach_run!(&mut self.lib3h_endpoint, |cs| { cs.process(self) })?;
+        let mut did_work = false;
+        for msg in self.client_defered_publishes.drain(..) {
+            self.lib3h_endpoint.publish(Span::fixme(), msg)?;
+            did_work = true;
+        }
 
         // process any messages from the client to us
-        let mut did_work = false;
         for msg in self.lib3h_endpoint.as_mut().drain_messages() {
             self.handle_msg_from_client(msg)?;
             did_work = true;
@@ -59,6 +64,15 @@ impl<'engine>
 
         // Process the space layer
         did_work = did_work || self.process_space_gateways()?;
+        did_work = self.process_space_defered_sends()? || did_work;
+
+        // Answer or retry pending publishes
+        did_work = self.process_publishes()? || did_work;
+        did_work = self.process_direct_messages()? || did_work;
+        did_work = self.process_streams()? || did_work;
+
+        // Move our shutdown along, if one was asked for
+        did_work = self.process_shutdown()? || did_work;
 
         // Done
         // trace!("({}).process_concrete() did_work = {}", self.name, did_work);
@@ -1,21 +1,29 @@
 use crate::{
     dht::{dht_config::DhtConfig, dht_protocol::*},
     engine::{
-        engine_actor::*, p2p_protocol::*, CanAdvertise, ChainId, EngineConfig, GatewayId,
+        engine_actor::*, p2p_protocol::*, rotation, CanAdvertise, ChainId, EngineConfig, GatewayId,
         GhostEngine, TransportConfig, TransportKeys,
     },
     error::{ErrorKind, Lib3hError, Lib3hResult},
     gateway::{protocol::*, GatewayOutputWrapType, P2pGateway},
+    keystore::Keystore,
+    peer_policy::PeerPolicy,
     track::Tracker,
     transport::{
-        self, memory_mock::ghost_transport_memory::*, protocol::*,
-        websocket::actor::GhostTransportWebsocket, TransportMultiplex,
+        self,
+        memory_mock::ghost_transport_memory::*,
+        protocol::*,
+        websocket::{
+            actor::GhostTransportWebsocket,
+            tls::{TlsCertificate, TlsConfig},
+        },
+        TransportMultiplex,
     },
 };
 use detach::Detach;
 use holochain_tracing::Span;
 use lib3h_crypto_api::CryptoSystem;
-use lib3h_ghost_actor::{prelude::*, RequestId};
+use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::{
     data_types::*,
     protocol::*,
@@ -41,8 +49,62 @@ impl<'engine> GhostEngine<'engine> {
         name: &str,
         dht_factory: DhtFactory,
     ) -> Lib3hResult<Self> {
-        let transport_keys = TransportKeys::new(crypto.as_crypto_system())?;
+        let transport_keys =
+            TransportKeys::from_config(crypto.as_crypto_system(), &config.transport_seed)?;
+        Self::with_transport_keys(
+            span,
+            crypto,
+            config,
+            name,
+            dht_factory,
+            transport_keys,
+            None,
+        )
+    }
+
+    /// Constructor for a GhostEngine whose TransportId keypair is stored in
+    /// `keystore`, instead of derived from `config.transport_seed`
+    pub fn with_keystore(
+        span: Span,
+        crypto: Box<dyn CryptoSystem>,
+        config: EngineConfig,
+        name: &str,
+        dht_factory: DhtFactory,
+        mut keystore: Keystore,
+    ) -> Lib3hResult<Self> {
+        if config.transport_seed.is_some() {
+            return Err(Lib3hError::new_other(
+                "the transport keys come either from a seed or from a keystore",
+            ));
+        }
+        let transport_keys = TransportKeys::from_keystore(&mut keystore)?;
+        Self::with_transport_keys(
+            span,
+            crypto,
+            config,
+            name,
+            dht_factory,
+            transport_keys,
+            Some(keystore),
+        )
+    }
 
+    fn with_transport_keys(
+        span: Span,
+        crypto: Box<dyn CryptoSystem>,
+        mut config: EngineConfig,
+        name: &str,
+        dht_factory: DhtFactory,
+        transport_keys: TransportKeys,
+        keystore: Option<Keystore>,
+    ) -> Lib3hResult<Self> {
+        for rotation in
+            rotation::load_transport_rotations(&config.work_dir, &transport_keys.transport_id)?
+        {
+            if !config.transport_rotations.contains(&rotation) {
+                config.transport_rotations.push(rotation);
+            }
+        }
         // This will change when multi-transport is impelmented
         assert_eq!(config.transport_configs.len(), 1);
         let transport_config = config.transport_configs[0].clone();
@@ -51,7 +113,19 @@ impl<'engine> GhostEngine<'engine> {
 
         let transport: DynTransportActor = match &transport_config {
             TransportConfig::Websocket(tls_config) => {
-                let tls = tls_config.clone();
+                let tls = match tls_config {
+                    TlsConfig::Ephemeral {
+                        require_client_cert,
+                    } => TlsConfig::PinnedCertificate {
+                        certificate: TlsCertificate::build_ephemeral(
+                            &config.work_dir,
+                            &transport_keys.transport_id,
+                            |data| Ok(transport_keys.sign(crypto.as_crypto_system(), data)?),
+                        )?,
+                        require_client_cert: *require_client_cert,
+                    },
+                    _ => tls_config.clone(),
+                };
                 Box::new(GhostTransportWebsocket::new(
                     transport_id,
                     tls,
@@ -68,7 +142,8 @@ impl<'engine> GhostEngine<'engine> {
             timestamp: crate::time::since_epoch_ms(),
         };
         // Create DhtConfig
-        let dht_config = DhtConfig::with_engine_config(&transport_id_uri, &config);
+        let mut dht_config = DhtConfig::with_engine_config(&transport_id_uri, &config);
+        dht_config.set_peer_policy(PeerPolicy::for_network(&config.network_policy)?);
         debug!("New MOCK Engine {} -> {:?}", name, this_net_peer);
         let mut multiplexer = Detach::new(GatewayParentWrapper::new(
             TransportMultiplex::new(P2pGateway::new(
@@ -110,6 +185,24 @@ impl<'engine> GhostEngine<'engine> {
                 Ok(())
             }),
         )?;
+        for spec in config.extra_bind_urls.iter() {
+            let spec = spec.clone();
+            multiplexer.as_mut().request(
+                Span::fixme(),
+                GatewayRequestToChild::Transport(RequestToChild::Bind { spec: spec.clone() }),
+                Box::new(move |_me: &mut GhostEngine<'engine>, response| {
+                    match response {
+                        GhostCallbackData::Response(Ok(
+                            GatewayRequestToChildResponse::Transport(RequestToChildResponse::Bind(
+                                bind_data,
+                            )),
+                        )) => debug!("bound {} to {:?}", spec, bind_data.bound_urls),
+                        _ => error!("failed to bind {}: {:?}", spec, response),
+                    }
+                    Ok(())
+                }),
+            )?;
+        }
 
         let (endpoint_parent, endpoint_self) = create_ghost_channel();
         let mut engine = GhostEngine {
@@ -123,8 +216,20 @@ impl<'engine> GhostEngine<'engine> {
             network_connections: HashSet::new(),
             space_gateway_map: HashMap::new(),
             transport_keys,
+            keystore,
+            transport_rotations: HashMap::new(),
             multiplexer_defered_sends: Vec::new(),
+            multiplexer_defered_publishes: Vec::new(),
+            client_defered_publishes: Vec::new(),
+            space_defered_sends: Vec::new(),
             pending_client_direct_messages: HashMap::new(),
+            received_direct_messages: HashMap::new(),
+            streams: HashMap::new(),
+            topic_subscribers: HashMap::new(),
+            pending_publishes: HashMap::new(),
+            pending_entry_stores: HashMap::new(),
+            rejected_aspects: HashMap::new(),
+            shutdown: None,
             client_endpoint: Some(endpoint_parent),
             lib3h_endpoint: Detach::new(
                 endpoint_self
@@ -208,12 +313,129 @@ impl<'engine> GhostEngine<'engine> {
         )
     }
 
+    /// Ask the network gateway for its traffic statistics,
+    /// which carry those of the transport below it
+    fn handle_get_transport_stats(&mut self, msg: ClientToLib3hMessage) -> GhostResult<()> {
+        self.multiplexer.request(
+            Span::fixme(),
+            GatewayRequestToChild::Transport(transport::protocol::RequestToChild::GetStats),
+            Box::new(move |_me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Transport(
+                        transport::protocol::RequestToChildResponse::GetStats(stats),
+                    ))) => {
+                        msg.respond(Ok(ClientToLib3hResponse::GetTransportStatsResult(stats)))?
+                    }
+                    GhostCallbackData::Response(Err(e)) => msg.respond(Err(e))?,
+                    GhostCallbackData::Timeout(bt) => {
+                        msg.respond(Err(format!("timeout: {:?}", bt).into()))?
+                    }
+                    _ => msg.respond(Err(format!("bad response: {:?}", response).into()))?,
+                }
+                Ok(())
+            }),
+        )
+    }
+
+    /// Answer with our connections, joined spaces and the peers of the network DHT
+    fn handle_get_network_state(&mut self, msg: ClientToLib3hMessage) -> GhostResult<()> {
+        self.multiplexer.request(
+            Span::fixme(),
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestState),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestState(dht_state),
+                    ))) => {
+                        let mut connections: Vec<Lib3hUri> =
+                            me.network_connections.iter().cloned().collect();
+                        connections.sort_by_key(|uri| uri.to_string());
+                        let mut joined_spaces: Vec<JoinedSpaceData> = me
+                            .space_gateway_map
+                            .keys()
+                            .map(|(space_address, agent_id)| JoinedSpaceData {
+                                space_address: space_address.clone(),
+                                agent_id: agent_id.clone(),
+                            })
+                            .collect();
+                        joined_spaces.sort_by_key(|space| {
+                            (space.space_address.to_string(), space.agent_id.to_string())
+                        });
+                        let this_peer = PeerStateData {
+                            peer_name: me.this_net_peer.peer_name.clone(),
+                            peer_location: me.this_net_peer.peer_location.clone(),
+                            timestamp: me.this_net_peer.timestamp,
+                            is_alive: true,
+                        };
+                        msg.respond(Ok(ClientToLib3hResponse::GetNetworkStateResult(
+                            NetworkStateData {
+                                this_peer,
+                                connections,
+                                peers: dht_state.peer_states(),
+                                joined_spaces,
+                            },
+                        )))?
+                    }
+                    GhostCallbackData::Response(Err(e)) => msg.respond(Err(e))?,
+                    GhostCallbackData::Timeout(bt) => {
+                        msg.respond(Err(format!("timeout: {:?}", bt).into()))?
+                    }
+                    _ => msg.respond(Err(format!("bad response: {:?}", response).into()))?,
+                }
+                Ok(())
+            }),
+        )
+    }
+
+    /// Answer with the peers and entry holdings of a space DHT
+    fn handle_get_space_state(
+        &mut self,
+        msg: ClientToLib3hMessage,
+        data: SpaceData,
+    ) -> GhostResult<()> {
+        let space_gateway = match self.get_space(&data.space_address, &data.agent_id) {
+            Ok(space_gateway) => space_gateway,
+            Err(e) => return msg.respond(Err(e)),
+        };
+        space_gateway.request(
+            Span::fixme(),
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestState),
+            Box::new(move |_me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestState(dht_state),
+                    ))) => msg.respond(Ok(ClientToLib3hResponse::GetSpaceStateResult(
+                        SpaceStateData {
+                            space_address: data.space_address,
+                            agent_id: data.agent_id,
+                            peers: dht_state.peer_states(),
+                            entry_count: dht_state.entry_count,
+                            aspect_count: dht_state.aspect_count,
+                        },
+                    )))?,
+                    GhostCallbackData::Response(Err(e)) => msg.respond(Err(e))?,
+                    GhostCallbackData::Timeout(bt) => {
+                        msg.respond(Err(format!("timeout: {:?}", bt).into()))?
+                    }
+                    _ => msg.respond(Err(format!("bad response: {:?}", response).into()))?,
+                }
+                Ok(())
+            }),
+        )
+    }
+
     /// Process any Client events or requests
     pub(crate) fn handle_msg_from_client(
         &mut self,
         mut msg: ClientToLib3hMessage,
     ) -> GhostResult<()> {
         let span = msg.span().child("handle_msg_from_client");
+        if self.is_shut_down() {
+            if msg.is_request() {
+                msg.respond(Err(Lib3hError::new_other("Engine is shut down")))?;
+            }
+            return Ok(());
+        }
         match msg.take_message().expect("exists") {
             ClientToLib3h::Bootstrap(data) => {
                 trace!("ClientToLib3h::Bootstrap: {:?}", &data);
@@ -238,10 +460,56 @@ impl<'engine> GhostEngine<'engine> {
                 self.handle_direct_message(span.follower("handle_direct_message"), msg, data)
                     .map_err(|e| GhostError::from(e.to_string()))
             }
+            ClientToLib3h::SendSignal(data) => {
+                trace!("ClientToLib3h::SendSignal: {:?}", data);
+                self.handle_send_signal(span.follower("handle_send_signal"), msg, data)
+                    .map_err(|e| GhostError::from(e.to_string()))
+            }
+            ClientToLib3h::OpenStream(data) => {
+                trace!("ClientToLib3h::OpenStream: {:?}", data);
+                self.handle_open_stream(span.follower("handle_open_stream"), msg, data)
+                    .map_err(|e| GhostError::from(e.to_string()))
+            }
+            ClientToLib3h::SendStreamChunk(data) => {
+                trace!("ClientToLib3h::SendStreamChunk: {:?}", data);
+                self.handle_send_stream_chunk(span.follower("handle_send_stream_chunk"), msg, data)
+                    .map_err(|e| GhostError::from(e.to_string()))
+            }
+            ClientToLib3h::CloseStream(data) => {
+                trace!("ClientToLib3h::CloseStream: {:?}", data);
+                let result = self
+                    .handle_close_stream(&data)
+                    .map(|_| ClientToLib3hResponse::CloseStreamResult);
+                msg.respond(result)
+            }
+            ClientToLib3h::Subscribe(data) => {
+                trace!("ClientToLib3h::Subscribe: {:?}", data);
+                let result = self
+                    .handle_subscribe(data)
+                    .map(|_| ClientToLib3hResponse::SubscribeResult);
+                msg.respond(result)
+            }
+            ClientToLib3h::Unsubscribe(data) => {
+                trace!("ClientToLib3h::Unsubscribe: {:?}", data);
+                let result = self
+                    .handle_unsubscribe(data)
+                    .map(|_| ClientToLib3hResponse::UnsubscribeResult);
+                msg.respond(result)
+            }
+            ClientToLib3h::PublishTopic(data) => {
+                trace!("ClientToLib3h::PublishTopic: {:?}", data);
+                self.handle_publish_topic(span.follower("handle_publish_topic"), msg, data)
+                    .map_err(|e| GhostError::from(e.to_string()))
+            }
             ClientToLib3h::PublishEntry(data) => {
                 trace!("ClientToLib3h::PublishEntry: {:?}", data);
-                self.handle_publish_entry(span.follower("handle_publish_entry"), &data)
-                    .map_err(|e| GhostError::from(e.to_string()))
+                match self.handle_publish_entry(span.follower("handle_publish_entry"), &data) {
+                    Ok(()) => self
+                        .track_publish(msg, data)
+                        .map_err(|e| GhostError::from(e.to_string())),
+                    Err(e) if msg.is_request() => msg.respond(Err(e)),
+                    Err(e) => Err(GhostError::from(e.to_string())),
+                }
             }
             ClientToLib3h::QueryEntry(data) => {
                 trace!("ClientToLib3h::QueryEntry: {:?}", data);
@@ -251,8 +519,76 @@ impl<'engine> GhostEngine<'engine> {
                 trace!("ClientToLib3h::QueryEntry: res = {:?}", res);
                 res
             }
+            ClientToLib3h::DeleteEntry(data) => {
+                trace!("ClientToLib3h::DeleteEntry: {:?}", data);
+                let result = self
+                    .handle_delete_entry(span.follower("handle_delete_entry"), data)
+                    .map(|_| ClientToLib3hResponse::DeleteEntryResult);
+                msg.respond(result)
+            }
             ClientToLib3h::FetchEntry(_) => panic!("FetchEntry Deprecated"),
+            ClientToLib3h::SetNetworkPolicy(data) => {
+                trace!("ClientToLib3h::SetNetworkPolicy: {:?}", data);
+                let result = self
+                    .handle_set_network_policy(span.follower("handle_set_network_policy"), data)
+                    .map(|_| ClientToLib3hResponse::SetNetworkPolicyResult);
+                msg.respond(result)
+            }
+            ClientToLib3h::RotateTransportKey(data) => {
+                trace!("ClientToLib3h::RotateTransportKey");
+                self.handle_rotate_transport_key(msg, data)
+                    .map_err(|e| GhostError::from(e.to_string()))
+            }
+            ClientToLib3h::GetTransportStats => {
+                trace!("ClientToLib3h::GetTransportStats");
+                self.handle_get_transport_stats(msg)
+            }
+            ClientToLib3h::GetNetworkState => {
+                trace!("ClientToLib3h::GetNetworkState");
+                self.handle_get_network_state(msg)
+            }
+            ClientToLib3h::GetSpaceState(data) => {
+                trace!("ClientToLib3h::GetSpaceState: {:?}", data);
+                self.handle_get_space_state(msg, data)
+            }
+            ClientToLib3h::Shutdown => {
+                trace!("ClientToLib3h::Shutdown");
+                self.handle_shutdown(msg)
+            }
+        }
+    }
+
+    /// Replace our network policy and hand the relevant part of it to every gateway.
+    /// TransportIds we know the rotations of are followed to their new ids.
+    pub(crate) fn handle_set_network_policy(
+        &mut self,
+        span: Span,
+        policy: NetworkPolicyData,
+    ) -> Lib3hResult<()> {
+        let policy = self.follow_transport_rotations(policy);
+        // Make sure the whole policy is valid before applying any of it
+        let network_peer_policy = PeerPolicy::for_network(&policy)?;
+        let mut space_peer_policies = Vec::new();
+        for chain_id in self.space_gateway_map.keys() {
+            let peer_policy = PeerPolicy::for_space(&policy, &chain_id.0)?;
+            space_peer_policies.push((chain_id.clone(), peer_policy));
+        }
+        self.multiplexer.publish(
+            span.follower("multiplexer.publish"),
+            GatewayRequestToChild::UpdatePeerPolicy(network_peer_policy),
+        )?;
+        for (chain_id, peer_policy) in space_peer_policies {
+            let space_gateway = self
+                .space_gateway_map
+                .get_mut(&chain_id)
+                .expect("Should have space gateway");
+            space_gateway.publish(
+                span.follower("space_gateway.publish"),
+                GatewayRequestToChild::UpdatePeerPolicy(peer_policy),
+            )?;
         }
+        self.config.network_policy = policy;
+        Ok(())
     }
 
     /// create a new gateway and add it to our gateway map
@@ -266,7 +602,11 @@ impl<'engine> GhostEngine<'engine> {
             return Err(Lib3hError::new_other("Already joined space"));
         }
         let agent_id_uri = Lib3hUri::with_agent_id(&agent_id);
-        let dht_config = DhtConfig::with_engine_config(&agent_id_uri, &self.config);
+        let mut dht_config = DhtConfig::with_engine_config(&agent_id_uri, &self.config);
+        dht_config.set_peer_policy(PeerPolicy::for_space(
+            &self.config.network_policy,
+            &space_address,
+        )?);
 
         // Create new space gateway for this ChainId
         let uniplex = TransportEndpointAsActor::new(
@@ -323,6 +663,42 @@ impl<'engine> GhostEngine<'engine> {
         // TODO END
     }
 
+    fn broadcast_leave(
+        &mut self,
+        span: Span,
+        space_address: SpaceHash,
+        peer: PeerData,
+    ) -> Lib3hResult<()> {
+        trace!(
+            "{} - Broadcasting LeaveSpace: {}, {}",
+            self.name,
+            space_address,
+            peer.peer_name,
+        );
+        let payload = P2pProtocol::BroadcastLeaveSpace(self.sign_leave_space(space_address, peer)?);
+        self.multiplexer
+            .publish(span, GatewayRequestToChild::SendAll(payload.into_bytes()))?;
+        Ok(())
+    }
+
+    /// Our signed departure of this agent from this space
+    pub(crate) fn sign_leave_space(
+        &self,
+        space_address: SpaceHash,
+        peer: PeerData,
+    ) -> Lib3hResult<LeaveSpaceData> {
+        let mut leave = LeaveSpaceData {
+            space_address,
+            peer_data: peer,
+            signature: Vec::new().into(),
+        };
+        leave.signature = self
+            .transport_keys
+            .sign(self.crypto.as_ref(), &leave.signed_content())?
+            .into();
+        Ok(leave)
+    }
+
     #[allow(non_snake_case)]
     fn handle_HandleGetAuthoringEntryListResult(
         &mut self,
@@ -516,21 +892,147 @@ impl<'engine> GhostEngine<'engine> {
         Ok(())
     }
 
+    /// Leave this space for this agent, if part of it:
+    /// destroy its gateway, tell the other nodes and let the client drop
+    /// what it was holding for the space.
+    /// There is nothing to hand off before leaving: every node of a space
+    /// mirrors all of its entries.
+    fn handle_leave_space(&mut self, span: Span, msg: &SpaceData) -> Lib3hResult<()> {
+        let this_peer = self.remove_space_gateway(msg)?;
+        let left_peer = PeerData {
+            timestamp: crate::time::since_epoch_ms(),
+            ..this_peer
+        };
+        self.broadcast_leave(
+            span.child("broadcast_leave"),
+            msg.space_address.clone(),
+            left_peer,
+        )?;
+
+        let list_data = GetListData {
+            space_address: msg.space_address.clone(),
+            provider_agent_id: msg.agent_id.clone(),
+            request_id: self.request_track.reserve(),
+        };
+        self.lib3h_endpoint.request(
+            span.follower("drop held entries"),
+            Lib3hToClient::HandleGetGossipingEntryList(list_data),
+            Box::new(|me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(
+                        Lib3hToClientResponse::HandleGetGossipingEntryListResult(msg),
+                    )) => me.drop_held_entries(msg),
+                    // we left anyway, the client just keeps what it holds
+                    response => warn!("could not list entries to drop: {:?}", response),
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
     /// Destroy gateway for this agent in this space, if part of it.
-    fn handle_leave_space(&mut self, _span: Span, msg: &SpaceData) -> Lib3hResult<()> {
+    /// Return our peer in that space.
+    pub(crate) fn remove_space_gateway(&mut self, msg: &SpaceData) -> Lib3hResult<PeerData> {
         let chain_id = (msg.space_address.clone(), msg.agent_id.clone());
+        self.rejected_aspects.remove(&chain_id);
+        self.drop_topic_subscriber(&msg.space_address, &msg.agent_id);
         match self.space_gateway_map.remove(&chain_id) {
-            Some(_space) => {
+            Some(mut space_gateway) => {
                 self.multiplexer
                     .as_mut()
                     .as_mut()
                     .remove_agent_space_route(&msg.space_address, &msg.agent_id);
-                Ok(())
+                Ok(space_gateway.as_mut().as_mut().this_peer())
             }
             None => Err(Lib3hError::new_other("Not part of that space")),
         }
     }
 
+    /// Tell the client it does not have to hold the entries of a space it left,
+    /// unless another of its agents in that space still holds them.
+    fn drop_held_entries(&mut self, entry_list: EntryListData) {
+        let remaining: Vec<ChainId> = self
+            .space_gateway_map
+            .keys()
+            .filter(|(space_address, _)| space_address == &entry_list.space_address)
+            .cloned()
+            .collect();
+        for (entry_address, aspect_address_list) in entry_list.address_map {
+            let drop_data = DropEntryData {
+                space_address: entry_list.space_address.clone(),
+                request_id: self.request_track.reserve(),
+                entry_address,
+                aspect_address_list: if aspect_address_list.is_empty() {
+                    None
+                } else {
+                    Some(aspect_address_list)
+                },
+            };
+            self.drop_entry_unless_held(drop_data, remaining.clone());
+        }
+    }
+
+    /// Ask the DHTs of the `remaining` agents in turn which aspects they hold,
+    /// and drop the others once all answered.
+    /// Deferred since we are called back by the client endpoint.
+    fn drop_entry_unless_held(
+        &mut self,
+        mut drop_data: DropEntryData,
+        mut remaining: Vec<ChainId>,
+    ) {
+        let space_gateway = match remaining.pop() {
+            None => {
+                self.client_defered_publishes
+                    .push(Lib3hToClient::HandleDropEntry(drop_data));
+                return;
+            }
+            Some(chain_id) => match self.space_gateway_map.get_mut(&chain_id) {
+                Some(space_gateway) => space_gateway,
+                None => return self.drop_entry_unless_held(drop_data, remaining),
+            },
+        };
+        let entry_address = drop_data.entry_address.clone();
+        let result = space_gateway.request(
+            Span::fixme(),
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestAspectsOf(entry_address.clone())),
+            Box::new(move |me, response| {
+                let held_aspects = match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestAspectsOf(held_aspects),
+                    ))) => held_aspects,
+                    response => {
+                        warn!(
+                            "keeping {}, could not tell if it is held: {:?}",
+                            drop_data.entry_address, response
+                        );
+                        return Ok(());
+                    }
+                };
+                if let Some(held_aspects) = held_aspects {
+                    match drop_data.aspect_address_list.as_mut() {
+                        // the whole entry is still held
+                        None => return Ok(()),
+                        Some(aspect_address_list) => {
+                            aspect_address_list.retain(|aspect| !held_aspects.contains(aspect));
+                            if aspect_address_list.is_empty() {
+                                return Ok(());
+                            }
+                        }
+                    }
+                }
+                me.drop_entry_unless_held(drop_data, remaining);
+                Ok(())
+            }),
+        );
+        if let Err(e) = result {
+            warn!(
+                "keeping {}, could not tell if it is held: {:?}",
+                entry_address, e
+            );
+        }
+    }
+
     pub(crate) fn prepare_direct_peer_msg(
         &mut self,
         space_address: SpaceHash,
@@ -567,63 +1069,6 @@ impl<'engine> GhostEngine<'engine> {
         Ok((space_gateway.as_mut(), Opaque::from(payload)))
     }
 
-    fn handle_direct_message(
-        &mut self,
-        span: Span,
-        client_to_lib3h_msg: ClientToLib3hMessage,
-        mut msg: DirectMessageData,
-    ) -> Lib3hResult<()> {
-        let to_agent_id = msg.to_agent_id.clone();
-        // Generate a new request_id for the network transport exchange.
-        // we can overwrite the value in the DirectMessageData because the ghost tracker will handle
-        // the request response pairing
-        let request_id = RequestId::new();
-        trace!(
-            "GhostEngine: mutating request id from {:?} to {:?} for {:?}",
-            msg.request_id,
-            request_id,
-            msg
-        );
-        msg.request_id = request_id.clone().into();
-        let (space_gateway, payload) = match self.prepare_direct_peer_msg(
-            msg.space_address.clone(),
-            msg.from_agent_id.clone(),
-            msg.to_agent_id.clone(),
-            P2pProtocol::DirectMessage(msg),
-        ) {
-            Ok(r) => r,
-            Err(e) => {
-                return Ok(client_to_lib3h_msg.respond(Err(e))?);
-            }
-        };
-
-        space_gateway.request(
-            span,
-            GatewayRequestToChild::Transport(transport::protocol::RequestToChild::create_send_message(
-                Lib3hUri::with_agent_id(&to_agent_id),
-                payload,
-            )),
-            Box::new(|me, response| {
-                debug!(
-                    "GhostEngine: response to handle_direct_message message: {:?}",
-                    response
-                );
-                match response {
-                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Transport(
-                        transport::protocol::RequestToChildResponse::SendMessageSuccess,
-                    ))) => {
-                        trace!("GhostEngine: insert pending client direct message with request id {:?}", request_id);
-                        me.pending_client_direct_messages
-                            .insert(request_id, client_to_lib3h_msg);
-                    }
-                    _ => client_to_lib3h_msg.respond(Err(format!("{:?}", response).into()))?,
-                };
-                Ok(())
-            }),
-        )?;
-        Ok(())
-    }
-
     fn handle_publish_entry(&mut self, span: Span, msg: &ProvidedEntryData) -> Lib3hResult<()> {
         // #fullsync - reflecting hold for now
         for aspect in &msg.entry.aspect_list {
@@ -766,10 +1211,18 @@ pub fn handle_GossipTo<
 mod tests {
     use super::*;
     use crate::{
-        dht::mirror_dht::MirrorDht, engine::GatewayId, tests::enable_logging_for_test,
+        dht::{dht_config::DEFAULT_TOMBSTONE_RETENTION_MS, mirror_dht::MirrorDht},
+        engine::{
+            p2p_protocol::{DirectMessageRejectedData, LeaveData, StreamAckData},
+            space_layer::MAX_REJECTED_ASPECTS,
+            verify_transport_rotation, verify_transport_signature, DirectMessageConfig, GatewayId,
+            PublishConfig, StreamConfig, TransportSeed,
+        },
+        tests::{enable_logging_for_test, request_for_test, test_callback, TestResponses},
         transport::memory_mock::memory_server,
     };
     use holochain_tracing::test_span;
+    use lib3h_crypto_api::Buffer;
     use lib3h_ghost_actor::{ghost_test_harness::ProcessingOptions, wait_can_track_did_work};
     use lib3h_sodium::SodiumCryptoSystem;
     use std::path::PathBuf;
@@ -778,6 +1231,8 @@ mod tests {
         //    state: String,
     }
 
+    type EngineResponses = TestResponses<ClientToLib3hResponse, Lib3hError>;
+
     // Real test network-id should be a hc version of sha256 of a string
     fn test_network_id() -> GatewayId {
         GatewayId {
@@ -786,19 +1241,31 @@ mod tests {
         }
     }
 
-    fn make_test_engine(test_net: &str) -> GhostEngine<'static> {
-        let crypto = Box::new(SodiumCryptoSystem::new());
-        let config = EngineConfig {
+    fn make_test_config(test_net: &str) -> EngineConfig {
+        EngineConfig {
             network_id: test_network_id(),
             transport_configs: vec![TransportConfig::Memory(test_net.into())],
             bootstrap_nodes: vec![],
             work_dir: PathBuf::new(),
             log_level: 'd',
             bind_url: Lib3hUri::with_memory("test_engine"),
+            extra_bind_urls: Vec::new(),
             dht_gossip_interval: 100,
             dht_timeout_threshold: 1000,
+            dht_tombstone_retention: DEFAULT_TOMBSTONE_RETENTION_MS,
             dht_custom_config: vec![],
-        };
+            network_policy: NetworkPolicyData::default(),
+            publish: PublishConfig::default(),
+            direct_message: DirectMessageConfig::default(),
+            stream: StreamConfig::default(),
+            transport_seed: None,
+            transport_rotations: Vec::new(),
+        }
+    }
+
+    fn make_test_engine(test_net: &str) -> GhostEngine<'static> {
+        let crypto = Box::new(SodiumCryptoSystem::new());
+        let config = make_test_config(test_net);
         let dht_factory = MirrorDht::new_with_config;
 
         let engine =
@@ -823,23 +1290,117 @@ mod tests {
         // check that bootstrap nodes were connected to
     }
 
-    fn make_test_join_request() -> SpaceData {
-        SpaceData {
-            /// Identifier of this request
-            request_id: "foo_id".into(),
-            space_address: "space_addr".into(),
-            agent_id: "agent_id".into(),
+    #[test]
+    fn test_ghost_engine_transport_seed() {
+        let crypto = SodiumCryptoSystem::new();
+        let dir = tempfile::tempdir().unwrap();
+        let seed_file = Some(TransportSeed::File(dir.path().join("seed")));
+        let keys = TransportKeys::from_config(&crypto, &seed_file).unwrap();
+        // same TransportId on restart
+        let restarted_keys = TransportKeys::from_config(&crypto, &seed_file).unwrap();
+        assert_eq!(keys.transport_id, restarted_keys.transport_id);
+        let seed = std::fs::read(dir.path().join("seed")).unwrap();
+        let seeded_keys =
+            TransportKeys::from_config(&crypto, &Some(TransportSeed::Seed(seed))).unwrap();
+        assert_eq!(keys.transport_id, seeded_keys.transport_id);
+        // a new one without a seed
+        let random_keys = TransportKeys::from_config(&crypto, &None).unwrap();
+        assert_ne!(keys.transport_id, random_keys.transport_id);
+        assert!(
+            TransportKeys::from_config(&crypto, &Some(TransportSeed::Seed(vec![1, 2, 3]))).is_err()
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(dir.path().join("seed")).unwrap();
+            assert_eq!(0o600, metadata.permissions().mode() & 0o777);
         }
     }
 
     #[test]
-    fn test_ghost_engine_join() {
-        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_join");
+    fn test_ghost_engine_transport_seed_redacted() {
+        let seed = TransportSeed::Seed(vec![42; 32]);
+        assert_eq!("Seed(\"<redacted>\")", format!("{:?}", seed));
+        let config = EngineConfig {
+            transport_seed: Some(seed),
+            ..make_test_config("test_ghost_engine_transport_seed_redacted")
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        assert!(!json.contains("\"transport_seed\""));
+        let config: EngineConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(None, config.transport_seed);
+        let seed_file = Some(TransportSeed::File("seed".into()));
+        let config = EngineConfig {
+            transport_seed: seed_file.clone(),
+            ..config
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        let config: EngineConfig = serde_json::from_str(&json).unwrap();
+        assert_eq!(seed_file, config.transport_seed);
+    }
 
-        let req_data = make_test_join_request();
-        let result = lib3h.as_mut().handle_join(test_span(""), &req_data);
-        assert!(result.is_ok());
-        assert_eq!(lib3h.as_ref().space_gateway_map.len(), 1);
+    #[test]
+    fn test_ghost_engine_keystore() {
+        let crypto = SodiumCryptoSystem::new().set_pwhash_interactive();
+        let dir = tempfile::tempdir().unwrap();
+        let passphrase: Box<dyn Buffer> = Box::new(b"pass".to_vec());
+        let start = |config: EngineConfig| {
+            let keystore =
+                Keystore::with_work_dir(crypto.box_clone(), dir.path(), &*passphrase).unwrap();
+            GhostEngine::with_keystore(
+                test_span(""),
+                crypto.box_clone(),
+                config,
+                "test_engine",
+                MirrorDht::new_with_config,
+                keystore,
+            )
+        };
+        let config = make_test_config("test_ghost_engine_keystore");
+        let transport_id = start(config.clone()).unwrap().transport_keys.transport_id;
+        // same TransportId on restart
+        let mut restarted = start(config.clone()).unwrap();
+        assert_eq!(transport_id, restarted.transport_keys.transport_id);
+        assert_eq!(
+            Lib3hUri::with_transport_id(&transport_id),
+            restarted.this_net_peer.peer_name
+        );
+        // and the one we rotated to after that
+        let rotation = restarted
+            .rotate_transport_keys(&RotateTransportKeyData {
+                seed: vec![1; 32].into(),
+            })
+            .unwrap();
+        let restarted = start(config.clone()).unwrap();
+        assert_eq!(
+            rotation.new_transport_id,
+            restarted.transport_keys.transport_id
+        );
+        // the keystore or a seed, not both
+        let seeded_config = EngineConfig {
+            transport_seed: Some(TransportSeed::File(dir.path().join("seed"))),
+            ..config
+        };
+        assert!(start(seeded_config).is_err());
+    }
+
+    fn make_test_join_request() -> SpaceData {
+        SpaceData {
+            /// Identifier of this request
+            request_id: "foo_id".into(),
+            space_address: "space_addr".into(),
+            agent_id: "agent_id".into(),
+        }
+    }
+
+    #[test]
+    fn test_ghost_engine_join() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_join");
+
+        let req_data = make_test_join_request();
+        let result = lib3h.as_mut().handle_join(test_span(""), &req_data);
+        assert!(result.is_ok());
+        assert_eq!(lib3h.as_ref().space_gateway_map.len(), 1);
         let result = lib3h.as_mut().handle_join(test_span(""), &req_data);
         assert_eq!(
             "Err(Lib3hError(Other(\"Already joined space\")))",
@@ -862,131 +1423,2248 @@ mod tests {
         );
     }
 
-    // this test simulates an unbind happening in our transport layer
-    // i.e. we moved to a different cell tower, or someone turned off the
-    // networking interface
     #[test]
-    fn test_ghost_engine_unbind() {
-        enable_logging_for_test(true);
-        let mut core = MockCore {
-            //        state: "".to_string(),
+    fn test_ghost_engine_leave_drops_entries() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_leave_drops_entries");
+        let mut core = MockCore {};
+        let req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        lib3h
+            .as_mut()
+            .handle_leave_space(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        let mut msgs = lib3h.drain_messages();
+        assert_eq!(1, msgs.len());
+        let mut msg = msgs.remove(0);
+        let list_data = match msg.take_message() {
+            Some(Lib3hToClient::HandleGetGossipingEntryList(list_data)) => list_data,
+            other => panic!("unexpected message: {:?}", other),
         };
-        let network_name = "test_ghost_engine_unbind";
-        let mut engine = make_test_engine_wrapper(network_name);
+        let mut address_map = std::collections::HashMap::new();
+        address_map.insert("entry_1".into(), vec!["aspect_1".into()]);
+        address_map.insert("entry_2".into(), vec![]);
+        msg.respond(Ok(
+            Lib3hToClientResponse::HandleGetGossipingEntryListResult(EntryListData {
+                space_address: list_data.space_address,
+                provider_agent_id: list_data.provider_agent_id,
+                request_id: list_data.request_id,
+                address_map,
+            }),
+        ))
+        .unwrap();
+        lib3h.process(&mut core).unwrap();
+
+        let mut dropped: Vec<String> = lib3h
+            .drain_messages()
+            .iter_mut()
+            .map(|msg| match msg.take_message() {
+                Some(Lib3hToClient::HandleDropEntry(data)) => {
+                    assert_eq!(req_data.space_address, data.space_address);
+                    data.entry_address.to_string()
+                }
+                other => panic!("unexpected message: {:?}", other),
+            })
+            .collect();
+        dropped.sort();
+        assert_eq!(vec!["entry_1", "entry_2"], dropped);
+    }
+
+    #[test]
+    fn test_ghost_engine_leave_keeps_entries_held_by_other_agents() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_leave_keeps_entries");
+        let mut core = MockCore {};
         let req_data = make_test_join_request();
-        let result = engine.as_mut().handle_join(test_span(""), &req_data);
-        assert!(result.is_ok());
-        let network = {
-            let mut verse = memory_server::get_memory_verse();
-            verse.get_network(network_name)
+        let mut other_data = req_data.clone();
+        other_data.agent_id = "other_agent_id".into();
+        for space_data in &[&req_data, &other_data] {
+            lib3h
+                .as_mut()
+                .handle_join(test_span(""), space_data)
+                .unwrap();
+        }
+        // the other agent holds part of what is listed below
+        for (entry_address, aspect_address) in &[("entry_1", "aspect_1"), ("entry_3", "aspect_4")] {
+            lib3h
+                .as_mut()
+                .space_gateway_map
+                .get_mut(&(
+                    other_data.space_address.clone(),
+                    other_data.agent_id.clone(),
+                ))
+                .unwrap()
+                .publish(
+                    test_span(""),
+                    GatewayRequestToChild::Dht(DhtRequestToChild::HoldEntryAspectAddress(
+                        EntryData {
+                            entry_address: (*entry_address).into(),
+                            aspect_list: vec![EntryAspectData {
+                                aspect_address: (*aspect_address).into(),
+                                type_hint: "test".into(),
+                                aspect: b"aspect".to_vec().into(),
+                                publish_ts: 0,
+                            }],
+                        },
+                    )),
+                )
+                .unwrap();
+        }
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        lib3h
+            .as_mut()
+            .handle_leave_space(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        let mut msgs = lib3h.drain_messages();
+        assert_eq!(1, msgs.len());
+        let mut msg = msgs.remove(0);
+        let list_data = match msg.take_message() {
+            Some(Lib3hToClient::HandleGetGossipingEntryList(list_data)) => list_data,
+            other => panic!("unexpected message: {:?}", other),
         };
+        let mut address_map = std::collections::HashMap::new();
+        address_map.insert("entry_1".into(), vec!["aspect_1".into(), "aspect_2".into()]);
+        address_map.insert("entry_2".into(), vec!["aspect_3".into()]);
+        address_map.insert("entry_3".into(), vec![]);
+        msg.respond(Ok(
+            Lib3hToClientResponse::HandleGetGossipingEntryListResult(EntryListData {
+                space_address: list_data.space_address,
+                provider_agent_id: list_data.provider_agent_id,
+                request_id: list_data.request_id,
+                address_map,
+            }),
+        ))
+        .unwrap();
+        for _ in 0..5 {
+            lib3h.process(&mut core).unwrap();
+        }
 
-        let my_url = &Lib3hUri::with_memory("addr_1");
-        //let my_url = &engine.as_ref().advertise();
-        assert!(network.lock().unwrap().unbind(my_url));
-        wait_can_track_did_work!(engine, core, ProcessingOptions::with_should_abort(false));
-        let mut msgs = engine.drain_messages();
-        println!("engine.drain() -> {:?}", msgs);
-        assert_eq!(msgs.len(), 3);
+        let mut dropped: Vec<(String, Option<Vec<AspectHash>>)> = lib3h
+            .drain_messages()
+            .iter_mut()
+            .map(|msg| match msg.take_message() {
+                Some(Lib3hToClient::HandleDropEntry(data)) => {
+                    (data.entry_address.to_string(), data.aspect_address_list)
+                }
+                other => panic!("unexpected message: {:?}", other),
+            })
+            .collect();
+        dropped.sort();
         assert_eq!(
-            "Some(Unbound(UnboundData { uri: Lib3hUri(\"mem://addr_1/\") }))",
-            format!("{:?}", msgs[2].take_message())
+            vec![
+                ("entry_1".to_string(), Some(vec!["aspect_2".into()])),
+                ("entry_2".to_string(), Some(vec!["aspect_3".into()])),
+            ],
+            dropped
         );
     }
 
     #[test]
-    fn test_ghost_engine_dm() {
-        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_dm");
+    fn test_ghost_engine_set_network_policy() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_set_network_policy");
         let req_data = make_test_join_request();
         let result = lib3h.as_mut().handle_join(test_span(""), &req_data);
         assert!(result.is_ok());
-
-        let direct_message = DirectMessageData {
-            request_id: "foo_id".into(),
-            space_address: "space_addr".into(),
-            from_agent_id: "agent_id".into(),
-            to_agent_id: "to_agent_id".into(),
-            content: b"foo content".to_vec().into(),
+        // Invalid policies should be refused as a whole
+        let mut policy = NetworkPolicyData::default();
+        policy.cidrs.block.push("not_a_cidr".into());
+        let result = lib3h
+            .as_mut()
+            .handle_set_network_policy(test_span(""), policy);
+        assert!(result.is_err());
+        assert_eq!(
+            lib3h.as_ref().config.network_policy,
+            NetworkPolicyData::default()
+        );
+        // Valid one should be stored
+        let policy = NetworkPolicyData {
+            cidrs: PeerFilterData {
+                allow: vec!["10.0.0.0/8".into()],
+                block: vec![],
+            },
+            spaces: vec![SpacePolicyData {
+                space_address: req_data.space_address.clone(),
+                agent_ids: PeerFilterData {
+                    allow: vec![],
+                    block: vec!["bad_agent".into()],
+                },
+            }],
+            ..Default::default()
         };
-
-        let msg = GhostMessage::test_constructor();
-
         let result = lib3h
             .as_mut()
-            .handle_direct_message(test_span(""), msg, direct_message);
+            .handle_set_network_policy(test_span(""), policy.clone());
         assert!(result.is_ok());
-        // TODO: assert somehow that the message got queued to the right place
-
-        /*
-            "Ok(DirectMessageData { space_address: HashString(\"space_addr\"), request_id: \"foo_id\", to_agent_id: HashString(\"agent_id\"), from_agent_id: HashString(\"to_agent_id\"), content: [102, 97, 107, 101, 32, 114, 101, 115, 112, 111, 110, 115, 101] })",
-            format!("{:?}", result)
-        );*/
+        assert_eq!(lib3h.as_ref().config.network_policy, policy);
     }
 
-    fn make_test_entry() -> ProvidedEntryData {
-        let aspect_list = Vec::new();
-        let entry_data = EntryData {
-            entry_address: "fake_address".into(),
-            aspect_list,
+    #[test]
+    fn test_ghost_engine_blocked_peer() {
+        enable_logging_for_test(true);
+        let network_name = "test_ghost_engine_blocked_peer";
+        let mut alex = make_test_engine_wrapper(network_name);
+        let mut billy = make_test_engine_wrapper(network_name);
+        let mut core = MockCore {};
+        let alex_name = alex.as_ref().this_net_peer.peer_name.clone();
+        let alex_location = alex.as_ref().this_net_peer.peer_location.clone();
+        let billy_location = billy.as_ref().this_net_peer.peer_location.clone();
+        // billy blocks alex's TransportId
+        let policy = NetworkPolicyData {
+            transport_ids: PeerFilterData {
+                allow: vec![],
+                block: vec![alex_name.lower_address().to_string()],
+            },
+            ..Default::default()
         };
-        ProvidedEntryData {
-            space_address: "space_addr".into(),
-            provider_agent_id: "agent_id".into(),
-            entry: entry_data,
+        billy
+            .as_mut()
+            .handle_set_network_policy(test_span(""), policy)
+            .unwrap();
+        alex.request(
+            test_span(""),
+            ClientToLib3h::Bootstrap(BootstrapData {
+                space_address: test_network_id().id.into(),
+                bootstrap_uri: billy_location.clone(),
+            }),
+            Box::new(|_: &mut MockCore, _| Ok(())),
+        )
+        .unwrap();
+        for _ in 0..10 {
+            alex.process(&mut core).unwrap();
+            billy.process(&mut core).unwrap();
+        }
+        for _ in 0..5 {
+            billy.process(&mut core).unwrap();
+        }
+
+        // alex's PeerName was dropped, so billy doesn't know of alex
+        let responses = request_for_test(&mut billy, ClientToLib3h::GetNetworkState);
+        for _ in 0..10 {
+            billy.process(&mut core).unwrap();
         }
+        let network_state = match responses.lock().unwrap().pop() {
+            Some(Ok(ClientToLib3hResponse::GetNetworkStateResult(state))) => state,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        assert!(!network_state
+            .peers
+            .iter()
+            .any(|peer| peer.peer_name == alex_name));
+
+        // and the connection was closed both ways
+        let network = {
+            let mut verse = memory_server::get_memory_verse();
+            verse.get_network(network_name)
+        };
+        let mut network = network.lock().unwrap();
+        assert!(!network
+            .get_server(&billy_location)
+            .unwrap()
+            .is_connected_to(&alex_location));
+        assert!(!network
+            .get_server(&alex_location)
+            .unwrap()
+            .is_connected_to(&billy_location));
     }
 
     #[test]
-    fn test_ghost_engine_publish() {
+    fn test_ghost_engine_block_connected_peer() {
         enable_logging_for_test(true);
+        let network_name = "test_ghost_engine_block_connected_peer";
+        let mut alex = make_test_engine_wrapper(network_name);
+        let mut billy = make_test_engine_wrapper(network_name);
+        let mut core = MockCore {};
+        let alex_name = alex.as_ref().this_net_peer.peer_name.clone();
+        let alex_location = alex.as_ref().this_net_peer.peer_location.clone();
+        let billy_location = billy.as_ref().this_net_peer.peer_location.clone();
+        alex.request(
+            test_span(""),
+            ClientToLib3h::Bootstrap(BootstrapData {
+                space_address: test_network_id().id.into(),
+                bootstrap_uri: billy_location.clone(),
+            }),
+            Box::new(|_: &mut MockCore, _| Ok(())),
+        )
+        .unwrap();
+        for _ in 0..10 {
+            alex.process(&mut core).unwrap();
+            billy.process(&mut core).unwrap();
+        }
+        let network = {
+            let mut verse = memory_server::get_memory_verse();
+            verse.get_network(network_name)
+        };
+        assert!(network
+            .lock()
+            .unwrap()
+            .get_server(&billy_location)
+            .unwrap()
+            .is_connected_to(&alex_location));
+
+        // billy blocks alex's TransportId once connected
+        let policy = NetworkPolicyData {
+            transport_ids: PeerFilterData {
+                allow: vec![],
+                block: vec![alex_name.lower_address().to_string()],
+            },
+            ..Default::default()
+        };
+        billy
+            .as_mut()
+            .handle_set_network_policy(test_span(""), policy)
+            .unwrap();
+        for _ in 0..10 {
+            alex.process(&mut core).unwrap();
+            billy.process(&mut core).unwrap();
+        }
 
-        let mut engine = make_test_engine_wrapper("test_ghost_engine_publish");
-        let req_data = make_test_join_request();
-        let result = engine.as_mut().handle_join(test_span(""), &req_data);
-        assert!(result.is_ok());
-
-        let mut core = MockCore {
-            //        state: "".to_string(),
+        // billy forgot alex and closed the connection both ways
+        let responses = request_for_test(&mut billy, ClientToLib3h::GetNetworkState);
+        for _ in 0..10 {
+            billy.process(&mut core).unwrap();
+        }
+        let network_state = match responses.lock().unwrap().pop() {
+            Some(Ok(ClientToLib3hResponse::GetNetworkStateResult(state))) => state,
+            other => panic!("unexpected response: {:?}", other),
         };
+        assert!(!network_state
+            .peers
+            .iter()
+            .any(|peer| peer.peer_name == alex_name));
+        let mut network = network.lock().unwrap();
+        assert!(!network
+            .get_server(&billy_location)
+            .unwrap()
+            .is_connected_to(&alex_location));
+        assert!(!network
+            .get_server(&alex_location)
+            .unwrap()
+            .is_connected_to(&billy_location));
+    }
 
-        let res = engine.process(&mut core);
-        println!("engine.process() -> {:?}", res);
+    fn get_transport_stats(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+    ) -> TransportStatsData {
+        let responses = request_for_test(lib3h, ClientToLib3h::GetTransportStats);
+        for _ in 0..10 {
+            lib3h.process(&mut MockCore {}).unwrap();
+        }
+        let response = responses.lock().unwrap().pop();
+        match response {
+            Some(Ok(ClientToLib3hResponse::GetTransportStatsResult(stats))) => stats,
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
 
-        let entry_data = make_test_entry();
+    #[test]
+    fn test_ghost_engine_get_transport_stats() {
+        let network_name = "test_ghost_engine_get_transport_stats";
+        let mut alex = make_test_engine_wrapper(network_name);
+        let mut billy = make_test_engine_wrapper(network_name);
+        let mut core = MockCore {};
+        let alex_location = alex.as_ref().this_net_peer.peer_location.clone();
+        let billy_location = billy.as_ref().this_net_peer.peer_location.clone();
+
+        let stats = get_transport_stats(&mut alex);
+        assert_eq!(0, stats.total.messages_sent);
+        assert!(stats.connections.is_empty());
+        // network gateway stats, with those of the memory transport attached
+        let inner = stats.inner.expect("transport stats");
+        assert!(inner.inner.is_none());
+
+        alex.request(
+            test_span(""),
+            ClientToLib3h::Bootstrap(BootstrapData {
+                space_address: test_network_id().id.into(),
+                bootstrap_uri: billy_location.clone(),
+            }),
+            Box::new(|_: &mut MockCore, _| Ok(())),
+        )
+        .unwrap();
+        for _ in 0..10 {
+            alex.process(&mut core).unwrap();
+            billy.process(&mut core).unwrap();
+        }
+        let stats = get_transport_stats(&mut alex);
+        let sent = stats.total.messages_sent;
+        assert!(sent > 0);
+        assert!(stats.total.bytes_sent > 0);
+        assert!(stats.total.messages_received > 0);
+        let connection = stats
+            .connections
+            .iter()
+            .find(|connection| connection.uri == billy_location)
+            .expect("connection to billy");
+        assert_eq!(sent, connection.stats.messages_sent);
+        assert_eq!(0, connection.stats.reconnects);
+        let inner = stats.inner.expect("transport stats");
+        assert_eq!(sent, inner.total.messages_sent);
+
+        // billy goes away
+        {
+            let network = memory_server::get_memory_verse().get_network(network_name);
+            let mut network = network.lock().unwrap();
+            network
+                .get_server(&alex_location)
+                .unwrap()
+                .request_close(&billy_location)
+                .unwrap();
+        }
+        for _ in 0..5 {
+            alex.process(&mut core).unwrap();
+        }
+        let stats = get_transport_stats(&mut alex);
+        assert!(!stats
+            .connections
+            .iter()
+            .any(|connection| connection.uri == billy_location));
+        // totals keep what the closed connection did
+        assert!(stats.total.messages_sent >= sent);
+        let inner = stats.inner.expect("transport stats");
+        assert!(!inner
+            .connections
+            .iter()
+            .any(|connection| connection.uri == billy_location));
+    }
 
-        let result = engine
+    #[test]
+    fn test_ghost_engine_get_state() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_get_state");
+        let mut core = MockCore {};
+        let req_data = make_test_join_request();
+        lib3h
             .as_mut()
-            .handle_publish_entry(test_span(""), &entry_data);
-        assert!(result.is_ok());
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
 
-        /* what should we observe to know that the entry was published?
-        let space_gateway = lib3h
+        let responses = EngineResponses::default();
+        let mut unjoined_data = req_data.clone();
+        unjoined_data.agent_id = "other_agent_id".into();
+        for request in &[
+            ClientToLib3h::GetNetworkState,
+            ClientToLib3h::GetSpaceState(req_data.clone()),
+            ClientToLib3h::GetSpaceState(unjoined_data),
+        ] {
+            lib3h
+                .request(test_span(""), request.clone(), test_callback(&responses))
+                .unwrap();
+        }
+        for _ in 0..10 {
+            lib3h.process(&mut core).unwrap();
+        }
+        let mut responses = responses.lock().unwrap();
+        assert_eq!(3, responses.len());
+
+        // the unjoined space is answered without waiting on any DHT
+        assert!(responses.remove(0).is_err());
+        let mut network_state = None;
+        let mut space_state = None;
+        for response in responses.drain(..) {
+            match response.unwrap() {
+                ClientToLib3hResponse::GetNetworkStateResult(state) => network_state = Some(state),
+                ClientToLib3hResponse::GetSpaceStateResult(state) => space_state = Some(state),
+                response => panic!("unexpected response: {:?}", response),
+            }
+        }
+
+        let network_state = network_state.expect("GetNetworkState was not answered");
+        assert!(network_state.this_peer.is_alive);
+        assert_eq!(
+            vec![JoinedSpaceData {
+                space_address: req_data.space_address.clone(),
+                agent_id: req_data.agent_id.clone(),
+            }],
+            network_state.joined_spaces
+        );
+
+        let space_state = space_state.expect("GetSpaceState was not answered");
+        assert_eq!(req_data.space_address, space_state.space_address);
+        assert_eq!(req_data.agent_id, space_state.agent_id);
+        assert_eq!(0, space_state.entry_count);
+        assert_eq!(0, space_state.aspect_count);
+        // our own agent is held by the space DHT
+        assert!(space_state
+            .peers
+            .iter()
+            .any(|peer| peer.peer_name == Lib3hUri::with_agent_id(&req_data.agent_id)));
+    }
+
+    #[test]
+    fn test_ghost_engine_shutdown() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_shutdown");
+        let mut core = MockCore {};
+        let req_data = make_test_join_request();
+        lib3h
             .as_mut()
-            .get_space(
-                &req_data.space_address.to_owned(),
-                &req_data.agent_id.to_owned(),
-            )
+            .handle_join(test_span(""), &req_data)
             .unwrap();
-        let msgs = space_gateway.as_mut().as_dht_mut().drain_messages();
-        assert_eq!(msgs.len(), 0);
 
-        {
+        let responses = EngineResponses::default();
+        for request in &[ClientToLib3h::Shutdown, ClientToLib3h::GetNetworkState] {
+            lib3h
+                .request(test_span(""), request.clone(), test_callback(&responses))
+                .unwrap();
+        }
+        for _ in 0..20 {
             lib3h.process(&mut core).unwrap();
         }
+        let mut responses = responses.lock().unwrap();
+        assert_eq!(2, responses.len());
+        // requests made after a Shutdown are refused right away
+        assert!(responses.remove(0).is_err());
+        assert_eq!(
+            ClientToLib3hResponse::ShutdownResult,
+            responses.remove(0).unwrap()
+        );
+        assert!(lib3h.as_mut().space_gateway_map.is_empty());
+    }
+
+    #[test]
+    fn test_ghost_engine_leave_signature() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_leave_signature");
+        let engine = lib3h.as_mut();
+        let mut leave = engine.sign_leave().unwrap();
+        let transport_id = leave.peer_name.lower_address();
+        let check = |leave: &LeaveData| {
+            verify_transport_signature(
+                engine.crypto.as_ref(),
+                &transport_id,
+                &leave.signed_content(),
+                &leave.signature,
+            )
+            .unwrap()
+        };
+        assert!(check(&leave));
+        leave.timestamp += 1;
+        assert!(!check(&leave));
+    }
+
+    fn space_peer_names(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+        space_data: &SpaceData,
+    ) -> Vec<Lib3hUri> {
+        let responses = request_for_test(lib3h, ClientToLib3h::GetSpaceState(space_data.clone()));
+        for _ in 0..10 {
+            lib3h.process(&mut MockCore {}).unwrap();
+        }
+        let response = responses.lock().unwrap().pop();
+        match response {
+            Some(Ok(ClientToLib3hResponse::GetSpaceStateResult(state))) => {
+                state.peers.into_iter().map(|peer| peer.peer_name).collect()
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
 
-        let space_gateway = lib3h
+    fn hold_space_peer(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+        space_data: &SpaceData,
+        peer_data: PeerData,
+    ) {
+        lib3h
             .as_mut()
-            .get_space(
-                &req_data.space_address.to_owned(),
-                &req_data.agent_id.to_owned(),
+            .space_gateway_map
+            .get_mut(&(
+                space_data.space_address.clone(),
+                space_data.agent_id.clone(),
+            ))
+            .unwrap()
+            .publish(
+                test_span(""),
+                GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(peer_data)),
             )
             .unwrap();
+    }
 
-        let msgs = space_gateway.as_mut().as_dht_mut().drain_messages();
+    #[test]
+    fn test_ghost_engine_leave_space_checks() {
+        let network_name = "test_ghost_engine_leave_space_checks";
+        let mut alex = make_test_engine_wrapper(network_name);
+        let mut billy = make_test_engine_wrapper(network_name);
+        let camille = make_test_engine(network_name);
+        let alex_data = make_test_join_request();
+        let mut billy_data = alex_data.clone();
+        billy_data.agent_id = "billy".into();
+        alex.as_mut()
+            .handle_join(test_span(""), &alex_data)
+            .unwrap();
+        billy
+            .as_mut()
+            .handle_join(test_span(""), &billy_data)
+            .unwrap();
+        let billy_chain_id = (
+            billy_data.space_address.clone(),
+            billy_data.agent_id.clone(),
+        );
+        let billy_peer = billy.as_mut().this_space_peer(billy_chain_id).unwrap();
+        let billy_agent = billy_peer.peer_name.clone();
+        hold_space_peer(&mut alex, &alex_data, billy_peer.clone());
+        assert!(space_peer_names(&mut alex, &alex_data).contains(&billy_agent));
+
+        let left_peer = PeerData {
+            timestamp: billy_peer.timestamp + 10,
+            ..billy_peer
+        };
+        let leave = billy
+            .as_ref()
+            .sign_leave_space(billy_data.space_address.clone(), left_peer.clone())
+            .unwrap();
+        let from = billy.as_ref().this_net_peer.peer_location.clone();
+        let serve =
+            |alex: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+             leave: LeaveSpaceData| {
+                alex.as_mut()
+                    .serve_P2pProtocol(
+                        test_span(""),
+                        &from,
+                        P2pProtocol::BroadcastLeaveSpace(leave),
+                    )
+                    .unwrap();
+                space_peer_names(alex, &alex_data).contains(&billy_agent)
+            };
+        // unsigned
+        let mut tampered_leave = leave.clone();
+        tampered_leave.peer_data.timestamp += 1;
+        assert!(serve(&mut alex, tampered_leave));
+        // signed by another node
+        let forged_leave = camille
+            .sign_leave_space(billy_data.space_address.clone(), left_peer.clone())
+            .unwrap();
+        assert!(serve(&mut alex, forged_leave));
+        // from the future
+        let mut future_peer = left_peer;
+        future_peer.timestamp += 2 * crate::time::MAX_CLOCK_SKEW_MS;
+        let future_leave = billy
+            .as_ref()
+            .sign_leave_space(billy_data.space_address.clone(), future_peer)
+            .unwrap();
+        assert!(serve(&mut alex, future_leave));
+        // only billy's node can
+        assert!(!serve(&mut alex, leave));
+    }
+
+    #[test]
+    fn test_ghost_engine_leave_checks() {
+        let network_name = "test_ghost_engine_leave_checks";
+        let mut alex = make_test_engine_wrapper(network_name);
+        let mut billy = make_test_engine_wrapper(network_name);
+        let camille = make_test_engine(network_name);
+        let alex_data = make_test_join_request();
+        let mut billy_data = alex_data.clone();
+        billy_data.agent_id = "billy".into();
+        alex.as_mut()
+            .handle_join(test_span(""), &alex_data)
+            .unwrap();
+        billy
+            .as_mut()
+            .handle_join(test_span(""), &billy_data)
+            .unwrap();
+        let leave = billy.as_ref().sign_leave().unwrap();
+        let billy_agent = Lib3hUri::with_agent_id(&billy_data.agent_id);
+        // alex holds billy's agent, located at billy's TransportId
+        let billy_peer = PeerData {
+            peer_name: billy_agent.clone(),
+            peer_location: Lib3hUri::with_transport_id(&leave.peer_name.lower_address()),
+            timestamp: leave.timestamp - 10,
+        };
+        hold_space_peer(&mut alex, &alex_data, billy_peer);
+        assert!(space_peer_names(&mut alex, &alex_data).contains(&billy_agent));
+        let from = billy.as_ref().this_net_peer.peer_location.clone();
+        let serve =
+            |alex: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+             leave: LeaveData| {
+                alex.as_mut()
+                    .serve_P2pProtocol(test_span(""), &from, P2pProtocol::BroadcastLeave(leave))
+                    .unwrap();
+                space_peer_names(alex, &alex_data).contains(&billy_agent)
+            };
+        // a leave from the future is ignored
+        let mut future_leave = leave.clone();
+        future_leave.timestamp += 2 * crate::time::MAX_CLOCK_SKEW_MS;
+        future_leave.signature = billy
+            .as_ref()
+            .transport_keys
+            .sign(
+                billy.as_ref().crypto.as_ref(),
+                &future_leave.signed_content(),
+            )
+            .unwrap()
+            .into();
+        assert!(serve(&mut alex, future_leave));
+        // as is another node signing billy's agent out
+        let mut forged_leave = camille.sign_leave().unwrap();
+        forged_leave.space_list = leave.space_list.clone();
+        forged_leave.signature = camille
+            .transport_keys
+            .sign(camille.crypto.as_ref(), &forged_leave.signed_content())
+            .unwrap()
+            .into();
+        assert!(serve(&mut alex, forged_leave));
+        // only billy's node can
+        assert!(!serve(&mut alex, leave));
+    }
+
+    #[test]
+    fn test_ghost_engine_transport_rotation() {
+        let mut rotating = make_test_engine_wrapper("test_ghost_engine_transport_rotation_a");
+        let engine = rotating.as_mut();
+        let first_id = engine.transport_keys.transport_id.clone();
+        let rotate = |engine: &mut GhostEngine, seed: u8| {
+            engine.rotate_transport_keys(&RotateTransportKeyData {
+                seed: vec![seed; 32].into(),
+            })
+        };
+        let first_rotation = rotate(engine, 1).unwrap();
+        let second_rotation = rotate(engine, 2).unwrap();
+        assert!(rotate(engine, 2).is_err());
+        // each rotation is signed by the keys of the previous one
+        assert_eq!(first_id, first_rotation.old_transport_id);
         assert_eq!(
-            "[GhostMessage {request_id: None, ..}]",
-            format!("{:?}", msgs)
-        ); */
+            first_rotation.new_transport_id,
+            second_rotation.old_transport_id
+        );
+        assert_eq!(
+            vec![first_rotation.clone(), second_rotation.clone()],
+            engine.config.transport_rotations
+        );
+        // the engine identifies with its new TransportId right away
+        let second_id = second_rotation.new_transport_id.clone();
+        assert_eq!(second_id, engine.transport_keys.transport_id);
+        assert_eq!(
+            Lib3hUri::with_transport_id(&second_id),
+            engine.this_net_peer.peer_name
+        );
+        rotating.process(&mut MockCore {}).unwrap();
+        let advertised: Vec<Address> = memory_server::get_memory_verse()
+            .get_network("test_ghost_engine_transport_rotation_a")
+            .lock()
+            .unwrap()
+            .discover()
+            .into_iter()
+            .map(|(_, transport_id)| transport_id)
+            .collect();
+        assert_eq!(vec![second_id.clone()], advertised);
+        let engine = rotating.as_mut();
+        let mut forged_rotation = second_rotation.clone();
+        forged_rotation.new_transport_id = first_id.clone();
+        assert!(verify_transport_rotation(engine.crypto.as_ref(), &second_rotation).unwrap());
+        assert!(!verify_transport_rotation(engine.crypto.as_ref(), &forged_rotation).unwrap());
+
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_transport_rotation_b");
+        let engine = lib3h.as_mut();
+        let policy = NetworkPolicyData {
+            transport_ids: PeerFilterData {
+                allow: vec![first_id.to_string()],
+                block: vec![],
+            },
+            ..Default::default()
+        };
+        engine
+            .handle_set_network_policy(test_span(""), policy)
+            .unwrap();
+        let location = Lib3hUri::with_memory("rotating");
+        engine
+            .handle_transport_rotation_received(test_span(""), &location, forged_rotation)
+            .unwrap();
+        assert!(engine.transport_rotations.is_empty());
+        // the allowlist follows the chain, whatever order we get it in
+        for rotation in &[&second_rotation, &first_rotation] {
+            engine
+                .handle_transport_rotation_received(test_span(""), &location, (*rotation).clone())
+                .unwrap();
+        }
+        assert_eq!(
+            vec![second_id.to_string()],
+            engine.config.network_policy.transport_ids.allow
+        );
+        let allowed_by_peer_policy = |transport_id: &Address| {
+            PeerPolicy::for_network(&engine.config.network_policy)
+                .unwrap()
+                .allows_peer_name(&Lib3hUri::with_transport_id(transport_id))
+        };
+        assert!(allowed_by_peer_policy(&second_id));
+        assert!(!allowed_by_peer_policy(&first_id));
+        assert!(!allowed_by_peer_policy(&"HcMother".into()));
+    }
+
+    fn network_peers(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+    ) -> Vec<PeerStateData> {
+        let responses = request_for_test(lib3h, ClientToLib3h::GetNetworkState);
+        for _ in 0..10 {
+            lib3h.process(&mut MockCore {}).unwrap();
+        }
+        let response = responses.lock().unwrap().pop();
+        match response {
+            Some(Ok(ClientToLib3hResponse::GetNetworkStateResult(state))) => state.peers,
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_ghost_engine_transport_rotation_relayed() {
+        let mut rotating = make_test_engine("test_ghost_engine_transport_rotation_relayed_a");
+        let first_id = rotating.transport_keys.transport_id.clone();
+        let rotation = rotating
+            .rotate_transport_keys(&RotateTransportKeyData {
+                seed: vec![1; 32].into(),
+            })
+            .unwrap();
+        let new_peer_name = Lib3hUri::with_transport_id(&rotation.new_transport_id);
+
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_transport_rotation_relayed_b");
+        let location = Lib3hUri::with_memory("rotating");
+        lib3h
+            .as_mut()
+            .multiplexer
+            .publish(
+                test_span(""),
+                GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(PeerData {
+                    peer_name: Lib3hUri::with_transport_id(&first_id),
+                    peer_location: location.clone(),
+                    timestamp: rotation.timestamp - 10,
+                })),
+            )
+            .unwrap();
+        let mut receive = |from: &Lib3hUri| {
+            lib3h
+                .as_mut()
+                .handle_transport_rotation_received(test_span(""), from, rotation.clone())
+                .unwrap();
+            for _ in 0..3 {
+                lib3h.process(&mut MockCore {}).unwrap();
+            }
+            network_peers(&mut lib3h)
+                .into_iter()
+                .find(|peer| peer.peer_name == new_peer_name)
+                .map(|peer| peer.peer_location)
+        };
+        // a third party relaying the certificate doesn't get the node's record
+        assert_eq!(None, receive(&Lib3hUri::with_memory("relayer")));
+        // the rotated node moves it
+        assert_eq!(Some(location.clone()), receive(&location));
+    }
+
+    #[test]
+    fn test_ghost_engine_transport_rotation_restart() {
+        let crypto = SodiumCryptoSystem::new();
+        let dir = tempfile::tempdir().unwrap();
+        let config = EngineConfig {
+            work_dir: dir.path().to_path_buf(),
+            transport_seed: Some(TransportSeed::File(dir.path().join("seed"))),
+            ..make_test_config("test_ghost_engine_transport_rotation_restart")
+        };
+        let start = |config: EngineConfig| {
+            GhostEngine::new(
+                test_span(""),
+                crypto.box_clone(),
+                config,
+                "test_engine",
+                MirrorDht::new_with_config,
+            )
+            .unwrap()
+        };
+        let mut engine = start(config.clone());
+        let rotation = engine
+            .rotate_transport_keys(&RotateTransportKeyData {
+                seed: vec![1; 32].into(),
+            })
+            .unwrap();
+        // restarted with the new seed and the rotation certificate
+        let engine = start(config.clone());
+        assert_eq!(
+            rotation.new_transport_id,
+            engine.transport_keys.transport_id
+        );
+        assert_eq!(vec![rotation.clone()], engine.config.transport_rotations);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let metadata = std::fs::metadata(dir.path().join("seed")).unwrap();
+            assert_eq!(0o600, metadata.permissions().mode() & 0o777);
+        }
+        // restarted with another key, the certificate is not ours
+        let other_dir = tempfile::tempdir().unwrap();
+        std::fs::copy(
+            dir.path().join("transport_rotations.bin"),
+            other_dir.path().join("transport_rotations.bin"),
+        )
+        .unwrap();
+        let engine = start(EngineConfig {
+            work_dir: other_dir.path().to_path_buf(),
+            transport_seed: None,
+            ..config
+        });
+        assert!(engine.config.transport_rotations.is_empty());
+    }
+
+    // this test simulates an unbind happening in our transport layer
+    // i.e. we moved to a different cell tower, or someone turned off the
+    // networking interface
+    #[test]
+    fn test_ghost_engine_unbind() {
+        enable_logging_for_test(true);
+        let mut core = MockCore {
+            //        state: "".to_string(),
+        };
+        let network_name = "test_ghost_engine_unbind";
+        let mut engine = make_test_engine_wrapper(network_name);
+        let req_data = make_test_join_request();
+        let result = engine.as_mut().handle_join(test_span(""), &req_data);
+        assert!(result.is_ok());
+        let network = {
+            let mut verse = memory_server::get_memory_verse();
+            verse.get_network(network_name)
+        };
+
+        let my_url = &Lib3hUri::with_memory("addr_1");
+        //let my_url = &engine.as_ref().advertise();
+        assert!(network.lock().unwrap().unbind(my_url));
+        wait_can_track_did_work!(engine, core, ProcessingOptions::with_should_abort(false));
+        let mut msgs = engine.drain_messages();
+        println!("engine.drain() -> {:?}", msgs);
+        assert_eq!(msgs.len(), 3);
+        assert_eq!(
+            "Some(Unbound(UnboundData { uri: Lib3hUri(\"mem://addr_1/\") }))",
+            format!("{:?}", msgs[2].take_message())
+        );
+    }
+
+    #[test]
+    fn test_ghost_engine_dm() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_dm");
+        let req_data = make_test_join_request();
+        let result = lib3h.as_mut().handle_join(test_span(""), &req_data);
+        assert!(result.is_ok());
+
+        let direct_message = DirectMessageData {
+            request_id: "foo_id".into(),
+            space_address: "space_addr".into(),
+            from_agent_id: "agent_id".into(),
+            to_agent_id: "to_agent_id".into(),
+            content: b"foo content".to_vec().into(),
+        };
+
+        let msg = GhostMessage::test_constructor();
+
+        let result = lib3h
+            .as_mut()
+            .handle_direct_message(test_span(""), msg, direct_message);
+        assert!(result.is_ok());
+        assert_eq!(1, lib3h.as_ref().pending_client_direct_messages.len());
+
+        /*
+            "Ok(DirectMessageData { space_address: HashString(\"space_addr\"), request_id: \"foo_id\", to_agent_id: HashString(\"agent_id\"), from_agent_id: HashString(\"to_agent_id\"), content: [102, 97, 107, 101, 32, 114, 101, 115, 112, 111, 110, 115, 101] })",
+            format!("{:?}", result)
+        );*/
+    }
+
+    fn make_test_entry() -> ProvidedEntryData {
+        let aspect_list = Vec::new();
+        let entry_data = EntryData {
+            entry_address: "fake_address".into(),
+            aspect_list,
+        };
+        ProvidedEntryData {
+            space_address: "space_addr".into(),
+            provider_agent_id: "agent_id".into(),
+            entry: entry_data,
+        }
+    }
+
+    #[test]
+    fn test_ghost_engine_publish() {
+        enable_logging_for_test(true);
+
+        let mut engine = make_test_engine_wrapper("test_ghost_engine_publish");
+        let req_data = make_test_join_request();
+        let result = engine.as_mut().handle_join(test_span(""), &req_data);
+        assert!(result.is_ok());
+
+        let mut core = MockCore {
+            //        state: "".to_string(),
+        };
+
+        let res = engine.process(&mut core);
+        println!("engine.process() -> {:?}", res);
+
+        let entry_data = make_test_entry();
+
+        let result = engine
+            .as_mut()
+            .handle_publish_entry(test_span(""), &entry_data);
+        assert!(result.is_ok());
+
+        /* what should we observe to know that the entry was published?
+        let space_gateway = lib3h
+            .as_mut()
+            .get_space(
+                &req_data.space_address.to_owned(),
+                &req_data.agent_id.to_owned(),
+            )
+            .unwrap();
+        let msgs = space_gateway.as_mut().as_dht_mut().drain_messages();
+        assert_eq!(msgs.len(), 0);
+
+        {
+            lib3h.process(&mut core).unwrap();
+        }
+
+        let space_gateway = lib3h
+            .as_mut()
+            .get_space(
+                &req_data.space_address.to_owned(),
+                &req_data.agent_id.to_owned(),
+            )
+            .unwrap();
+
+        let msgs = space_gateway.as_mut().as_dht_mut().drain_messages();
+        assert_eq!(
+            "[GhostMessage {request_id: None, ..}]",
+            format!("{:?}", msgs)
+        ); */
+    }
+
+    fn publish_test_entry(
+        engine: GhostEngine<'static>,
+    ) -> (
+        GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+        EngineResponses,
+    ) {
+        let mut lib3h: GhostEngineParentWrapper<MockCore, GhostEngine, Lib3hError> =
+            GhostParentWrapper::new(engine, "test_engine");
+        let mut core = MockCore {};
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &make_test_join_request())
+            .unwrap();
+        // someone to ask
+        hold_space_peer(
+            &mut lib3h,
+            &make_test_join_request(),
+            PeerData {
+                peer_name: Lib3hUri::with_agent_id(&"holder_agent_id".into()),
+                peer_location: Lib3hUri::with_transport_id(&"holder_transport_id".into()),
+                timestamp: crate::time::since_epoch_ms(),
+            },
+        );
+        lib3h.process(&mut core).unwrap();
+
+        let mut entry_data = make_test_entry();
+        entry_data.entry.aspect_list.push(EntryAspectData {
+            aspect_address: "fake_aspect_address".into(),
+            type_hint: "fake_type_hint".into(),
+            aspect: b"fake aspect".to_vec().into(),
+            publish_ts: 0,
+        });
+        let responses = EngineResponses::default();
+        lib3h
+            .request(
+                test_span(""),
+                ClientToLib3h::PublishEntry(entry_data),
+                test_callback(&responses),
+            )
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        (lib3h, responses)
+    }
+
+    #[test]
+    fn test_ghost_engine_publish_acknowledged() {
+        let engine = make_test_engine("test_ghost_engine_publish_acknowledged");
+        let (mut lib3h, responses) = publish_test_entry(engine);
+        let mut core = MockCore {};
+        let request_id = lib3h
+            .as_ref()
+            .pending_publishes
+            .keys()
+            .next()
+            .expect("publish should wait for an acknowledgement")
+            .clone();
+        // let it ask the holder
+        for _ in 0..5 {
+            lib3h.process(&mut core).unwrap();
+        }
+        // only the agents we asked can confirm
+        for from_agent_id in &["other_agent_id", "holder_agent_id"] {
+            lib3h
+                .as_mut()
+                .handle_store_entry_result(StoreEntryResultData {
+                    space_address: "space_addr".into(),
+                    request_id: request_id.clone(),
+                    from_agent_id: (*from_agent_id).into(),
+                    to_agent_id: "agent_id".into(),
+                    stored: true,
+                    unconfirmed: false,
+                });
+        }
+        lib3h.process(&mut core).unwrap();
+        lib3h.process(&mut core).unwrap();
+
+        let mut responses = responses.lock().unwrap();
+        assert_eq!(1, responses.len());
+        match responses.remove(0) {
+            Ok(ClientToLib3hResponse::PublishEntryResult(result)) => {
+                assert_eq!(1, result.replication_factor);
+                assert_eq!(vec![Address::from("holder_agent_id")], result.stored_by);
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+        assert!(lib3h.as_ref().pending_publishes.is_empty());
+    }
+
+    #[test]
+    fn test_ghost_engine_publish_gives_up() {
+        let mut engine = make_test_engine("test_ghost_engine_publish_gives_up");
+        engine.config.publish = PublishConfig {
+            replication_factor: 2,
+            retry_interval_ms: 10,
+            max_attempts: 2,
+        };
+        let (mut lib3h, responses) = publish_test_entry(engine);
+        let mut core = MockCore {};
+        for _ in 0..20 {
+            lib3h.process(&mut core).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let mut responses = responses.lock().unwrap();
+        assert_eq!(1, responses.len());
+        match responses.remove(0) {
+            Ok(ClientToLib3hResponse::PublishEntryResult(result)) => {
+                assert_eq!(2, result.replication_factor);
+                assert!(result.stored_by.is_empty());
+            }
+            other => panic!("unexpected response: {:?}", other),
+        }
+    }
+
+    fn send_test_direct_message(
+        engine: GhostEngine<'static>,
+    ) -> (
+        GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+        EngineResponses,
+    ) {
+        let mut lib3h: GhostEngineParentWrapper<MockCore, GhostEngine, Lib3hError> =
+            GhostParentWrapper::new(engine, "test_engine");
+        let mut core = MockCore {};
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &make_test_join_request())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+
+        let responses = EngineResponses::default();
+        lib3h
+            .request(
+                test_span(""),
+                ClientToLib3h::SendDirectMessage(DirectMessageData {
+                    request_id: "foo_id".into(),
+                    space_address: "space_addr".into(),
+                    from_agent_id: "agent_id".into(),
+                    to_agent_id: "to_agent_id".into(),
+                    content: b"foo content".to_vec().into(),
+                }),
+                test_callback(&responses),
+            )
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        (lib3h, responses)
+    }
+
+    #[test]
+    fn test_ghost_engine_dm_times_out() {
+        let mut engine = make_test_engine("test_ghost_engine_dm_times_out");
+        engine.config.direct_message = DirectMessageConfig {
+            timeout_ms: 10,
+            max_retries: 1,
+        };
+        let (mut lib3h, responses) = send_test_direct_message(engine);
+        let mut core = MockCore {};
+        for _ in 0..20 {
+            lib3h.process(&mut core).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        let responses = responses.lock().unwrap();
+        assert_eq!(1, responses.len());
+        assert_eq!(
+            "Err(Lib3hError(DirectMessageTimeout(\"to_agent_id\")))",
+            format!("{:?}", responses[0])
+        );
+        assert!(lib3h.as_ref().pending_client_direct_messages.is_empty());
+    }
+
+    #[test]
+    fn test_ghost_engine_dm_rejected() {
+        let engine = make_test_engine("test_ghost_engine_dm_rejected");
+        let (mut lib3h, responses) = send_test_direct_message(engine);
+        let mut core = MockCore {};
+        let request_id = lib3h
+            .as_ref()
+            .pending_client_direct_messages
+            .keys()
+            .next()
+            .expect("Should have pending direct message")
+            .clone();
+        let rejection = DirectMessageRejectedData {
+            space_address: "space_addr".into(),
+            request_id: request_id.clone(),
+            from_agent_id: "to_agent_id".into(),
+            to_agent_id: "agent_id".into(),
+            reason: "no handler".into(),
+        };
+        lib3h
+            .as_mut()
+            .handle_direct_message_rejected(rejection)
+            .unwrap();
+        // a late answer is ignored
+        lib3h
+            .as_mut()
+            .handle_direct_message_result(DirectMessageData {
+                request_id,
+                space_address: "space_addr".into(),
+                from_agent_id: "to_agent_id".into(),
+                to_agent_id: "agent_id".into(),
+                content: b"late".to_vec().into(),
+            })
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+
+        let responses = responses.lock().unwrap();
+        assert_eq!(1, responses.len());
+        let error = format!("{:?}", responses[0]);
+        assert!(error.contains("DirectMessageRejected"), "{}", error);
+        assert!(error.contains("no handler"), "{}", error);
+        assert!(lib3h.as_ref().pending_client_direct_messages.is_empty());
+    }
+
+    #[test]
+    fn test_ghost_engine_dm_retry_received_once() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_dm_retry_received_once");
+        let mut core = MockCore {};
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &make_test_join_request())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let direct_message = DirectMessageData {
+            request_id: "retried_id".into(),
+            space_address: "space_addr".into(),
+            from_agent_id: "from_agent_id".into(),
+            to_agent_id: "agent_id".into(),
+            content: b"foo content".to_vec().into(),
+        };
+        let key = (
+            direct_message.from_agent_id.clone(),
+            direct_message.request_id.clone(),
+        );
+        for _ in 0..2 {
+            lib3h
+                .as_mut()
+                .handle_remote_direct_message(test_span(""), direct_message.clone())
+                .unwrap();
+            lib3h.process(&mut core).unwrap();
+        }
+        // our client only gets it once
+        let mut msgs = lib3h.drain_messages();
+        assert_eq!(1, msgs.len());
+        let mut msg = msgs.remove(0);
+        assert_eq!(
+            Some(Lib3hToClient::HandleSendDirectMessage(
+                direct_message.clone()
+            )),
+            msg.take_message()
+        );
+        assert!(!lib3h.as_ref().received_direct_messages[&key].is_answered());
+        let mut result = direct_message.clone();
+        result.from_agent_id = direct_message.to_agent_id.clone();
+        result.to_agent_id = direct_message.from_agent_id.clone();
+        msg.respond(Ok(Lib3hToClientResponse::HandleSendDirectMessageResult(
+            result,
+        )))
+        .unwrap();
+        lib3h.process(&mut core).unwrap();
+        assert!(lib3h.as_ref().received_direct_messages[&key].is_answered());
+
+        // later retries get that answer, not our client
+        lib3h
+            .as_mut()
+            .handle_remote_direct_message(test_span(""), direct_message)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        assert!(lib3h.drain_messages().is_empty());
+    }
+
+    fn signal_received_list(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+    ) -> Vec<SignalData> {
+        lib3h
+            .drain_messages()
+            .iter_mut()
+            .filter_map(|msg| match msg.take_message() {
+                Some(Lib3hToClient::SignalReceived(data)) => Some(data),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ghost_engine_signal_local_agents() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_signal_local_agents");
+        let mut core = MockCore {};
+        let mut req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        req_data.agent_id = "other_agent_id".into();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let responses = EngineResponses::default();
+        lib3h
+            .request(
+                test_span(""),
+                ClientToLib3h::SendSignal(SignalData {
+                    space_address: "space_addr".into(),
+                    from_agent_id: "agent_id".into(),
+                    to_agent_id_list: None,
+                    content: b"typing".to_vec().into(),
+                }),
+                test_callback(&responses),
+            )
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.process(&mut core).unwrap();
+
+        // the sender does not get its own signal
+        let signal_list = signal_received_list(&mut lib3h);
+        assert_eq!(1, signal_list.len());
+        assert_eq!(
+            Some(vec![Address::from("other_agent_id")]),
+            signal_list[0].to_agent_id_list
+        );
+        assert_eq!(
+            "[Ok(SendSignalResult)]",
+            format!("{:?}", responses.lock().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_ghost_engine_signal_received() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_signal_received");
+        let mut core = MockCore {};
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &make_test_join_request())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        lib3h
+            .as_mut()
+            .handle_signal_received(SignalData {
+                space_address: "space_addr".into(),
+                from_agent_id: "remote_agent_id".into(),
+                to_agent_id_list: Some(vec!["agent_id".into(), "unknown_agent_id".into()]),
+                content: b"online".to_vec().into(),
+            })
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        let signal_list = signal_received_list(&mut lib3h);
+        assert_eq!(1, signal_list.len());
+        assert_eq!(
+            Address::from("remote_agent_id"),
+            signal_list[0].from_agent_id
+        );
+        assert_eq!(
+            Some(vec![Address::from("agent_id")]),
+            signal_list[0].to_agent_id_list
+        );
+    }
+
+    #[test]
+    fn test_ghost_engine_signal_locate_recipients() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_signal_locate_recipients");
+        let mut req_data = make_test_join_request();
+        for agent_id in &["agent_id", "other_agent_id"] {
+            req_data.agent_id = (*agent_id).into();
+            lib3h
+                .as_mut()
+                .handle_join(test_span(""), &req_data)
+                .unwrap();
+        }
+        let node_a = Lib3hUri::with_transport_id(&"node_a".into());
+        let node_b = Lib3hUri::with_transport_id(&"node_b".into());
+        let peer = |agent_id: &str, location: &Lib3hUri| PeerData {
+            peer_name: Lib3hUri::with_agent_id(&agent_id.into()),
+            peer_location: location.clone(),
+            timestamp: 42,
+        };
+        let peer_list = vec![
+            peer("remote_1", &node_a),
+            peer("remote_2", &node_b),
+            peer("remote_3", &node_a),
+            peer("not_recipient", &node_b),
+            // our own agents are not reached over the network
+            peer("other_agent_id", &node_a),
+        ];
+        let (local_list, node_map) =
+            lib3h
+                .as_ref()
+                .locate_recipients(&req_data.space_address, peer_list, |agent_id| {
+                    *agent_id != Address::from("agent_id")
+                        && *agent_id != Address::from("not_recipient")
+                });
+        assert_eq!(vec![Address::from("other_agent_id")], local_list);
+        // a single message per node, to all its recipients
+        assert_eq!(2, node_map.len());
+        assert_eq!(
+            vec![Address::from("remote_1"), Address::from("remote_3")],
+            node_map[&node_a]
+        );
+        assert_eq!(vec![Address::from("remote_2")], node_map[&node_b]);
+    }
+
+    #[test]
+    fn test_ghost_engine_signal_remote_agents() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_signal_remote_agents");
+        let mut core = MockCore {};
+        let mut req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        req_data.agent_id = "other_agent_id".into();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        let sender_data = make_test_join_request();
+        for (agent_id, node) in &[("remote_1", "node_a"), ("remote_2", "node_a")] {
+            hold_space_peer(
+                &mut lib3h,
+                &sender_data,
+                PeerData {
+                    peer_name: Lib3hUri::with_agent_id(&(*agent_id).into()),
+                    peer_location: Lib3hUri::with_transport_id(&(*node).into()),
+                    timestamp: crate::time::since_epoch_ms(),
+                },
+            );
+        }
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let responses = request_and_process(
+            &mut lib3h,
+            ClientToLib3h::SendSignal(SignalData {
+                space_address: "space_addr".into(),
+                from_agent_id: "agent_id".into(),
+                to_agent_id_list: Some(vec![
+                    "remote_1".into(),
+                    "remote_2".into(),
+                    "other_agent_id".into(),
+                ]),
+                content: b"typing".to_vec().into(),
+            }),
+        );
+        // sent in a later process loop, once the peer list came back
+        lib3h.process(&mut core).unwrap();
+        lib3h.process(&mut core).unwrap();
+        assert!(lib3h.as_ref().space_defered_sends.is_empty());
+
+        // remote agents are not handed to our client
+        let signal_list = signal_received_list(&mut lib3h);
+        assert_eq!(1, signal_list.len());
+        assert_eq!(
+            Some(vec![Address::from("other_agent_id")]),
+            signal_list[0].to_agent_id_list
+        );
+        assert_eq!(
+            "[Ok(SendSignalResult)]",
+            format!("{:?}", responses.lock().unwrap())
+        );
+    }
+
+    fn topic_message_list(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+    ) -> Vec<TopicMessageData> {
+        lib3h
+            .drain_messages()
+            .iter_mut()
+            .filter_map(|msg| match msg.take_message() {
+                Some(Lib3hToClient::HandleTopicMessage(data)) => Some(data),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn make_test_subscription(agent_id: &str) -> TopicSubscriptionData {
+        TopicSubscriptionData {
+            space_address: "space_addr".into(),
+            agent_id: agent_id.into(),
+            topic: "chat".to_string(),
+        }
+    }
+
+    fn publish_test_topic_message(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+    ) -> EngineResponses {
+        let responses = request_and_process(
+            lib3h,
+            ClientToLib3h::PublishTopic(TopicMessageData {
+                space_address: "space_addr".into(),
+                topic: "chat".to_string(),
+                from_agent_id: "agent_id".into(),
+                to_agent_id: None,
+                content: b"hello".to_vec().into(),
+            }),
+        );
+        lib3h.process(&mut MockCore {}).unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        responses
+    }
+
+    #[test]
+    fn test_ghost_engine_topic_local_subscribers() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_topic_local_subscribers");
+        let mut req_data = make_test_join_request();
+        for agent_id in &["agent_id", "other_agent_id", "third_agent_id"] {
+            req_data.agent_id = (*agent_id).into();
+            lib3h
+                .as_mut()
+                .handle_join(test_span(""), &req_data)
+                .unwrap();
+        }
+        lib3h.process(&mut MockCore {}).unwrap();
+        lib3h.drain_messages();
+
+        let responses = request_and_process(
+            &mut lib3h,
+            ClientToLib3h::Subscribe(make_test_subscription("other_agent_id")),
+        );
+        assert_eq!(
+            "[Ok(SubscribeResult)]",
+            format!("{:?}", responses.lock().unwrap())
+        );
+
+        // only the subscriber gets it
+        let responses = publish_test_topic_message(&mut lib3h);
+        let message_list = topic_message_list(&mut lib3h);
+        assert_eq!(1, message_list.len());
+        assert_eq!(
+            Some(Address::from("other_agent_id")),
+            message_list[0].to_agent_id
+        );
+        assert_eq!(
+            "[Ok(PublishTopicResult)]",
+            format!("{:?}", responses.lock().unwrap())
+        );
+
+        let responses = request_and_process(
+            &mut lib3h,
+            ClientToLib3h::Unsubscribe(make_test_subscription("other_agent_id")),
+        );
+        assert_eq!(
+            "[Ok(UnsubscribeResult)]",
+            format!("{:?}", responses.lock().unwrap())
+        );
+        publish_test_topic_message(&mut lib3h);
+        assert!(topic_message_list(&mut lib3h).is_empty());
+        assert!(lib3h.as_ref().topic_subscribers.is_empty());
+    }
+
+    #[test]
+    fn test_ghost_engine_topic_message_received() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_topic_message_received");
+        let req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        lib3h.drain_messages();
+        lib3h.as_mut().handle_remote_subscribe(
+            &Lib3hUri::with_agent_id(&"remote_agent_id".into()),
+            make_test_subscription("remote_agent_id"),
+        );
+        lib3h
+            .as_mut()
+            .handle_subscribe(make_test_subscription("agent_id"))
+            .unwrap();
+
+        // delivered to our subscriber only
+        lib3h
+            .as_mut()
+            .handle_topic_message_received(TopicMessageData {
+                space_address: "space_addr".into(),
+                topic: "chat".to_string(),
+                from_agent_id: "remote_agent_id".into(),
+                to_agent_id: None,
+                content: b"hello".to_vec().into(),
+            })
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        let message_list = topic_message_list(&mut lib3h);
+        assert_eq!(1, message_list.len());
+        assert_eq!(Some(Address::from("agent_id")), message_list[0].to_agent_id);
+
+        // leaving the space drops our subscription, not the remote one
+        lib3h
+            .as_mut()
+            .handle_leave_space(test_span(""), &req_data)
+            .unwrap();
+        let key = (SpaceHash::from("space_addr"), "chat".to_string());
+        assert_eq!(
+            vec![&Address::from("remote_agent_id")],
+            lib3h.as_ref().topic_subscribers[&key]
+                .iter()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_ghost_engine_topic_remote_subscribers() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_topic_remote_subscribers");
+        let req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        let remote_agent = Lib3hUri::with_agent_id(&"remote_agent_id".into());
+        let now = crate::time::since_epoch_ms();
+        hold_space_peer(
+            &mut lib3h,
+            &req_data,
+            PeerData {
+                peer_name: remote_agent.clone(),
+                peer_location: Lib3hUri::with_transport_id(&"remote_node".into()),
+                timestamp: now - 10,
+            },
+        );
+        lib3h.process(&mut MockCore {}).unwrap();
+        let key = (SpaceHash::from("space_addr"), "chat".to_string());
+        let subscribers =
+            |lib3h: &GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>| {
+                lib3h
+                    .as_ref()
+                    .topic_subscribers
+                    .get(&key)
+                    .cloned()
+                    .unwrap_or_default()
+            };
+
+        // an agent can only subscribe itself
+        lib3h
+            .as_mut()
+            .handle_remote_subscribe(&remote_agent, make_test_subscription("other_agent_id"));
+        assert!(subscribers(&lib3h).is_empty());
+        lib3h
+            .as_mut()
+            .handle_remote_subscribe(&remote_agent, make_test_subscription("remote_agent_id"));
+        assert!(subscribers(&lib3h).contains(&Address::from("remote_agent_id")));
+        lib3h.as_mut().handle_remote_unsubscribe(
+            &Lib3hUri::with_agent_id(&"other_agent_id".into()),
+            make_test_subscription("remote_agent_id"),
+        );
+        assert!(subscribers(&lib3h).contains(&Address::from("remote_agent_id")));
+
+        // and is dropped once the space DHT forgets it
+        lib3h
+            .as_mut()
+            .space_gateway_map
+            .get_mut(&(req_data.space_address.clone(), req_data.agent_id.clone()))
+            .unwrap()
+            .publish(
+                test_span(""),
+                GatewayRequestToChild::Dht(DhtRequestToChild::DropPeer {
+                    peer_name: remote_agent,
+                    timestamp: now,
+                    location: None,
+                }),
+            )
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        assert!(subscribers(&lib3h).is_empty());
+    }
+
+    /// make `msg` of the engine, and process it once
+    fn request_and_process(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+        msg: ClientToLib3h,
+    ) -> EngineResponses {
+        let responses = request_for_test(lib3h, msg);
+        lib3h.process(&mut MockCore {}).unwrap();
+        responses
+    }
+
+    fn open_test_stream(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+    ) -> EngineResponses {
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &make_test_join_request())
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        lib3h.drain_messages();
+        request_and_process(
+            lib3h,
+            ClientToLib3h::OpenStream(OpenStreamData {
+                space_address: "space_addr".into(),
+                from_agent_id: "agent_id".into(),
+                to_agent_id: "to_agent_id".into(),
+            }),
+        )
+    }
+
+    fn make_test_stream_ack(stream_id: &str, sequence: Option<u64>) -> StreamAckData {
+        StreamAckData {
+            space_address: "space_addr".into(),
+            stream_id: stream_id.to_string(),
+            from_agent_id: "to_agent_id".into(),
+            to_agent_id: "agent_id".into(),
+            sequence,
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_ghost_engine_stream_window() {
+        let mut engine = make_test_engine("test_ghost_engine_stream_window");
+        engine.config.stream.window = 2;
+        let mut lib3h: GhostEngineParentWrapper<MockCore, GhostEngine, Lib3hError> =
+            GhostParentWrapper::new(engine, "test_engine");
+        let open_responses = open_test_stream(&mut lib3h);
+        let stream_id = lib3h.as_ref().streams.keys().next().unwrap().clone();
+        lib3h
+            .as_mut()
+            .handle_stream_ack(make_test_stream_ack(&stream_id, None))
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        let stream = match open_responses.lock().unwrap().remove(0) {
+            Ok(ClientToLib3hResponse::OpenStreamResult(stream)) => stream,
+            other => panic!("unexpected response: {:?}", other),
+        };
+        assert_eq!(stream_id, stream.stream_id);
+
+        let chunk_responses: Vec<EngineResponses> = (0..3)
+            .map(|sequence| {
+                request_and_process(
+                    &mut lib3h,
+                    ClientToLib3h::SendStreamChunk(StreamChunkData {
+                        space_address: "space_addr".into(),
+                        stream_id: stream_id.clone(),
+                        from_agent_id: "agent_id".into(),
+                        to_agent_id: "to_agent_id".into(),
+                        sequence,
+                        content: vec![sequence as u8; 16].into(),
+                    }),
+                )
+            })
+            .collect();
+        // only 2 chunks can wait for the recipient
+        assert!(chunk_responses[0].lock().unwrap().is_empty());
+        assert!(chunk_responses[1].lock().unwrap().is_empty());
+        assert!(format!("{:?}", chunk_responses[2].lock().unwrap()).contains("window is full"));
+
+        lib3h
+            .as_mut()
+            .handle_stream_ack(make_test_stream_ack(&stream_id, Some(0)))
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        assert_eq!(
+            "[Ok(SendStreamChunkResult)]",
+            format!("{:?}", chunk_responses[0].lock().unwrap())
+        );
+
+        let close_responses = request_and_process(&mut lib3h, ClientToLib3h::CloseStream(stream));
+        assert_eq!(
+            "[Ok(CloseStreamResult)]",
+            format!("{:?}", close_responses.lock().unwrap())
+        );
+        assert!(format!("{:?}", chunk_responses[1].lock().unwrap()).contains("Stream closed"));
+        assert!(lib3h.as_ref().streams.is_empty());
+    }
+
+    #[test]
+    fn test_ghost_engine_stream_times_out() {
+        let mut engine = make_test_engine("test_ghost_engine_stream_times_out");
+        engine.config.stream.timeout_ms = 10;
+        let mut lib3h: GhostEngineParentWrapper<MockCore, GhostEngine, Lib3hError> =
+            GhostParentWrapper::new(engine, "test_engine");
+        let open_responses = open_test_stream(&mut lib3h);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        lib3h.process(&mut MockCore {}).unwrap();
+
+        assert!(format!("{:?}", open_responses.lock().unwrap()).contains("DirectMessageTimeout"));
+        assert!(lib3h.as_ref().streams.is_empty());
+        let closed = lib3h
+            .drain_messages()
+            .iter_mut()
+            .any(|msg| matches!(msg.take_message(), Some(Lib3hToClient::StreamClosed(_))));
+        assert!(closed);
+    }
+
+    fn make_test_remote_stream() -> StreamData {
+        StreamData {
+            space_address: "space_addr".into(),
+            stream_id: "stream_id".into(),
+            from_agent_id: "remote_agent_id".into(),
+            to_agent_id: "agent_id".into(),
+        }
+    }
+
+    fn make_test_remote_chunk(sequence: u64) -> StreamChunkData {
+        StreamChunkData {
+            space_address: "space_addr".into(),
+            stream_id: "stream_id".into(),
+            from_agent_id: "remote_agent_id".into(),
+            to_agent_id: "agent_id".into(),
+            sequence,
+            content: b"chunk".to_vec().into(),
+        }
+    }
+
+    /// A remote agent opens a stream to our agent, that our client accepts
+    fn accept_test_remote_stream(
+        lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+    ) {
+        let mut core = MockCore {};
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &make_test_join_request())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let stream = make_test_remote_stream();
+        lib3h
+            .as_mut()
+            .handle_remote_open_stream(test_span(""), stream.clone())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        // known before our client answers, but takes no chunk yet
+        assert_eq!(1, lib3h.as_ref().streams.len());
+        lib3h
+            .as_mut()
+            .handle_remote_stream_chunk(test_span(""), make_test_remote_chunk(0))
+            .unwrap();
+        // nor a second StreamOpen
+        lib3h
+            .as_mut()
+            .handle_remote_open_stream(test_span(""), stream.clone())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        let mut msgs = lib3h.drain_messages();
+        assert_eq!(1, msgs.len());
+        let mut msg = msgs.remove(0);
+        assert_eq!(
+            Some(Lib3hToClient::HandleOpenStream(stream)),
+            msg.take_message()
+        );
+        msg.respond(Ok(Lib3hToClientResponse::HandleOpenStreamResult))
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        assert_eq!(1, lib3h.as_ref().streams.len());
+    }
+
+    #[test]
+    fn test_ghost_engine_stream_received() {
+        let mut engine = make_test_engine("test_ghost_engine_stream_received");
+        engine.config.stream.window = 1;
+        let mut lib3h: GhostEngineParentWrapper<MockCore, GhostEngine, Lib3hError> =
+            GhostParentWrapper::new(engine, "test_engine");
+        let mut core = MockCore {};
+        accept_test_remote_stream(&mut lib3h);
+
+        let receive =
+            |lib3h: &mut GhostEngineParentWrapper<MockCore, GhostEngine<'static>, Lib3hError>,
+             sequence| {
+                lib3h
+                    .as_mut()
+                    .handle_remote_stream_chunk(test_span(""), make_test_remote_chunk(sequence))
+                    .unwrap();
+                lib3h.process(&mut MockCore {}).unwrap();
+                lib3h.drain_messages()
+            };
+        let mut msgs = receive(&mut lib3h, 0);
+        assert_eq!(1, msgs.len());
+        // out of the window
+        assert!(receive(&mut lib3h, 1).is_empty());
+        msgs.remove(0)
+            .respond(Ok(Lib3hToClientResponse::HandleStreamChunkResult))
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        // not a new chunk
+        assert!(receive(&mut lib3h, 0).is_empty());
+        assert_eq!(1, receive(&mut lib3h, 1).len());
+
+        let stream = make_test_remote_stream();
+        lib3h
+            .as_mut()
+            .handle_remote_close_stream(stream.clone())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        assert!(lib3h.as_ref().streams.is_empty());
+        let mut msgs = lib3h.drain_messages();
+        assert_eq!(
+            Some(Lib3hToClient::StreamClosed(stream)),
+            msgs[0].take_message()
+        );
+    }
+
+    #[test]
+    fn test_ghost_engine_stream_sequence_out_of_range() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_stream_sequence_out_of_range");
+        accept_test_remote_stream(&mut lib3h);
+
+        // the last sequence has no next one, so it is refused
+        lib3h
+            .as_mut()
+            .handle_remote_stream_chunk(test_span(""), make_test_remote_chunk(u64::MAX))
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        assert!(lib3h.drain_messages().is_empty());
+        lib3h
+            .as_mut()
+            .handle_remote_stream_chunk(test_span(""), make_test_remote_chunk(0))
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        assert_eq!(1, lib3h.drain_messages().len());
+
+        // and our client can't send it either
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_stream_sequence_out_of_range");
+        open_test_stream(&mut lib3h);
+        let stream_id = lib3h.as_ref().streams.keys().next().unwrap().clone();
+        lib3h
+            .as_mut()
+            .handle_stream_ack(make_test_stream_ack(&stream_id, None))
+            .unwrap();
+        lib3h.process(&mut MockCore {}).unwrap();
+        let responses = request_and_process(
+            &mut lib3h,
+            ClientToLib3h::SendStreamChunk(StreamChunkData {
+                space_address: "space_addr".into(),
+                stream_id: stream_id.clone(),
+                from_agent_id: "agent_id".into(),
+                to_agent_id: "to_agent_id".into(),
+                sequence: u64::MAX,
+                content: vec![0; 16].into(),
+            }),
+        );
+        assert!(format!("{:?}", responses.lock().unwrap()).contains("out of range"));
+    }
+
+    #[test]
+    fn test_ghost_engine_stream_received_idle() {
+        let mut engine = make_test_engine("test_ghost_engine_stream_received_idle");
+        engine.config.stream.timeout_ms = 10;
+        let mut lib3h: GhostEngineParentWrapper<MockCore, GhostEngine, Lib3hError> =
+            GhostParentWrapper::new(engine, "test_engine");
+        accept_test_remote_stream(&mut lib3h);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        lib3h.process(&mut MockCore {}).unwrap();
+
+        assert!(lib3h.as_ref().streams.is_empty());
+        let mut msgs = lib3h.drain_messages();
+        assert_eq!(
+            Some(Lib3hToClient::StreamClosed(make_test_remote_stream())),
+            msgs[0].take_message()
+        );
+    }
+
+    #[test]
+    fn test_ghost_engine_store_entry() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_store_entry");
+        let mut core = MockCore {};
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &make_test_join_request())
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let mut entry = make_test_entry().entry;
+        for aspect_address in &["aspect_1", "aspect_2"] {
+            entry.aspect_list.push(EntryAspectData {
+                aspect_address: (*aspect_address).into(),
+                type_hint: "fake_type_hint".into(),
+                aspect: b"fake aspect".to_vec().into(),
+                publish_ts: 0,
+            });
+        }
+        lib3h
+            .as_mut()
+            .handle_store_entry(StoreEntryData {
+                space_address: "space_addr".into(),
+                request_id: "publish_id".into(),
+                from_agent_id: "publisher_agent_id".into(),
+                to_agent_id: "agent_id".into(),
+                entry,
+            })
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        let msgs = lib3h.drain_messages();
+        assert_eq!(2, msgs.len());
+        assert_eq!(1, lib3h.as_ref().pending_entry_stores.len());
+        for mut msg in msgs {
+            match msg.take_message() {
+                Some(Lib3hToClient::HandleStoreEntryAspect(data)) => {
+                    assert_eq!(Address::from("publisher_agent_id"), data.provider_agent_id);
+                }
+                other => panic!("unexpected message: {:?}", other),
+            }
+            msg.respond(Ok(Lib3hToClientResponse::HandleStoreEntryAspectResult))
+                .unwrap();
+        }
+        lib3h.process(&mut core).unwrap();
+        assert!(lib3h.as_ref().pending_entry_stores.is_empty());
+    }
+
+    #[test]
+    fn test_ghost_engine_hold_entry_rejected() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_hold_entry_rejected");
+        let mut core = MockCore {};
+        let req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let chain_id = (req_data.space_address.clone(), req_data.agent_id.clone());
+        let mut entry = make_test_entry().entry;
+        for aspect_address in &["valid_aspect", "invalid_aspect"] {
+            entry.aspect_list.push(EntryAspectData {
+                aspect_address: (*aspect_address).into(),
+                type_hint: "fake_type_hint".into(),
+                aspect: b"fake aspect".to_vec().into(),
+                publish_ts: 0,
+            });
+        }
+        let hold_entry = |lib3h: &mut GhostEngineParentWrapper<_, GhostEngine, _>,
+                          entry: &EntryData| {
+            lib3h
+                .as_mut()
+                .handle_hold_entry_requested(
+                    &chain_id,
+                    Lib3hUri::with_agent_id(&req_data.agent_id),
+                    entry.clone(),
+                )
+                .unwrap();
+            lib3h.process(&mut MockCore {}).unwrap();
+            lib3h.drain_messages()
+        };
+
+        for mut msg in hold_entry(&mut lib3h, &entry) {
+            let aspect_address = match msg.take_message() {
+                Some(Lib3hToClient::HandleStoreEntryAspect(data)) => {
+                    data.entry_aspect.aspect_address
+                }
+                other => panic!("unexpected message: {:?}", other),
+            };
+            if aspect_address == AspectHash::from("invalid_aspect") {
+                msg.respond(Err("invalid".into())).unwrap();
+            } else {
+                msg.respond(Ok(Lib3hToClientResponse::HandleStoreEntryAspectResult))
+                    .unwrap();
+            }
+        }
+        lib3h.process(&mut core).unwrap();
+        assert!(lib3h.as_ref().rejected_aspects[&chain_id]
+            .iter()
+            .any(|(aspect_address, _)| *aspect_address == AspectHash::from("invalid_aspect")));
+
+        // gossip of the rejected aspect is dropped without asking again
+        let mut msgs = hold_entry(&mut lib3h, &entry);
+        assert_eq!(1, msgs.len());
+        match msgs[0].take_message() {
+            Some(Lib3hToClient::HandleStoreEntryAspect(data)) => {
+                assert_eq!(
+                    AspectHash::from("valid_aspect"),
+                    data.entry_aspect.aspect_address
+                )
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+
+        // other content under that address is validated again
+        for aspect in entry.aspect_list.iter_mut() {
+            aspect.aspect = b"genuine aspect".to_vec().into();
+        }
+        assert_eq!(2, hold_entry(&mut lib3h, &entry).len());
+
+        // remote peers can't grow the rejected aspects past a limit
+        let mut junk = make_test_entry().entry;
+        for i in 0..MAX_REJECTED_ASPECTS + 10 {
+            junk.aspect_list.push(EntryAspectData {
+                aspect_address: AspectHash::from(format!("junk_aspect_{}", i).as_str()),
+                type_hint: "fake_type_hint".into(),
+                aspect: b"junk".to_vec().into(),
+                publish_ts: 0,
+            });
+        }
+        for msg in hold_entry(&mut lib3h, &junk) {
+            msg.respond(Err("invalid".into())).unwrap();
+        }
+        lib3h.process(&mut core).unwrap();
+        assert_eq!(
+            MAX_REJECTED_ASPECTS,
+            lib3h.as_ref().rejected_aspects[&chain_id].len()
+        );
+
+        // leaving the space forgets about it
+        lib3h
+            .as_mut()
+            .handle_leave_space(test_span(""), &req_data)
+            .unwrap();
+        assert!(!lib3h.as_ref().rejected_aspects.contains_key(&chain_id));
+    }
+
+    #[test]
+    fn test_ghost_engine_delete_entry() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_delete_entry");
+        let mut core = MockCore {};
+        let req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let responses = EngineResponses::default();
+        lib3h
+            .request(
+                test_span(""),
+                ClientToLib3h::DeleteEntry(DeleteEntryData {
+                    space_address: req_data.space_address.clone(),
+                    provider_agent_id: req_data.agent_id.clone(),
+                    entry_address: "fake_address".into(),
+                    aspect_address_list: None,
+                }),
+                test_callback(&responses),
+            )
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.process(&mut core).unwrap();
+        assert_eq!(
+            "[Ok(DeleteEntryResult)]",
+            format!("{:?}", responses.lock().unwrap())
+        );
+        let mut msgs = lib3h.drain_messages();
+        assert_eq!(1, msgs.len());
+        match msgs[0].take_message() {
+            Some(Lib3hToClient::HandleDropEntry(data)) => {
+                assert_eq!(EntryHash::from("fake_address"), data.entry_address);
+                assert_eq!(None, data.aspect_address_list);
+            }
+            other => panic!("unexpected message: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_ghost_engine_tombstone_signature() {
+        let mut lib3h = make_test_engine_wrapper("test_ghost_engine_tombstone_signature");
+        let mut core = MockCore {};
+        let req_data = make_test_join_request();
+        lib3h
+            .as_mut()
+            .handle_join(test_span(""), &req_data)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        lib3h.drain_messages();
+
+        let engine = lib3h.as_mut();
+        let mut tombstone = TombstoneData {
+            space_address: req_data.space_address.clone(),
+            entry_address: "fake_address".into(),
+            aspect_address_list: Some(vec!["fake_aspect_address".into()]),
+            provider_agent_id: req_data.agent_id.clone(),
+            timestamp: crate::time::since_epoch_ms(),
+            signer: engine.this_net_peer.peer_name.clone(),
+            signature: Vec::new().into(),
+        };
+        tombstone.signature = engine
+            .transport_keys
+            .sign(engine.crypto.as_ref(), &tombstone.signed_content())
+            .unwrap()
+            .into();
+        let mut refused = tombstone.clone();
+        refused.entry_address = "other_fake_address".into();
+        refused.signature = engine
+            .transport_keys
+            .sign(engine.crypto.as_ref(), &refused.signed_content())
+            .unwrap()
+            .into();
+        let chain_id = (req_data.space_address.clone(), req_data.agent_id.clone());
+
+        // tampered with: ignored
+        let mut tampered = tombstone.clone();
+        tampered.aspect_address_list = None;
+        engine
+            .handle_hold_tombstone_requested(&chain_id, tampered)
+            .unwrap();
+        lib3h.process(&mut core).unwrap();
+        assert_eq!(0, lib3h.drain_messages().len());
+
+        // otherwise the client decides
+        for (tombstone, approved) in vec![(refused, false), (tombstone, true)] {
+            lib3h
+                .as_mut()
+                .handle_hold_tombstone_requested(&chain_id, tombstone.clone())
+                .unwrap();
+            lib3h.process(&mut core).unwrap();
+            let mut msgs = lib3h.drain_messages();
+            assert_eq!(1, msgs.len());
+            let mut msg = msgs.remove(0);
+            match msg.take_message() {
+                Some(Lib3hToClient::HandleDeleteEntry(data)) => {
+                    assert_eq!(tombstone.entry_address, data.entry_address);
+                    assert_eq!(tombstone.provider_agent_id, data.provider_agent_id);
+                }
+                other => panic!("unexpected message: {:?}", other),
+            }
+            msg.respond(if approved {
+                Ok(Lib3hToClientResponse::HandleDeleteEntryResult)
+            } else {
+                Err("not the author".into())
+            })
+            .unwrap();
+            lib3h.process(&mut core).unwrap();
+            lib3h.process(&mut core).unwrap();
+            let mut msgs = lib3h.drain_messages();
+            if !approved {
+                assert_eq!(0, msgs.len());
+                continue;
+            }
+            assert_eq!(1, msgs.len());
+            match msgs[0].take_message() {
+                Some(Lib3hToClient::HandleDropEntry(data)) => assert_eq!(
+                    Some(vec![AspectHash::from("fake_aspect_address")]),
+                    data.aspect_address_list
+                ),
+                other => panic!("unexpected message: {:?}", other),
+            }
+        }
     }
 
     fn make_test_query(space_address: SpaceHash) -> QueryEntryData {
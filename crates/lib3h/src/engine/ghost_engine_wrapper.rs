@@ -116,6 +116,26 @@ where
                             ClientToLib3hResponse::LeaveSpaceResult => {
                                 server_success(request_id.clone(), space_addr, agent)
                             }
+                            ClientToLib3hResponse::SendSignalResult => {
+                                server_success(request_id.clone(), space_addr, agent)
+                            }
+                            // no Lib3hClientProtocol request is answered with these
+                            ClientToLib3hResponse::DeleteEntryResult
+                            | ClientToLib3hResponse::SetNetworkPolicyResult
+                            | ClientToLib3hResponse::SendStreamChunkResult
+                            | ClientToLib3hResponse::CloseStreamResult
+                            | ClientToLib3hResponse::SubscribeResult
+                            | ClientToLib3hResponse::UnsubscribeResult
+                            | ClientToLib3hResponse::PublishTopicResult
+                            | ClientToLib3hResponse::GetTransportStatsResult(_)
+                            | ClientToLib3hResponse::GetNetworkStateResult(_)
+                            | ClientToLib3hResponse::GetSpaceStateResult(_)
+                            | ClientToLib3hResponse::PublishEntryResult(_)
+                            | ClientToLib3hResponse::OpenStreamResult(_)
+                            | ClientToLib3hResponse::RotateTransportKeyResult(_) => unreachable!(),
+                            ClientToLib3hResponse::ShutdownResult => {
+                                Lib3hServerProtocol::Terminated
+                            }
                             ClientToLib3hResponse::SendDirectMessageResult(sent_data) => {
                                 let mut data = sent_data;
                                 data.request_id = request_id.clone();
@@ -219,6 +239,17 @@ where
                 data.space_address.clone(),
                 data.provider_agent_id.clone(),
             ),
+            Lib3hClientProtocol::SendSignal(data) => (
+                "".to_string(),
+                data.space_address.clone(),
+                data.from_agent_id.clone(),
+            ),
+            // has no request_id of its own, but must be sent as a request to be answered
+            Lib3hClientProtocol::Shutdown => (
+                "shutdown".to_string(),
+                SpaceHash::from("bogus_address"),
+                "bogus_agent".into(),
+            ),
             msg => unimplemented!("Handle this case: {:?}", msg),
         };
 
@@ -292,7 +323,26 @@ where
                 lib3h_to_client_msg
             );
 
-            self.tracker.set(tracker_request_id.as_str(), Some(msg));
+            // Lib3hServerProtocol has no streams, topics nor deletions: leave them
+            // unanswered, the engine refuses a stream or deletion once its request
+            // timed out
+            match &lib3h_to_client_msg {
+                Lib3hToClient::HandleOpenStream(_)
+                | Lib3hToClient::HandleDeleteEntry(_)
+                | Lib3hToClient::HandleStreamChunk(_)
+                | Lib3hToClient::StreamClosed(_)
+                | Lib3hToClient::HandleTopicMessage(_) => continue,
+                _ => (),
+            }
+
+            // Lib3hClientProtocol has no answer to HandleStoreEntryAspect,
+            // publishers are told we can't confirm storing it
+            if let Lib3hToClient::HandleStoreEntryAspect(_) = &lib3h_to_client_msg {
+                msg.respond(Ok(Lib3hToClientResponse::HandleStoreEntryAspectUnconfirmed))
+                    .map_err(|e| Lib3hProtocolError::new(ErrorKind::Other(e.to_string())))?;
+            } else if msg.is_request() {
+                self.tracker.set(tracker_request_id.as_str(), Some(msg));
+            }
             let lib3h_server_protocol_msg: Lib3hServerProtocol =
                 self.inject_request_id(tracker_request_id.clone(), lib3h_to_client_msg.into());
             responses.push(lib3h_server_protocol_msg);
@@ -330,6 +380,8 @@ where
                     .insert(request_id.clone(), data.request_id.clone());
                 data.request_id = request_id
             }
+            // a notification, there is nothing to answer
+            Lib3hServerProtocol::SignalReceived(_) => (),
             msg => error!("[inject_request_id] CONVERT ME: {:?}", msg),
         }
         msg
@@ -365,6 +417,8 @@ mod tests {
                 EngineError,
             >,
         >,
+        /// what the client answered to injected requests
+        request_results: Vec<GhostCallbackData<Lib3hToClientResponse, EngineError>>,
     }
 
     impl CanAdvertise for MockGhostEngine {
@@ -384,6 +438,7 @@ mod tests {
                         .request_id_prefix("engine")
                         .build(),
                 ),
+                request_results: Vec::new(),
             }
         }
     }
@@ -455,8 +510,9 @@ mod tests {
 
         /// create a fake lib3h request
         pub fn inject_lib3h_request(&mut self, msg: Lib3hToClient) {
-            let f: GhostCallback<_, _, _> = Box::new(|_user_data, cb_data| {
+            let f: GhostCallback<_, _, _> = Box::new(|me: &mut MockGhostEngine, cb_data| {
                 debug!("inject_lib3h_request: {:?}", cb_data);
+                me.request_results.push(cb_data);
                 Ok(())
             });
 
@@ -560,5 +616,28 @@ mod tests {
             "Ok((true, [Disconnected(DisconnectedData { network_id: \"\" })]))",
             format!("{:?}", result)
         );
+
+        // legacy clients don't confirm storing aspects
+        detach_run!(&mut legacy.engine, |l| l.as_mut().inject_lib3h_request(
+            Lib3hToClient::HandleStoreEntryAspect(StoreEntryAspectData {
+                request_id: "store_req_id".into(),
+                space_address: "fake_space_address".into(),
+                provider_agent_id: "fake_id".into(),
+                entry_address: "fake_entry_address".into(),
+                entry_aspect: EntryAspectData {
+                    aspect_address: "fake_aspect_address".into(),
+                    type_hint: "fake_type_hint".into(),
+                    aspect: b"fake aspect".to_vec().into(),
+                    publish_ts: 0,
+                },
+            })
+        ));
+        legacy.process().unwrap();
+        legacy.process().unwrap();
+        let request_results = &legacy.engine.as_ref().as_ref().request_results;
+        assert_eq!(
+            "Some(Response(Ok(HandleStoreEntryAspectUnconfirmed)))",
+            format!("{:?}", request_results.last())
+        );
     }
 }
@@ -1,25 +1,39 @@
+mod direct_message;
 pub mod engine_actor;
 pub mod ghost_engine;
 pub mod ghost_engine_wrapper;
 mod network_layer;
 pub mod p2p_protocol;
+mod publish;
+mod rotation;
+mod shutdown;
+mod signal;
 mod space_layer;
+mod stream;
+mod tombstone;
+mod topic;
 
 use crate::{
     dht::dht_protocol::*,
-    engine::engine_actor::ClientToLib3hMessage,
     error::*,
     gateway::{protocol::*, P2pGateway},
+    keystore::{Keystore, TRANSPORT_KEY_ID},
     track::Tracker,
     transport::{websocket::tls::TlsConfig, TransportMultiplex},
 };
 use detach::Detach;
 use lib3h_crypto_api::{Buffer, CryptoSystem};
-use lib3h_ghost_actor::{prelude::*, RequestId};
-use lib3h_protocol::{protocol::*, types::SpaceHash, uri::Lib3hUri, Address};
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{
+    data_types::{NetworkPolicyData, TransportRotationData},
+    protocol::*,
+    types::{AspectHash, SpaceHash},
+    uri::Lib3hUri,
+    Address,
+};
 use std::{
     collections::{HashMap, HashSet},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 /// Identifier of a source chain: SpaceAddress+AgentId
@@ -40,9 +54,6 @@ enum RealEngineTrackerData {
     GetAuthoringEntryList,
     /// once we have the AuthoringEntryListResponse, fetch data for entries
     DataForAuthorEntry,
-    /// gossip has requested we store data, send a hold request to core
-    /// core should respond ??
-    HoldEntryRequested,
 }
 
 /// Transport specific configuration
@@ -65,9 +76,121 @@ pub struct EngineConfig {
     pub work_dir: PathBuf,
     pub log_level: char,
     pub bind_url: Lib3hUri,
+    /// More specs to bind, to listen on several interfaces at once,
+    /// e.g. `wss://[fd00::2]:0` next to an IPv4 `bind_url`
+    #[serde(default)]
+    pub extra_bind_urls: Vec<Lib3hUri>,
     pub dht_gossip_interval: u64,
     pub dht_timeout_threshold: u64,
+    /// How long deletions are remembered, in ms after they were issued
+    #[serde(default = "crate::dht::dht_config::default_tombstone_retention")]
+    pub dht_tombstone_retention: u64,
     pub dht_custom_config: Vec<u8>,
+    /// Allow/block lists for remote nodes and agents, can be replaced at runtime
+    #[serde(default)]
+    pub network_policy: NetworkPolicyData,
+    /// How many holders a published entry must reach
+    #[serde(default)]
+    pub publish: PublishConfig,
+    /// How long to wait for the answer to a direct message
+    #[serde(default)]
+    pub direct_message: DirectMessageConfig,
+    /// Flow control of streams between agents
+    #[serde(default)]
+    pub stream: StreamConfig,
+    /// Seed of our TransportId keypair, for the TransportId to stay the same
+    /// across restarts. Without it a new keypair is generated on each start.
+    /// A seed given inline is never serialized, only a seed file is.
+    #[serde(default, skip_serializing_if = "TransportSeed::is_secret")]
+    pub transport_seed: Option<TransportSeed>,
+    /// Certificates of our past TransportId rotations, oldest first.
+    /// Sent to the nodes connecting to us so they know our previous ids.
+    #[serde(default)]
+    pub transport_rotations: Vec<TransportRotationData>,
+}
+
+/// Where the engine gets the seed of its TransportId keypair
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+#[serde(tag = "type", content = "data")]
+pub enum TransportSeed {
+    /// The seed itself, `CryptoSystem::sign_seed_bytes()` long
+    Seed(Vec<u8>),
+    /// File holding the seed, created with a random one if missing
+    File(PathBuf),
+}
+
+impl TransportSeed {
+    fn is_secret(transport_seed: &Option<TransportSeed>) -> bool {
+        matches!(transport_seed, Some(TransportSeed::Seed(_)))
+    }
+}
+
+impl std::fmt::Debug for TransportSeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TransportSeed::Seed(_) => f.debug_tuple("Seed").field(&"<redacted>").finish(),
+            TransportSeed::File(path) => f.debug_tuple("File").field(path).finish(),
+        }
+    }
+}
+
+/// Replication settings for `ClientToLib3h::PublishEntry`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PublishConfig {
+    /// How many agents of other nodes must confirm storing a published entry
+    pub replication_factor: usize,
+    /// How long to wait for confirmations before asking again, in ms
+    pub retry_interval_ms: u64,
+    /// How many times to ask before answering with the confirmations we got
+    pub max_attempts: u32,
+}
+
+impl Default for PublishConfig {
+    fn default() -> Self {
+        PublishConfig {
+            replication_factor: 1,
+            retry_interval_ms: 1000,
+            max_attempts: 5,
+        }
+    }
+}
+
+/// Delivery settings for `ClientToLib3h::SendDirectMessage`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DirectMessageConfig {
+    /// How long to wait for the recipient's answer before sending again, in ms
+    pub timeout_ms: u64,
+    /// How many times to send again before answering with an error
+    pub max_retries: u32,
+}
+
+impl Default for DirectMessageConfig {
+    fn default() -> Self {
+        DirectMessageConfig {
+            timeout_ms: 5000,
+            max_retries: 2,
+        }
+    }
+}
+
+/// Flow control settings for `ClientToLib3h::OpenStream`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StreamConfig {
+    /// How many chunks of a stream can wait for the recipient at a time
+    pub window: usize,
+    /// How long to wait for the other end before closing the stream, in ms:
+    /// for the recipient's answers, or for the next chunk of an incoming stream
+    pub timeout_ms: u64,
+}
+
+impl Default for StreamConfig {
+    fn default() -> Self {
+        StreamConfig {
+            window: 8,
+            timeout_ms: 30000,
+        }
+    }
 }
 
 pub struct TransportKeys {
@@ -80,16 +203,126 @@ pub struct TransportKeys {
 }
 impl TransportKeys {
     pub fn new(crypto: &dyn CryptoSystem) -> Lib3hResult<Self> {
-        let hcm0 = hcid::HcidEncoding::with_kind("hcm0")?;
         let mut public_key: Box<dyn Buffer> = Box::new(vec![0; crypto.sign_public_key_bytes()]);
         let mut secret_key = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
         crypto.sign_keypair(&mut public_key, &mut secret_key)?;
+        Self::with_keypair(public_key, secret_key)
+    }
+
+    /// Derive our keypair from `seed`: the same seed gives the same TransportId
+    pub fn with_seed(crypto: &dyn CryptoSystem, seed: &dyn Buffer) -> Lib3hResult<Self> {
+        if seed.len() != crypto.sign_seed_bytes() {
+            return Err(Lib3hError::new_other(&format!(
+                "transport seed must be {} bytes, not {}",
+                crypto.sign_seed_bytes(),
+                seed.len()
+            )));
+        }
+        let seed = seed.box_clone();
+        let mut public_key: Box<dyn Buffer> = Box::new(vec![0; crypto.sign_public_key_bytes()]);
+        let mut secret_key = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        crypto.sign_seed_keypair(&seed, &mut public_key, &mut secret_key)?;
+        Self::with_keypair(public_key, secret_key)
+    }
+
+    /// Keys derived from the configured seed, or new ones if there is none
+    pub fn from_config(
+        crypto: &dyn CryptoSystem,
+        transport_seed: &Option<TransportSeed>,
+    ) -> Lib3hResult<Self> {
+        let seed = match transport_seed {
+            None => return Self::new(crypto),
+            Some(TransportSeed::Seed(seed_bytes)) => {
+                let mut seed = crypto.buf_new_secure(seed_bytes.len());
+                seed.write(0, seed_bytes)?;
+                seed
+            }
+            Some(TransportSeed::File(path)) => Self::read_seed_file(crypto, path)?,
+        };
+        Self::with_seed(crypto, &*seed)
+    }
+
+    /// Keys stored in `keystore`, generated and stored there on first use
+    pub fn from_keystore(keystore: &mut Keystore) -> Lib3hResult<Self> {
+        let (public_key, secret_key) = keystore.sign_keypair(TRANSPORT_KEY_ID)?;
+        Self::with_keypair(Box::new(public_key), secret_key)
+    }
+
+    /// Read the seed stored at `path`, or store a new random one there,
+    /// readable by us only
+    fn read_seed_file(crypto: &dyn CryptoSystem, path: &Path) -> Lib3hResult<Box<dyn Buffer>> {
+        if path.exists() {
+            let mut seed_bytes = std::fs::read(path)?;
+            let mut seed = crypto.buf_new_secure(seed_bytes.len());
+            seed.write(0, &seed_bytes)?;
+            for byte in seed_bytes.iter_mut() {
+                *byte = 0;
+            }
+            return Ok(seed);
+        }
+        let mut seed = crypto.buf_new_secure(crypto.sign_seed_bytes());
+        crypto.randombytes_buf(&mut seed)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        crate::private_file::write(path, &seed.read_lock())?;
+        Ok(seed)
+    }
+
+    fn with_keypair(public_key: Box<dyn Buffer>, secret_key: Box<dyn Buffer>) -> Lib3hResult<Self> {
+        let hcm0 = hcid::HcidEncoding::with_kind("hcm0")?;
         Ok(Self {
             transport_id: hcm0.encode(&public_key)?.into(),
             transport_public_key: public_key,
             transport_secret_key: secret_key,
         })
     }
+
+    /// Sign `data` with our TransportId secret key
+    pub fn sign(&self, crypto: &dyn CryptoSystem, data: &[u8]) -> Lib3hResult<Vec<u8>> {
+        let mut signature: Box<dyn Buffer> = Box::new(vec![0; crypto.sign_bytes()]);
+        let message: Box<dyn Buffer> = Box::new(data.to_vec());
+        crypto.sign(&mut signature, &message, &self.transport_secret_key)?;
+        let signature = signature.read_lock().to_vec();
+        Ok(signature)
+    }
+}
+
+/// Check that `signature` of `data` was made by the secret key of `transport_id`
+pub fn verify_transport_signature(
+    crypto: &dyn CryptoSystem,
+    transport_id: &Address,
+    data: &[u8],
+    signature: &[u8],
+) -> Lib3hResult<bool> {
+    let transport_id: String = transport_id.clone().into();
+    let public_key: Box<dyn Buffer> =
+        Box::new(hcid::HcidEncoding::with_kind("hcm0")?.decode(&transport_id)?);
+    let signature: Box<dyn Buffer> = Box::new(signature.to_vec());
+    let message: Box<dyn Buffer> = Box::new(data.to_vec());
+    Ok(crypto.sign_verify(&signature, &message, &public_key)?)
+}
+
+/// Check that both TransportIds of a rotation certificate signed it
+pub fn verify_transport_rotation(
+    crypto: &dyn CryptoSystem,
+    rotation: &TransportRotationData,
+) -> Lib3hResult<bool> {
+    if rotation.old_transport_id == rotation.new_transport_id {
+        return Ok(false);
+    }
+    let content = rotation.signed_content();
+    Ok(verify_transport_signature(
+        crypto,
+        &rotation.old_transport_id,
+        &content,
+        &rotation.signature,
+    )? && verify_transport_signature(
+        crypto,
+        &rotation.new_transport_id,
+        &content,
+        &rotation.new_signature,
+    )?)
 }
 
 pub trait CanAdvertise {
@@ -121,12 +354,40 @@ pub struct GhostEngine<'engine> {
     #[allow(dead_code)]
     /// transport_id data, public/private keys, etc
     transport_keys: TransportKeys,
+    /// where `transport_keys` are stored, if the engine was built with a keystore
+    keystore: Option<Keystore>,
+    /// verified rotation certificates of remote nodes, by old TransportId
+    transport_rotations: HashMap<Address, TransportRotationData>,
     /// items we need to send on our multiplexer in another process loop
     multiplexer_defered_sends: Vec<(Lib3hUri, lib3h_protocol::data_types::Opaque)>,
+    /// requests we need to publish on our multiplexer in another process loop
+    multiplexer_defered_publishes: Vec<GatewayRequestToChild>,
+    /// items we need to publish to the client in another process loop
+    client_defered_publishes: Vec<Lib3hToClient>,
+    /// messages to agents of a space we need to send in another process loop,
+    /// with the peer list of that space
+    space_defered_sends: Vec<(signal::SpaceDeferedSend, Vec<PeerData>)>,
 
     /// when client gives us a SendDirectMessage, we need to cache the
     /// GhostMessage, re-hydrate when a response comes back from a remote
-    pending_client_direct_messages: HashMap<RequestId, ClientToLib3hMessage>,
+    pending_client_direct_messages: HashMap<String, direct_message::PendingDirectMessage>,
+    /// direct messages from remote agents, by sender and request_id
+    received_direct_messages: HashMap<(Address, String), direct_message::ReceivedDirectMessage>,
+    /// streams opened by or to our agents, by stream id
+    streams: HashMap<String, stream::Stream>,
+    /// subscribers of the topics of our spaces, ours and remote ones
+    topic_subscribers: HashMap<(SpaceHash, String), HashSet<Address>>,
+
+    /// PublishEntry requests waiting for holders to confirm they store the entry
+    pending_publishes: HashMap<String, publish::PendingPublish>,
+    /// entries a publisher asked us to store, waiting on our client
+    pending_entry_stores: HashMap<String, publish::PendingEntryStore>,
+    /// aspects our client found invalid, by address and content hash,
+    /// never held nor asked for again
+    rejected_aspects: HashMap<ChainId, HashSet<(AspectHash, Vec<u8>)>>,
+
+    /// set once the client asked us to shut down
+    shutdown: Option<shutdown::ShutdownState>,
 
     client_endpoint: Option<
         GhostEndpoint<
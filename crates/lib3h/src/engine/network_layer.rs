@@ -1,6 +1,9 @@
 use crate::{
     dht::dht_protocol::*,
-    engine::{ghost_engine::handle_GossipTo, p2p_protocol::P2pProtocol, GhostEngine},
+    engine::{
+        ghost_engine::handle_GossipTo, p2p_protocol::P2pProtocol, verify_transport_signature,
+        GhostEngine,
+    },
     error::{ErrorKind, Lib3hError, Lib3hResult},
     gateway::protocol::*,
     transport,
@@ -8,7 +11,7 @@ use crate::{
 
 use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
-use lib3h_protocol::{data_types::*, protocol::*, uri::Lib3hUri, DidWork};
+use lib3h_protocol::{data_types::*, protocol::*, uri::Lib3hUri, Address, DidWork};
 use rmp_serde::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
 
@@ -44,6 +47,10 @@ impl<'engine> GhostEngine<'engine> {
                 Box::new(move |_me, _response| Ok(())),
             )?;
         }
+        for request in self.multiplexer_defered_publishes.drain(..) {
+            did_work = true;
+            self.multiplexer.publish(Span::fixme(), request)?;
+        }
 
         // Done
         Ok(did_work)
@@ -90,6 +97,9 @@ impl<'engine> GhostEngine<'engine> {
                 // Disconnect from that peer by calling a Close on it.
                 // FIXME
             }
+            DhtRequestToParent::PeerLeft(_peer_name) => {
+                // no-op
+            }
             // No entries in Network DHT
             DhtRequestToParent::HoldEntryRequested {
                 from_peer_name: _,
@@ -100,6 +110,13 @@ impl<'engine> GhostEngine<'engine> {
             DhtRequestToParent::EntryPruned(_) => {
                 unreachable!();
             }
+            // Tombstones are for space DHTs, a peer could still gossip one here
+            DhtRequestToParent::HoldTombstoneRequested(tombstone) => {
+                warn!(
+                    "Ignoring tombstone gossiped on the network: {:?}",
+                    tombstone
+                );
+            }
             DhtRequestToParent::RequestEntry(_) => {
                 unreachable!();
             }
@@ -130,12 +147,15 @@ impl<'engine> GhostEngine<'engine> {
                     error!("unhandled error {}", error);
                 }
             }
-            transport::protocol::RequestToParent::IncomingConnection { uri } => {
+            transport::protocol::RequestToParent::IncomingConnection { uri, .. } => {
                 self.handle_incoming_connection(
                     span.child("handle_incoming_connection"),
                     uri.clone(),
                 )?;
             }
+            transport::protocol::RequestToParent::ConnectionVerified { uri, verified_id } => {
+                debug!("Connection to {} verified as {}", uri, verified_id);
+            }
             transport::protocol::RequestToParent::ReceivedData { uri, payload } => {
                 debug!("Received message from: {} | size: {}", uri, payload.len());
                 if payload.len() == 0 {
@@ -153,6 +173,9 @@ impl<'engine> GhostEngine<'engine> {
                     self.serve_P2pProtocol(span.child("serve_P2pProtocol"), uri, p2p_msg)?;
                 }
             }
+            transport::protocol::RequestToParent::ConnectionClosed { uri } => {
+                debug!("Connection closed: {}", uri);
+            }
         };
         Ok(())
     }
@@ -208,6 +231,7 @@ impl<'engine> GhostEngine<'engine> {
                             if let Some(peer_data) = maybe_peer_data {
                                 trace!("AllJoinedSpaceList ; sending back to {:?}", peer_data);
                                 me.defer_send(peer_data.peer_name.clone(), payload.into());
+                                me.send_transport_rotations(&peer_data.peer_name);
                                 /* TODO: #777
                                 me.multiplexer.publish(
                                     span.follower("publish TODO name"),
@@ -245,10 +269,10 @@ impl<'engine> GhostEngine<'engine> {
     /// Serve a P2pProtocol sent to us by the network.
     /// TODO #150
     #[allow(non_snake_case)]
-    fn serve_P2pProtocol(
+    pub(crate) fn serve_P2pProtocol(
         &mut self,
         span: Span,
-        _from: &Lib3hUri,
+        from: &Lib3hUri,
         p2p_msg: P2pProtocol,
     ) -> Lib3hResult<()> {
         match p2p_msg {
@@ -300,6 +324,22 @@ impl<'engine> GhostEngine<'engine> {
             P2pProtocol::PeerName(_, _, _) => {
                 // no-op
             }
+            P2pProtocol::StoreEntry(_)
+            | P2pProtocol::StoreEntryResult(_)
+            | P2pProtocol::DirectMessageRejected(_)
+            | P2pProtocol::Signal(_)
+            | P2pProtocol::StreamOpen(_)
+            | P2pProtocol::StreamChunk(_)
+            | P2pProtocol::StreamAck(_)
+            | P2pProtocol::StreamClose(_)
+            | P2pProtocol::TopicSubscribe(_)
+            | P2pProtocol::TopicUnsubscribe(_)
+            | P2pProtocol::TopicMessage(_) => {
+                warn!(
+                    "Ignoring space message sent at the network layer: {:?}",
+                    p2p_msg
+                );
+            }
             P2pProtocol::BroadcastJoinSpace(gateway_id, peer_data) => {
                 debug!("Received JoinSpace: {} {:?}", gateway_id, peer_data);
                 for (_, space_gateway) in self.space_gateway_map.iter_mut() {
@@ -308,6 +348,45 @@ impl<'engine> GhostEngine<'engine> {
                         GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(peer_data.clone())),
                     )?;
                 }
+                self.send_topic_subscriptions(&gateway_id, &Address::from(peer_data.peer_name))?;
+            }
+            P2pProtocol::BroadcastLeaveSpace(leave) => {
+                debug!("Received LeaveSpace: {:?}", leave);
+                // signed by the node the agent is located at
+                let peer_data = &leave.peer_data;
+                match verify_transport_signature(
+                    self.crypto.as_ref(),
+                    &peer_data.peer_location.lower_address(),
+                    &leave.signed_content(),
+                    &leave.signature,
+                ) {
+                    Ok(true) => (),
+                    result => {
+                        warn!(
+                            "Ignoring LeaveSpace with bad signature {:?}: {:?}",
+                            leave, result
+                        );
+                        return Ok(());
+                    }
+                }
+                let latest = crate::time::since_epoch_ms() + crate::time::MAX_CLOCK_SKEW_MS;
+                if peer_data.timestamp > latest {
+                    warn!("Ignoring LeaveSpace from the future: {:?}", leave);
+                    return Ok(());
+                }
+                for (chain_id, space_gateway) in self.space_gateway_map.iter_mut() {
+                    if chain_id.0 != leave.space_address {
+                        continue;
+                    }
+                    space_gateway.publish(
+                        span.follower("P2pProtocol::BroadcastLeaveSpace"),
+                        GatewayRequestToChild::Dht(DhtRequestToChild::DropPeer {
+                            peer_name: peer_data.peer_name.clone(),
+                            timestamp: peer_data.timestamp,
+                            location: Some(peer_data.peer_location.clone()),
+                        }),
+                    )?;
+                }
             }
             P2pProtocol::AllJoinedSpaceList(join_list) => {
                 debug!("Received AllJoinedSpaceList: {:?}", join_list);
@@ -320,12 +399,68 @@ impl<'engine> GhostEngine<'engine> {
                                 peer_data.clone(),
                             )),
                         )?;
+                        self.send_topic_subscriptions(
+                            &space_address,
+                            &Address::from(peer_data.peer_name),
+                        )?;
                     }
                 }
             }
             P2pProtocol::CapnProtoMessage(_) => {
                 panic!("Gateway should handle this case and NOT pass it to us");
             }
+            P2pProtocol::BroadcastLeave(leave) => {
+                debug!("Received Leave: {:?}", leave);
+                match verify_transport_signature(
+                    self.crypto.as_ref(),
+                    &leave.peer_name.lower_address(),
+                    &leave.signed_content(),
+                    &leave.signature,
+                ) {
+                    Ok(true) => (),
+                    result => {
+                        warn!(
+                            "Ignoring Leave with bad signature {:?}: {:?}",
+                            leave, result
+                        );
+                        return Ok(());
+                    }
+                }
+                // a departure in the future would keep the peer out until then
+                let latest = crate::time::since_epoch_ms() + crate::time::MAX_CLOCK_SKEW_MS;
+                if leave.timestamp > latest {
+                    warn!("Ignoring Leave from the future: {:?}", leave);
+                    return Ok(());
+                }
+                self.multiplexer.publish(
+                    span.follower("P2pProtocol::BroadcastLeave"),
+                    GatewayRequestToChild::Dht(DhtRequestToChild::DropPeer {
+                        peer_name: leave.peer_name.clone(),
+                        timestamp: leave.timestamp,
+                        location: None,
+                    }),
+                )?;
+                // only agents located at the signer's TransportId are dropped
+                let signer_location = Lib3hUri::with_transport_id(&leave.peer_name.lower_address());
+                for (space_address, agent_uri) in leave.space_list {
+                    for (chain_id, space_gateway) in self.space_gateway_map.iter_mut() {
+                        if chain_id.0 != space_address {
+                            continue;
+                        }
+                        space_gateway.publish(
+                            span.follower("P2pProtocol::BroadcastLeave"),
+                            GatewayRequestToChild::Dht(DhtRequestToChild::DropPeer {
+                                peer_name: agent_uri.clone(),
+                                timestamp: leave.timestamp,
+                                location: Some(signer_location.clone()),
+                            }),
+                        )?;
+                    }
+                }
+            }
+            P2pProtocol::BroadcastRotation(rotation) => {
+                self.handle_transport_rotation_received(span, from, rotation)?;
+            }
         };
         Ok(())
     }
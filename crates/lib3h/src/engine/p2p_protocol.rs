@@ -1,8 +1,12 @@
 use crate::dht::dht_protocol::PeerData;
 use lib3h_protocol::{
-    data_types::{DirectMessageData, Opaque},
+    data_types::{
+        DirectMessageData, EntryData, Opaque, SignalData, StreamChunkData, StreamData,
+        TopicMessageData, TopicSubscriptionData, TransportRotationData,
+    },
     types::SpaceHash,
     uri::Lib3hUri,
+    Address,
 };
 use rmp_serde::{Deserializer, Serializer};
 use serde::{Deserialize, Serialize};
@@ -28,6 +32,33 @@ pub enum P2pProtocol {
     /// doubly encoded in this P2pProtocol enum variant,
     /// once all messages have transitioned, we can drop this layer
     CapnProtoMessage(Vec<u8>),
+    /// Signed notice that a node is leaving the network, sent on shutdown
+    BroadcastLeave(LeaveData),
+    /// Signed notice that an agent left a space, sent to all when leaving it
+    BroadcastLeaveSpace(LeaveSpaceData),
+    /// Ask an agent to store an entry we published
+    StoreEntry(StoreEntryData),
+    /// Tell the publisher whether we stored its entry
+    StoreEntryResult(StoreEntryResultData),
+    /// Recipient's client did not answer a DirectMessage
+    DirectMessageRejected(DirectMessageRejectedData),
+    /// One-way message to the listed agents hosted by the receiving node
+    Signal(SignalData),
+    /// Ask an agent to accept a stream
+    StreamOpen(StreamData),
+    StreamChunk(StreamChunkData),
+    /// Recipient's answer to a StreamOpen or StreamChunk
+    StreamAck(StreamAckData),
+    /// Either end closed the stream
+    StreamClose(StreamData),
+    /// An agent subscribed to a topic of the space
+    TopicSubscribe(TopicSubscriptionData),
+    TopicUnsubscribe(TopicSubscriptionData),
+    /// Message to the subscribers of a topic hosted by the receiving node
+    TopicMessage(TopicMessageData),
+    /// Signed notice that a node changed its TransportId, sent to its peers
+    /// when it rotates its key and to the nodes connecting to it afterwards
+    BroadcastRotation(TransportRotationData),
 }
 
 /// DHT gossip data
@@ -39,6 +70,110 @@ pub struct GossipData {
     pub bundle: Opaque,
 }
 
+/// Entry a publisher wants a holder to store
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct StoreEntryData {
+    pub space_address: SpaceHash,
+    /// Identifies the publish, to be sent back in the StoreEntryResult
+    pub request_id: String,
+    /// The publisher
+    pub from_agent_id: Address,
+    /// The holder
+    pub to_agent_id: Address,
+    pub entry: EntryData,
+}
+
+/// Holder's answer to a StoreEntry
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct StoreEntryResultData {
+    pub space_address: SpaceHash,
+    pub request_id: String,
+    /// The holder
+    pub from_agent_id: Address,
+    /// The publisher
+    pub to_agent_id: Address,
+    /// false if the holder's client did not store every aspect
+    pub stored: bool,
+    /// true if the holder's client can't confirm storing, so asking it
+    /// again is pointless
+    pub unconfirmed: bool,
+}
+
+/// Recipient's refusal of a DirectMessage
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct DirectMessageRejectedData {
+    pub space_address: SpaceHash,
+    pub request_id: String,
+    /// The recipient
+    pub from_agent_id: Address,
+    /// The sender
+    pub to_agent_id: Address,
+    /// What the recipient's client answered instead
+    pub reason: String,
+}
+
+/// Recipient's answer to a StreamOpen or StreamChunk
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct StreamAckData {
+    pub space_address: SpaceHash,
+    pub stream_id: String,
+    /// The recipient
+    pub from_agent_id: Address,
+    /// The sender
+    pub to_agent_id: Address,
+    /// The chunk taken, None for the StreamOpen
+    pub sequence: Option<u64>,
+    /// Why the recipient's client refused it, if it did
+    pub error: Option<String>,
+}
+
+/// A node leaving the network and the spaces it was in
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct LeaveData {
+    /// PeerName of the node on the network gateway, i.e. its TransportId uri
+    pub peer_name: Lib3hUri,
+    /// When it left, peers heard from after this are not dropped
+    pub timestamp: PeerTimestamp,
+    /// The spaces it left, with the agent it had joined as
+    pub space_list: Vec<(SpaceHash, Lib3hUri)>,
+    /// Signature of `signed_content()` by the node's TransportId
+    pub signature: Opaque,
+}
+
+impl LeaveData {
+    /// The bytes covered by `signature`
+    pub fn signed_content(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        (&self.peer_name, self.timestamp, &self.space_list)
+            .serialize(&mut Serializer::new(&mut out))
+            .unwrap();
+        out
+    }
+}
+
+/// An agent leaving a space
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
+pub struct LeaveSpaceData {
+    pub space_address: SpaceHash,
+    /// The agent, located at its node's TransportId uri, timestamped with
+    /// the time it left
+    pub peer_data: PeerData,
+    /// Signature of `signed_content()` by the node's TransportId
+    pub signature: Opaque,
+}
+
+impl LeaveSpaceData {
+    /// The bytes covered by `signature`, tagged so they can't pass for
+    /// those of a LeaveData
+    pub fn signed_content(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        ("LeaveSpace", &self.space_address, &self.peer_data)
+            .serialize(&mut Serializer::new(&mut out))
+            .unwrap();
+        out
+    }
+}
+
 impl P2pProtocol {
     /// rust messagepack decode these bytes into a P2pProtocol instance
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, rmp_serde::decode::Error> {
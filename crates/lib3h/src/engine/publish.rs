@@ -0,0 +1,384 @@
+use crate::{
+    dht::dht_protocol::*,
+    engine::{
+        engine_actor::ClientToLib3hMessage,
+        p2p_protocol::{P2pProtocol, StoreEntryData, StoreEntryResultData},
+        GhostEngine,
+    },
+    error::Lib3hResult,
+    gateway::protocol::*,
+    transport,
+};
+
+use holochain_tracing::Span;
+use lib3h_ghost_actor::{prelude::*, RequestId};
+use lib3h_protocol::{
+    data_types::{ProvidedEntryData, PublishEntryResultData, StoreEntryAspectData},
+    protocol::{ClientToLib3hResponse, Lib3hToClient, Lib3hToClientResponse},
+    uri::Lib3hUri,
+    Address, DidWork,
+};
+use std::collections::HashSet;
+
+/// A PublishEntry waiting for holders to confirm they store the entry
+pub(crate) struct PendingPublish {
+    /// the client's message, answered once we are done
+    msg: ClientToLib3hMessage,
+    data: ProvidedEntryData,
+    /// agents that confirmed storing the entry
+    stored_by: Vec<Address>,
+    /// agents already asked, only asked again when there is no one else
+    asked: HashSet<Address>,
+    /// agents whose client can't confirm storing, never asked again
+    unconfirmed_by: HashSet<Address>,
+    attempts: u32,
+    /// when the current attempt stops waiting, in ms since epoch
+    attempt_deadline: u64,
+    /// peers of the space, set by its DHT for the current attempt
+    peer_list: Option<Vec<PeerData>>,
+}
+
+/// A StoreEntry waiting on our client to store each aspect
+pub(crate) struct PendingEntryStore {
+    data: StoreEntryData,
+    /// aspects not answered yet
+    awaiting: usize,
+    /// false once the client failed to store an aspect
+    stored: bool,
+    /// true once the client could not confirm storing an aspect
+    unconfirmed: bool,
+}
+
+/// Publish related private methods
+impl<'engine> GhostEngine<'engine> {
+    /// Ask holders to store a published entry. `msg` is answered by
+    /// `process_publishes()` once `replication_factor` of them confirmed,
+    /// or we ran out of attempts.
+    pub(crate) fn track_publish(
+        &mut self,
+        msg: ClientToLib3hMessage,
+        data: ProvidedEntryData,
+    ) -> Lib3hResult<()> {
+        let pending = PendingPublish {
+            msg,
+            data,
+            stored_by: Vec::new(),
+            asked: HashSet::new(),
+            unconfirmed_by: HashSet::new(),
+            attempts: 0,
+            attempt_deadline: 0,
+            peer_list: None,
+        };
+        if pending.data.entry.aspect_list.is_empty() {
+            // nothing to store
+            return self.finish_publish(pending);
+        }
+        let request_id: String = RequestId::new().into();
+        self.pending_publishes.insert(request_id, pending);
+        Ok(())
+    }
+
+    /// Answer the publishes that are done and ask holders for the others
+    pub(crate) fn process_publishes(&mut self) -> Lib3hResult<DidWork> {
+        let config = self.config.publish.clone();
+        let mut did_work = false;
+        let request_id_list: Vec<String> = self.pending_publishes.keys().cloned().collect();
+        for request_id in request_id_list {
+            let now = crate::time::since_epoch_ms();
+            let pending = self
+                .pending_publishes
+                .get_mut(&request_id)
+                .expect("Should have pending publish");
+            if pending.stored_by.len() >= config.replication_factor
+                || (pending.attempts >= config.max_attempts && now >= pending.attempt_deadline)
+            {
+                let pending = self
+                    .pending_publishes
+                    .remove(&request_id)
+                    .expect("Should have pending publish");
+                self.finish_publish(pending)?;
+            } else if let Some(peer_list) = pending.peer_list.take() {
+                self.send_store_entry(&request_id, peer_list)?;
+            } else if now >= pending.attempt_deadline && pending.attempts < config.max_attempts {
+                pending.attempts += 1;
+                pending.attempt_deadline = now + config.retry_interval_ms;
+                self.request_publish_peers(&request_id)?;
+            } else {
+                continue;
+            }
+            did_work = true;
+        }
+        Ok(did_work)
+    }
+
+    /// Ask the space DHT for the peers to send the entry to
+    fn request_publish_peers(&mut self, request_id: &str) -> Lib3hResult<()> {
+        let (space_address, agent_id) = {
+            let data = &self.pending_publishes[request_id].data;
+            (data.space_address.clone(), data.provider_agent_id.clone())
+        };
+        let space_gateway = match self.get_space(&space_address, &agent_id) {
+            Ok(space_gateway) => space_gateway,
+            Err(_) => {
+                // left the space, no one else will confirm
+                let pending = self
+                    .pending_publishes
+                    .remove(request_id)
+                    .expect("Should have pending publish");
+                return self.finish_publish(pending);
+            }
+        };
+        let request_id = request_id.to_string();
+        space_gateway.request(
+            Span::fixme(),
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestPeerList),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestPeerList(peer_list),
+                    ))) => {
+                        if let Some(pending) = me.pending_publishes.get_mut(&request_id) {
+                            pending.peer_list = Some(peer_list);
+                        }
+                    }
+                    _ => warn!("no peer list to publish {}: {:?}", request_id, response),
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Send a StoreEntry to as many peers as confirmations are missing,
+    /// preferring the ones we did not ask yet
+    fn send_store_entry(&mut self, request_id: &str, peer_list: Vec<PeerData>) -> Lib3hResult<()> {
+        let factor = self.config.publish.replication_factor;
+        let pending = self
+            .pending_publishes
+            .get_mut(request_id)
+            .expect("Should have pending publish");
+        let this_peer_name = Lib3hUri::with_agent_id(&pending.data.provider_agent_id);
+        let mut holder_list: Vec<Address> = peer_list
+            .into_iter()
+            .filter(|peer| peer.peer_name != this_peer_name)
+            .map(|peer| peer.peer_name.into())
+            .filter(|agent_id| {
+                !pending.stored_by.contains(agent_id) && !pending.unconfirmed_by.contains(agent_id)
+            })
+            .collect();
+        holder_list.sort_by_key(|agent_id| pending.asked.contains(agent_id));
+        holder_list.truncate(factor.saturating_sub(pending.stored_by.len()));
+        pending.asked.extend(holder_list.iter().cloned());
+        let data = pending.data.clone();
+        trace!(
+            "{} - Asking {:?} to store {}",
+            self.name,
+            holder_list,
+            data.entry.entry_address
+        );
+        for to_agent_id in holder_list {
+            let store_entry = StoreEntryData {
+                space_address: data.space_address.clone(),
+                request_id: request_id.to_string(),
+                from_agent_id: data.provider_agent_id.clone(),
+                to_agent_id: to_agent_id.clone(),
+                entry: data.entry.clone(),
+            };
+            let (space_gateway, payload) = match self.prepare_direct_peer_msg(
+                data.space_address.clone(),
+                data.provider_agent_id.clone(),
+                to_agent_id.clone(),
+                P2pProtocol::StoreEntry(store_entry),
+            ) {
+                Ok(r) => r,
+                // left the space, `request_publish_peers()` will finish it
+                Err(_) => return Ok(()),
+            };
+            space_gateway.publish(
+                Span::fixme(),
+                GatewayRequestToChild::Transport(
+                    transport::protocol::RequestToChild::create_send_message(
+                        Lib3hUri::with_agent_id(&to_agent_id),
+                        payload,
+                    ),
+                ),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn finish_publish(&mut self, pending: PendingPublish) -> Lib3hResult<()> {
+        let replication_factor = self.config.publish.replication_factor;
+        if pending.stored_by.len() < replication_factor {
+            warn!(
+                "{} - Entry {} stored by {} agents, wanted {}",
+                self.name,
+                pending.data.entry.entry_address,
+                pending.stored_by.len(),
+                replication_factor
+            );
+        }
+        pending
+            .msg
+            .respond(Ok(ClientToLib3hResponse::PublishEntryResult(
+                PublishEntryResultData {
+                    space_address: pending.data.space_address,
+                    provider_agent_id: pending.data.provider_agent_id,
+                    entry_address: pending.data.entry.entry_address,
+                    replication_factor,
+                    stored_by: pending.stored_by,
+                },
+            )))?;
+        Ok(())
+    }
+
+    /// A holder answered one of our StoreEntry
+    pub(crate) fn handle_store_entry_result(&mut self, data: StoreEntryResultData) {
+        match self.pending_publishes.get_mut(&data.request_id) {
+            Some(pending) if !pending.asked.contains(&data.from_agent_id) => warn!(
+                "{} was not asked to store the entry of publish {}",
+                data.from_agent_id, data.request_id
+            ),
+            Some(pending) if data.stored => {
+                if !pending.stored_by.contains(&data.from_agent_id) {
+                    pending.stored_by.push(data.from_agent_id);
+                }
+            }
+            Some(pending) if data.unconfirmed => {
+                debug!(
+                    "{} can't confirm storing the entry of publish {}",
+                    data.from_agent_id, data.request_id
+                );
+                pending.unconfirmed_by.insert(data.from_agent_id);
+            }
+            Some(_) => debug!(
+                "{} did not store the entry of publish {}",
+                data.from_agent_id, data.request_id
+            ),
+            None => trace!("StoreEntryResult for a finished publish: {:?}", data),
+        }
+    }
+
+    /// A publisher asks us to store its entry: hand each aspect to our
+    /// client, then tell the publisher whether they all got stored
+    pub(crate) fn handle_store_entry(&mut self, data: StoreEntryData) -> Lib3hResult<()> {
+        if self
+            .get_space(&data.space_address, &data.to_agent_id)
+            .is_err()
+        {
+            warn!(
+                "{} - Asked to store an entry for {} which did not join {}",
+                self.name, data.to_agent_id, data.space_address
+            );
+            return Ok(());
+        }
+        let aspect_list = data.entry.aspect_list.clone();
+        if aspect_list.is_empty() {
+            return self.reply_store_entry(data, true, false);
+        }
+        let store_id = self.request_track.reserve();
+        self.pending_entry_stores.insert(
+            store_id.clone(),
+            PendingEntryStore {
+                data: data.clone(),
+                awaiting: aspect_list.len(),
+                stored: true,
+                unconfirmed: false,
+            },
+        );
+        for aspect in aspect_list {
+            let store_id = store_id.clone();
+            self.lib3h_endpoint.request(
+                Span::fixme(),
+                Lib3hToClient::HandleStoreEntryAspect(StoreEntryAspectData {
+                    request_id: self.request_track.reserve(),
+                    space_address: data.space_address.clone(),
+                    provider_agent_id: data.from_agent_id.clone(),
+                    entry_address: data.entry.entry_address.clone(),
+                    entry_aspect: aspect,
+                }),
+                Box::new(move |me, response| {
+                    let (stored, unconfirmed) = match response {
+                        GhostCallbackData::Response(Ok(
+                            Lib3hToClientResponse::HandleStoreEntryAspectResult,
+                        )) => (true, false),
+                        GhostCallbackData::Response(Ok(
+                            Lib3hToClientResponse::HandleStoreEntryAspectUnconfirmed,
+                        )) => (false, true),
+                        _ => {
+                            debug!("aspect not stored: {:?}", response);
+                            (false, false)
+                        }
+                    };
+                    me.entry_aspect_stored(&store_id, stored, unconfirmed)?;
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn entry_aspect_stored(
+        &mut self,
+        store_id: &str,
+        stored: bool,
+        unconfirmed: bool,
+    ) -> Lib3hResult<()> {
+        let done = match self.pending_entry_stores.get_mut(store_id) {
+            Some(pending) => {
+                pending.awaiting = pending.awaiting.saturating_sub(1);
+                pending.stored = pending.stored && stored;
+                pending.unconfirmed = pending.unconfirmed || unconfirmed;
+                pending.awaiting == 0
+            }
+            None => false,
+        };
+        if done {
+            let pending = self
+                .pending_entry_stores
+                .remove(store_id)
+                .expect("Should have pending entry store");
+            self.reply_store_entry(pending.data, pending.stored, pending.unconfirmed)?;
+        }
+        Ok(())
+    }
+
+    fn reply_store_entry(
+        &mut self,
+        data: StoreEntryData,
+        stored: bool,
+        unconfirmed: bool,
+    ) -> Lib3hResult<()> {
+        let publisher = data.from_agent_id.clone();
+        let result = StoreEntryResultData {
+            space_address: data.space_address.clone(),
+            request_id: data.request_id,
+            from_agent_id: data.to_agent_id.clone(),
+            to_agent_id: publisher.clone(),
+            stored,
+            unconfirmed,
+        };
+        let (space_gateway, payload) = match self.prepare_direct_peer_msg(
+            data.space_address,
+            data.to_agent_id,
+            publisher.clone(),
+            P2pProtocol::StoreEntryResult(result),
+        ) {
+            Ok(r) => r,
+            Err(e) => {
+                debug!("StoreEntryResult not sent: {:?}", e);
+                return Ok(());
+            }
+        };
+        space_gateway.publish(
+            Span::fixme(),
+            GatewayRequestToChild::Transport(
+                transport::protocol::RequestToChild::create_send_message(
+                    Lib3hUri::with_agent_id(&publisher),
+                    payload,
+                ),
+            ),
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,369 @@
+use crate::{
+    dht::dht_protocol::*,
+    engine::{
+        engine_actor::ClientToLib3hMessage, p2p_protocol::P2pProtocol, verify_transport_rotation,
+        GhostEngine, TransportConfig, TransportKeys, TransportSeed,
+    },
+    error::{Lib3hError, Lib3hResult},
+    gateway::protocol::*,
+    keystore::TRANSPORT_KEY_ID,
+    private_file,
+    transport::{
+        protocol::{RequestToChild, RequestToChildResponse},
+        websocket::tls::{TlsCertificate, TlsConfig},
+    },
+};
+
+use holochain_tracing::Span;
+use lib3h_crypto_api::Buffer;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{
+    data_types::{NetworkPolicyData, Opaque, RotateTransportKeyData, TransportRotationData},
+    protocol::ClientToLib3hResponse,
+    uri::Lib3hUri,
+    Address,
+};
+use rmp_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// file name of our rotation certificates within the engine's work_dir
+const TRANSPORT_ROTATIONS_FILE: &str = "transport_rotations.bin";
+
+/// Our rotation certificates stored in `work_dir`, up to the one to
+/// `transport_id`: those of a rotation we did not restart with are left out
+pub(crate) fn load_transport_rotations(
+    work_dir: &Path,
+    transport_id: &Address,
+) -> Lib3hResult<Vec<TransportRotationData>> {
+    let path = work_dir.join(TRANSPORT_ROTATIONS_FILE);
+    if work_dir.as_os_str().is_empty() || !path.exists() {
+        return Ok(Vec::new());
+    }
+    let data = std::fs::read(path)?;
+    let mut rotations: Vec<TransportRotationData> =
+        Deserialize::deserialize(&mut Deserializer::new(&data[..]))?;
+    match rotations
+        .iter()
+        .rposition(|rotation| rotation.new_transport_id == *transport_id)
+    {
+        Some(position) => rotations.truncate(position + 1),
+        None => rotations.clear(),
+    }
+    Ok(rotations)
+}
+
+/// Transport key rotation related private methods
+/// A node rotating its key signs a certificate with both its current and its
+/// new TransportId. Peers holding the certificate move the node's DHT record
+/// and their allow/block lists over to the new id.
+impl<'engine> GhostEngine<'engine> {
+    /// Sign the rotation to the keys of `data.seed`, then send the
+    /// certificate to every peer of the network. `msg` is answered with it.
+    pub(crate) fn handle_rotate_transport_key(
+        &mut self,
+        msg: ClientToLib3hMessage,
+        data: RotateTransportKeyData,
+    ) -> Lib3hResult<()> {
+        let rotation = match self.rotate_transport_keys(&data) {
+            Ok(rotation) => rotation,
+            Err(e) => return Ok(msg.respond(Err(e))?),
+        };
+        let payload: Opaque = P2pProtocol::BroadcastRotation(rotation.clone())
+            .into_bytes()
+            .into();
+        self.multiplexer.request(
+            Span::fixme(),
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestPeerList),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestPeerList(peer_list),
+                    ))) => {
+                        for peer in peer_list {
+                            if peer.peer_name != me.this_net_peer.peer_name {
+                                me.multiplexer_defered_sends
+                                    .push((peer.peer_name, payload.clone()));
+                            }
+                        }
+                        msg.respond(Ok(ClientToLib3hResponse::RotateTransportKeyResult(
+                            rotation,
+                        )))?;
+                    }
+                    _ => msg.respond(Err(format!(
+                        "no peer list to send the rotation: {:?}",
+                        response
+                    )
+                    .into()))?,
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Rotate to the keys of `data.seed`: sign the certificate of the rotation
+    /// from our current keys, store it and the new keys, then identify with
+    /// the new keys right away
+    pub(crate) fn rotate_transport_keys(
+        &mut self,
+        data: &RotateTransportKeyData,
+    ) -> Lib3hResult<TransportRotationData> {
+        let mut seed = self.crypto.buf_new_secure(data.seed.len());
+        seed.write(0, &data.seed)?;
+        let new_keys = TransportKeys::with_seed(self.crypto.as_ref(), &*seed)?;
+        if new_keys.transport_id == self.transport_keys.transport_id {
+            return Err(Lib3hError::new_other("already using this transport key"));
+        }
+        let certificate = self.ephemeral_certificate(&new_keys)?;
+        let mut rotation = TransportRotationData {
+            old_transport_id: self.transport_keys.transport_id.clone(),
+            new_transport_id: new_keys.transport_id.clone(),
+            timestamp: crate::time::since_epoch_ms(),
+            signature: Opaque::new(),
+            new_signature: Opaque::new(),
+        };
+        let content = rotation.signed_content();
+        rotation.signature = self
+            .transport_keys
+            .sign(self.crypto.as_ref(), &content)?
+            .into();
+        rotation.new_signature = new_keys.sign(self.crypto.as_ref(), &content)?.into();
+        // the certificate first: restarting with the old keys ignores it
+        self.config.transport_rotations.push(rotation.clone());
+        if let Err(e) = self
+            .save_transport_rotations()
+            .and_then(|()| self.save_transport_keys(&*seed, &new_keys))
+        {
+            self.config.transport_rotations.pop();
+            return Err(e);
+        }
+        debug!(
+            "{} - Rotated transport key to {}",
+            self.name, rotation.new_transport_id
+        );
+        self.set_transport_keys(new_keys, certificate)?;
+        Ok(rotation)
+    }
+
+    /// A certificate bound to `keys`, if our transport presents an ephemeral one
+    fn ephemeral_certificate(&self, keys: &TransportKeys) -> Lib3hResult<Option<TlsCertificate>> {
+        match &self.config.transport_configs[0] {
+            TransportConfig::Websocket(TlsConfig::Ephemeral { .. }) => {
+                Ok(Some(TlsCertificate::build_ephemeral(
+                    &self.config.work_dir,
+                    &keys.transport_id,
+                    |data| Ok(keys.sign(self.crypto.as_ref(), data)?),
+                )?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Store our rotation certificates in our work_dir, if we have one
+    fn save_transport_rotations(&self) -> Lib3hResult<()> {
+        let work_dir = &self.config.work_dir;
+        if work_dir.as_os_str().is_empty() {
+            return Ok(());
+        }
+        let mut data = Vec::new();
+        self.config
+            .transport_rotations
+            .serialize(&mut Serializer::new(&mut data))
+            .map_err(|e| Lib3hError::new_other(&format!("{:?}", e)))?;
+        std::fs::create_dir_all(work_dir)?;
+        private_file::write(&work_dir.join(TRANSPORT_ROTATIONS_FILE), &data)?;
+        Ok(())
+    }
+
+    /// Store `keys` where we got our current ones from, if they are stored:
+    /// in our keystore, or their `seed` in our seed file
+    fn save_transport_keys(&mut self, seed: &dyn Buffer, keys: &TransportKeys) -> Lib3hResult<()> {
+        if let Some(keystore) = &mut self.keystore {
+            keystore.replace_sign_keypair(
+                TRANSPORT_KEY_ID,
+                keys.transport_public_key.read_lock().to_vec(),
+                &*keys.transport_secret_key,
+            )?;
+        } else if let Some(TransportSeed::File(path)) = &self.config.transport_seed {
+            private_file::write(path, &seed.read_lock())?;
+        }
+        Ok(())
+    }
+
+    /// Identify with `keys` from now on: in our transport, presenting
+    /// `certificate` if we have one, and in our network DHT
+    fn set_transport_keys(
+        &mut self,
+        keys: TransportKeys,
+        certificate: Option<TlsCertificate>,
+    ) -> Lib3hResult<()> {
+        let transport_id = keys.transport_id.clone();
+        self.transport_keys = keys;
+        self.this_net_peer.peer_name = Lib3hUri::with_transport_id(&transport_id);
+        self.this_net_peer.timestamp = crate::time::since_epoch_ms();
+        self.multiplexer.request(
+            Span::fixme(),
+            GatewayRequestToChild::Transport(RequestToChild::SetTransportId {
+                transport_id,
+                certificate,
+            }),
+            Box::new(|_me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Transport(
+                        RequestToChildResponse::SetTransportId,
+                    ))) => (),
+                    _ => error!(
+                        "failed to rotate our transport's TransportId: {:?}",
+                        response
+                    ),
+                }
+                Ok(())
+            }),
+        )?;
+        self.multiplexer.publish(
+            Span::fixme(),
+            GatewayRequestToChild::Dht(DhtRequestToChild::UpdateThisPeerName(
+                self.this_net_peer.peer_name.clone(),
+            )),
+        )?;
+        Ok(())
+    }
+
+    /// A node sent us one of its rotations, from `peer_location`.
+    /// Certificates are relayed by every peer: any of them makes us follow
+    /// the rotation in our allow list, but the node's record only moves
+    /// when it comes from where we know that node to be.
+    pub(crate) fn handle_transport_rotation_received(
+        &mut self,
+        span: Span,
+        peer_location: &Lib3hUri,
+        rotation: TransportRotationData,
+    ) -> Lib3hResult<()> {
+        match verify_transport_rotation(self.crypto.as_ref(), &rotation) {
+            Ok(true) => (),
+            result => {
+                warn!(
+                    "Ignoring transport rotation with bad signature {:?}: {:?}",
+                    rotation, result
+                );
+                return Ok(());
+            }
+        }
+        // a key only rotates once, the first certificate we got wins
+        match self.transport_rotations.get(&rotation.old_transport_id) {
+            Some(known) if *known != rotation => return Ok(()),
+            Some(_) => (),
+            None => {
+                debug!(
+                    "{} - Received transport rotation: {:?}",
+                    self.name, rotation
+                );
+                self.transport_rotations
+                    .insert(rotation.old_transport_id.clone(), rotation.clone());
+                let policy = self.follow_transport_rotations(self.config.network_policy.clone());
+                if policy != self.config.network_policy {
+                    self.handle_set_network_policy(span.child("follow rotation"), policy)?;
+                }
+            }
+        }
+        let peer_location = peer_location.clone();
+        self.multiplexer.request(
+            span.follower("P2pProtocol::BroadcastRotation"),
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestPeerList),
+            Box::new(move |me, response| {
+                let peer_list = match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestPeerList(peer_list),
+                    ))) => peer_list,
+                    _ => {
+                        warn!("no peer list to follow a rotation: {:?}", response);
+                        return Ok(());
+                    }
+                };
+                let from_rotated_node = peer_list.iter().any(|peer| {
+                    let transport_id = peer.peer_name.lower_address();
+                    peer.peer_location == peer_location
+                        && (transport_id == rotation.old_transport_id
+                            || transport_id == rotation.new_transport_id)
+                });
+                if !from_rotated_node {
+                    debug!(
+                        "rotation of {} relayed by {}, not moving its record",
+                        rotation.old_transport_id, peer_location
+                    );
+                    return Ok(());
+                }
+                let latest_transport_id = me
+                    .next_transport_ids(&rotation.old_transport_id)
+                    .pop()
+                    .unwrap_or(rotation.new_transport_id);
+                me.multiplexer_defered_publishes
+                    .push(GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(
+                        PeerData {
+                            peer_name: Lib3hUri::with_transport_id(&latest_transport_id),
+                            peer_location,
+                            timestamp: rotation.timestamp,
+                        },
+                    )));
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// The TransportIds `transport_id` rotated to, oldest first
+    fn next_transport_ids(&self, transport_id: &Address) -> Vec<Address> {
+        let mut next_ids = Vec::new();
+        let mut current_id = transport_id;
+        while let Some(rotation) = self.transport_rotations.get(current_id) {
+            current_id = &rotation.new_transport_id;
+            // certificates could rotate back to an id of the chain
+            if current_id == transport_id || next_ids.contains(current_id) {
+                break;
+            }
+            next_ids.push(current_id.clone());
+        }
+        next_ids
+    }
+
+    /// Follow the rotations of the listed TransportIds: an allowed node is
+    /// only allowed with its latest id, a blocked node is blocked with all of them
+    pub(crate) fn follow_transport_rotations(
+        &self,
+        mut policy: NetworkPolicyData,
+    ) -> NetworkPolicyData {
+        let filter = &mut policy.transport_ids;
+        let mut latest_ids: Vec<String> = Vec::new();
+        for transport_id in filter.allow.iter() {
+            let latest_id = self
+                .next_transport_ids(&transport_id.clone().into())
+                .pop()
+                .map_or_else(|| transport_id.clone(), |next_id| next_id.to_string());
+            if !latest_ids.contains(&latest_id) {
+                latest_ids.push(latest_id);
+            }
+        }
+        filter.allow = latest_ids;
+        let mut next_ids: Vec<String> = Vec::new();
+        for transport_id in filter.block.iter() {
+            for next_id in self.next_transport_ids(&transport_id.clone().into()) {
+                let next_id = next_id.to_string();
+                if !filter.block.contains(&next_id) && !next_ids.contains(&next_id) {
+                    next_ids.push(next_id);
+                }
+            }
+        }
+        filter.block.append(&mut next_ids);
+        policy
+    }
+
+    /// Send our rotation certificates to a node connecting to us
+    pub(crate) fn send_transport_rotations(&mut self, to: &Lib3hUri) {
+        for rotation in self.config.transport_rotations.iter() {
+            let payload = P2pProtocol::BroadcastRotation(rotation.clone()).into_bytes();
+            self.multiplexer_defered_sends
+                .push((to.clone(), payload.into()));
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::{
+    dht::dht_protocol::*,
+    engine::{
+        engine_actor::ClientToLib3hMessage,
+        p2p_protocol::{LeaveData, P2pProtocol},
+        GhostEngine,
+    },
+    error::Lib3hResult,
+    gateway::protocol::*,
+    transport,
+};
+
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{
+    data_types::SpaceData, protocol::ClientToLib3hResponse, uri::Lib3hUri, DidWork,
+};
+
+/// How long each step waits on the gateways before moving on anyway
+const SHUTDOWN_STEP_TIMEOUT_MS: u64 = 2000;
+
+/// Steps of a shutdown, in order
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ShutdownStep {
+    /// space gateways send what they still have queued
+    FlushSpaces,
+    /// leave every space and ask the network DHT for our peers
+    ListPeers,
+    /// send our signed LeaveData to each of those peers
+    BroadcastLeave,
+    /// the network gateway flushes and closes all connections
+    CloseTransport,
+    /// client has been answered
+    Done,
+}
+
+pub(crate) struct ShutdownState {
+    step: ShutdownStep,
+    /// replies to wait for before taking the next step
+    awaiting: usize,
+    /// the client's Shutdown request, answered at the end
+    msg: Option<ClientToLib3hMessage>,
+    /// our departure, signed when the shutdown started
+    leave: LeaveData,
+    /// network peers to notify
+    peer_list: Vec<PeerData>,
+}
+
+fn step_options() -> GhostTrackRequestOptions {
+    GhostTrackRequestOptions::default()
+        .timeout(std::time::Duration::from_millis(SHUTDOWN_STEP_TIMEOUT_MS))
+}
+
+/// Shutdown related private methods
+impl<'engine> GhostEngine<'engine> {
+    /// true once the client asked us to shut down, whether we are done or not
+    pub(crate) fn is_shut_down(&self) -> bool {
+        self.shutdown.is_some()
+    }
+
+    /// Start shutting down. `msg` is answered by `process_shutdown()` once
+    /// all connections are closed.
+    pub(crate) fn handle_shutdown(&mut self, msg: ClientToLib3hMessage) -> GhostResult<()> {
+        let leave = match self.sign_leave() {
+            Ok(leave) => leave,
+            Err(e) => return msg.respond(Err(e)),
+        };
+        let chain_id_list: Vec<_> = self.space_gateway_map.keys().cloned().collect();
+        debug!(
+            "{} - Shutting down, flushing {} space gateways",
+            self.name,
+            chain_id_list.len()
+        );
+        self.shutdown = Some(ShutdownState {
+            step: ShutdownStep::FlushSpaces,
+            awaiting: chain_id_list.len(),
+            msg: Some(msg),
+            leave,
+            peer_list: Vec::new(),
+        });
+        for chain_id in chain_id_list {
+            let space_gateway = self
+                .space_gateway_map
+                .get_mut(&chain_id)
+                .expect("Should have space gateway");
+            space_gateway.request_options(
+                Span::fixme(),
+                GatewayRequestToChild::Transport(transport::protocol::RequestToChild::Shutdown),
+                Box::new(move |me, response| {
+                    if let GhostCallbackData::Response(Ok(_)) = response {
+                    } else {
+                        warn!("space gateway {:?} did not flush: {:?}", chain_id, response);
+                    }
+                    me.shutdown_reply_received();
+                    Ok(())
+                }),
+                step_options(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Our signed departure from the network and from all joined spaces
+    pub(crate) fn sign_leave(&self) -> Lib3hResult<LeaveData> {
+        let mut leave = LeaveData {
+            peer_name: self.this_net_peer.peer_name.clone(),
+            timestamp: crate::time::since_epoch_ms(),
+            space_list: self
+                .space_gateway_map
+                .keys()
+                .map(|(space_address, agent_id)| {
+                    (space_address.clone(), Lib3hUri::with_agent_id(agent_id))
+                })
+                .collect(),
+            signature: Vec::new().into(),
+        };
+        leave.signature = self
+            .transport_keys
+            .sign(self.crypto.as_ref(), &leave.signed_content())?
+            .into();
+        Ok(leave)
+    }
+
+    fn shutdown_reply_received(&mut self) {
+        if let Some(state) = self.shutdown.as_mut() {
+            state.awaiting = state.awaiting.saturating_sub(1);
+        }
+    }
+
+    /// Take the next shutdown step once the previous one got all its replies
+    pub(crate) fn process_shutdown(&mut self) -> Lib3hResult<DidWork> {
+        let step = match &self.shutdown {
+            Some(state) if state.awaiting == 0 => state.step.clone(),
+            _ => return Ok(false),
+        };
+        match step {
+            ShutdownStep::FlushSpaces => {
+                for (space_address, agent_id) in
+                    self.space_gateway_map.keys().cloned().collect::<Vec<_>>()
+                {
+                    let space_data = SpaceData {
+                        request_id: String::new(),
+                        space_address,
+                        agent_id,
+                    };
+                    self.remove_space_gateway(&space_data)?;
+                }
+                self.set_shutdown_step(ShutdownStep::ListPeers, 1);
+                self.multiplexer.request_options(
+                    Span::fixme(),
+                    GatewayRequestToChild::Dht(DhtRequestToChild::RequestPeerList),
+                    Box::new(|me, response| {
+                        match response {
+                            GhostCallbackData::Response(Ok(
+                                GatewayRequestToChildResponse::Dht(
+                                    DhtRequestToChildResponse::RequestPeerList(peer_list),
+                                ),
+                            )) => {
+                                if let Some(state) = me.shutdown.as_mut() {
+                                    state.peer_list = peer_list;
+                                }
+                            }
+                            _ => warn!("no peer list to broadcast our leave: {:?}", response),
+                        }
+                        me.shutdown_reply_received();
+                        Ok(())
+                    }),
+                    step_options(),
+                )?;
+            }
+            ShutdownStep::ListPeers => {
+                let state = self.shutdown.as_mut().expect("shutdown started");
+                let leave = state.leave.clone();
+                let peer_list = std::mem::take(&mut state.peer_list);
+                let peer_list: Vec<PeerData> = peer_list
+                    .into_iter()
+                    .filter(|peer| peer.peer_name != leave.peer_name)
+                    .collect();
+                let payload = P2pProtocol::BroadcastLeave(leave).into_bytes();
+                self.set_shutdown_step(ShutdownStep::BroadcastLeave, peer_list.len());
+                for peer in peer_list {
+                    self.multiplexer.request_options(
+                        Span::fixme(),
+                        GatewayRequestToChild::Transport(
+                            transport::protocol::RequestToChild::SendMessage {
+                                uri: peer.peer_name.clone(),
+                                payload: payload.clone().into(),
+                            },
+                        ),
+                        Box::new(move |me, response| {
+                            if let GhostCallbackData::Response(Ok(_)) = response {
+                            } else {
+                                warn!("leave not sent to {}: {:?}", peer.peer_name, response);
+                            }
+                            me.shutdown_reply_received();
+                            Ok(())
+                        }),
+                        step_options(),
+                    )?;
+                }
+            }
+            ShutdownStep::BroadcastLeave => {
+                self.set_shutdown_step(ShutdownStep::CloseTransport, 1);
+                // no timeout of our own: the gateway gives up flushing by itself
+                self.multiplexer.request(
+                    Span::fixme(),
+                    GatewayRequestToChild::Transport(transport::protocol::RequestToChild::Shutdown),
+                    Box::new(|me, response| {
+                        let result = match response {
+                            GhostCallbackData::Response(Ok(_)) => {
+                                Ok(ClientToLib3hResponse::ShutdownResult)
+                            }
+                            GhostCallbackData::Response(Err(e)) => Err(e),
+                            GhostCallbackData::Timeout(bt) => {
+                                Err(format!("timeout: {:?}", bt).into())
+                            }
+                        };
+                        me.network_connections.clear();
+                        let maybe_msg = me.shutdown.as_mut().and_then(|state| state.msg.take());
+                        me.set_shutdown_step(ShutdownStep::Done, 0);
+                        if let Some(msg) = maybe_msg {
+                            msg.respond(result)?;
+                        }
+                        Ok(())
+                    }),
+                )?;
+            }
+            ShutdownStep::CloseTransport | ShutdownStep::Done => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    fn set_shutdown_step(&mut self, step: ShutdownStep, awaiting: usize) {
+        if let Some(state) = self.shutdown.as_mut() {
+            trace!("{} - Shutdown step: {:?}", self.name, step);
+            state.step = step;
+            state.awaiting = awaiting;
+        }
+    }
+}
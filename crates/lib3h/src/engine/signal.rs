@@ -0,0 +1,225 @@
+use crate::{
+    dht::dht_protocol::*,
+    engine::{engine_actor::ClientToLib3hMessage, p2p_protocol::P2pProtocol, GhostEngine},
+    error::Lib3hResult,
+    gateway::protocol::*,
+    transport,
+};
+
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{
+    data_types::{SignalData, TopicMessageData},
+    protocol::{ClientToLib3hResponse, Lib3hToClient},
+    types::SpaceHash,
+    uri::Lib3hUri,
+    Address, DidWork,
+};
+use std::collections::BTreeMap;
+
+/// A message to agents of a space, waiting for the next process loop with
+/// the space peer list: the list comes back while our space gateways are
+/// being processed, out of `space_gateway_map`, so we cannot send from there.
+pub(crate) enum SpaceDeferedSend {
+    Signal(ClientToLib3hMessage, SignalData),
+    TopicMessage(ClientToLib3hMessage, TopicMessageData),
+    /// to every other node of the space, from one of our agents
+    Broadcast(SpaceHash, Address, P2pProtocol),
+}
+
+/// Signal related private methods
+/// Signals are not answered nor retried: a recipient that is not reachable
+/// right now just misses it.
+impl<'engine> GhostEngine<'engine> {
+    /// Ask the space DHT where the recipients are, then send the signal.
+    /// `msg` is answered once it is sent out.
+    pub(crate) fn handle_send_signal(
+        &mut self,
+        span: Span,
+        msg: ClientToLib3hMessage,
+        data: SignalData,
+    ) -> Lib3hResult<()> {
+        let space_gateway = match self.get_space(&data.space_address, &data.from_agent_id) {
+            Ok(space_gateway) => space_gateway,
+            Err(e) => return Ok(msg.respond(Err(e))?),
+        };
+        space_gateway.request(
+            span,
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestPeerList),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestPeerList(peer_list),
+                    ))) => me
+                        .space_defered_sends
+                        .push((SpaceDeferedSend::Signal(msg, data), peer_list)),
+                    _ => {
+                        msg.respond(Err(
+                            format!("no peer list to send signal: {:?}", response).into()
+                        ))?
+                    }
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Send the messages that were waiting on the peer list of their space
+    pub(crate) fn process_space_defered_sends(&mut self) -> Lib3hResult<DidWork> {
+        let defered_sends = std::mem::take(&mut self.space_defered_sends);
+        let did_work = !defered_sends.is_empty();
+        for (defered_send, peer_list) in defered_sends {
+            match defered_send {
+                SpaceDeferedSend::Signal(msg, data) => {
+                    let result = self
+                        .send_signal(&data, peer_list)
+                        .map(|_| ClientToLib3hResponse::SendSignalResult);
+                    msg.respond(result)?;
+                }
+                SpaceDeferedSend::TopicMessage(msg, data) => {
+                    let result = self
+                        .send_topic_message(&data, peer_list)
+                        .map(|_| ClientToLib3hResponse::PublishTopicResult);
+                    msg.respond(result)?;
+                }
+                SpaceDeferedSend::Broadcast(space_address, from_agent_id, p2p_msg) => {
+                    if let Err(e) =
+                        self.send_to_space(&space_address, &from_agent_id, p2p_msg, peer_list)
+                    {
+                        warn!(
+                            "GhostEngine: could not send to space {}: {:?}",
+                            space_address, e
+                        );
+                    }
+                }
+            }
+        }
+        Ok(did_work)
+    }
+
+    /// Deliver the signal to recipients hosted by this node, and send it
+    /// once to every other node hosting some of them
+    fn send_signal(&mut self, data: &SignalData, peer_list: Vec<PeerData>) -> Lib3hResult<()> {
+        let (local_list, node_map) =
+            self.locate_recipients(&data.space_address, peer_list, |agent_id| {
+                match &data.to_agent_id_list {
+                    Some(to_agent_id_list) => to_agent_id_list.contains(agent_id),
+                    None => *agent_id != data.from_agent_id,
+                }
+            });
+        for agent_id in local_list {
+            self.deliver_signal(data, agent_id)?;
+        }
+        for (peer_location, agent_id_list) in node_map {
+            trace!(
+                "GhostEngine: sending signal to {:?} at {}",
+                agent_id_list,
+                peer_location
+            );
+            // the receiving node delivers it to each of its agents in the list
+            let to_agent_id = agent_id_list[0].clone();
+            let signal = SignalData {
+                to_agent_id_list: Some(agent_id_list),
+                ..data.clone()
+            };
+            self.send_to_agent(
+                &data.space_address,
+                &data.from_agent_id,
+                &to_agent_id,
+                P2pProtocol::Signal(signal),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Split the recipients of a message to a space between our agents,
+    /// and the other nodes hosting some of them, listed by the space DHT
+    pub(crate) fn locate_recipients(
+        &self,
+        space_address: &SpaceHash,
+        peer_list: Vec<PeerData>,
+        is_recipient: impl Fn(&Address) -> bool,
+    ) -> (Vec<Address>, BTreeMap<Lib3hUri, Vec<Address>>) {
+        let local_list: Vec<Address> = self
+            .space_gateway_map
+            .keys()
+            .filter(|(chain_space_address, agent_id)| {
+                chain_space_address == space_address && is_recipient(agent_id)
+            })
+            .map(|(_, agent_id)| agent_id.clone())
+            .collect();
+        let mut node_map: BTreeMap<Lib3hUri, Vec<Address>> = BTreeMap::new();
+        for peer in peer_list {
+            let agent_id = Address::from(peer.peer_name);
+            let chain_id = (space_address.clone(), agent_id.clone());
+            if is_recipient(&agent_id) && !self.space_gateway_map.contains_key(&chain_id) {
+                node_map
+                    .entry(peer.peer_location)
+                    .or_default()
+                    .push(agent_id);
+            }
+        }
+        (local_list, node_map)
+    }
+
+    /// Send a message to a remote agent over the space gateway of one of ours
+    pub(crate) fn send_to_agent(
+        &mut self,
+        space_address: &SpaceHash,
+        from_agent_id: &Address,
+        to_agent_id: &Address,
+        p2p_msg: P2pProtocol,
+    ) -> Lib3hResult<()> {
+        let (space_gateway, payload) = self.prepare_direct_peer_msg(
+            space_address.clone(),
+            from_agent_id.clone(),
+            to_agent_id.clone(),
+            p2p_msg,
+        )?;
+        space_gateway.publish(
+            Span::fixme(),
+            GatewayRequestToChild::Transport(
+                transport::protocol::RequestToChild::create_send_message(
+                    Lib3hUri::with_agent_id(to_agent_id),
+                    payload,
+                ),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// A remote node sent a signal to some of our agents
+    pub(crate) fn handle_signal_received(&mut self, data: SignalData) -> Lib3hResult<()> {
+        let agent_id_list: Vec<Address> = self
+            .space_gateway_map
+            .keys()
+            .filter(|(space_address, agent_id)| {
+                *space_address == data.space_address
+                    && data
+                        .to_agent_id_list
+                        .as_ref()
+                        .is_none_or(|list| list.contains(agent_id))
+            })
+            .map(|(_, agent_id)| agent_id.clone())
+            .collect();
+        if agent_id_list.is_empty() {
+            debug!("GhostEngine: no recipient here for signal {:?}", data);
+        }
+        for agent_id in agent_id_list {
+            self.deliver_signal(&data, agent_id)?;
+        }
+        Ok(())
+    }
+
+    /// Hand a signal to our client, addressed to one of its agents
+    fn deliver_signal(&mut self, data: &SignalData, agent_id: Address) -> Lib3hResult<()> {
+        let signal = SignalData {
+            to_agent_id_list: Some(vec![agent_id]),
+            ..data.clone()
+        };
+        self.lib3h_endpoint
+            .publish(Span::fixme(), Lib3hToClient::SignalReceived(signal))?;
+        Ok(())
+    }
+}
@@ -10,12 +10,15 @@ use crate::{
 };
 use detach::prelude::*;
 use holochain_tracing::Span;
+use lib3h_crypto_api::Buffer;
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::{data_types::*, protocol::*, types::SpaceHash, uri::Lib3hUri, DidWork};
 use rmp_serde::Deserializer;
 use serde::Deserialize;
 use std::collections::HashMap;
-use url::Url;
+
+/// rejected aspects remembered per space, remote peers can't grow it further
+pub(crate) const MAX_REJECTED_ASPECTS: usize = 1024;
 
 /// Space layer related private methods
 /// Engine does not process a space gateway's Transport because it is shared with the network layer
@@ -131,8 +134,9 @@ impl<'engine> GhostEngine<'engine> {
                             GatewayRequestToChild::Dht(DhtRequestToChild::HoldPeer(peer_data)),
                         );
                     }
-                    DhtRequestToParent::PeerTimedOut(_peer_name) => {
-                        // no-op
+                    DhtRequestToParent::PeerTimedOut(peer_name)
+                    | DhtRequestToParent::PeerLeft(peer_name) => {
+                        self.drop_remote_topic_subscriber(&chain_id.0, &peer_name.into());
                     }
                     // HoldEntryRequested from gossip
                     // -> Send each aspect to Core for validation
@@ -140,25 +144,12 @@ impl<'engine> GhostEngine<'engine> {
                         from_peer_name,
                         entry,
                     } => {
-                        for aspect in entry.aspect_list {
-                            let lib3h_msg = StoreEntryAspectData {
-                                request_id: self.request_track.reserve(),
-                                space_address: chain_id.0.clone(),
-                                provider_agent_id: from_peer_name.clone().into(),
-                                entry_address: entry.entry_address.clone(),
-                                entry_aspect: aspect,
-                            };
-                            // TODO - not sure what core is expected to send back here
-                            //      - right now these tracks will timeout
-                            self.request_track.set(
-                                &lib3h_msg.request_id,
-                                Some(RealEngineTrackerData::HoldEntryRequested),
-                            );
-                            self.lib3h_endpoint.publish(
-                                Span::fixme(),
-                                Lib3hToClient::HandleStoreEntryAspect(lib3h_msg),
-                            )?;
-                        }
+                        self.handle_hold_entry_requested(chain_id, from_peer_name, entry)?;
+                    }
+                    // HoldTombstoneRequested from gossip
+                    // -> Check its signature before holding it
+                    DhtRequestToParent::HoldTombstoneRequested(tombstone) => {
+                        self.handle_hold_tombstone_requested(chain_id, tombstone)?;
                     }
                     DhtRequestToParent::EntryPruned(_address) => {
                         // TODO #174
@@ -236,9 +227,15 @@ impl<'engine> GhostEngine<'engine> {
                     RequestToParent::ErrorOccured { uri: _, error } => {
                         panic!("can't handle {:?}", error);
                     }
-                    RequestToParent::IncomingConnection { uri } => {
+                    RequestToParent::IncomingConnection { uri, .. } => {
                         panic!("can't handle incoming connection {:?}", uri);
                     }
+                    RequestToParent::ConnectionVerified { uri, .. } => {
+                        trace!("space_layer: connection verified {}", uri);
+                    }
+                    RequestToParent::ConnectionClosed { uri } => {
+                        trace!("space_layer: connection closed {}", uri);
+                    }
                     RequestToParent::ReceivedData { uri, payload } => {
                         if payload.len() == 0 {
                             panic!("We should no longer ever be sending zero length messages");
@@ -265,73 +262,149 @@ impl<'engine> GhostEngine<'engine> {
         Ok(true /* fixme */)
     }
 
-    /// process P2pProtocol messages that have bubbled up to the space_layer
-    fn handle_p2p_protocol(
+    /// Hash of what the client validates of an aspect, as the sender
+    /// chooses its address
+    fn aspect_content_hash(&self, aspect: &EntryAspectData) -> Lib3hResult<Vec<u8>> {
+        let mut data = (aspect.type_hint.len() as u64).to_le_bytes().to_vec();
+        data.extend_from_slice(aspect.type_hint.as_bytes());
+        data.extend_from_slice(&aspect.aspect);
+        let data: Box<dyn Buffer> = Box::new(data);
+        let mut hash = self
+            .crypto
+            .buf_new_insecure(self.crypto.generic_hash_min_bytes());
+        self.crypto.generic_hash(&mut hash, &data, None)?;
+        let hash = hash.read_lock().to_vec();
+        Ok(hash)
+    }
+
+    /// Have our client validate each aspect gossip wants us to hold.
+    /// Only accepted aspects are handed to the space DHT, which records and
+    /// gossips them on. Rejected aspects are remembered with their content,
+    /// so later gossip of that same content is dropped without asking again.
+    pub(crate) fn handle_hold_entry_requested(
         &mut self,
-        span: Span,
-        _from: &Url,
-        p2p_msg: P2pProtocol,
+        chain_id: &ChainId,
+        from_peer_name: Lib3hUri,
+        entry: EntryData,
     ) -> Lib3hResult<()> {
-        match p2p_msg {
-            P2pProtocol::DirectMessage(dm_data) => {
-                self.lib3h_endpoint.request(
-                    span,
-                    Lib3hToClient::HandleSendDirectMessage(dm_data),
-                    Box::new(move |me, resp| match resp {
+        let mut aspect_list = Vec::new();
+        for aspect in entry.aspect_list {
+            let rejection = (
+                aspect.aspect_address.clone(),
+                self.aspect_content_hash(&aspect)?,
+            );
+            let is_rejected = self
+                .rejected_aspects
+                .get(chain_id)
+                .into_iter()
+                .any(|rejected| rejected.contains(&rejection));
+            if !is_rejected {
+                aspect_list.push((aspect, rejection));
+            }
+        }
+        for (aspect, rejection) in aspect_list {
+            let lib3h_msg = StoreEntryAspectData {
+                request_id: self.request_track.reserve(),
+                space_address: chain_id.0.clone(),
+                provider_agent_id: from_peer_name.clone().into(),
+                entry_address: entry.entry_address.clone(),
+                entry_aspect: aspect.clone(),
+            };
+            let chain_id = chain_id.clone();
+            let entry_address = entry.entry_address.clone();
+            self.lib3h_endpoint.request(
+                Span::fixme(),
+                Lib3hToClient::HandleStoreEntryAspect(lib3h_msg),
+                Box::new(move |me, response| {
+                    match response {
+                        // a legacy client stores without confirming it
                         GhostCallbackData::Response(Ok(
-                            Lib3hToClientResponse::HandleSendDirectMessageResult(dm_data),
+                            Lib3hToClientResponse::HandleStoreEntryAspectResult,
+                        ))
+                        | GhostCallbackData::Response(Ok(
+                            Lib3hToClientResponse::HandleStoreEntryAspectUnconfirmed,
                         )) => {
-                            let to_agent_id = dm_data.to_agent_id.clone();
-                            let (space_gateway, payload) = match me.prepare_direct_peer_msg(
-                                dm_data.space_address.clone(),
-                                dm_data.from_agent_id.clone(),
-                                dm_data.to_agent_id.clone(),
-                                P2pProtocol::DirectMessageResult(dm_data.clone()),
-                            ) {
-                                Ok(r) => r,
-                                Err(e) => panic!("{:?}", e),
+                            let space_gateway = match me.space_gateway_map.get_mut(&chain_id) {
+                                Some(space_gateway) => space_gateway,
+                                None => return Ok(()),
                             };
-                            trace!(
-                                "handle_p2p_protocol: Got p2p_msg for {}: {:?}",
-                                to_agent_id.clone(),
-                                dm_data
-                            );
-
                             space_gateway.publish(
                                 Span::fixme(),
-                                GatewayRequestToChild::Transport(
-                                    RequestToChild::create_send_message(
-                                        Lib3hUri::with_agent_id(&to_agent_id),
-                                        payload,
-                                    ),
+                                GatewayRequestToChild::Dht(
+                                    DhtRequestToChild::HoldEntryAspectAddress(EntryData {
+                                        entry_address,
+                                        aspect_list: vec![aspect],
+                                    }),
                                 ),
                             )?;
-                            Ok(())
                         }
-                        _ => panic!("unhandled: {:?}", resp),
-                    }),
-                )?;
+                        GhostCallbackData::Response(Err(e)) => {
+                            warn!(
+                                "Rejected aspect {} of entry {}: {:?}",
+                                aspect.aspect_address, entry_address, e
+                            );
+                            if me.space_gateway_map.contains_key(&chain_id) {
+                                let rejected = me.rejected_aspects.entry(chain_id).or_default();
+                                if rejected.len() >= MAX_REJECTED_ASPECTS {
+                                    // forgotten aspects are only validated again
+                                    let forgotten = rejected.iter().next().cloned();
+                                    if let Some(forgotten) = forgotten {
+                                        rejected.remove(&forgotten);
+                                    }
+                                }
+                                rejected.insert(rejection);
+                            }
+                        }
+                        // not validated, gossip will ask again
+                        _ => debug!("HandleStoreEntryAspect got: {:?}", response),
+                    }
+                    Ok(())
+                }),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// process P2pProtocol messages that have bubbled up to the space_layer
+    fn handle_p2p_protocol(
+        &mut self,
+        span: Span,
+        from: &Lib3hUri,
+        p2p_msg: P2pProtocol,
+    ) -> Lib3hResult<()> {
+        match p2p_msg {
+            P2pProtocol::DirectMessage(dm_data) => {
+                self.handle_remote_direct_message(span, dm_data)?;
             }
             P2pProtocol::DirectMessageResult(dm_data) => {
-                trace!(
-                    "pending_client_messages: {:?}",
-                    self.pending_client_direct_messages
-                );
-                if let Some(msg) = self
-                    .pending_client_direct_messages
-                    .remove(&dm_data.request_id)
-                {
-                    trace!(
-                        "space_layer respond with send direct message result: {:?}",
-                        dm_data
-                    );
-                    msg.respond(Ok(ClientToLib3hResponse::SendDirectMessageResult(dm_data)))?;
-                } else {
-                    error!(
-                        "space_layer couldn't find message with request_id sourced from {:?}",
-                        dm_data
-                    );
-                }
+                self.handle_direct_message_result(dm_data)?;
+            }
+            P2pProtocol::DirectMessageRejected(data) => {
+                self.handle_direct_message_rejected(data)?;
+            }
+            P2pProtocol::Signal(data) => {
+                self.handle_signal_received(data)?;
+            }
+            P2pProtocol::StreamOpen(data) => {
+                self.handle_remote_open_stream(span, data)?;
+            }
+            P2pProtocol::StreamChunk(data) => {
+                self.handle_remote_stream_chunk(span, data)?;
+            }
+            P2pProtocol::StreamAck(data) => {
+                self.handle_stream_ack(data)?;
+            }
+            P2pProtocol::StreamClose(data) => {
+                self.handle_remote_close_stream(data)?;
+            }
+            P2pProtocol::TopicSubscribe(data) => {
+                self.handle_remote_subscribe(from, data);
+            }
+            P2pProtocol::TopicUnsubscribe(data) => {
+                self.handle_remote_unsubscribe(from, data);
+            }
+            P2pProtocol::TopicMessage(data) => {
+                self.handle_topic_message_received(data)?;
             }
             P2pProtocol::Gossip(gossip_data) => {
                 let remote_gossip = RemoteGossipBundleData {
@@ -345,6 +418,8 @@ impl<'engine> GhostEngine<'engine> {
                     GatewayRequestToChild::Dht(DhtRequestToChild::HandleGossip(remote_gossip)),
                 )?;
             }
+            P2pProtocol::StoreEntry(data) => self.handle_store_entry(data)?,
+            P2pProtocol::StoreEntryResult(data) => self.handle_store_entry_result(data),
             _ => {
                 panic!("can't handle space layer receive of {:?}", p2p_msg);
             }
@@ -0,0 +1,498 @@
+use crate::{
+    engine::{engine_actor::ClientToLib3hMessage, p2p_protocol::*, GhostEngine},
+    error::{ErrorKind, Lib3hError, Lib3hResult},
+    gateway::protocol::*,
+    transport,
+};
+
+use holochain_tracing::Span;
+use lib3h_ghost_actor::{prelude::*, RequestId};
+use lib3h_protocol::{
+    data_types::{OpenStreamData, StreamChunkData, StreamData},
+    protocol::{ClientToLib3hResponse, Lib3hToClient, Lib3hToClientResponse},
+    types::SpaceHash,
+    uri::Lib3hUri,
+    Address, DidWork,
+};
+use std::collections::{HashMap, HashSet};
+
+/// A stream one of our agents opened, or accepted
+pub(crate) struct Stream {
+    data: StreamData,
+    /// true if our agent opened it and sends the chunks
+    outgoing: bool,
+    /// the client's OpenStream, answered once the recipient accepted the stream
+    open_msg: Option<ClientToLib3hMessage>,
+    /// chunks sent, answered once the recipient took them
+    unacked: HashMap<u64, ClientToLib3hMessage>,
+    /// true once the recipient accepted it
+    accepted: bool,
+    /// chunks received, that our client has not answered yet
+    received: HashSet<u64>,
+    /// lowest sequence the next chunk can have
+    next_sequence: u64,
+    /// when we stop waiting for the other end, in ms since epoch
+    deadline: u64,
+}
+
+impl Stream {
+    /// Our agent
+    fn local_agent_id(&self) -> &Address {
+        if self.outgoing {
+            &self.data.from_agent_id
+        } else {
+            &self.data.to_agent_id
+        }
+    }
+
+    fn remote_agent_id(&self) -> &Address {
+        if self.outgoing {
+            &self.data.to_agent_id
+        } else {
+            &self.data.from_agent_id
+        }
+    }
+
+    /// Outgoing streams wait on the recipient's acks, and incoming ones on
+    /// the sender's chunks while our client has none to answer
+    fn is_stale(&self, now: u64) -> bool {
+        let waiting = if self.outgoing {
+            self.open_msg.is_some() || !self.unacked.is_empty()
+        } else {
+            self.received.is_empty()
+        };
+        waiting && now >= self.deadline
+    }
+
+    /// Answer the client's requests still waiting on the recipient
+    fn fail(self, error: impl Fn() -> Lib3hError) -> Lib3hResult<()> {
+        if let Some(msg) = self.open_msg {
+            msg.respond(Err(error()))?;
+        }
+        for (_, msg) in self.unacked {
+            msg.respond(Err(error()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Stream related private methods
+/// Chunks go through the same route as direct messages. Flow control relies on
+/// answers: the recipient acknowledges a chunk once its client answered
+/// `HandleStreamChunk`, and a sender has at most `EngineConfig::stream.window`
+/// chunks waiting for that. Both ends enforce the window and increasing sequences.
+impl<'engine> GhostEngine<'engine> {
+    /// Ask the recipient to accept a new stream, `msg` is answered once it did
+    pub(crate) fn handle_open_stream(
+        &mut self,
+        span: Span,
+        msg: ClientToLib3hMessage,
+        data: OpenStreamData,
+    ) -> Lib3hResult<()> {
+        if let Err(e) = self.get_space(&data.space_address, &data.from_agent_id) {
+            return Ok(msg.respond(Err(e))?);
+        }
+        let stream = Stream {
+            data: StreamData {
+                space_address: data.space_address,
+                stream_id: RequestId::new().into(),
+                from_agent_id: data.from_agent_id,
+                to_agent_id: data.to_agent_id,
+            },
+            outgoing: true,
+            open_msg: Some(msg),
+            unacked: HashMap::new(),
+            accepted: false,
+            received: HashSet::new(),
+            next_sequence: 0,
+            deadline: crate::time::since_epoch_ms() + self.config.stream.timeout_ms,
+        };
+        let data = stream.data.clone();
+        self.streams.insert(data.stream_id.clone(), stream);
+        self.send_on_stream(span, &data.stream_id, P2pProtocol::StreamOpen(data.clone()))
+    }
+
+    /// Send a chunk if the stream's window allows it
+    pub(crate) fn handle_send_stream_chunk(
+        &mut self,
+        span: Span,
+        msg: ClientToLib3hMessage,
+        data: StreamChunkData,
+    ) -> Lib3hResult<()> {
+        let window = self.config.stream.window;
+        let deadline = crate::time::since_epoch_ms() + self.config.stream.timeout_ms;
+        let stream = match self.streams.get_mut(&data.stream_id) {
+            Some(stream) => stream,
+            None => return Ok(msg.respond(Err(Lib3hError::new_other("Unknown stream")))?),
+        };
+        let next_sequence = if !stream.outgoing || stream.open_msg.is_some() {
+            Err("Stream is not open for sending")
+        } else if stream.unacked.len() >= window {
+            Err("Stream window is full, wait for the result of previous chunks")
+        } else if data.sequence < stream.next_sequence {
+            Err("Chunk sequences must increase")
+        } else {
+            data.sequence
+                .checked_add(1)
+                .ok_or("Chunk sequence is out of range")
+        };
+        let next_sequence = match next_sequence {
+            Ok(next_sequence) => next_sequence,
+            Err(refusal) => return Ok(msg.respond(Err(Lib3hError::new_other(refusal)))?),
+        };
+        if stream.unacked.is_empty() {
+            stream.deadline = deadline;
+        }
+        stream.next_sequence = next_sequence;
+        stream.unacked.insert(data.sequence, msg);
+        let chunk = StreamChunkData {
+            space_address: stream.data.space_address.clone(),
+            from_agent_id: stream.data.from_agent_id.clone(),
+            to_agent_id: stream.data.to_agent_id.clone(),
+            ..data
+        };
+        self.send_on_stream(
+            span,
+            &chunk.stream_id.clone(),
+            P2pProtocol::StreamChunk(chunk),
+        )
+    }
+
+    /// Our client closes a stream: tell the other end
+    pub(crate) fn handle_close_stream(&mut self, data: &StreamData) -> Lib3hResult<()> {
+        let stream = self
+            .streams
+            .remove(&data.stream_id)
+            .ok_or_else(|| Lib3hError::new_other("Unknown stream"))?;
+        let result = self.send_stream_close(&stream);
+        stream.fail(|| Lib3hError::new_other("Stream closed"))?;
+        result
+    }
+
+    /// Close the streams that waited too long on the other end,
+    /// and drop the ones of agents that left their space
+    pub(crate) fn process_streams(&mut self) -> Lib3hResult<DidWork> {
+        let now = crate::time::since_epoch_ms();
+        let stale_list: Vec<String> = self
+            .streams
+            .iter()
+            .filter(|(_, stream)| {
+                stream.is_stale(now)
+                    || !self.space_gateway_map.contains_key(&(
+                        stream.data.space_address.clone(),
+                        stream.local_agent_id().clone(),
+                    ))
+            })
+            .map(|(stream_id, _)| stream_id.clone())
+            .collect();
+        let did_work = !stale_list.is_empty();
+        for stream_id in stale_list {
+            debug!("GhostEngine: closing stale stream {}", stream_id);
+            let stream = self.streams.remove(&stream_id).expect("Should have stream");
+            if let Err(e) = self.send_stream_close(&stream) {
+                debug!("GhostEngine: could not close stream {}: {:?}", stream_id, e);
+            }
+            let data = stream.data.clone();
+            let remote_agent_id = stream.remote_agent_id().to_string();
+            stream.fail(|| {
+                Lib3hError::new(ErrorKind::DirectMessageTimeout(remote_agent_id.clone()))
+            })?;
+            self.lib3h_endpoint
+                .publish(Span::fixme(), Lib3hToClient::StreamClosed(data))?;
+        }
+        Ok(did_work)
+    }
+
+    /// Send a message to the other end of a stream. If it can't be sent,
+    /// the stream is dropped and the client's requests answered with the error
+    fn send_on_stream(
+        &mut self,
+        span: Span,
+        stream_id: &str,
+        p2p_msg: P2pProtocol,
+    ) -> Lib3hResult<()> {
+        let (space_address, local_agent_id, remote_agent_id) = {
+            let stream = &self.streams[stream_id];
+            (
+                stream.data.space_address.clone(),
+                stream.local_agent_id().clone(),
+                stream.remote_agent_id().clone(),
+            )
+        };
+        let result = self.send_stream_msg(
+            span,
+            space_address,
+            local_agent_id,
+            remote_agent_id,
+            p2p_msg,
+        );
+        if let Err(e) = result {
+            debug!("GhostEngine: dropping stream {}: {:?}", stream_id, e);
+            let error = e.to_string();
+            if let Some(stream) = self.streams.remove(stream_id) {
+                stream
+                    .fail(|| Lib3hError::new(ErrorKind::DirectMessageUnreachable(error.clone())))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell the other end of a stream we dropped that it is closed
+    fn send_stream_close(&mut self, stream: &Stream) -> Lib3hResult<()> {
+        self.send_stream_msg(
+            Span::fixme(),
+            stream.data.space_address.clone(),
+            stream.local_agent_id().clone(),
+            stream.remote_agent_id().clone(),
+            P2pProtocol::StreamClose(stream.data.clone()),
+        )
+    }
+
+    fn send_stream_msg(
+        &mut self,
+        span: Span,
+        space_address: SpaceHash,
+        local_agent_id: Address,
+        remote_agent_id: Address,
+        p2p_msg: P2pProtocol,
+    ) -> Lib3hResult<()> {
+        let (space_gateway, payload) = self.prepare_direct_peer_msg(
+            space_address,
+            local_agent_id,
+            remote_agent_id.clone(),
+            p2p_msg,
+        )?;
+        space_gateway.publish(
+            span,
+            GatewayRequestToChild::Transport(
+                transport::protocol::RequestToChild::create_send_message(
+                    Lib3hUri::with_agent_id(&remote_agent_id),
+                    payload,
+                ),
+            ),
+        )?;
+        Ok(())
+    }
+
+    /// A remote agent opens a stream to one of our agents: ask our client.
+    /// The stream is known from now on, but takes no chunk until our client accepts it.
+    pub(crate) fn handle_remote_open_stream(
+        &mut self,
+        span: Span,
+        data: StreamData,
+    ) -> Lib3hResult<()> {
+        if self.streams.contains_key(&data.stream_id) {
+            warn!("Ignoring StreamOpen of a known stream: {:?}", data);
+            return Ok(());
+        }
+        self.streams.insert(
+            data.stream_id.clone(),
+            Stream {
+                data: data.clone(),
+                outgoing: false,
+                open_msg: None,
+                unacked: HashMap::new(),
+                accepted: false,
+                received: HashSet::new(),
+                next_sequence: 0,
+                deadline: crate::time::since_epoch_ms() + self.config.stream.timeout_ms,
+            },
+        );
+        let stream_id = data.stream_id.clone();
+        let ack = StreamAckData {
+            space_address: data.space_address.clone(),
+            stream_id: data.stream_id.clone(),
+            from_agent_id: data.to_agent_id.clone(),
+            to_agent_id: data.from_agent_id.clone(),
+            sequence: None,
+            error: None,
+        };
+        self.lib3h_endpoint.request(
+            span,
+            Lib3hToClient::HandleOpenStream(data),
+            Box::new(move |me, response| {
+                if !me.streams.contains_key(&stream_id) {
+                    // closed while our client was deciding
+                    return Ok(());
+                }
+                let error = match response {
+                    GhostCallbackData::Response(Ok(
+                        Lib3hToClientResponse::HandleOpenStreamResult,
+                    )) => None,
+                    _ => Some(format!("{:?}", response)),
+                };
+                let refused = error.is_some();
+                me.send_on_stream(
+                    Span::fixme(),
+                    &stream_id,
+                    P2pProtocol::StreamAck(StreamAckData { error, ..ack }),
+                )?;
+                if refused {
+                    me.streams.remove(&stream_id);
+                } else if let Some(stream) = me.streams.get_mut(&stream_id) {
+                    stream.accepted = true;
+                    stream.deadline = crate::time::since_epoch_ms() + me.config.stream.timeout_ms;
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// A chunk for one of our agents: hand it to our client,
+    /// and acknowledge it once the client answered
+    pub(crate) fn handle_remote_stream_chunk(
+        &mut self,
+        span: Span,
+        data: StreamChunkData,
+    ) -> Lib3hResult<()> {
+        let window = self.config.stream.window;
+        let deadline = crate::time::since_epoch_ms() + self.config.stream.timeout_ms;
+        let stream = match self.streams.get_mut(&data.stream_id) {
+            Some(stream)
+                if !stream.outgoing
+                    && stream.accepted
+                    && stream.data.from_agent_id == data.from_agent_id
+                    && stream.data.to_agent_id == data.to_agent_id =>
+            {
+                stream
+            }
+            _ => {
+                warn!("Ignoring chunk of an unknown stream: {}", data.stream_id);
+                return Ok(());
+            }
+        };
+        let next_sequence = if stream.received.len() >= window {
+            Err("Stream window is full")
+        } else if data.sequence < stream.next_sequence {
+            Err("Chunk sequences must increase")
+        } else {
+            data.sequence
+                .checked_add(1)
+                .ok_or("Chunk sequence is out of range")
+        };
+        let mut ack = StreamAckData {
+            space_address: data.space_address.clone(),
+            stream_id: data.stream_id.clone(),
+            from_agent_id: data.to_agent_id.clone(),
+            to_agent_id: data.from_agent_id.clone(),
+            sequence: Some(data.sequence),
+            error: None,
+        };
+        let next_sequence = match next_sequence {
+            Ok(next_sequence) => next_sequence,
+            Err(refusal) => {
+                debug!(
+                    "GhostEngine: refusing chunk {} of stream {}: {}",
+                    data.sequence, data.stream_id, refusal
+                );
+                ack.error = Some(refusal.to_string());
+                return self.send_on_stream(span, &data.stream_id, P2pProtocol::StreamAck(ack));
+            }
+        };
+        stream.next_sequence = next_sequence;
+        stream.received.insert(data.sequence);
+        stream.deadline = deadline;
+        let sequence = data.sequence;
+        self.lib3h_endpoint.request(
+            span,
+            Lib3hToClient::HandleStreamChunk(data),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(
+                        Lib3hToClientResponse::HandleStreamChunkResult,
+                    )) => (),
+                    _ => ack.error = Some(format!("{:?}", response)),
+                }
+                let deadline = crate::time::since_epoch_ms() + me.config.stream.timeout_ms;
+                match me.streams.get_mut(&ack.stream_id) {
+                    Some(stream) => {
+                        stream.received.remove(&sequence);
+                        stream.deadline = deadline;
+                    }
+                    // closed while our client had the chunk
+                    None => return Ok(()),
+                }
+                let stream_id = ack.stream_id.clone();
+                me.send_on_stream(Span::fixme(), &stream_id, P2pProtocol::StreamAck(ack))?;
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// The recipient accepted, refused, or took a chunk of one of our streams
+    pub(crate) fn handle_stream_ack(&mut self, data: StreamAckData) -> Lib3hResult<()> {
+        let deadline = crate::time::since_epoch_ms() + self.config.stream.timeout_ms;
+        let stream = match self.streams.get_mut(&data.stream_id) {
+            Some(stream) if stream.outgoing && stream.data.to_agent_id == data.from_agent_id => {
+                stream
+            }
+            _ => {
+                debug!("GhostEngine: ignoring late stream ack {:?}", data);
+                return Ok(());
+            }
+        };
+        stream.deadline = deadline;
+        let rejection = data.error.as_ref().map(|error| {
+            Lib3hError::new(ErrorKind::DirectMessageRejected(format!(
+                "{}: {}",
+                data.from_agent_id, error
+            )))
+        });
+        match data.sequence {
+            None => {
+                let msg = match stream.open_msg.take() {
+                    Some(msg) => msg,
+                    None => return Ok(()),
+                };
+                let stream_data = stream.data.clone();
+                match rejection {
+                    Some(rejection) => {
+                        self.streams.remove(&data.stream_id);
+                        msg.respond(Err(rejection))?;
+                    }
+                    None => {
+                        stream.accepted = true;
+                        msg.respond(Ok(ClientToLib3hResponse::OpenStreamResult(stream_data)))?
+                    }
+                }
+            }
+            Some(sequence) => {
+                if let Some(msg) = stream.unacked.remove(&sequence) {
+                    match rejection {
+                        Some(rejection) => msg.respond(Err(rejection))?,
+                        None => msg.respond(Ok(ClientToLib3hResponse::SendStreamChunkResult))?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The other end closed a stream
+    pub(crate) fn handle_remote_close_stream(&mut self, data: StreamData) -> Lib3hResult<()> {
+        match self.streams.get(&data.stream_id) {
+            Some(stream) if stream.data == data => {}
+            _ => {
+                debug!(
+                    "GhostEngine: ignoring close of an unknown stream {:?}",
+                    data
+                );
+                return Ok(());
+            }
+        }
+        if let Some(stream) = self.streams.remove(&data.stream_id) {
+            let remote_agent_id = stream.remote_agent_id().to_string();
+            stream.fail(|| {
+                Lib3hError::new(ErrorKind::DirectMessageRejected(format!(
+                    "{}: stream closed",
+                    remote_agent_id
+                )))
+            })?;
+        }
+        self.lib3h_endpoint
+            .publish(Span::fixme(), Lib3hToClient::StreamClosed(data))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    dht::dht_protocol::*,
+    engine::{verify_transport_signature, ChainId, GhostEngine},
+    error::{Lib3hError, Lib3hResult},
+    gateway::protocol::*,
+};
+
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{
+    data_types::{DeleteEntryData, DropEntryData},
+    protocol::{Lib3hToClient, Lib3hToClientResponse},
+};
+
+/// Tombstone related private methods
+/// Whether the agent behind a tombstone may delete that entry is for clients
+/// to decide, they know its author: we only check which node signed it.
+impl<'engine> GhostEngine<'engine> {
+    /// Sign a tombstone for the entry and have the space DHT spread it
+    pub(crate) fn handle_delete_entry(
+        &mut self,
+        span: Span,
+        data: DeleteEntryData,
+    ) -> Lib3hResult<()> {
+        let mut tombstone = TombstoneData {
+            space_address: data.space_address.clone(),
+            entry_address: data.entry_address,
+            aspect_address_list: data.aspect_address_list,
+            provider_agent_id: data.provider_agent_id.clone(),
+            timestamp: crate::time::since_epoch_ms(),
+            signer: self.this_net_peer.peer_name.clone(),
+            signature: Vec::new().into(),
+        };
+        tombstone.signature = self
+            .transport_keys
+            .sign(self.crypto.as_ref(), &tombstone.signed_content())?
+            .into();
+        let chain_id = (data.space_address, data.provider_agent_id);
+        self.hold_tombstone(span, &chain_id, tombstone)
+    }
+
+    /// Gossip brought a tombstone: hold it if its signer is who it claims
+    /// and our client approves the deletion
+    pub(crate) fn handle_hold_tombstone_requested(
+        &mut self,
+        chain_id: &ChainId,
+        tombstone: TombstoneData,
+    ) -> Lib3hResult<()> {
+        if tombstone.space_address != chain_id.0 {
+            warn!(
+                "Ignoring tombstone for space {} gossiped in {}",
+                tombstone.space_address, chain_id.0
+            );
+            return Ok(());
+        }
+        match verify_transport_signature(
+            self.crypto.as_ref(),
+            &tombstone.signer.lower_address(),
+            &tombstone.signed_content(),
+            &tombstone.signature,
+        ) {
+            Ok(true) => (),
+            result => {
+                warn!(
+                    "Ignoring tombstone with bad signature {:?}: {:?}",
+                    tombstone, result
+                );
+                return Ok(());
+            }
+        }
+        let delete_entry = DeleteEntryData {
+            space_address: tombstone.space_address.clone(),
+            provider_agent_id: tombstone.provider_agent_id.clone(),
+            entry_address: tombstone.entry_address.clone(),
+            aspect_address_list: tombstone.aspect_address_list.clone(),
+        };
+        let chain_id = chain_id.clone();
+        self.lib3h_endpoint.request(
+            Span::fixme(),
+            Lib3hToClient::HandleDeleteEntry(delete_entry),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(
+                        Lib3hToClientResponse::HandleDeleteEntryResult,
+                    )) => {
+                        if let Err(e) = me.hold_tombstone(Span::fixme(), &chain_id, tombstone) {
+                            debug!("Approved tombstone not held: {:?}", e);
+                        }
+                    }
+                    response => debug!(
+                        "Ignoring tombstone {:?} the client did not approve: {:?}",
+                        tombstone, response
+                    ),
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Have the space DHT hold and gossip the tombstone,
+    /// and tell our client to drop what it deletes.
+    /// Deferred since we may be called back by the client endpoint.
+    fn hold_tombstone(
+        &mut self,
+        span: Span,
+        chain_id: &ChainId,
+        tombstone: TombstoneData,
+    ) -> Lib3hResult<()> {
+        let drop_entry = DropEntryData {
+            space_address: tombstone.space_address.clone(),
+            request_id: self.request_track.reserve(),
+            entry_address: tombstone.entry_address.clone(),
+            aspect_address_list: tombstone.aspect_address_list.clone(),
+        };
+        let space_gateway = self
+            .space_gateway_map
+            .get_mut(chain_id)
+            .ok_or_else(|| Lib3hError::new_other("Not part of that space"))?;
+        space_gateway.publish(
+            span.follower("hold_tombstone"),
+            GatewayRequestToChild::Dht(DhtRequestToChild::HoldTombstone(tombstone)),
+        )?;
+        self.client_defered_publishes
+            .push(Lib3hToClient::HandleDropEntry(drop_entry));
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{
+    dht::dht_protocol::*,
+    engine::{
+        engine_actor::ClientToLib3hMessage, p2p_protocol::P2pProtocol, signal::SpaceDeferedSend,
+        GhostEngine,
+    },
+    error::Lib3hResult,
+    gateway::protocol::*,
+};
+
+use holochain_tracing::Span;
+use lib3h_ghost_actor::prelude::*;
+use lib3h_protocol::{
+    data_types::{TopicMessageData, TopicSubscriptionData},
+    protocol::Lib3hToClient,
+    types::SpaceHash,
+    uri::Lib3hUri,
+    Address,
+};
+
+/// Topic related private methods
+/// Every node of a space keeps track of who subscribed to which topic:
+/// subscriptions are sent to the nodes of the space when made, and to agents
+/// joining it later. Only an agent can subscribe itself. Subscribers leaving the
+/// space, or timing out, are dropped with their peer.
+impl<'engine> GhostEngine<'engine> {
+    pub(crate) fn handle_subscribe(&mut self, data: TopicSubscriptionData) -> Lib3hResult<()> {
+        self.get_space(&data.space_address, &data.agent_id)?;
+        let is_new = self
+            .topic_subscribers
+            .entry((data.space_address.clone(), data.topic.clone()))
+            .or_default()
+            .insert(data.agent_id.clone());
+        if is_new {
+            self.broadcast_to_space(
+                &data.space_address,
+                &data.agent_id,
+                P2pProtocol::TopicSubscribe(data.clone()),
+            )?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn handle_unsubscribe(&mut self, data: TopicSubscriptionData) -> Lib3hResult<()> {
+        self.get_space(&data.space_address, &data.agent_id)?;
+        if self.remove_topic_subscriber(&data) {
+            self.broadcast_to_space(
+                &data.space_address,
+                &data.agent_id,
+                P2pProtocol::TopicUnsubscribe(data.clone()),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Ask the space DHT where the subscribers are, then send the message.
+    /// `msg` is answered once it is sent out.
+    pub(crate) fn handle_publish_topic(
+        &mut self,
+        span: Span,
+        msg: ClientToLib3hMessage,
+        data: TopicMessageData,
+    ) -> Lib3hResult<()> {
+        let space_gateway = match self.get_space(&data.space_address, &data.from_agent_id) {
+            Ok(space_gateway) => space_gateway,
+            Err(e) => return Ok(msg.respond(Err(e))?),
+        };
+        space_gateway.request(
+            span,
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestPeerList),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestPeerList(peer_list),
+                    ))) => me
+                        .space_defered_sends
+                        .push((SpaceDeferedSend::TopicMessage(msg, data), peer_list)),
+                    _ => msg.respond(Err(format!(
+                        "no peer list to publish topic: {:?}",
+                        response
+                    )
+                    .into()))?,
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Deliver the message to our subscribers, and send it once to every
+    /// other node hosting some
+    pub(crate) fn send_topic_message(
+        &mut self,
+        data: &TopicMessageData,
+        peer_list: Vec<PeerData>,
+    ) -> Lib3hResult<()> {
+        let subscribers = self
+            .topic_subscribers
+            .get(&(data.space_address.clone(), data.topic.clone()))
+            .cloned()
+            .unwrap_or_default();
+        let (local_list, node_map) =
+            self.locate_recipients(&data.space_address, peer_list, |agent_id| {
+                subscribers.contains(agent_id)
+            });
+        for agent_id in local_list {
+            self.deliver_topic_message(data, agent_id)?;
+        }
+        for (_, agent_id_list) in node_map {
+            // the receiving node knows which of its agents subscribed
+            let message = TopicMessageData {
+                to_agent_id: None,
+                ..data.clone()
+            };
+            self.send_to_agent(
+                &data.space_address,
+                &data.from_agent_id,
+                &agent_id_list[0],
+                P2pProtocol::TopicMessage(message),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Ask the space DHT for its peers, then send the message to the space
+    fn broadcast_to_space(
+        &mut self,
+        space_address: &SpaceHash,
+        from_agent_id: &Address,
+        p2p_msg: P2pProtocol,
+    ) -> Lib3hResult<()> {
+        let space_gateway = self.get_space(space_address, from_agent_id)?;
+        let space_address = space_address.clone();
+        let from_agent_id = from_agent_id.clone();
+        space_gateway.request(
+            Span::fixme(),
+            GatewayRequestToChild::Dht(DhtRequestToChild::RequestPeerList),
+            Box::new(move |me, response| {
+                match response {
+                    GhostCallbackData::Response(Ok(GatewayRequestToChildResponse::Dht(
+                        DhtRequestToChildResponse::RequestPeerList(peer_list),
+                    ))) => me.space_defered_sends.push((
+                        SpaceDeferedSend::Broadcast(space_address, from_agent_id, p2p_msg),
+                        peer_list,
+                    )),
+                    _ => warn!("no peer list to broadcast {:?}: {:?}", p2p_msg, response),
+                }
+                Ok(())
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Send a message once to every other node of the space
+    pub(crate) fn send_to_space(
+        &mut self,
+        space_address: &SpaceHash,
+        from_agent_id: &Address,
+        p2p_msg: P2pProtocol,
+        peer_list: Vec<PeerData>,
+    ) -> Lib3hResult<()> {
+        let (_, node_map) = self.locate_recipients(space_address, peer_list, |_| true);
+        for (_, agent_id_list) in node_map {
+            self.send_to_agent(
+                space_address,
+                from_agent_id,
+                &agent_id_list[0],
+                p2p_msg.clone(),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// A remote node sent a topic message: deliver it to our subscribers
+    pub(crate) fn handle_topic_message_received(
+        &mut self,
+        data: TopicMessageData,
+    ) -> Lib3hResult<()> {
+        let agent_id_list: Vec<Address> = match self
+            .topic_subscribers
+            .get(&(data.space_address.clone(), data.topic.clone()))
+        {
+            Some(subscribers) => subscribers
+                .iter()
+                .filter(|agent_id| {
+                    self.space_gateway_map
+                        .contains_key(&(data.space_address.clone(), (*agent_id).clone()))
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        if agent_id_list.is_empty() {
+            debug!(
+                "GhostEngine: no subscriber here for topic message {:?}",
+                data
+            );
+        }
+        for agent_id in agent_id_list {
+            self.deliver_topic_message(&data, agent_id)?;
+        }
+        Ok(())
+    }
+
+    fn deliver_topic_message(
+        &mut self,
+        data: &TopicMessageData,
+        agent_id: Address,
+    ) -> Lib3hResult<()> {
+        let message = TopicMessageData {
+            to_agent_id: Some(agent_id),
+            ..data.clone()
+        };
+        self.lib3h_endpoint
+            .publish(Span::fixme(), Lib3hToClient::HandleTopicMessage(message))?;
+        Ok(())
+    }
+
+    /// `from` is the remote agent that sent the subscription
+    pub(crate) fn handle_remote_subscribe(&mut self, from: &Lib3hUri, data: TopicSubscriptionData) {
+        if !is_from_subscriber(from, &data) {
+            return;
+        }
+        self.topic_subscribers
+            .entry((data.space_address, data.topic))
+            .or_default()
+            .insert(data.agent_id);
+    }
+
+    pub(crate) fn handle_remote_unsubscribe(
+        &mut self,
+        from: &Lib3hUri,
+        data: TopicSubscriptionData,
+    ) {
+        if is_from_subscriber(from, &data) {
+            self.remove_topic_subscriber(&data);
+        }
+    }
+
+    /// Return true if the agent was subscribed
+    fn remove_topic_subscriber(&mut self, data: &TopicSubscriptionData) -> bool {
+        let key = (data.space_address.clone(), data.topic.clone());
+        let (removed, is_empty) = match self.topic_subscribers.get_mut(&key) {
+            Some(subscribers) => (subscribers.remove(&data.agent_id), subscribers.is_empty()),
+            None => (false, false),
+        };
+        if is_empty {
+            self.topic_subscribers.remove(&key);
+        }
+        removed
+    }
+
+    /// An agent left a space: forget its subscriptions there
+    pub(crate) fn drop_topic_subscriber(&mut self, space_address: &SpaceHash, agent_id: &Address) {
+        for ((topic_space_address, _), subscribers) in self.topic_subscribers.iter_mut() {
+            if topic_space_address == space_address {
+                subscribers.remove(agent_id);
+            }
+        }
+        self.topic_subscribers
+            .retain(|_, subscribers| !subscribers.is_empty());
+    }
+
+    /// The space DHT forgot a remote agent: forget its subscriptions there too
+    pub(crate) fn drop_remote_topic_subscriber(
+        &mut self,
+        space_address: &SpaceHash,
+        agent_id: &Address,
+    ) {
+        let chain_id = (space_address.clone(), agent_id.clone());
+        if !self.space_gateway_map.contains_key(&chain_id) {
+            self.drop_topic_subscriber(space_address, agent_id);
+        }
+    }
+
+    /// An agent joined a space we are in: tell it what our agents subscribed to
+    pub(crate) fn send_topic_subscriptions(
+        &mut self,
+        space_address: &SpaceHash,
+        to_agent_id: &Address,
+    ) -> Lib3hResult<()> {
+        let mut subscription_list = Vec::new();
+        for ((topic_space_address, topic), subscribers) in self.topic_subscribers.iter() {
+            if topic_space_address != space_address {
+                continue;
+            }
+            for agent_id in subscribers {
+                let chain_id = (space_address.clone(), agent_id.clone());
+                if self.space_gateway_map.contains_key(&chain_id) {
+                    subscription_list.push(TopicSubscriptionData {
+                        space_address: space_address.clone(),
+                        agent_id: agent_id.clone(),
+                        topic: topic.clone(),
+                    });
+                }
+            }
+        }
+        for subscription in subscription_list {
+            let from_agent_id = subscription.agent_id.clone();
+            self.send_to_agent(
+                space_address,
+                &from_agent_id,
+                to_agent_id,
+                P2pProtocol::TopicSubscribe(subscription),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Only the agent itself can change its subscriptions
+fn is_from_subscriber(from: &Lib3hUri, data: &TopicSubscriptionData) -> bool {
+    if from.lower_address() != data.agent_id {
+        warn!(
+            "Ignoring subscription change of {} sent by {}",
+            data.agent_id, from
+        );
+        return false;
+    }
+    true
+}
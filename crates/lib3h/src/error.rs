@@ -62,6 +62,12 @@ pub enum ErrorKind {
     CryptoApiError(CryptoError),
     /// Error occuring when the key is not present in the Map.
     KeyNotFound(String),
+    /// A direct message could not be sent to its recipient.
+    DirectMessageUnreachable(String),
+    /// A direct message got no answer from its recipient in time.
+    DirectMessageTimeout(String),
+    /// The recipient's client refused a direct message.
+    DirectMessageRejected(String),
     /// Yet undefined error.
     Other(String),
     /// Hints that destructuring should not be exhaustive.
@@ -84,7 +90,11 @@ impl StdError for Lib3hError {
             ErrorKind::HcId(ref err) => Some(err),
             ErrorKind::RmpSerdeDecodeError(ref err) => Some(err),
             ErrorKind::CryptoApiError(ref err) => Some(err),
-            ErrorKind::Other(ref _s) | ErrorKind::KeyNotFound(ref _s) => None,
+            ErrorKind::Other(ref _s)
+            | ErrorKind::KeyNotFound(ref _s)
+            | ErrorKind::DirectMessageUnreachable(ref _s)
+            | ErrorKind::DirectMessageTimeout(ref _s)
+            | ErrorKind::DirectMessageRejected(ref _s) => None,
             _ => unreachable!(),
         }
     }
@@ -101,6 +111,15 @@ impl fmt::Display for Lib3hError {
             ErrorKind::RmpSerdeDecodeError(ref err) => err.fmt(f),
             ErrorKind::CryptoApiError(ref err) => err.fmt(f),
             ErrorKind::KeyNotFound(ref s) => write!(f, "Key: '{}' not found", s),
+            ErrorKind::DirectMessageUnreachable(ref s) => {
+                write!(f, "Direct message recipient unreachable: '{}'", s)
+            }
+            ErrorKind::DirectMessageTimeout(ref s) => {
+                write!(f, "Direct message timed out waiting for: '{}'", s)
+            }
+            ErrorKind::DirectMessageRejected(ref s) => {
+                write!(f, "Direct message rejected by recipient: '{}'", s)
+            }
             ErrorKind::Other(ref s) => write!(f, "Unknown error encountered: '{}'.", s),
             _ => unreachable!(),
         }
@@ -37,6 +37,7 @@ impl
         detach_run!(&mut self.message_encoding, |enc| { enc.process(self) })?;
 
         self.process_transport_pending_sends()?;
+        self.process_transport_shutdown()?;
 
         // Update this_peer cache
         self.inner_dht.request(
@@ -159,6 +160,20 @@ impl P2pGateway {
                 )?;
                 Ok(())
             }
+            GatewayRequestToChild::UpdatePeerPolicy(peer_policy) => {
+                debug!(
+                    "({}) updating peer policy: {:?}",
+                    self.identifier.nickname, peer_policy
+                );
+                self.peer_policy = peer_policy.clone();
+                // give refused remotes a new chance under the new policy
+                self.filtered_uris.clear();
+                self.close_filtered_peers(span.child("close_filtered_peers"))?;
+                self.inner_dht
+                    .publish(span, DhtRequestToChild::UpdatePeerPolicy(peer_policy))?;
+                msg.respond(Ok(GatewayRequestToChildResponse::UpdatePeerPolicySuccess))?;
+                Ok(())
+            }
         }
     }
 }
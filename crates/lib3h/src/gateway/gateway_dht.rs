@@ -94,6 +94,9 @@ impl P2pGateway {
             DhtRequestToParent::PeerTimedOut(_peer_name) => {
                 // TODO
             }
+            DhtRequestToParent::PeerLeft(_peer_name) => {
+                // no-op
+            }
             // No entries in Network DHT
             DhtRequestToParent::HoldEntryRequested {
                 from_peer_name: _,
@@ -104,6 +107,9 @@ impl P2pGateway {
             DhtRequestToParent::EntryPruned(_) => {
                 unreachable!();
             }
+            DhtRequestToParent::HoldTombstoneRequested(_) => {
+                // no-op
+            }
             DhtRequestToParent::RequestEntry(_) => {
                 self.endpoint_self.request(
                     span,
@@ -11,12 +11,21 @@ use crate::{
 use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
 use lib3h_p2p_protocol::p2p::P2pMessage;
-use lib3h_protocol::{data_types::*, uri::Lib3hUri};
+use lib3h_protocol::{
+    data_types::*,
+    uri::{Lib3hUri, UriScheme},
+    Address,
+};
 
 /// Private internals
 impl P2pGateway {
     /// Handle IncomingConnection event from child transport
-    fn handle_incoming_connection(&mut self, span: Span, uri: Lib3hUri) -> TransportResult<()> {
+    fn handle_incoming_connection(
+        &mut self,
+        span: Span,
+        uri: Lib3hUri,
+        verified_id: Option<Address>,
+    ) -> TransportResult<()> {
         self.inner_dht.request(
             span.child("handle_incoming_connection"),
             DhtRequestToChild::RequestThisPeer,
@@ -38,6 +47,7 @@ impl P2pGateway {
                         GatewayRequestToParent::Transport(
                             transport::protocol::RequestToParent::IncomingConnection {
                                 uri: this_peer.peer_name.clone(),
+                                verified_id,
                             },
                         ),
                     )?;
@@ -85,6 +95,72 @@ impl P2pGateway {
         Ok(())
     }
 
+    /// Should data received from this low-level uri be dropped?
+    fn priv_is_filtered(&self, uri: &Lib3hUri) -> bool {
+        if self.filtered_uris.contains(uri) || !self.peer_policy.allows_location(uri) {
+            return true;
+        }
+        // the peer we know behind that uri
+        if let Some(peer_name) = self.peer_names.get(uri) {
+            if !self.peer_policy.allows_peer_name(peer_name) {
+                return true;
+            }
+        }
+        if let Some(transport_id) = self.verified_ids.get(uri) {
+            if !self
+                .peer_policy
+                .allows_peer_name(&Lib3hUri::with_transport_id(transport_id))
+            {
+                return true;
+            }
+        }
+        // space gateways receive from agent uris directly
+        uri.is_scheme(UriScheme::Agent) && !self.peer_policy.allows_peer_name(uri)
+    }
+
+    /// Close our connections to the peers the peer policy now blocks
+    pub(crate) fn close_filtered_peers(&mut self, span: Span) -> GhostResult<()> {
+        let mut uris: Vec<Lib3hUri> = self
+            .peer_names
+            .keys()
+            .chain(self.verified_ids.keys())
+            .filter(|uri| self.priv_is_filtered(uri))
+            .cloned()
+            .collect();
+        uris.sort();
+        uris.dedup();
+        for uri in uris {
+            warn!(
+                "({}) Closing connection to {}: blocked by peer policy",
+                self.identifier.nickname, uri
+            );
+            self.priv_close_filtered(span.child("close_filtered_peers"), &uri)?;
+        }
+        Ok(())
+    }
+
+    /// Close our connection to a uri blocked by the peer policy,
+    /// and forget what we know of it
+    fn priv_close_filtered(&mut self, span: Span, uri: &Lib3hUri) -> GhostResult<()> {
+        self.filtered_uris.insert(uri.clone());
+        self.verified_ids.remove(uri);
+        self.peer_names.remove(uri);
+        self.stats.closed(uri);
+        self.inner_transport.as_mut().request(
+            span,
+            transport::protocol::RequestToChild::Close { uri: uri.clone() },
+            Box::new(|me, response| {
+                if let GhostCallbackData::Response(Err(e)) = response {
+                    warn!(
+                        "({}) could not close filtered connection: {:?}",
+                        me.identifier.nickname, e
+                    );
+                }
+                Ok(())
+            }),
+        )
+    }
+
     fn priv_decode_on_receive(
         &mut self,
         span: Span,
@@ -122,6 +198,23 @@ impl P2pGateway {
                 if self.identifier.id != gateway_id.clone().into() {
                     panic!("BAD gateway {:?} != {:?}", self.identifier.id, gateway_id);
                 }
+                if !self.peer_policy.allows_peer_name(&peer_name) {
+                    warn!(
+                        "({}) refusing PeerName {} from {}: blocked by peer policy",
+                        self.identifier.nickname, peer_name, uri,
+                    );
+                    return self.priv_close_filtered(span, &uri);
+                }
+                if let Some(transport_id) = self.verified_ids.get(&uri) {
+                    if peer_name.lower_address() != *transport_id {
+                        warn!(
+                            "({}) refusing PeerName {} from {}: connection was authenticated as {}",
+                            self.identifier.nickname, peer_name, uri, transport_id,
+                        );
+                        return self.priv_close_filtered(span, &uri);
+                    }
+                }
+                self.peer_names.insert(uri.clone(), peer_name.clone());
                 let peer = PeerData {
                     peer_name,
                     peer_location: uri.clone(),
@@ -161,12 +254,10 @@ impl P2pGateway {
                         )?;
                     }
                     Ok(P2pMessage::MsgPong(pong)) => {
-                        let now = crate::time::since_epoch_ms();
-                        info!(
-                            "got pong from {} indicating latency = {} ms",
-                            uri,
-                            now - pong.ping_send_epoch_ms,
-                        );
+                        let latency =
+                            crate::time::since_epoch_ms().saturating_sub(pong.ping_send_epoch_ms);
+                        info!("got pong from {} indicating latency = {} ms", uri, latency,);
+                        self.stats.round_trip(&uri, latency);
                     }
                     _ => panic!("failed to decode P2pMessage"),
                 }
@@ -227,6 +318,75 @@ impl P2pGateway {
                     }),
                 );
             }
+            transport::protocol::RequestToChild::GetStats => {
+                // our own counters, with those of the child transport attached
+                self.inner_transport.as_mut().request(
+                    span.child("handle_transport_RequestToChild"),
+                    transport_request,
+                    Box::new(|me, response| {
+                        let response = match response {
+                            GhostCallbackData::Timeout(bt) => {
+                                parent_request.respond(Err(format!("timeout: {:?}", bt).into()))?;
+                                return Ok(());
+                            }
+                            GhostCallbackData::Response(response) => response,
+                        };
+                        match response {
+                            Ok(transport::protocol::RequestToChildResponse::GetStats(inner)) => {
+                                let depths = me.pending_send_queue_depths();
+                                me.stats.set_queue_depths(depths);
+                                let mut stats = me.stats.to_data();
+                                stats.inner = Some(Box::new(inner));
+                                parent_request.respond(Ok(
+                                    GatewayRequestToChildResponse::Transport(
+                                        transport::protocol::RequestToChildResponse::GetStats(
+                                            stats,
+                                        ),
+                                    ),
+                                ))?;
+                            }
+                            Ok(response) => {
+                                parent_request.respond(Err(format!(
+                                    "bad response: {:?}",
+                                    response
+                                )
+                                .into()))?;
+                            }
+                            Err(e) => parent_request.respond(Err(e.into()))?,
+                        }
+                        Ok(())
+                    }),
+                )?;
+            }
+            transport::protocol::RequestToChild::Shutdown => {
+                self.shutdown_after_pending_sends(parent_request)?;
+            }
+            transport::protocol::RequestToChild::Close { .. }
+            | transport::protocol::RequestToChild::SetTransportId { .. } => {
+                if let transport::protocol::RequestToChild::Close { uri } = &transport_request {
+                    self.verified_ids.remove(uri);
+                    self.peer_names.remove(uri);
+                    self.stats.closed(uri);
+                }
+                // Forward to child transport
+                self.inner_transport.as_mut().request(
+                    span.child("handle_transport_RequestToChild"),
+                    transport_request,
+                    Box::new(|_me, response| {
+                        match response {
+                            GhostCallbackData::Timeout(bt) => {
+                                parent_request.respond(Err(format!("timeout: {:?}", bt).into()))?
+                            }
+                            GhostCallbackData::Response(response) => parent_request.respond(
+                                response
+                                    .map(GatewayRequestToChildResponse::Transport)
+                                    .map_err(|e| e.into()),
+                            )?,
+                        }
+                        Ok(())
+                    }),
+                )?;
+            }
             transport::protocol::RequestToChild::SendMessage { uri, payload } => {
                 debug!(
                     "gateway_transport: SendMessage, first resolving address {:?}",
@@ -263,6 +423,18 @@ impl P2pGateway {
             transport::protocol::RequestToChildResponse::SendMessageSuccess => {
                 // no-op
             }
+            transport::protocol::RequestToChildResponse::GetStats(_stats) => {
+                // no-op
+            }
+            transport::protocol::RequestToChildResponse::Shutdown => {
+                // no-op
+            }
+            transport::protocol::RequestToChildResponse::Close => {
+                // no-op
+            }
+            transport::protocol::RequestToChildResponse::SetTransportId => {
+                // no-op
+            }
         };
         Ok(())
     }
@@ -280,14 +452,37 @@ impl P2pGateway {
         let span = msg.span().child("handle_transport_RequestToParent");
         let msg = msg.take_message().expect("exists");
         match &msg {
-            transport::protocol::RequestToParent::ErrorOccured { uri: _, error: _ } => {
+            transport::protocol::RequestToParent::ErrorOccured { uri, error: _ } => {
+                self.stats.error(uri);
                 // pass any errors back up the chain so network layer can handle them (i.e.)
                 self.endpoint_self.publish(
                     Span::fixme(),
                     GatewayRequestToParent::Transport(msg.clone()),
                 )?;
             }
-            transport::protocol::RequestToParent::IncomingConnection { uri } => {
+            transport::protocol::RequestToParent::IncomingConnection { uri, verified_id } => {
+                if self.priv_is_filtered(uri) {
+                    warn!(
+                        "({}) Closing incoming connection from {}: blocked by peer policy",
+                        self.identifier.nickname, uri
+                    );
+                    self.priv_close_filtered(span, uri)?;
+                    return Ok(());
+                }
+                if let Some(transport_id) = verified_id {
+                    if !self
+                        .peer_policy
+                        .allows_peer_name(&Lib3hUri::with_transport_id(transport_id))
+                    {
+                        warn!(
+                            "({}) Closing incoming connection from {} ({}): blocked by peer policy",
+                            self.identifier.nickname, uri, transport_id
+                        );
+                        self.priv_close_filtered(span, uri)?;
+                        return Ok(());
+                    }
+                    self.verified_ids.insert(uri.clone(), transport_id.clone());
+                }
                 // TODO
                 info!(
                     "({}) Incoming connection opened: {}",
@@ -296,8 +491,24 @@ impl P2pGateway {
                 self.handle_incoming_connection(
                     span.child("transport::protocol::RequestToParent::IncomingConnection"),
                     uri.clone(),
+                    verified_id.clone(),
                 )?;
             }
+            transport::protocol::RequestToParent::ConnectionVerified { uri, verified_id } => {
+                if !self
+                    .peer_policy
+                    .allows_peer_name(&Lib3hUri::with_transport_id(verified_id))
+                {
+                    warn!(
+                        "({}) Closing connection to {} ({}): blocked by peer policy",
+                        self.identifier.nickname, uri, verified_id
+                    );
+                    self.priv_close_filtered(span, uri)?;
+                    return Ok(());
+                }
+                // the PeerName received over it must match
+                self.verified_ids.insert(uri.clone(), verified_id.clone());
+            }
             transport::protocol::RequestToParent::ReceivedData { uri, payload } => {
                 // TODO
                 trace!(
@@ -306,13 +517,29 @@ impl P2pGateway {
                     uri,
                     payload.len()
                 );
+                self.stats.received(uri, payload.len());
                 // trace!("Deserialize msg: {:?}", payload);
-                if payload.len() == 0 {
+                if self.priv_is_filtered(uri) {
+                    debug!(
+                        "({}) Dropping message from {}: blocked by peer policy",
+                        self.identifier.nickname, uri
+                    );
+                } else if payload.len() == 0 {
                     panic!("We should no longer ever be sending zero length messages");
                 } else {
                     self.priv_decode_on_receive(span, uri.clone(), payload.clone())?;
                 }
             }
+            transport::protocol::RequestToParent::ConnectionClosed { uri } => {
+                // a new connection from that uri may prove another id
+                self.verified_ids.remove(uri);
+                self.peer_names.remove(uri);
+                self.stats.closed(uri);
+                self.endpoint_self.publish(
+                    Span::fixme(),
+                    GatewayRequestToParent::Transport(msg.clone()),
+                )?;
+            }
         };
         Ok(())
     }
@@ -327,3 +554,125 @@ impl P2pGateway {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dht::{dht_config::DhtConfig, mirror_dht::MirrorDht},
+        engine::GatewayId,
+        gateway::GatewayOutputWrapType,
+        transport::protocol::*,
+    };
+
+    fn test_gateway() -> (
+        TransportActorSelfEndpoint<()>,
+        GatewayParentWrapper<(), P2pGateway>,
+    ) {
+        let (endpoint_parent, endpoint_self) = create_ghost_channel();
+        let transport_endpoint: TransportActorSelfEndpoint<()> =
+            endpoint_self.as_context_endpoint_builder().build();
+        let gateway: GatewayParentWrapper<(), P2pGateway> = GhostParentWrapper::new(
+            P2pGateway::new(
+                GatewayOutputWrapType::DoNotWrapOutput,
+                GatewayId {
+                    nickname: "test".to_string(),
+                    id: "test_network".into(),
+                },
+                Lib3hUri::with_undefined(),
+                Box::new(TransportEndpointAsActor::new(endpoint_parent)),
+                MirrorDht::new_with_config,
+                &DhtConfig::new(&Lib3hUri::with_transport_id(&"test_peer".into())),
+            ),
+            "test_gateway_",
+        );
+        (transport_endpoint, gateway)
+    }
+
+    #[test]
+    fn test_gateway_forgets_verified_ids_on_close() {
+        let (mut transport_endpoint, mut gateway) = test_gateway();
+        let uri = Lib3hUri::with_memory("remote");
+        let remote_id: Address = "remote_transport_id".into();
+
+        transport_endpoint
+            .publish(
+                Span::fixme(),
+                RequestToParent::IncomingConnection {
+                    uri: uri.clone(),
+                    verified_id: Some(remote_id.clone()),
+                },
+            )
+            .unwrap();
+        for _ in 0..3 {
+            gateway.process(&mut ()).unwrap();
+        }
+        assert_eq!(Some(&remote_id), gateway.as_ref().verified_ids.get(&uri));
+
+        transport_endpoint
+            .publish(
+                Span::fixme(),
+                RequestToParent::ConnectionClosed { uri: uri.clone() },
+            )
+            .unwrap();
+        for _ in 0..3 {
+            gateway.process(&mut ()).unwrap();
+        }
+        assert!(gateway.as_ref().verified_ids.is_empty());
+        // and the close reached the network layer
+        assert!(gateway.drain_messages().into_iter().any(|mut msg| {
+            match msg.take_message() {
+                Some(GatewayRequestToParent::Transport(RequestToParent::ConnectionClosed {
+                    uri: closed,
+                })) => closed == uri,
+                _ => false,
+            }
+        }));
+    }
+
+    #[test]
+    fn test_gateway_checks_peer_name_of_outgoing_connections() {
+        let (mut transport_endpoint, mut gateway) = test_gateway();
+        let uri = Lib3hUri::with_memory("remote");
+        let remote_id: Address = "remote_transport_id".into();
+
+        // a connection we dialed proved the remote's TransportId
+        transport_endpoint
+            .publish(
+                Span::fixme(),
+                RequestToParent::ConnectionVerified {
+                    uri: uri.clone(),
+                    verified_id: remote_id.clone(),
+                },
+            )
+            .unwrap();
+        for _ in 0..3 {
+            gateway.process(&mut ()).unwrap();
+        }
+        assert_eq!(Some(&remote_id), gateway.as_ref().verified_ids.get(&uri));
+
+        let peer_name = |transport_id: &Address| {
+            P2pProtocol::PeerName(
+                "test_network".to_string(),
+                Lib3hUri::with_transport_id(transport_id),
+                0,
+            )
+            .into_bytes()
+            .into()
+        };
+
+        // it may only announce that TransportId
+        gateway
+            .as_mut()
+            .priv_on_receive(Span::fixme(), uri.clone(), peer_name(&remote_id))
+            .unwrap();
+        assert!(gateway.as_ref().peer_names.contains_key(&uri));
+        gateway
+            .as_mut()
+            .priv_on_receive(Span::fixme(), uri.clone(), peer_name(&"other_id".into()))
+            .unwrap();
+        assert!(gateway.as_ref().filtered_uris.contains(&uri));
+        assert!(gateway.as_ref().peer_names.is_empty());
+        assert!(gateway.as_ref().verified_ids.is_empty());
+    }
+}
@@ -7,12 +7,15 @@ use crate::{
 };
 use holochain_tracing::Span;
 use lib3h_ghost_actor::prelude::*;
-use lib3h_protocol::data_types::*;
+use lib3h_protocol::{data_types::*, uri::Lib3hUri};
 use rmp_serde::Serializer;
 use serde::Serialize;
+use std::collections::HashMap;
 
 const SEND_RETRY_INTERVAL_MS: u64 = 20;
 const SEND_RETRY_TIMEOUT_MS: u64 = 20000;
+/// how long a Shutdown waits for pending sends before closing the transport
+const SHUTDOWN_FLUSH_TIMEOUT_MS: u64 = 2000;
 
 /// we want to invoke this on the very next process call
 /// set our last_attempt back far enough to ensure this
@@ -43,6 +46,57 @@ impl P2pGateway {
         }
     }
 
+    /// hold a Shutdown request until our pending sends are flushed
+    pub(crate) fn shutdown_after_pending_sends(
+        &mut self,
+        parent_request: GatewayToChildMessage,
+    ) -> GhostResult<()> {
+        if self.shutdown.is_some() {
+            return parent_request.respond(Err("Gateway is already shutting down".into()));
+        }
+        let deadline = std::time::Instant::now()
+            .checked_add(std::time::Duration::from_millis(SHUTDOWN_FLUSH_TIMEOUT_MS))
+            .expect("can add");
+        self.shutdown = Some((parent_request, deadline));
+        Ok(())
+    }
+
+    /// once our pending sends are flushed, or we stopped waiting for them,
+    /// fail what is left and forward the Shutdown request to our child transport
+    pub(crate) fn process_transport_shutdown(&mut self) -> GhostResult<()> {
+        let parent_request = match self.shutdown.take() {
+            None => return Ok(()),
+            Some((parent_request, deadline)) => {
+                if !self.pending_send_queue.is_empty() && std::time::Instant::now() < deadline {
+                    self.shutdown = Some((parent_request, deadline));
+                    return Ok(());
+                }
+                parent_request
+            }
+        };
+        let meta_list = self.pending_send_queue.drain(..).collect::<Vec<_>>();
+        for send_meta in meta_list {
+            (send_meta.cb)(Err("shut down before sending".into()))?;
+        }
+        self.inner_transport.request(
+            Span::fixme(),
+            transport::protocol::RequestToChild::Shutdown,
+            Box::new(move |_me, response| {
+                match response {
+                    GhostCallbackData::Timeout(bt) => {
+                        parent_request.respond(Err(format!("timeout: {:?}", bt).into()))?
+                    }
+                    GhostCallbackData::Response(response) => parent_request.respond(
+                        response
+                            .map(GatewayRequestToChildResponse::Transport)
+                            .map_err(|e| e.into()),
+                    )?,
+                }
+                Ok(())
+            }),
+        )
+    }
+
     /// will attempt to resolve uri && pass call to priv_send_with_full_low_uri
     pub(crate) fn send_with_partial_high_uri(
         &mut self,
@@ -85,6 +139,23 @@ impl P2pGateway {
         Ok(())
     }
 
+    /// number of pending sends per remote, as far as we know its uri
+    pub(crate) fn pending_send_queue_depths(&self) -> HashMap<Lib3hUri, u64> {
+        let mut depths = HashMap::new();
+        for send_meta in self.pending_send_queue.iter() {
+            let uri = match &send_meta.send_data {
+                SendData::WithPartialHighUri(send_data) => send_data.partial_high_uri.clone(),
+                SendData::WithFullLowUri(send_data) => {
+                    let mut uri = send_data.full_low_uri.clone();
+                    uri.clear_agent_id();
+                    uri
+                }
+            };
+            *depths.entry(uri).or_insert(0) += 1;
+        }
+        depths
+    }
+
     /// check / dispatch a pending send attempt
     fn priv_send_check_dispatch(&mut self, send_meta: SendMetaData) -> GhostResult<()> {
         let now = std::time::Instant::now();
@@ -265,9 +336,17 @@ impl P2pGateway {
 
         let mut uri = send_data.full_low_uri.clone();
 
-        // at last, we need to drop the high-level agent id
-        uri.clear_agent_id();
+        // at last, we need to drop the high-level agent id,
+        // the network transport keeps the TransportId to check the remote with
+        if let GatewayOutputWrapType::WrapOutputWithP2pDirectMessage = self.wrap_output_type {
+            uri.clear_agent_id();
+        }
 
+        let mut stats_uri = uri.clone();
+        stats_uri.clear_agent_id();
+        let payload_len = payload.len();
+        // opens the connection if there is none yet
+        self.stats.connecting(&stats_uri);
         self.inner_transport.request(
             Span::fixme(),
             transport::protocol::RequestToChild::SendMessage { uri, payload },
@@ -276,11 +355,13 @@ impl P2pGateway {
                     GhostCallbackData::Response(Ok(
                         transport::protocol::RequestToChildResponse::SendMessageSuccess,
                     )) => {
+                        me.stats.sent(&stats_uri, payload_len);
                         cb(Ok(GatewayRequestToChildResponse::Transport(
                             transport::protocol::RequestToChildResponse::SendMessageSuccess,
                         )))?;
                     }
                     _ => {
+                        me.stats.error(&stats_uri);
                         me.priv_send_queue_pending(SendMetaData {
                             send_data: SendData::WithFullLowUri(send_data),
                             last_attempt,
@@ -11,14 +11,18 @@ use crate::{
     engine::GatewayId,
     gateway::protocol::*,
     message_encoding::*,
+    peer_policy::PeerPolicy,
     transport::{self, error::TransportResult},
 };
 
 use detach::prelude::*;
 use holochain_tracing::Span;
 use lib3h_ghost_actor::GhostResult;
-use lib3h_protocol::{data_types::Opaque, uri::Lib3hUri};
-use std::boxed::Box;
+use lib3h_protocol::{data_types::Opaque, uri::Lib3hUri, Address};
+use std::{
+    boxed::Box,
+    collections::{HashMap, HashSet},
+};
 
 pub enum GatewayOutputWrapType {
     DoNotWrapOutput,
@@ -46,8 +50,23 @@ pub struct P2pGateway {
     endpoint_self: Detach<GatewaySelfEndpoint<P2pGateway>>,
     /// cached data from inner dht
     this_peer: PeerData,
+    /// allow/block lists for remotes of this gateway
+    peer_policy: PeerPolicy,
+    /// low-level uris whose handshake was refused by our peer_policy
+    filtered_uris: HashSet<Lib3hUri>,
+    /// TransportIds proven by low-level connections (e.g. mutual TLS),
+    /// the PeerName received over that connection must match
+    verified_ids: HashMap<Lib3hUri, Address>,
+    /// PeerNames received over low-level uris, so a policy update
+    /// can close the connections of the peers it blocks
+    peer_names: HashMap<Lib3hUri, Lib3hUri>,
 
     pending_send_queue: Vec<send_data_types::SendMetaData>,
+    /// traffic to and from the low-level uris of our peers
+    stats: transport::stats::TransportStats,
+    /// Shutdown request waiting for `pending_send_queue` to be flushed,
+    /// and when we stop waiting
+    shutdown: Option<(GatewayToChildMessage, std::time::Instant)>,
 }
 
 pub(crate) mod send_data_types {
@@ -3,11 +3,12 @@ use crate::{
     engine::GatewayId,
     gateway::{GatewayOutputWrapType, P2pGateway},
     message_encoding::*,
-    transport,
+    transport::{self, stats::TransportStats},
 };
 use detach::prelude::*;
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::uri::{Lib3hUri, UriScheme};
+use std::collections::{HashMap, HashSet};
 
 //--------------------------------------------------------------------------------------------------
 // Constructors
@@ -58,7 +59,13 @@ impl P2pGateway {
             endpoint_parent: Some(endpoint_parent),
             endpoint_self,
             this_peer,
+            peer_policy: dht_config.peer_policy().clone(),
+            filtered_uris: HashSet::new(),
+            verified_ids: HashMap::new(),
+            peer_names: HashMap::new(),
             pending_send_queue: Vec::new(),
+            stats: TransportStats::new(),
+            shutdown: None,
         }
     }
 
@@ -1,4 +1,4 @@
-use crate::{dht::dht_protocol::*, error::*, transport};
+use crate::{dht::dht_protocol::*, error::*, peer_policy::PeerPolicy, transport};
 use lib3h_ghost_actor::prelude::*;
 use lib3h_protocol::data_types::*;
 
@@ -9,6 +9,7 @@ pub enum GatewayRequestToChild {
     Dht(DhtRequestToChild),
     Bootstrap(BootstrapData),
     SendAll(Vec<u8>),
+    UpdatePeerPolicy(PeerPolicy),
 }
 
 #[derive(Debug)]
@@ -16,6 +17,7 @@ pub enum GatewayRequestToChildResponse {
     Transport(transport::protocol::RequestToChildResponse),
     Dht(DhtRequestToChildResponse),
     BootstrapSuccess,
+    UpdatePeerPolicySuccess,
 }
 
 #[derive(Debug)]
@@ -0,0 +1,214 @@
+//! Derive all the keys of a node from one root secret with `CryptoSystem::kdf`,
+//! so backing up the root secret is enough to restore them.
+//! Each purpose has its own kdf context, and keys of a purpose are told
+//! apart by index. Agent keys are derived from a subkey of their space.
+
+use crate::{
+    engine::TransportKeys,
+    error::{Lib3hError, Lib3hResult},
+};
+use lib3h_crypto_api::{Buffer, CryptoSystem};
+use lib3h_protocol::types::SpaceHash;
+
+/// kdf context of transport signing keys
+const TRANSPORT_SIGN_CONTEXT: &[u8] = b"l3h_tsig";
+/// kdf context of key exchange keys
+const KX_CONTEXT: &[u8] = b"l3h_kx__";
+/// kdf context of the subkeys of spaces
+const SPACE_CONTEXT: &[u8] = b"l3h_spac";
+/// kdf context of agent keys, derived from a space subkey
+const SPACE_AGENT_CONTEXT: &[u8] = b"l3h_agnt";
+
+/// The secret all our keys are derived from
+pub struct RootKey {
+    crypto: Box<dyn CryptoSystem>,
+    key: Box<dyn Buffer>,
+}
+
+impl RootKey {
+    /// `key` must be `CryptoSystem::kdf_key_bytes()` long
+    pub fn new(crypto: Box<dyn CryptoSystem>, key: &dyn Buffer) -> Lib3hResult<Self> {
+        if key.len() != crypto.kdf_key_bytes() {
+            return Err(Lib3hError::new_other(&format!(
+                "root key must be {} bytes, not {}",
+                crypto.kdf_key_bytes(),
+                key.len()
+            )));
+        }
+        let mut secure_key = crypto.buf_new_secure(key.len());
+        secure_key.write(0, &key.read_lock())?;
+        Ok(Self {
+            crypto,
+            key: secure_key,
+        })
+    }
+
+    /// A new random root key
+    pub fn generate(crypto: Box<dyn CryptoSystem>) -> Lib3hResult<Self> {
+        let mut key = crypto.buf_new_secure(crypto.kdf_key_bytes());
+        crypto.randombytes_buf(&mut key)?;
+        Ok(Self { crypto, key })
+    }
+
+    /// A copy of the root key, to back it up
+    pub fn export(&self) -> Lib3hResult<Box<dyn Buffer>> {
+        let mut key = self.crypto.buf_new_secure(self.key.len());
+        key.write(0, &self.key.read_lock())?;
+        Ok(key)
+    }
+
+    /// Seed of the transport signing keypair `index`
+    pub fn transport_sign_seed(&self, index: u64) -> Lib3hResult<Box<dyn Buffer>> {
+        derive(
+            self.crypto.as_crypto_system(),
+            &*self.key,
+            TRANSPORT_SIGN_CONTEXT,
+            index,
+            self.crypto.sign_seed_bytes(),
+        )
+    }
+
+    /// The transport keypair `index`, see `transport_sign_seed()`
+    pub fn transport_keys(&self, index: u64) -> Lib3hResult<TransportKeys> {
+        TransportKeys::with_seed(
+            self.crypto.as_crypto_system(),
+            &*self.transport_sign_seed(index)?,
+        )
+    }
+
+    /// Seed of the key exchange keypair `index`
+    pub fn kx_seed(&self, index: u64) -> Lib3hResult<Box<dyn Buffer>> {
+        derive(
+            self.crypto.as_crypto_system(),
+            &*self.key,
+            KX_CONTEXT,
+            index,
+            self.crypto.kx_seed_bytes(),
+        )
+    }
+
+    /// Seed of the signing keypair of our agent `index` in `space_address`
+    pub fn space_agent_seed(
+        &self,
+        space_address: &SpaceHash,
+        index: u64,
+    ) -> Lib3hResult<Box<dyn Buffer>> {
+        let space_key = derive(
+            self.crypto.as_crypto_system(),
+            &*self.key,
+            SPACE_CONTEXT,
+            self.space_index(space_address)?,
+            self.crypto.kdf_key_bytes(),
+        )?;
+        derive(
+            self.crypto.as_crypto_system(),
+            &*space_key,
+            SPACE_AGENT_CONTEXT,
+            index,
+            self.crypto.sign_seed_bytes(),
+        )
+    }
+
+    /// kdf index of a space: the first 8 bytes of the hash of its address
+    fn space_index(&self, space_address: &SpaceHash) -> Lib3hResult<u64> {
+        let data: Box<dyn Buffer> = Box::new(space_address.to_string().into_bytes());
+        let mut hash = self
+            .crypto
+            .buf_new_insecure(self.crypto.generic_hash_min_bytes());
+        self.crypto.generic_hash(&mut hash, &data, None)?;
+        let mut index_bytes = [0; 8];
+        index_bytes.copy_from_slice(&hash.read_lock()[..8]);
+        Ok(u64::from_le_bytes(index_bytes))
+    }
+}
+
+/// Derive a secret of `size` bytes from `parent`
+fn derive(
+    crypto: &dyn CryptoSystem,
+    parent: &dyn Buffer,
+    context: &[u8],
+    index: u64,
+    size: usize,
+) -> Lib3hResult<Box<dyn Buffer>> {
+    let parent = parent.box_clone();
+    let context: Box<dyn Buffer> = Box::new(context.to_vec());
+    let mut out = crypto.buf_new_secure(size);
+    crypto.kdf(&mut out, index, &context, &parent)?;
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_sodium::SodiumCryptoSystem;
+
+    fn test_root_key() -> RootKey {
+        let key: Box<dyn Buffer> = Box::new((0..32).collect::<Vec<u8>>());
+        RootKey::new(Box::new(SodiumCryptoSystem::new()), &*key).unwrap()
+    }
+
+    fn to_hex(buffer: &dyn Buffer) -> String {
+        buffer
+            .read_lock()
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    /// Vectors checked against BLAKE2b keyed with the parent key,
+    /// salted with the index and personalized with the context
+    #[test]
+    fn test_key_derivation_vectors() {
+        let root_key = test_root_key();
+        assert_eq!(
+            "306ab51a41ea68b8a570d61d70e75ad13e6231fb47bed1155cd324aabcc7da5a",
+            to_hex(&*root_key.transport_sign_seed(0).unwrap())
+        );
+        assert_eq!(
+            "3c85f826b55fbfedda6df5bbcc2659eb3338593bb402e3b15c40a63d7b19f56b",
+            to_hex(&*root_key.transport_sign_seed(1).unwrap())
+        );
+        assert_eq!(
+            "17e315e05ed0a9e31b67cf9fe94cb0b41819ea2da23dfbe521e8626d0230bb42",
+            to_hex(&*root_key.kx_seed(0).unwrap())
+        );
+        assert_eq!(
+            "ff504c022ab0139ffd8c27f4a90c5b85f4d2e8e51085b3ab5e51d245a8832cdb",
+            to_hex(&*root_key.space_agent_seed(&"space_addr".into(), 0).unwrap())
+        );
+        assert_eq!(
+            "f9b54a2247b8f8183f4b8d42745c601af52d258986651801411b23b5cc0f1e78",
+            to_hex(
+                &*root_key
+                    .space_agent_seed(&"other_space_addr".into(), 0)
+                    .unwrap()
+            )
+        );
+        let transport_id: String = root_key.transport_keys(0).unwrap().transport_id.into();
+        assert_eq!(
+            "HcMcigR6rQgf6hsnbhRcZNCASay7ceqms5F8cTctEVTx8pyrOsrUzPr97boz9or",
+            transport_id
+        );
+    }
+
+    #[test]
+    fn test_key_derivation_is_deterministic() {
+        let root_key = test_root_key();
+        let restored = RootKey::new(
+            Box::new(SodiumCryptoSystem::new()),
+            &*root_key.export().unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            root_key.transport_keys(3).unwrap().transport_id,
+            restored.transport_keys(3).unwrap().transport_id
+        );
+        assert_ne!(
+            root_key.transport_keys(3).unwrap().transport_id,
+            restored.transport_keys(4).unwrap().transport_id
+        );
+
+        let short_key: Box<dyn Buffer> = Box::new(vec![0; 16]);
+        assert!(RootKey::new(Box::new(SodiumCryptoSystem::new()), &*short_key).is_err());
+    }
+}
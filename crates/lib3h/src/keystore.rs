@@ -1,19 +1,95 @@
-//! This is a stub of the keystore so we can prove out the encoding transport
+//! The keystore actor holds our keypairs and works with their secret keys,
+//! which never leave it unless exported.
+//! Secret keys are kept in secure buffers, and on disk in a single file
+//! sealed with a key derived from a passphrase.
 
-use crate::error::{Lib3hError, Lib3hResult};
+use crate::error::{ErrorKind, Lib3hError, Lib3hResult};
 
 use detach::prelude::*;
+use lib3h_crypto_api::{Buffer, CryptoSystem};
 use lib3h_ghost_actor::prelude::*;
+use rmp_serde::{Deserializer, Serializer};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+/// file name of the keystore within the engine's work_dir
+pub const KEYSTORE_FILE: &str = "keystore.bin";
+
+/// id of the engine's TransportId keypair
+pub const TRANSPORT_KEY_ID: &str = "transport";
 
 pub mod keystore_protocol {
+    use lib3h_crypto_api::Buffer;
+
+    /// What a keypair is used for
+    #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+    pub enum KeyKind {
+        /// signature keypair
+        Sign,
+        /// key exchange keypair
+        Kx,
+    }
+
+    /// Our side of a key exchange
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    pub enum KxRole {
+        Client,
+        Server,
+    }
+
     #[derive(Debug)]
     pub enum RequestToChild {
+        /// Generate a new keypair and store it under `id`
+        Generate { id: String, kind: KeyKind },
+        /// Store an existing keypair under `id`
+        Import {
+            id: String,
+            kind: KeyKind,
+            public_key: Vec<u8>,
+            secret_key: Box<dyn Buffer>,
+        },
+        /// Get a copy of the keypair stored under `id`
+        Export { id: String },
+        /// Sign `payload` with the secret key of the Sign keypair `id`
         Sign { id: String, payload: Vec<u8> },
+        /// Check that `signature` of `payload` was made by the secret key of `public_key`
+        Verify {
+            public_key: Vec<u8>,
+            payload: Vec<u8>,
+            signature: Vec<u8>,
+        },
+        /// Derive session keys between the Kx keypair `id` and a remote public key
+        Kx {
+            id: String,
+            role: KxRole,
+            remote_public_key: Vec<u8>,
+        },
     }
 
     #[derive(Debug)]
     pub enum RequestToChildResponse {
-        Sign { signature: Vec<u8> },
+        Generate {
+            public_key: Vec<u8>,
+        },
+        Import,
+        Export {
+            kind: KeyKind,
+            public_key: Vec<u8>,
+            secret_key: Box<dyn Buffer>,
+        },
+        Sign {
+            signature: Vec<u8>,
+        },
+        Verify {
+            valid: bool,
+        },
+        Kx {
+            rx: Box<dyn Buffer>,
+            tx: Box<dyn Buffer>,
+        },
     }
 
     #[derive(Debug)]
@@ -61,7 +137,7 @@ type KeystoreParentEndpoint = GhostEndpoint<
 >;
 
 type KeystoreSelfEndpoint = GhostContextEndpoint<
-    KeystoreStub,
+    Keystore,
     RequestToParent,
     RequestToParentResponse,
     RequestToChild,
@@ -72,13 +148,51 @@ type KeystoreSelfEndpoint = GhostContextEndpoint<
 type KeystoreMessageFromParent =
     GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, Lib3hError>;
 
-pub struct KeystoreStub {
+/// A keypair held by the keystore
+struct Keypair {
+    kind: KeyKind,
+    public_key: Vec<u8>,
+    secret_key: Box<dyn Buffer>,
+}
+
+/// A keypair as sealed in the keystore file
+#[derive(Serialize, Deserialize)]
+struct StoredKeypair {
+    id: String,
+    kind: KeyKind,
+    public_key: Vec<u8>,
+    secret_key: Vec<u8>,
+}
+
+/// The keystore file: the list of StoredKeypair, encrypted with `aead`
+/// under the `pwhash` of the passphrase and `salt`
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    salt: Vec<u8>,
+    nonce: Vec<u8>,
+    cipher: Vec<u8>,
+}
+
+pub struct Keystore {
     endpoint_parent: Option<KeystoreParentEndpoint>,
     endpoint_self: Detach<KeystoreSelfEndpoint>,
+    crypto: Box<dyn CryptoSystem>,
+    /// where the keypairs are stored
+    path: PathBuf,
+    salt: Vec<u8>,
+    /// aead key derived from the passphrase
+    file_key: Box<dyn Buffer>,
+    keypairs: HashMap<String, Keypair>,
 }
 
-impl KeystoreStub {
-    pub fn new() -> Self {
+impl Keystore {
+    /// Open the keystore stored at `path`, or create an empty one there.
+    /// Fails if the passphrase does not open an existing keystore.
+    pub fn new(
+        crypto: Box<dyn CryptoSystem>,
+        path: PathBuf,
+        passphrase: &dyn Buffer,
+    ) -> Lib3hResult<Self> {
         let (endpoint_parent, endpoint_self) = create_ghost_channel();
         let endpoint_parent = Some(endpoint_parent);
         let endpoint_self = Detach::new(
@@ -87,30 +201,380 @@ impl KeystoreStub {
                 .request_id_prefix("keystore_to_parent_")
                 .build(),
         );
-        Self {
+        let mut keystore = Self {
             endpoint_parent,
             endpoint_self,
+            file_key: crypto.buf_new_secure(crypto.aead_secret_bytes()),
+            crypto,
+            path,
+            salt: Vec::new(),
+            keypairs: HashMap::new(),
+        };
+        if keystore.path.exists() {
+            keystore.load(passphrase)?;
+        } else {
+            let mut salt = keystore
+                .crypto
+                .buf_new_insecure(keystore.crypto.pwhash_salt_bytes());
+            keystore.crypto.randombytes_buf(&mut salt)?;
+            keystore.salt = salt.read_lock().to_vec();
+            keystore.derive_file_key(passphrase)?;
+            keystore.save()?;
         }
+        Ok(keystore)
     }
 
-    fn handle_msg_from_parent(&mut self, mut msg: KeystoreMessageFromParent) -> Lib3hResult<()> {
-        match msg.take_message().expect("exists") {
-            RequestToChild::Sign { id, payload } => self.handle_sign(msg, id, payload),
+    /// Open the keystore of an engine, stored in its `work_dir`
+    pub fn with_work_dir(
+        crypto: Box<dyn CryptoSystem>,
+        work_dir: &Path,
+        passphrase: &dyn Buffer,
+    ) -> Lib3hResult<Self> {
+        std::fs::create_dir_all(work_dir)?;
+        Self::new(crypto, work_dir.join(KEYSTORE_FILE), passphrase)
+    }
+
+    fn derive_file_key(&mut self, passphrase: &dyn Buffer) -> Lib3hResult<()> {
+        let passphrase = passphrase.box_clone();
+        let salt: Box<dyn Buffer> = Box::new(self.salt.clone());
+        let mut hash = self.crypto.buf_new_secure(self.crypto.pwhash_bytes());
+        self.crypto.pwhash(&mut hash, &passphrase, &salt)?;
+        if hash.len() != self.file_key.len() {
+            return Err(Lib3hError::new_other(
+                "pwhash and aead key sizes differ, cannot seal the keystore",
+            ));
+        }
+        self.file_key.write(0, &hash.read_lock())?;
+        Ok(())
+    }
+
+    fn load(&mut self, passphrase: &dyn Buffer) -> Lib3hResult<()> {
+        let data = std::fs::read(&self.path)?;
+        let mut de = Deserializer::new(&data[..]);
+        let file: KeystoreFile = Deserialize::deserialize(&mut de)?;
+        self.salt = file.salt;
+        self.derive_file_key(passphrase)?;
+
+        let nonce: Box<dyn Buffer> = Box::new(file.nonce);
+        let cipher: Box<dyn Buffer> = Box::new(file.cipher);
+        let mut plain = self.crypto.buf_new_secure(
+            cipher
+                .len()
+                .checked_sub(self.crypto.aead_auth_bytes())
+                .ok_or_else(|| Lib3hError::new_other("keystore file is truncated"))?,
+        );
+        self.crypto
+            .aead_decrypt(&mut plain, &cipher, None, &nonce, &self.file_key)
+            .map_err(|_| {
+                Lib3hError::new_other("cannot open keystore: wrong passphrase or corrupted file")
+            })?;
+
+        let stored_list: Vec<StoredKeypair> = {
+            let plain = plain.read_lock();
+            let mut de = Deserializer::new(&plain[..]);
+            Deserialize::deserialize(&mut de)?
+        };
+        for mut stored in stored_list {
+            let mut secret_key = self.crypto.buf_new_secure(stored.secret_key.len());
+            secret_key.write(0, &stored.secret_key)?;
+            zero_vec(&mut stored.secret_key);
+            self.keypairs.insert(
+                stored.id,
+                Keypair {
+                    kind: stored.kind,
+                    public_key: stored.public_key,
+                    secret_key,
+                },
+            );
         }
+        Ok(())
+    }
+
+    /// Seal all our keypairs into the keystore file
+    fn save(&self) -> Lib3hResult<()> {
+        let mut stored_list: Vec<StoredKeypair> = self
+            .keypairs
+            .iter()
+            .map(|(id, keypair)| StoredKeypair {
+                id: id.clone(),
+                kind: keypair.kind,
+                public_key: keypair.public_key.clone(),
+                secret_key: keypair.secret_key.read_lock().to_vec(),
+            })
+            .collect();
+        let mut serialized = Vec::new();
+        let result = stored_list.serialize(&mut Serializer::new(&mut serialized));
+        for stored in stored_list.iter_mut() {
+            zero_vec(&mut stored.secret_key);
+        }
+        result.map_err(|e| Lib3hError::new_other(&format!("{:?}", e)))?;
+        let mut plain = self.crypto.buf_new_secure(serialized.len());
+        plain.write(0, &serialized)?;
+        zero_vec(&mut serialized);
+
+        let mut nonce = self.crypto.buf_new_insecure(self.crypto.aead_nonce_bytes());
+        self.crypto.randombytes_buf(&mut nonce)?;
+        let mut cipher = self
+            .crypto
+            .buf_new_insecure(plain.len() + self.crypto.aead_auth_bytes());
+        self.crypto
+            .aead_encrypt(&mut cipher, &plain, None, &nonce, &self.file_key)?;
+
+        let file = KeystoreFile {
+            salt: self.salt.clone(),
+            nonce: nonce.read_lock().to_vec(),
+            cipher: cipher.read_lock().to_vec(),
+        };
+        let mut data = Vec::new();
+        file.serialize(&mut Serializer::new(&mut data))
+            .map_err(|e| Lib3hError::new_other(&format!("{:?}", e)))?;
+        // readable by us only, not to expose it to offline passphrase guessing
+        crate::private_file::write(&self.path, &data)?;
+        Ok(())
+    }
+
+    /// The public key and a copy of the secret key of the Sign keypair `id`,
+    /// generated and stored first if we have none
+    pub fn sign_keypair(&mut self, id: &str) -> Lib3hResult<(Vec<u8>, Box<dyn Buffer>)> {
+        if !self.keypairs.contains_key(id) {
+            self.handle_generate(id.to_string(), KeyKind::Sign)?;
+        }
+        let keypair = self.get_keypair(id, KeyKind::Sign)?;
+        let mut secret_key = self.crypto.buf_new_secure(keypair.secret_key.len());
+        secret_key.write(0, &keypair.secret_key.read_lock())?;
+        Ok((keypair.public_key.clone(), secret_key))
     }
 
-    fn handle_sign(
+    /// Store a copy of the Sign keypair `public_key`/`secret_key` as `id`,
+    /// in place of the one we had
+    pub fn replace_sign_keypair(
         &mut self,
-        msg: KeystoreMessageFromParent,
-        _id: String,
-        _payload: Vec<u8>,
+        id: &str,
+        public_key: Vec<u8>,
+        secret_key: &dyn Buffer,
     ) -> Lib3hResult<()> {
-        // THIS IS A STUB, just responding with empty signature
-        msg.respond(Ok(RequestToChildResponse::Sign {
-            signature: b"".to_vec(),
-        }))?;
+        let mut secure_key = self.crypto.buf_new_secure(secret_key.len());
+        secure_key.write(0, &secret_key.read_lock())?;
+        let keypair = Keypair {
+            kind: KeyKind::Sign,
+            public_key,
+            secret_key: secure_key,
+        };
+        let previous = self.keypairs.insert(id.to_string(), keypair);
+        if let Err(e) = self.save() {
+            match previous {
+                Some(previous) => self.keypairs.insert(id.to_string(), previous),
+                None => self.keypairs.remove(id),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn get_keypair(&self, id: &str, kind: KeyKind) -> Lib3hResult<&Keypair> {
+        let keypair = self
+            .keypairs
+            .get(id)
+            .ok_or_else(|| Lib3hError::new(ErrorKind::KeyNotFound(id.to_string())))?;
+        if keypair.kind != kind {
+            return Err(Lib3hError::new_other(&format!(
+                "key {} is a {:?} keypair, not {:?}",
+                id, keypair.kind, kind
+            )));
+        }
+        Ok(keypair)
+    }
+
+    fn add_keypair(&mut self, id: String, keypair: Keypair) -> Lib3hResult<()> {
+        if self.keypairs.contains_key(&id) {
+            return Err(Lib3hError::new_other(&format!("key {} already exists", id)));
+        }
+        self.keypairs.insert(id.clone(), keypair);
+        if let Err(e) = self.save() {
+            self.keypairs.remove(&id);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn handle_msg_from_parent(&mut self, mut msg: KeystoreMessageFromParent) -> Lib3hResult<()> {
+        let result = match msg.take_message().expect("exists") {
+            RequestToChild::Generate { id, kind } => self.handle_generate(id, kind),
+            RequestToChild::Import {
+                id,
+                kind,
+                public_key,
+                secret_key,
+            } => self.handle_import(id, kind, public_key, secret_key),
+            RequestToChild::Export { id } => self.handle_export(&id),
+            RequestToChild::Sign { id, payload } => self.handle_sign(&id, payload),
+            RequestToChild::Verify {
+                public_key,
+                payload,
+                signature,
+            } => self.handle_verify(public_key, payload, signature),
+            RequestToChild::Kx {
+                id,
+                role,
+                remote_public_key,
+            } => self.handle_kx(&id, role, remote_public_key),
+        };
+        msg.respond(result)?;
         Ok(())
     }
+
+    fn handle_generate(
+        &mut self,
+        id: String,
+        kind: KeyKind,
+    ) -> Lib3hResult<RequestToChildResponse> {
+        let (public_key_bytes, secret_key_bytes) = match kind {
+            KeyKind::Sign => (
+                self.crypto.sign_public_key_bytes(),
+                self.crypto.sign_secret_key_bytes(),
+            ),
+            KeyKind::Kx => (
+                self.crypto.kx_public_key_bytes(),
+                self.crypto.kx_secret_key_bytes(),
+            ),
+        };
+        let mut public_key = self.crypto.buf_new_insecure(public_key_bytes);
+        let mut secret_key = self.crypto.buf_new_secure(secret_key_bytes);
+        match kind {
+            KeyKind::Sign => self.crypto.sign_keypair(&mut public_key, &mut secret_key)?,
+            KeyKind::Kx => self.crypto.kx_keypair(&mut public_key, &mut secret_key)?,
+        }
+        let public_key = public_key.read_lock().to_vec();
+        self.add_keypair(
+            id,
+            Keypair {
+                kind,
+                public_key: public_key.clone(),
+                secret_key,
+            },
+        )?;
+        Ok(RequestToChildResponse::Generate { public_key })
+    }
+
+    fn handle_import(
+        &mut self,
+        id: String,
+        kind: KeyKind,
+        public_key: Vec<u8>,
+        secret_key: Box<dyn Buffer>,
+    ) -> Lib3hResult<RequestToChildResponse> {
+        let (public_key_bytes, secret_key_bytes) = match kind {
+            KeyKind::Sign => (
+                self.crypto.sign_public_key_bytes(),
+                self.crypto.sign_secret_key_bytes(),
+            ),
+            KeyKind::Kx => (
+                self.crypto.kx_public_key_bytes(),
+                self.crypto.kx_secret_key_bytes(),
+            ),
+        };
+        if public_key.len() != public_key_bytes || secret_key.len() != secret_key_bytes {
+            return Err(Lib3hError::new_other(&format!(
+                "bad key sizes for a {:?} keypair",
+                kind
+            )));
+        }
+        // hold it in our own secure buffer, whatever the caller gave us
+        let mut secure_secret_key = self.crypto.buf_new_secure(secret_key_bytes);
+        secure_secret_key.write(0, &secret_key.read_lock())?;
+        self.add_keypair(
+            id,
+            Keypair {
+                kind,
+                public_key,
+                secret_key: secure_secret_key,
+            },
+        )?;
+        Ok(RequestToChildResponse::Import)
+    }
+
+    fn handle_export(&self, id: &str) -> Lib3hResult<RequestToChildResponse> {
+        let keypair = self
+            .keypairs
+            .get(id)
+            .ok_or_else(|| Lib3hError::new(ErrorKind::KeyNotFound(id.to_string())))?;
+        let mut secret_key = self.crypto.buf_new_secure(keypair.secret_key.len());
+        secret_key.write(0, &keypair.secret_key.read_lock())?;
+        Ok(RequestToChildResponse::Export {
+            kind: keypair.kind,
+            public_key: keypair.public_key.clone(),
+            secret_key,
+        })
+    }
+
+    fn handle_sign(&self, id: &str, payload: Vec<u8>) -> Lib3hResult<RequestToChildResponse> {
+        let keypair = self.get_keypair(id, KeyKind::Sign)?;
+        let mut signature = self.crypto.buf_new_insecure(self.crypto.sign_bytes());
+        let payload: Box<dyn Buffer> = Box::new(payload);
+        self.crypto
+            .sign(&mut signature, &payload, &keypair.secret_key)?;
+        let signature = signature.read_lock().to_vec();
+        Ok(RequestToChildResponse::Sign { signature })
+    }
+
+    fn handle_verify(
+        &self,
+        public_key: Vec<u8>,
+        payload: Vec<u8>,
+        signature: Vec<u8>,
+    ) -> Lib3hResult<RequestToChildResponse> {
+        if public_key.len() != self.crypto.sign_public_key_bytes()
+            || signature.len() != self.crypto.sign_bytes()
+        {
+            return Ok(RequestToChildResponse::Verify { valid: false });
+        }
+        let public_key: Box<dyn Buffer> = Box::new(public_key);
+        let payload: Box<dyn Buffer> = Box::new(payload);
+        let signature: Box<dyn Buffer> = Box::new(signature);
+        let valid = self.crypto.sign_verify(&signature, &payload, &public_key)?;
+        Ok(RequestToChildResponse::Verify { valid })
+    }
+
+    fn handle_kx(
+        &self,
+        id: &str,
+        role: KxRole,
+        remote_public_key: Vec<u8>,
+    ) -> Lib3hResult<RequestToChildResponse> {
+        let keypair = self.get_keypair(id, KeyKind::Kx)?;
+        let public_key: Box<dyn Buffer> = Box::new(keypair.public_key.clone());
+        let remote_public_key: Box<dyn Buffer> = Box::new(remote_public_key);
+        let mut rx = self
+            .crypto
+            .buf_new_secure(self.crypto.kx_session_key_bytes());
+        let mut tx = self
+            .crypto
+            .buf_new_secure(self.crypto.kx_session_key_bytes());
+        match role {
+            KxRole::Client => self.crypto.kx_client_session_keys(
+                &mut rx,
+                &mut tx,
+                &public_key,
+                &keypair.secret_key,
+                &remote_public_key,
+            )?,
+            KxRole::Server => self.crypto.kx_server_session_keys(
+                &mut rx,
+                &mut tx,
+                &public_key,
+                &keypair.secret_key,
+                &remote_public_key,
+            )?,
+        }
+        Ok(RequestToChildResponse::Kx { rx, tx })
+    }
+}
+
+/// Overwrite a copy of secret data we are done with
+fn zero_vec(data: &mut [u8]) {
+    for byte in data.iter_mut() {
+        *byte = 0;
+    }
 }
 
 impl
@@ -120,7 +584,7 @@ impl
         RequestToChild,
         RequestToChildResponse,
         Lib3hError,
-    > for KeystoreStub
+    > for Keystore
 {
     fn take_parent_endpoint(&mut self) -> Option<KeystoreParentEndpoint> {
         std::mem::replace(&mut self.endpoint_parent, None)
@@ -128,9 +592,179 @@ impl
 
     fn process_concrete(&mut self) -> GhostResult<WorkWasDone> {
         detach_run!(&mut self.endpoint_self, |es| es.process(self))?;
+        let mut did_work = false;
         for msg in self.endpoint_self.as_mut().drain_messages() {
             self.handle_msg_from_parent(msg).expect("no ghost errors");
+            did_work = true;
         }
-        Ok(false.into())
+        Ok(did_work.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib3h_sodium::SodiumCryptoSystem;
+
+    fn test_crypto() -> Box<dyn CryptoSystem> {
+        Box::new(SodiumCryptoSystem::new().set_pwhash_interactive())
+    }
+
+    fn test_passphrase(passphrase: &str) -> Box<dyn Buffer> {
+        Box::new(passphrase.as_bytes().to_vec())
+    }
+
+    fn request_for_test(
+        keystore: &mut KeystoreActorParentWrapperDyn<()>,
+        request: RequestToChild,
+    ) -> RequestToChildResponse {
+        let responses = crate::tests::request_for_test(keystore, request);
+        keystore.process(&mut ()).unwrap();
+        keystore.process(&mut ()).unwrap();
+        let response = responses.lock().unwrap().pop().expect("a response");
+        response.expect("bad response")
+    }
+
+    #[test]
+    fn test_keystore_sign_and_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore =
+            Keystore::with_work_dir(test_crypto(), dir.path(), &*test_passphrase("pass")).unwrap();
+        let mut keystore: KeystoreActorParentWrapperDyn<()> =
+            GhostParentWrapperDyn::new(Box::new(keystore), "test_keystore");
+
+        let public_key = match request_for_test(
+            &mut keystore,
+            RequestToChild::Generate {
+                id: "transport".to_string(),
+                kind: KeyKind::Sign,
+            },
+        ) {
+            RequestToChildResponse::Generate { public_key } => public_key,
+            response => panic!("bad response: {:?}", response),
+        };
+        let signature = match request_for_test(
+            &mut keystore,
+            RequestToChild::Sign {
+                id: "transport".to_string(),
+                payload: b"hello".to_vec(),
+            },
+        ) {
+            RequestToChildResponse::Sign { signature } => signature,
+            response => panic!("bad response: {:?}", response),
+        };
+        assert_eq!(
+            "Verify { valid: true }",
+            format!(
+                "{:?}",
+                request_for_test(
+                    &mut keystore,
+                    RequestToChild::Verify {
+                        public_key: public_key.clone(),
+                        payload: b"hello".to_vec(),
+                        signature: signature.clone(),
+                    },
+                )
+            )
+        );
+        assert_eq!(
+            "Verify { valid: false }",
+            format!(
+                "{:?}",
+                request_for_test(
+                    &mut keystore,
+                    RequestToChild::Verify {
+                        public_key: public_key.clone(),
+                        payload: b"bye".to_vec(),
+                        signature,
+                    },
+                )
+            )
+        );
+
+        // the keypair is still there once reopened, not with another passphrase
+        let mut keystore =
+            Keystore::with_work_dir(test_crypto(), dir.path(), &*test_passphrase("pass")).unwrap();
+        assert_eq!(public_key, keystore.keypairs["transport"].public_key);
+        assert!(keystore.handle_sign("transport", b"hello".to_vec()).is_ok());
+        assert!(keystore
+            .handle_generate("transport".to_string(), KeyKind::Sign)
+            .is_err());
+        assert!(
+            Keystore::with_work_dir(test_crypto(), dir.path(), &*test_passphrase("wrong")).is_err()
+        );
+
+        // and readable by its owner only
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let path = dir.path().join(KEYSTORE_FILE);
+            let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(0o600, mode & 0o777);
+        }
+    }
+
+    #[test]
+    fn test_keystore_import_export_and_kx() {
+        let dir = tempfile::tempdir().unwrap();
+        let crypto = test_crypto();
+        let mut keystore =
+            Keystore::with_work_dir(test_crypto(), dir.path(), &*test_passphrase("pass")).unwrap();
+
+        // a remote client keypair, imported then exported back
+        let mut client_pk = crypto.buf_new_insecure(crypto.kx_public_key_bytes());
+        let mut client_sk = crypto.buf_new_secure(crypto.kx_secret_key_bytes());
+        crypto.kx_keypair(&mut client_pk, &mut client_sk).unwrap();
+        keystore
+            .handle_import(
+                "client".to_string(),
+                KeyKind::Kx,
+                client_pk.read_lock().to_vec(),
+                client_sk.box_clone(),
+            )
+            .unwrap();
+        match keystore.handle_export("client").unwrap() {
+            RequestToChildResponse::Export {
+                kind,
+                public_key,
+                secret_key,
+            } => {
+                assert_eq!(KeyKind::Kx, kind);
+                assert_eq!(client_pk.read_lock().to_vec(), public_key);
+                assert_eq!(
+                    client_sk.read_lock().to_vec(),
+                    secret_key.read_lock().to_vec()
+                );
+            }
+            response => panic!("bad response: {:?}", response),
+        }
+        // a Kx keypair cannot sign
+        assert!(keystore.handle_sign("client", b"hello".to_vec()).is_err());
+
+        let server_pk = match keystore
+            .handle_generate("server".to_string(), KeyKind::Kx)
+            .unwrap()
+        {
+            RequestToChildResponse::Generate { public_key } => public_key,
+            response => panic!("bad response: {:?}", response),
+        };
+        let session_keys = |response| match response {
+            RequestToChildResponse::Kx { rx, tx } => {
+                (rx.read_lock().to_vec(), tx.read_lock().to_vec())
+            }
+            response => panic!("bad response: {:?}", response),
+        };
+        let (client_rx, client_tx) = session_keys(
+            keystore
+                .handle_kx("client", KxRole::Client, server_pk)
+                .unwrap(),
+        );
+        let (server_rx, server_tx) = session_keys(
+            keystore
+                .handle_kx("server", KxRole::Server, client_pk.read_lock().to_vec())
+                .unwrap(),
+        );
+        assert_eq!(client_rx, server_tx);
+        assert_eq!(client_tx, server_rx);
     }
 }
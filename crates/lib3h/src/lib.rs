@@ -9,7 +9,7 @@ extern crate lib3h_p2p_protocol;
 extern crate lib3h_protocol;
 extern crate lib3h_zombie_actor as lib3h_ghost_actor;
 extern crate nanoid;
-extern crate native_tls;
+extern crate openssl;
 extern crate tungstenite;
 #[macro_use]
 extern crate lazy_static;
@@ -27,8 +27,11 @@ pub mod dht;
 pub mod engine;
 pub mod error;
 pub mod gateway;
+pub mod key_derivation;
 pub mod keystore;
 pub mod message_encoding;
+pub mod peer_policy;
+pub mod private_file;
 pub mod time;
 pub mod track;
 #[macro_use]
@@ -39,6 +42,7 @@ pub mod transport;
 #[cfg(test)]
 mod tests {
     extern crate test;
+    use lib3h_ghost_actor::prelude::*;
     use std::io::{Read, Seek, Write};
 
     // for this to actually show log entries you also have to run the tests like this:
@@ -58,6 +62,47 @@ mod tests {
             .try_init();
     }
 
+    /// responses collected by `test_callback`
+    pub type TestResponses<Response, Error> =
+        std::sync::Arc<std::sync::Mutex<Vec<Result<Response, Error>>>>;
+
+    /// a callback pushing the responses it gets to `responses`
+    pub fn test_callback<UserData, Response: 'static, Error: 'static>(
+        responses: &TestResponses<Response, Error>,
+    ) -> GhostCallback<UserData, Response, Error> {
+        let responses = responses.clone();
+        Box::new(move |_, response| {
+            if let GhostCallbackData::Response(response) = response {
+                responses.lock().unwrap().push(response);
+            }
+            Ok(())
+        })
+    }
+
+    /// make `request` of an actor, its responses arrive as it is processed
+    pub fn request_for_test<UserData, Request, Response, Event, EventResponse, Error, Actor>(
+        actor: &mut Actor,
+        request: Request,
+    ) -> TestResponses<Response, Error>
+    where
+        Request: 'static,
+        Response: 'static,
+        Event: 'static,
+        EventResponse: 'static,
+        Error: 'static + std::fmt::Debug,
+        Actor: GhostCanTrack<UserData, Request, Response, Event, EventResponse, Error>,
+    {
+        let responses = TestResponses::default();
+        actor
+            .request(
+                holochain_tracing::test_span(""),
+                request,
+                test_callback(&responses),
+            )
+            .unwrap();
+        responses
+    }
+
     static DATA: &'static [u8] = b"this is some tempfile data";
 
     fn bench_unit_control_work() {
@@ -0,0 +1,219 @@
+//! Allow/block filtering of remote peers, derived from the engine's NetworkPolicyData.
+
+use crate::{dht::dht_protocol::PeerData, error::*};
+use lib3h_protocol::{
+    data_types::{NetworkPolicyData, PeerFilterData},
+    types::SpaceHash,
+    uri::Lib3hUri,
+};
+use std::net::IpAddr;
+
+/// An ip range in CIDR notation, e.g. `192.168.0.0/16`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Parse `addr/prefix_len`. A bare address is treated as a single host.
+    pub fn parse(s: &str) -> Lib3hResult<Self> {
+        let mut split = s.trim().splitn(2, '/');
+        let addr: IpAddr = split
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .map_err(|_| Lib3hError::new_other(&format!("bad cidr address: {}", s)))?;
+        let max_len = match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_len = match split.next() {
+            None => max_len,
+            Some(len) => len
+                .parse()
+                .ok()
+                .filter(|len| *len <= max_len)
+                .ok_or_else(|| Lib3hError::new_other(&format!("bad cidr prefix: {}", s)))?,
+        };
+        Ok(Self { addr, prefix_len })
+    }
+
+    /// Is `ip` within this range?
+    /// IPv4-mapped IPv6 addresses are matched against IPv4 ranges.
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => match v6.to_ipv4() {
+                Some(v4) if v6.segments()[5] == 0xffff => IpAddr::V4(v4),
+                _ => *ip,
+            },
+            _ => *ip,
+        };
+        match (&self.addr, &ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                prefix_eq(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                prefix_eq(&net.octets(), &ip.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Compare the first `prefix_len` bits of two addresses
+fn prefix_eq(a: &[u8], b: &[u8], prefix_len: u8) -> bool {
+    let full_bytes = (prefix_len / 8) as usize;
+    if a[..full_bytes] != b[..full_bytes] {
+        return false;
+    }
+    let rem_bits = prefix_len % 8;
+    if rem_bits == 0 {
+        return true;
+    }
+    let mask = 0xffu8 << (8 - rem_bits);
+    a[full_bytes] & mask == b[full_bytes] & mask
+}
+
+/// The part of a NetworkPolicyData that applies to a single gateway:
+/// the network gateway filters on TransportIds, a space gateway on agent ids.
+/// Both filter remote locations by CIDR.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct PeerPolicy {
+    ids: PeerFilterData,
+    allow_cidrs: Vec<Cidr>,
+    block_cidrs: Vec<Cidr>,
+}
+
+impl PeerPolicy {
+    /// Policy for the network gateway (TransportIds)
+    pub fn for_network(policy: &NetworkPolicyData) -> Lib3hResult<Self> {
+        Self::with_ids(policy, policy.transport_ids.clone())
+    }
+
+    /// Policy for the gateway of a space (agent ids)
+    pub fn for_space(policy: &NetworkPolicyData, space_address: &SpaceHash) -> Lib3hResult<Self> {
+        let ids = policy
+            .spaces
+            .iter()
+            .find(|space| &space.space_address == space_address)
+            .map(|space| space.agent_ids.clone())
+            .unwrap_or_default();
+        Self::with_ids(policy, ids)
+    }
+
+    fn with_ids(policy: &NetworkPolicyData, ids: PeerFilterData) -> Lib3hResult<Self> {
+        let parse_all = |list: &Vec<String>| -> Lib3hResult<Vec<Cidr>> {
+            list.iter().map(|s| Cidr::parse(s)).collect()
+        };
+        Ok(Self {
+            ids,
+            allow_cidrs: parse_all(&policy.cidrs.allow)?,
+            block_cidrs: parse_all(&policy.cidrs.block)?,
+        })
+    }
+
+    /// Check a peer's name (`transportid:HcM...` or `agentid:HcS...`)
+    pub fn allows_peer_name(&self, peer_name: &Lib3hUri) -> bool {
+        let id = peer_name.lower_address().to_string();
+        if self.ids.block.contains(&id) {
+            return false;
+        }
+        self.ids.allow.is_empty() || self.ids.allow.contains(&id)
+    }
+
+    /// Check the remote address of a connection.
+    /// Locations without an ip host (e.g. `mem://` or `transportid:`)
+    /// are not subject to CIDR rules.
+    pub fn allows_location(&self, location: &Lib3hUri) -> bool {
+        let ip = match location.host() {
+            Some(url::Host::Ipv4(ip)) => IpAddr::V4(ip),
+            Some(url::Host::Ipv6(ip)) => IpAddr::V6(ip),
+            _ => return true,
+        };
+        if self.block_cidrs.iter().any(|cidr| cidr.contains(&ip)) {
+            return false;
+        }
+        self.allow_cidrs.is_empty() || self.allow_cidrs.iter().any(|cidr| cidr.contains(&ip))
+    }
+
+    pub fn allows(&self, peer: &PeerData) -> bool {
+        self.allows_peer_name(&peer.peer_name) && self.allows_location(&peer.peer_location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_persistence_api::hash::HashString;
+    use lib3h_protocol::data_types::SpacePolicyData;
+    use std::convert::TryFrom;
+
+    fn filter(allow: &[&str], block: &[&str]) -> PeerFilterData {
+        PeerFilterData {
+            allow: allow.iter().map(|s| s.to_string()).collect(),
+            block: block.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn uri(s: &str) -> Lib3hUri {
+        Lib3hUri::try_from(s).unwrap()
+    }
+
+    #[test]
+    fn test_cidr_parse() {
+        assert!(Cidr::parse("10.0.0.0/8").is_ok());
+        assert!(Cidr::parse("fd00::/8").is_ok());
+        assert!(Cidr::parse("127.0.0.1").is_ok());
+        assert!(Cidr::parse("10.0.0.0/33").is_err());
+        assert!(Cidr::parse("not-an-ip/8").is_err());
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        let cidr = Cidr::parse("192.168.4.0/22").unwrap();
+        assert!(cidr.contains(&"192.168.7.255".parse().unwrap()));
+        assert!(!cidr.contains(&"192.168.8.1".parse().unwrap()));
+        assert!(cidr.contains(&"::ffff:192.168.5.1".parse().unwrap()));
+        let cidr = Cidr::parse("fd00::/8").unwrap();
+        assert!(cidr.contains(&"fd12::1".parse().unwrap()));
+        assert!(!cidr.contains(&"fe80::1".parse().unwrap()));
+        assert!(!cidr.contains(&"10.0.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_network_policy_filters_transport_ids_and_cidrs() {
+        let policy = PeerPolicy::for_network(&NetworkPolicyData {
+            transport_ids: filter(&[], &["HcMbad"]),
+            cidrs: filter(&["10.0.0.0/8"], &["10.6.6.0/24"]),
+            spaces: vec![],
+        })
+        .unwrap();
+        let good = Lib3hUri::with_transport_id(&HashString::from("HcMgood"));
+        let bad = Lib3hUri::with_transport_id(&HashString::from("HcMbad"));
+        assert!(policy.allows_peer_name(&good));
+        assert!(!policy.allows_peer_name(&bad));
+        assert!(policy.allows_location(&uri("wss://10.1.2.3:1234/")));
+        assert!(!policy.allows_location(&uri("wss://10.6.6.6:1234/")));
+        assert!(!policy.allows_location(&uri("wss://192.168.0.1:1234/")));
+        assert!(policy.allows_location(&Lib3hUri::with_memory("addr_1")));
+    }
+
+    #[test]
+    fn test_space_policy_filters_agent_ids() {
+        let network_policy = NetworkPolicyData {
+            spaces: vec![SpacePolicyData {
+                space_address: "space_a".into(),
+                agent_ids: filter(&["alex"], &[]),
+            }],
+            ..Default::default()
+        };
+        let alex = Lib3hUri::with_agent_id(&HashString::from("alex"));
+        let billy = Lib3hUri::with_agent_id(&HashString::from("billy"));
+        let policy = PeerPolicy::for_space(&network_policy, &"space_a".into()).unwrap();
+        assert!(policy.allows_peer_name(&alex));
+        assert!(!policy.allows_peer_name(&billy));
+        let policy = PeerPolicy::for_space(&network_policy, &"space_b".into()).unwrap();
+        assert!(policy.allows_peer_name(&billy));
+    }
+}
@@ -1,5 +1,8 @@
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// How far ahead of our clock a remote timestamp may be
+pub const MAX_CLOCK_SKEW_MS: u64 = 60_000;
+
 pub fn since_epoch_ms() -> u64 {
     let since_the_epoch;
     //unsafe {
@@ -121,8 +121,8 @@ impl From<tungstenite::Error> for TransportError {
     }
 }
 
-impl From<native_tls::Error> for TransportError {
-    fn from(error: native_tls::Error) -> Self {
+impl From<openssl::error::ErrorStack> for TransportError {
+    fn from(error: openssl::error::ErrorStack) -> Self {
         Self::new(format!("{:?}", error))
     }
 }
@@ -135,10 +135,10 @@ impl<S: std::fmt::Debug + std::io::Read + std::io::Write>
     }
 }
 
-impl<T: std::io::Read + std::io::Write + std::fmt::Debug> From<native_tls::HandshakeError<T>>
+impl<T: std::io::Read + std::io::Write + std::fmt::Debug> From<openssl::ssl::HandshakeError<T>>
     for TransportError
 {
-    fn from(error: native_tls::HandshakeError<T>) -> Self {
+    fn from(error: openssl::ssl::HandshakeError<T>) -> Self {
         Self::new(format!("{:?}", error))
     }
 }
@@ -2,6 +2,7 @@ use crate::transport::{
     error::{ErrorKind, TransportError},
     memory_mock::memory_server::{self, *},
     protocol::{RequestToChildResponse::SendMessageSuccess, *},
+    stats::TransportStats,
 };
 use detach::Detach;
 use holochain_tracing::Span;
@@ -60,6 +61,7 @@ pub struct GhostTransportMemory {
     connections: HashSet<Lib3hUri>,
     last_discover: Option<Instant>,
     discover_interval_ms: u128,
+    stats: TransportStats,
 }
 
 impl Discovery for GhostTransportMemory {
@@ -110,6 +112,7 @@ impl GhostTransportMemory {
             maybe_my_address: None,
             last_discover: start,
             discover_interval_ms: u128::from(interval),
+            stats: TransportStats::new(),
         }
     }
 
@@ -138,6 +141,7 @@ impl GhostTransportMemory {
                                                 Span::fixme(),
                                                 RequestToParent::IncomingConnection {
                                                     uri: found_uri.clone(),
+                                                    verified_id: None,
                                                 },
                                             )
                                             .expect("should be able to publish");
@@ -204,6 +208,7 @@ impl
                                 Span::fixme(),
                                 RequestToParent::IncomingConnection {
                                     uri: in_cid.clone(),
+                                    verified_id: None,
                                 },
                             )?;
                         }
@@ -246,6 +251,7 @@ impl
                                 from_addr,
                                 payload
                             );
+                            self.stats.received(&from_addr, payload.len());
                             self.endpoint_self.publish(
                                 Span::fixme(),
                                 RequestToParent::ReceivedData {
@@ -254,8 +260,18 @@ impl
                                 },
                             )?;
                         }
+                        MemoryEvent::ConnectionClosed(uri) => {
+                            trace!("MemoryEvent::ConnectionClosed: {:?}", uri);
+                            self.connections.remove(&uri);
+                            self.stats.closed(&uri);
+                            self.endpoint_self.publish(
+                                Span::fixme(),
+                                RequestToParent::ConnectionClosed { uri },
+                            )?;
+                        }
                         MemoryEvent::Unbind(url) => {
                             trace!("MemoryEvent::Unbind: {:?}", url);
+                            self.stats.error(&url);
                             self.endpoint_self.publish(
                                 Span::fixme(),
                                 RequestToParent::ErrorOccured {
@@ -284,11 +300,16 @@ impl
                         .map_err(|e| GhostError::from(e.to_string()))?;
                     // respond to our parent
                     msg.respond(Ok(RequestToChildResponse::Bind(BindResultData {
-                        bound_url: bound_url,
+                        bound_url: bound_url.clone(),
+                        bound_urls: vec![bound_url],
                     })))?;
                 }
-                RequestToChild::SendMessage { uri, payload, .. } => {
+                RequestToChild::SendMessage {
+                    mut uri, payload, ..
+                } => {
                     trace!("mem send: {:?}", payload);
+                    // memory connections can't prove the remote's TransportId
+                    uri.clear_agent_id();
                     // make sure we have bound and get our address if so
                     //let my_addr = is_bound!(self, request_id, SendMessage);
 
@@ -303,6 +324,7 @@ impl
                             // get destinations server
                             match self.network.lock().unwrap().get_server(&uri) {
                                 None => {
+                                    self.stats.error(&uri);
                                     msg.respond(Err(TransportError::new(format!(
                                         "No Memory server at this uri: {}",
                                         uri
@@ -314,6 +336,8 @@ impl
                                     if &uri == my_addr {
                                         // if so we can add the message directly to our own inbox
                                         trace!("Send-to-self: payload:{:?}", payload);
+                                        self.stats.sent(&uri, payload.len());
+                                        self.stats.received(&uri, payload.len());
                                         self.endpoint_self.publish(
                                             Span::fixme(),
                                             RequestToParent::ReceivedData { uri: uri, payload },
@@ -323,6 +347,7 @@ impl
                                         if self.connections.get(&uri).is_none() {
                                             match server.request_connect(&my_addr) {
                                                 Err(err) => {
+                                                    self.stats.error(&uri);
                                                     msg.respond(Err(err))?;
                                                     continue;
                                                 }
@@ -337,6 +362,7 @@ impl
                                         );
                                         // Send it data from us
                                         server.post(&my_addr, &payload).unwrap();
+                                        self.stats.sent(&uri, payload.len());
                                     }
                                     msg.respond(Ok(SendMessageSuccess))?;
                                 }
@@ -344,6 +370,46 @@ impl
                         }
                     };
                 }
+                RequestToChild::GetStats => {
+                    msg.respond(Ok(RequestToChildResponse::GetStats(self.stats.to_data())))?;
+                }
+                RequestToChild::Shutdown => {
+                    // sends are posted right away, so there is nothing to flush
+                    if let Some(my_addr) = self.maybe_my_address.take() {
+                        let mut network = self.network.lock().unwrap();
+                        for remote_addr in self.connections.drain() {
+                            if let Some(server) = network.get_server(&remote_addr) {
+                                let _ = server.request_close(&my_addr);
+                            }
+                        }
+                        network.unbind(&my_addr);
+                        self.stats.closed_all();
+                    }
+                    msg.respond(Ok(RequestToChildResponse::Shutdown))?;
+                }
+                RequestToChild::Close { uri } => {
+                    if let Some(my_addr) = &self.maybe_my_address {
+                        let mut network = self.network.lock().unwrap();
+                        // the remote stops receiving from us
+                        if let Some(server) = network.get_server(&uri) {
+                            let _ = server.request_close(my_addr);
+                        }
+                        // and we stop receiving from it, our server then
+                        // notifies us with a ConnectionClosed
+                        if let Some(server) = network.get_server(my_addr) {
+                            let _ = server.request_close(&uri);
+                        }
+                    }
+                    msg.respond(Ok(RequestToChildResponse::Close))?;
+                }
+                RequestToChild::SetTransportId { transport_id, .. } => {
+                    self.transport_id = transport_id;
+                    if self.maybe_my_address.is_some() {
+                        self.advertise()
+                            .map_err(|e| GhostError::from(e.to_string()))?;
+                    }
+                    msg.respond(Ok(RequestToChildResponse::SetTransportId))?;
+                }
             }
         }
         Ok(true.into())
@@ -386,7 +452,7 @@ mod tests {
                 Box::new(|ud: &mut Lib3hUri, r| {
                     match r {
                         GhostCallbackData::Response(Ok(RequestToChildResponse::Bind(
-                            BindResultData { bound_url },
+                            BindResultData { bound_url, .. },
                         ))) => *ud = bound_url,
                         _ => assert!(false),
                     };
@@ -517,18 +583,97 @@ mod tests {
         let msg = requests[0].take_message();
         // which url was discovered is non-deterministic
         assert!(
-            "Some(IncomingConnection { uri: Lib3hUri(\"mem://addr_1/\") })" == format!("{:?}", msg)
-                || "Some(IncomingConnection { uri: Lib3hUri(\"mem://addr_2/\") })"
+            "Some(IncomingConnection { uri: Lib3hUri(\"mem://addr_1/\"), verified_id: None })" == format!("{:?}", msg)
+                || "Some(IncomingConnection { uri: Lib3hUri(\"mem://addr_2/\"), verified_id: None })"
                     == format!("{:?}", msg)
         );
         assert_eq!(
-            "Some(IncomingConnection { uri: Lib3hUri(\"mem://addr_1/\") })",
+            "Some(IncomingConnection { uri: Lib3hUri(\"mem://addr_1/\"), verified_id: None })",
             format!("{:?}", requests[1].take_message())
         );
         assert_eq!(
             "Some(ReceivedData { uri: Lib3hUri(\"mem://addr_1/\"), payload: \"test message\" })",
             format!("{:?}", requests[2].take_message())
         );
+
+        let stats = transport1.stats.to_data();
+        assert_eq!(1, stats.total.messages_sent);
+        assert_eq!(12, stats.total.bytes_sent);
+        assert_eq!(1, stats.total.errors);
+        let stats = transport2.stats.to_data();
+        assert_eq!(1, stats.total.messages_received);
+        assert_eq!(12, stats.total.bytes_received);
+        assert_eq!(Lib3hUri::with_memory("addr_1"), stats.connections[0].uri);
+    }
+
+    #[test]
+    fn test_gmem_transport_shutdown() {
+        let (mut transport1, mut t1_endpoint) = make_test_transport("1", "shutdown_net");
+        let (mut transport2, mut t2_endpoint) = make_test_transport("2", "shutdown_net");
+        let mut bound_transport1_address = Lib3hUri::with_undefined();
+        do_bind(&mut t1_endpoint);
+        let mut bound_transport2_address = Lib3hUri::with_undefined();
+        do_bind(&mut t2_endpoint);
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
+        transport2.process().unwrap();
+        let _ = t2_endpoint.process(&mut bound_transport2_address);
+
+        t1_endpoint
+            .publish(
+                test_span(""),
+                RequestToChild::create_send_message(
+                    Lib3hUri::with_memory("addr_2"),
+                    b"test message".to_vec().into(),
+                ),
+            )
+            .unwrap();
+        transport1.process().unwrap();
+        transport2.process().unwrap();
+        assert!(transport2
+            .connections
+            .contains(&Lib3hUri::with_memory("addr_1")));
+
+        t1_endpoint
+            .request(
+                test_span(""),
+                RequestToChild::Shutdown,
+                Box::new(|_: &mut Lib3hUri, r| {
+                    assert_eq!("Response(Ok(Shutdown))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
+        assert!(transport1.connections.is_empty());
+        assert!(transport1.stats.to_data().connections.is_empty());
+
+        // the remote sees the connection close
+        transport2.process().unwrap();
+        assert!(!transport2
+            .connections
+            .contains(&Lib3hUri::with_memory("addr_1")));
+
+        // and we can't send anymore
+        t1_endpoint
+            .request(
+                test_span(""),
+                RequestToChild::create_send_message(
+                    Lib3hUri::with_memory("addr_2"),
+                    b"test message".to_vec().into(),
+                ),
+                Box::new(|_: &mut Lib3hUri, r| {
+                    assert_eq!(
+                        "Response(Err(TransportError(Other(\"Transport must be bound before sending\"))))",
+                        &format!("{:?}", r)
+                    );
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        transport1.process().unwrap();
+        let _ = t1_endpoint.process(&mut bound_transport1_address);
     }
 
     #[test]
@@ -567,5 +712,4 @@ mod tests {
             format!("{:?}", msg)
         );
     }
-
 }
@@ -43,7 +43,10 @@ impl MemoryNet {
             advertised_machines: HashSet::new(),
         }
     }
+    /// `uri` is reachable as `transport_id`, replacing what it advertised before
     pub fn advertise(&mut self, uri: Lib3hUri, transport_id: Address) {
+        self.advertised_machines
+            .retain(|(advertised_uri, _)| *advertised_uri != uri);
         self.advertised_machines.insert((uri, transport_id));
     }
     pub fn discover(&mut self) -> Vec<(Lib3hUri, Address)> {
@@ -3,6 +3,7 @@
 pub mod error;
 pub mod memory_mock;
 pub mod protocol;
+pub mod stats;
 pub mod websocket;
 
 #[macro_use]
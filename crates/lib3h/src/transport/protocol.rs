@@ -1,18 +1,44 @@
-use crate::transport::error::TransportError;
+use crate::transport::{error::TransportError, websocket::tls::TlsCertificate};
 use detach::prelude::*;
 use lib3h_ghost_actor::prelude::*;
-use lib3h_protocol::{data_types::Opaque, uri::Lib3hUri};
+use lib3h_protocol::{
+    data_types::{Opaque, TransportStatsData},
+    uri::Lib3hUri,
+    Address,
+};
 
 #[derive(Debug, Clone)]
 pub struct BindResultData {
     pub bound_url: Lib3hUri,
+    /// Every url the transport now listens on, across all its bindings.
+    /// A wildcard spec such as `wss://[::]:0` expands to one url per interface.
+    pub bound_urls: Vec<Lib3hUri>,
 }
 
 /// Transport protocol enums for use with GhostActor implementation
 #[derive(Debug, Clone)]
 pub enum RequestToChild {
-    Bind { spec: Lib3hUri }, // wss://0.0.0.0:0 -> all network interfaces first available port
-    SendMessage { uri: Lib3hUri, payload: Opaque },
+    Bind {
+        spec: Lib3hUri,
+    }, // wss://0.0.0.0:0 -> all network interfaces first available port
+    SendMessage {
+        uri: Lib3hUri,
+        payload: Opaque,
+    },
+    /// traffic counters, in total and per connection
+    GetStats,
+    /// send what is still queued, then close every connection and stop listening
+    Shutdown,
+    /// close our connection to `uri`, if any
+    Close {
+        uri: Lib3hUri,
+    },
+    /// our TransportId rotated: identify as `transport_id` from now on,
+    /// presenting `certificate` to new connections if we have one
+    SetTransportId {
+        transport_id: Address,
+        certificate: Option<TlsCertificate>,
+    },
 }
 
 impl RequestToChild {
@@ -25,6 +51,10 @@ impl RequestToChild {
 pub enum RequestToChildResponse {
     Bind(BindResultData),
     SendMessageSuccess,
+    GetStats(TransportStatsData),
+    Shutdown,
+    Close,
+    SetTransportId,
 }
 
 #[derive(Debug, Clone)]
@@ -36,11 +66,24 @@ pub enum RequestToParent {
     },
     IncomingConnection {
         uri: Lib3hUri,
+        /// TransportId the remote proved at the transport layer
+        /// (e.g. with a mutual TLS client certificate), if any
+        verified_id: Option<Address>,
+    },
+    /// a connection we opened proved the remote's TransportId
+    /// at the transport layer (e.g. with a pinned TLS certificate)
+    ConnectionVerified {
+        uri: Lib3hUri,
+        verified_id: Address,
     },
     ReceivedData {
         uri: Lib3hUri,
         payload: Opaque,
     },
+    /// a connection was closed, by either side
+    ConnectionClosed {
+        uri: Lib3hUri,
+    },
 }
 
 #[derive(Debug, Clone)]
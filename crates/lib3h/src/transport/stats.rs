@@ -0,0 +1,190 @@
+//! Traffic counters kept by transport actors, reported with `RequestToChild::GetStats`
+
+use lib3h_protocol::{
+    data_types::{ConnectionStatsData, TrafficStatsData, TransportStatsData},
+    uri::Lib3hUri,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Counters per open connection, and totals over the lifetime of a transport.
+/// Totals keep what closed connections did.
+#[derive(Debug, Default)]
+pub struct TransportStats {
+    total: TrafficStatsData,
+    connections: HashMap<Lib3hUri, TrafficStatsData>,
+    /// remotes we connected to ourselves, so we can tell a reconnect
+    outgoing: HashSet<Lib3hUri>,
+    /// reconnect counts of outgoing connections that were closed
+    closed: HashMap<Lib3hUri, u64>,
+}
+
+impl TransportStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// apply `f` to the totals and to the counters of `uri`
+    fn update<F: Fn(&mut TrafficStatsData)>(&mut self, uri: &Lib3hUri, f: F) {
+        f(&mut self.total);
+        f(self.connections.entry(uri.clone()).or_default());
+    }
+
+    /// We are opening a connection to `uri`
+    pub fn connecting(&mut self, uri: &Lib3hUri) {
+        self.outgoing.insert(uri.clone());
+        let stats = self.connections.entry(uri.clone()).or_default();
+        if let Some(reconnects) = self.closed.remove(uri) {
+            stats.reconnects = reconnects + 1;
+            self.total.reconnects += 1;
+        }
+    }
+
+    /// The connection to `uri` is gone
+    pub fn closed(&mut self, uri: &Lib3hUri) {
+        if let Some(stats) = self.connections.remove(uri) {
+            self.total.queue_depth = self.total.queue_depth.saturating_sub(stats.queue_depth);
+            if self.outgoing.remove(uri) {
+                self.closed.insert(uri.clone(), stats.reconnects);
+            }
+        }
+    }
+
+    /// Every connection is gone
+    pub fn closed_all(&mut self) {
+        let uri_list: Vec<Lib3hUri> = self.connections.keys().cloned().collect();
+        for uri in uri_list {
+            self.closed(&uri);
+        }
+    }
+
+    /// A message of `bytes` was sent to `uri`
+    pub fn sent(&mut self, uri: &Lib3hUri, bytes: usize) {
+        let now = crate::time::since_epoch_ms();
+        self.update(uri, |stats| {
+            stats.messages_sent += 1;
+            stats.bytes_sent += bytes as u64;
+            stats.last_activity_ms = Some(now);
+        });
+    }
+
+    /// A message of `bytes` was received from `uri`
+    pub fn received(&mut self, uri: &Lib3hUri, bytes: usize) {
+        let now = crate::time::since_epoch_ms();
+        self.update(uri, |stats| {
+            stats.messages_received += 1;
+            stats.bytes_received += bytes as u64;
+            stats.last_activity_ms = Some(now);
+        });
+    }
+
+    /// Sending to or receiving from `uri` failed
+    pub fn error(&mut self, uri: &Lib3hUri) {
+        self.update(uri, |stats| stats.errors += 1);
+    }
+
+    /// A round trip to `uri` took `rtt_ms`. The total holds the latest measurement.
+    pub fn round_trip(&mut self, uri: &Lib3hUri, rtt_ms: u64) {
+        self.update(uri, |stats| stats.rtt_ms = Some(rtt_ms));
+    }
+
+    /// Replace the number of messages waiting to be sent, per remote.
+    /// Remotes missing from `depths` have nothing queued.
+    pub fn set_queue_depths(&mut self, depths: HashMap<Lib3hUri, u64>) {
+        self.total.queue_depth = 0;
+        for stats in self.connections.values_mut() {
+            stats.queue_depth = 0;
+        }
+        for (uri, depth) in depths {
+            self.update(&uri, |stats| stats.queue_depth += depth);
+        }
+    }
+
+    pub fn to_data(&self) -> TransportStatsData {
+        let mut connections: Vec<ConnectionStatsData> = self
+            .connections
+            .iter()
+            .map(|(uri, stats)| ConnectionStatsData {
+                uri: uri.clone(),
+                stats: stats.clone(),
+            })
+            .collect();
+        connections.sort_by_key(|connection| connection.uri.to_string());
+        TransportStatsData {
+            total: self.total.clone(),
+            connections,
+            inner: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uri(name: &str) -> Lib3hUri {
+        Lib3hUri::with_memory(name)
+    }
+
+    #[test]
+    fn test_stats_count_per_connection_and_total() {
+        let mut stats = TransportStats::new();
+        stats.sent(&uri("a"), 10);
+        stats.sent(&uri("b"), 5);
+        stats.received(&uri("a"), 3);
+        stats.error(&uri("b"));
+        stats.round_trip(&uri("a"), 42);
+
+        let data = stats.to_data();
+        assert_eq!(2, data.total.messages_sent);
+        assert_eq!(15, data.total.bytes_sent);
+        assert_eq!(1, data.total.messages_received);
+        assert_eq!(3, data.total.bytes_received);
+        assert_eq!(1, data.total.errors);
+        assert_eq!(Some(42), data.total.rtt_ms);
+        assert!(data.total.last_activity_ms.is_some());
+
+        assert_eq!(2, data.connections.len());
+        assert_eq!(uri("a"), data.connections[0].uri);
+        assert_eq!(10, data.connections[0].stats.bytes_sent);
+        assert_eq!(Some(42), data.connections[0].stats.rtt_ms);
+        assert_eq!(1, data.connections[1].stats.errors);
+        assert_eq!(None, data.connections[1].stats.rtt_ms);
+    }
+
+    #[test]
+    fn test_stats_totals_survive_closed_connections() {
+        let mut stats = TransportStats::new();
+        stats.connecting(&uri("a"));
+        stats.sent(&uri("a"), 10);
+        stats.closed(&uri("a"));
+        let data = stats.to_data();
+        assert!(data.connections.is_empty());
+        assert_eq!(10, data.total.bytes_sent);
+
+        stats.connecting(&uri("a"));
+        stats.closed(&uri("a"));
+        stats.connecting(&uri("a"));
+        let data = stats.to_data();
+        assert_eq!(2, data.total.reconnects);
+        assert_eq!(2, data.connections[0].stats.reconnects);
+        assert_eq!(0, data.connections[0].stats.bytes_sent);
+    }
+
+    #[test]
+    fn test_stats_queue_depth() {
+        let mut stats = TransportStats::new();
+        let mut depths = HashMap::new();
+        depths.insert(uri("a"), 3);
+        depths.insert(uri("b"), 1);
+        stats.set_queue_depths(depths);
+        assert_eq!(4, stats.to_data().total.queue_depth);
+
+        stats.closed(&uri("a"));
+        assert_eq!(1, stats.to_data().total.queue_depth);
+
+        stats.set_queue_depths(HashMap::new());
+        let data = stats.to_data();
+        assert_eq!(0, data.total.queue_depth);
+        assert_eq!(0, data.connections[0].stats.queue_depth);
+    }
+}
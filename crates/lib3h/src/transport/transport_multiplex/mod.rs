@@ -93,7 +93,10 @@ mod tests {
                             spec.set_path("bound");
                             self.bound_url = spec.clone();
                             msg.respond(Ok(GatewayRequestToChildResponse::Transport(
-                                RequestToChildResponse::Bind(BindResultData { bound_url: spec }),
+                                RequestToChildResponse::Bind(BindResultData {
+                                    bound_url: spec.clone(),
+                                    bound_urls: vec![spec],
+                                }),
                             )))?;
                         }
                         RequestToChild::SendMessage { uri, payload, .. } => {
@@ -102,6 +105,26 @@ mod tests {
                                 RequestToChildResponse::SendMessageSuccess,
                             )))?;
                         }
+                        RequestToChild::GetStats => {
+                            msg.respond(Ok(GatewayRequestToChildResponse::Transport(
+                                RequestToChildResponse::GetStats(Default::default()),
+                            )))?;
+                        }
+                        RequestToChild::Shutdown => {
+                            msg.respond(Ok(GatewayRequestToChildResponse::Transport(
+                                RequestToChildResponse::Shutdown,
+                            )))?;
+                        }
+                        RequestToChild::Close { .. } => {
+                            msg.respond(Ok(GatewayRequestToChildResponse::Transport(
+                                RequestToChildResponse::Close,
+                            )))?;
+                        }
+                        RequestToChild::SetTransportId { .. } => {
+                            msg.respond(Ok(GatewayRequestToChildResponse::Transport(
+                                RequestToChildResponse::SetTransportId,
+                            )))?;
+                        }
                     },
                     _ => unimplemented!(),
                 }
@@ -208,24 +208,41 @@ impl<
         >,
     ) -> Lib3hResult<()> {
         match msg.take_message().expect("exists") {
-            RequestToChild::Bind { spec } => self.handle_route_bind(msg, spec),
             RequestToChild::SendMessage { uri, payload, .. } => {
                 debug!("handle_route_send to {}", uri.clone());
                 self.handle_route_send_message(msg, uri, payload)
             }
+            RequestToChild::Shutdown => {
+                // routes share the connections of our inner_gateway,
+                // which is shut down by our parent
+                msg.respond(Ok(RequestToChildResponse::Shutdown))?;
+                Ok(())
+            }
+            RequestToChild::Close { .. } => {
+                // a route has no connection of its own to close
+                msg.respond(Ok(RequestToChildResponse::Close))?;
+                Ok(())
+            }
+            RequestToChild::SetTransportId { .. } => {
+                // routes are identified by an agent, not by a TransportId
+                msg.respond(Err("a route has no TransportId".into()))?;
+                Ok(())
+            }
+            request => self.handle_route_forward(msg, request),
         }
     }
 
-    /// private handler for Bind requests from a route
-    fn handle_route_bind(
+    /// private handler for Bind and GetStats requests from a route,
+    /// which are answered by our inner_gateway as is
+    fn handle_route_forward(
         &mut self,
         msg: GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, TransportError>,
-        spec: Lib3hUri,
+        request: RequestToChild,
     ) -> Lib3hResult<()> {
-        // forward the bind to our inner_gateway
+        // forward the request to our inner_gateway
         self.inner_gateway.as_mut().request(
             Span::fixme(),
-            GatewayRequestToChild::Transport(RequestToChild::Bind { spec }),
+            GatewayRequestToChild::Transport(request),
             Box::new(|_, response| {
                 let response = {
                     match response {
@@ -47,7 +47,8 @@ macro_rules! wait_for_bind_result {
                 spec: expected_transport_address.clone(),
             };
             let re = format!(
-                "Response\\(Ok\\(Bind\\(BindResultData \\{{ bound_url: Lib3hUri\\(\"{:?}\"\\) \\}}\\)\\)\\)",
+                "Response\\(Ok\\(Bind\\(BindResultData \\{{ bound_url: Lib3hUri\\(\"{}\"\\), bound_urls: \\[Lib3hUri\\(\"{}\"\\)\\] \\}}\\)\\)\\)",
+                expected_transport_address.to_string(),
                 expected_transport_address.to_string()
             );
 
@@ -1,6 +1,7 @@
 use crate::transport::{
     error::{TransportError, TransportResult},
     protocol::*,
+    stats::TransportStats,
     websocket::{
         streams::{ConnectionStatus, StreamEvent, StreamManager},
         tls::TlsConfig,
@@ -25,6 +26,13 @@ use lib3h_protocol::{
 
 // Use mDNS for bootstrapping
 use lib3h_mdns::{MulticastDns, MulticastDnsBuilder};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// How long a Shutdown waits for pending messages to be sent
+const SHUTDOWN_FLUSH_TIMEOUT_MS: u64 = 2000;
 
 pub type Message =
     GhostMessage<RequestToChild, RequestToParent, RequestToChildResponse, TransportError>;
@@ -37,31 +45,40 @@ pub struct GhostTransportWebsocket {
     endpoint_self: Detach<GhostTransportWebsocketEndpointContext>,
     streams: StreamManager<std::net::TcpStream>,
     bound_url: Option<Lib3hUri>,
+    bound_urls: Vec<Lib3hUri>,
     pending: Vec<Message>,
     mdns: Option<MulticastDns>,
+    stats: TransportStats,
+    /// Shutdown request waiting for `pending` to be flushed, and when we stop waiting
+    shutdown: Option<(Message, Instant)>,
 }
 
 // Here we just need to use mDNS, but use it only once, with advertise probably, and that's all.
 impl Discovery for GhostTransportWebsocket {
     fn advertise(&mut self) -> DiscoveryResult<()> {
-        // Lazily instantiate mDNS
-        if self.mdns.is_none() {
-            let uri: url::Url = self
-                .bound_url
-                .clone()
-                .ok_or_else(|| DiscoveryError::new_other("Must bind URL before advertising."))?
-                .into();
-
-            // let uri: url::Url = uri.into();
-
-            let netid: String = self.network_id_address.clone().into();
-
-            let mut mdns = MulticastDnsBuilder::new()
-                .own_record(&netid, &[&uri.clone().into_string()])
-                .build()?;
-            mdns.insert_record(&netid, &[&uri.into_string()]);
+        if self.bound_urls.is_empty() {
+            return Err(DiscoveryError::new_other(
+                "Must bind URL before advertising.",
+            ));
+        }
+        let netid: String = self.network_id_address.clone().into();
+        let urls: Vec<String> = self.bound_urls.iter().map(|uri| uri.to_string()).collect();
+        let urls: Vec<&str> = urls.iter().map(|uri| uri.as_str()).collect();
 
-            self.mdns = Some(mdns);
+        match &mut self.mdns {
+            // Lazily instantiate mDNS
+            None => {
+                let mut mdns = MulticastDnsBuilder::new()
+                    .own_record(&netid, &urls)
+                    .build()?;
+                mdns.insert_record(&netid, &urls);
+                self.mdns = Some(mdns);
+            }
+            // We got bound to more urls since
+            Some(mdns) => {
+                mdns.insert_own_record(&netid, &urls);
+                mdns.insert_record(&netid, &urls);
+            }
         }
 
         match &mut self.mdns {
@@ -135,8 +152,11 @@ impl GhostTransportWebsocket {
             ),
             streams: StreamManager::with_std_tcp_stream(tls_config),
             bound_url: None,
+            bound_urls: Vec::new(),
             pending: Vec::new(),
             mdns: None,
+            stats: TransportStats::new(),
+            shutdown: None,
         }
     }
 
@@ -144,6 +164,11 @@ impl GhostTransportWebsocket {
         self.bound_url.clone()
     }
 
+    /// All the urls we listen on
+    pub fn bound_urls(&self) -> Vec<Lib3hUri> {
+        self.bound_urls.clone()
+    }
+
     /// Actually sends the message via an existing stream.
     /// Assumptions:
     /// * msg is a RequestToChild::SendMessage
@@ -156,6 +181,19 @@ impl GhostTransportWebsocket {
             .expect("GhostMessage must have inner RequestToChild")
         {
             RequestToChild::SendMessage { uri, payload, .. } => {
+                let (conn_uri, expected_id) = split_expected_id(&uri);
+                if let (Some(expected_id), Some(verified_id)) =
+                    (expected_id, self.streams.verified_id(&conn_uri))
+                {
+                    if expected_id != verified_id {
+                        self.stats.error(&conn_uri);
+                        let _ = msg.respond(Err(TransportError::new(format!(
+                            "{} is pinned to TransportId {}, expected {}",
+                            conn_uri, verified_id, expected_id
+                        ))));
+                        return Ok(());
+                    }
+                }
                 match self.bound_url.clone() {
                     None => {
                         let _ = msg.respond(Err(TransportError::new(
@@ -167,13 +205,14 @@ impl GhostTransportWebsocket {
                         trace!(
                             "(GhostTransportWebsocket).SendMessage from {} to  {} | {:?}",
                             my_addr,
-                            uri,
+                            conn_uri,
                             payload
                         );
                         let bytes = payload.as_bytes();
                         // Send it data from us
-                        if let Err(error) = self.streams.send(&uri, &bytes) {
+                        if let Err(error) = self.streams.send(&conn_uri, &bytes) {
                             trace!("Error during StreamManager::send: {:?}", error);
+                            self.stats.error(&conn_uri);
                             // In case of an error we reconstruct the GhostMessage and return
                             // it as error so the calling context can put it back into the pending
                             // list to try again later.
@@ -181,6 +220,7 @@ impl GhostTransportWebsocket {
                             msg.put_message(RequestToChild::create_send_message(uri, payload));
                             Err(msg)
                         } else {
+                            self.stats.sent(&conn_uri, bytes.len());
                             let _ = msg.respond(Ok(RequestToChildResponse::SendMessageSuccess));
                             Ok(())
                         }
@@ -196,46 +236,57 @@ impl GhostTransportWebsocket {
     fn process_actor_inbox(&mut self) -> TransportResult<()> {
         for mut msg in self.endpoint_self.drain_messages() {
             match msg.take_message().expect("exist") {
-                RequestToChild::Bind { spec: url } => {
-                    let maybe_bound_url = self.streams.bind(&url);
-                    msg.respond(maybe_bound_url.clone().map(|url| {
-                        RequestToChildResponse::Bind(BindResultData {
-                            bound_url: url.into(),
-                        })
-                    }))?;
-
-                    if let Ok(url) = maybe_bound_url {
-                        trace!("Websocket binding to: {}", url);
-                        self.bound_url = Some(url.clone().into());
+                RequestToChild::Bind { spec: url } => match self.streams.bind(&url) {
+                    Err(e) => msg.respond(Err(e))?,
+                    Ok(urls) => {
+                        trace!("Websocket bound {} to: {:?}", url, urls);
+                        let urls: Vec<Lib3hUri> = urls.into_iter().map(Lib3hUri::from).collect();
+                        let bound_url = urls.first().cloned().unwrap_or_else(|| url.clone());
+                        // additional bindings don't change our primary address
+                        if self.bound_url.is_none() {
+                            self.bound_url = Some(bound_url.clone());
+                        }
+                        self.bound_urls.extend(urls);
+                        msg.respond(Ok(RequestToChildResponse::Bind(BindResultData {
+                            bound_url,
+                            bound_urls: self.bound_urls.clone(),
+                        })))?;
                         self.advertise()
                             .map_err(|e| TransportError::from(e.to_string()))?;
                     }
-                }
+                },
                 RequestToChild::SendMessage { uri, payload, .. } => {
+                    let (conn_uri, expected_id) = split_expected_id(&uri);
                     // make sure we have bound and got our address
                     if self.bound_url.is_none() {
                         msg.respond(Err(TransportError::new(
                             "Transport must be bound before sending".to_string(),
                         )))?;
+                    } else if self.shutdown.is_some() {
+                        msg.respond(Err(TransportError::new(
+                            "Transport is shutting down".to_string(),
+                        )))?;
                     } else {
                         // Trying to find established connection for URI:
-                        match self.streams.connection_status(&uri) {
+                        match self.streams.connection_status(&conn_uri) {
                             ConnectionStatus::None => {
                                 // If there is none, try to connect:
                                 trace!(
                                     "No open connection to {} found when sending data. Trying to connect...",
-                                    uri,
+                                    conn_uri,
                                 );
-                                match self.streams.connect(&uri) {
+                                match self.streams.connect(&conn_uri, expected_id) {
                                     Ok(()) => {
-                                        trace!("New connection to {} initialized", uri.to_string())
+                                        trace!("New connection to {} initialized", conn_uri);
+                                        self.stats.connecting(&conn_uri);
                                     }
                                     Err(error) => {
                                         trace!(
                                             "Could not connect to {}! Transport error: {:?}",
-                                            uri.to_string(),
+                                            conn_uri,
                                             error
                                         );
+                                        self.stats.error(&conn_uri);
                                     }
                                 }
 
@@ -262,6 +313,40 @@ impl GhostTransportWebsocket {
                         }
                     };
                 }
+                RequestToChild::GetStats => {
+                    msg.respond(Ok(RequestToChildResponse::GetStats(self.stats.to_data())))?;
+                }
+                RequestToChild::Shutdown => {
+                    if self.shutdown.is_some() {
+                        msg.respond(Err(TransportError::new(
+                            "Transport is already shutting down".to_string(),
+                        )))?;
+                    } else if self.bound_url.is_none() {
+                        // nothing can be queued or connected
+                        msg.respond(Ok(RequestToChildResponse::Shutdown))?;
+                    } else {
+                        let deadline = Instant::now()
+                            .checked_add(Duration::from_millis(SHUTDOWN_FLUSH_TIMEOUT_MS))
+                            .expect("can add");
+                        self.shutdown = Some((msg, deadline));
+                    }
+                }
+                RequestToChild::Close { uri } => {
+                    let result = self.streams.close(&uri);
+                    self.stats.closed(&uri);
+                    msg.respond(result.map(|()| RequestToChildResponse::Close))?;
+                }
+                RequestToChild::SetTransportId {
+                    transport_id,
+                    certificate,
+                } => {
+                    self.transport_id = transport_id;
+                    let result = match certificate {
+                        Some(certificate) => self.streams.set_certificate(certificate),
+                        None => Ok(()),
+                    };
+                    msg.respond(result.map(|()| RequestToChildResponse::SetTransportId))?;
+                }
             }
         }
         Ok(())
@@ -275,22 +360,31 @@ impl GhostTransportWebsocket {
                         "Error in GhostWebsocketTransport stream connection to {:?}: {:?}",
                         uri, error
                     );
-                    self.endpoint_self.publish(
-                        Span::fixme(),
-                        RequestToParent::ErrorOccured {
-                            uri: uri.into(),
-                            error,
-                        },
-                    )?;
+                    let uri: Lib3hUri = uri.into();
+                    self.stats.error(&uri);
+                    self.endpoint_self
+                        .publish(Span::fixme(), RequestToParent::ErrorOccured { uri, error })?;
                 }
                 StreamEvent::ConnectResult(uri_connnected, _) => {
                     trace!("StreamEvent::ConnectResult: {:?}", uri_connnected);
+                    if let Some(verified_id) = self.streams.verified_id(&uri_connnected) {
+                        self.endpoint_self.publish(
+                            Span::fixme(),
+                            RequestToParent::ConnectionVerified {
+                                uri: uri_connnected.into(),
+                                verified_id,
+                            },
+                        )?;
+                    }
                 }
-                StreamEvent::IncomingConnectionEstablished(uri) => {
+                StreamEvent::IncomingConnectionEstablished(uri, verified_id) => {
                     trace!("StreamEvent::IncomingConnectionEstablished: {:?}", uri);
                     self.endpoint_self.publish(
                         Span::fixme(),
-                        RequestToParent::IncomingConnection { uri: uri.into() },
+                        RequestToParent::IncomingConnection {
+                            uri: uri.into(),
+                            verified_id,
+                        },
                     )?;
                 }
                 StreamEvent::ReceivedData(uri, payload) => {
@@ -298,16 +392,25 @@ impl GhostTransportWebsocket {
                         "StreamEvent::ReceivedData: {:?}",
                         String::from_utf8(payload.clone())
                     );
+                    let uri: Lib3hUri = uri.into();
+                    self.stats.received(&uri, payload.len());
                     self.endpoint_self.publish(
                         Span::fixme(),
                         RequestToParent::ReceivedData {
-                            uri: uri.into(),
+                            uri,
                             payload: Opaque::from(payload),
                         },
                     )?;
                 }
                 StreamEvent::ConnectionClosed(uri) => {
                     trace!("StreamEvent::ConnectionClosed: {}", uri);
+                    let uri: Lib3hUri = uri.into();
+                    self.stats.closed(&uri);
+                    self.endpoint_self
+                        .publish(Span::fixme(), RequestToParent::ConnectionClosed { uri })?;
+                }
+                StreamEvent::RoundTrip(uri, rtt) => {
+                    self.stats.round_trip(&uri.into(), rtt.as_millis() as u64);
                 }
             }
         }
@@ -316,19 +419,23 @@ impl GhostTransportWebsocket {
 
     fn process_pending_messages(&mut self) -> TransportResult<()> {
         let mut temp = Vec::new();
+        let mut queue_depths = HashMap::new();
         while let Some(mut msg) = self.pending.pop() {
             trace!("Processing pending message...");
             let inner_msg = msg.take_message().expect("exists");
             if let RequestToChild::SendMessage { uri, payload, .. } = inner_msg {
-                if self.streams.connection_status(&uri) == ConnectionStatus::Ready {
+                let (conn_uri, _) = split_expected_id(&uri);
+                if self.streams.connection_status(&conn_uri) == ConnectionStatus::Ready {
                     trace!("Sending pending message to: {:?}", uri);
                     msg.put_message(RequestToChild::create_send_message(uri, payload));
                     if let Err(msg) = self.handle_send_message(msg) {
                         trace!("Error while sending message, putting it back in pending list");
+                        *queue_depths.entry(conn_uri).or_insert(0) += 1;
                         temp.push(msg);
                     }
                 } else {
                     msg.put_message(RequestToChild::create_send_message(uri, payload));
+                    *queue_depths.entry(conn_uri).or_insert(0) += 1;
                     temp.push(msg);
                 }
             } else {
@@ -336,6 +443,40 @@ impl GhostTransportWebsocket {
             }
         }
         self.pending = temp;
+        self.stats.set_queue_depths(queue_depths);
+        Ok(())
+    }
+
+    /// Once `pending` is flushed, or we stopped waiting for it,
+    /// close every connection, stop listening and answer the Shutdown request
+    fn process_shutdown(&mut self) -> TransportResult<()> {
+        let msg = match self.shutdown.take() {
+            None => return Ok(()),
+            Some((msg, deadline)) => {
+                if !self.pending.is_empty() && Instant::now() < deadline {
+                    self.shutdown = Some((msg, deadline));
+                    return Ok(());
+                }
+                msg
+            }
+        };
+        for msg in self.pending.drain(..) {
+            msg.respond(Err(TransportError::new(
+                "Transport shut down before sending".to_string(),
+            )))?;
+        }
+        if let Err(e) = self.streams.close_all() {
+            warn!("Error closing streams on shutdown: {:?}", e);
+        }
+        self.streams.unbind_all();
+        self.stats.closed_all();
+        if let Err(e) = self.release() {
+            warn!("Error releasing mDNS on shutdown: {:?}", e);
+        }
+        self.mdns = None;
+        self.bound_url = None;
+        self.bound_urls.clear();
+        msg.respond(Ok(RequestToChildResponse::Shutdown))?;
         Ok(())
     }
 }
@@ -401,17 +542,31 @@ impl
         let (did_work, stream_events) = self.streams.process()?;
         self.process_stream_events(stream_events)?;
         self.process_pending_messages()?;
+        self.process_shutdown()?;
 
         Ok(did_work.into())
     }
 }
 
+/// The uri of our connection to `uri`, and the TransportId the remote
+/// must prove, which the network gateway passes along as its `?a=` id
+fn split_expected_id(uri: &Lib3hUri) -> (Lib3hUri, Option<Address>) {
+    let mut conn_uri = uri.clone();
+    conn_uri.clear_agent_id();
+    (conn_uri, uri.agent_id())
+}
+
 #[cfg(test)]
 mod tests {
 
     use super::*;
     use crate::{
-        tests::enable_logging_for_test, transport::websocket::tls::TlsConfig, wait_for_bind_result,
+        tests::enable_logging_for_test,
+        transport::websocket::tls::{
+            tests::{ephemeral_certificate, forged_certificate},
+            TlsConfig,
+        },
+        wait_for_bind_result,
     };
     use lib3h_ghost_actor::{wait1_for_callback, wait_for_message, wait_until_no_work};
     use std::net::TcpListener;
@@ -512,6 +667,372 @@ mod tests {
             None,
             "ReceivedData \\{ uri: Lib3hUri\\(\"wss://127\\.0\\.0\\.1:\\d+/\"\\), payload: \"test message\" \\}"
         );
+
+        // both ends counted the message
+        wait1_for_callback!(
+            transport1,
+            t1_endpoint,
+            RequestToChild::GetStats,
+            "total: TrafficStatsData \\{ bytes_sent: 12, bytes_received: 0, messages_sent: 1, "
+        );
+        wait1_for_callback!(
+            transport2,
+            t2_endpoint,
+            RequestToChild::GetStats,
+            "total: TrafficStatsData \\{ bytes_sent: 0, bytes_received: 12, messages_sent: 0, messages_received: 1, "
+        );
+    }
+
+    /// Bind `spec` and return all the urls reported in the BindResultData
+    fn bind_urls(
+        transport: &mut GhostTransportWebsocket,
+        endpoint: &mut GhostTransportWebsocketEndpointContextParent<Vec<Lib3hUri>>,
+        spec: &str,
+    ) -> Vec<Lib3hUri> {
+        endpoint
+            .request(
+                Span::fixme(),
+                RequestToChild::Bind {
+                    spec: Url::parse(spec).unwrap().into(),
+                },
+                Box::new(|bound_urls: &mut Vec<Lib3hUri>, r| {
+                    if let GhostCallbackData::Response(Ok(RequestToChildResponse::Bind(data))) = r {
+                        *bound_urls = data.bound_urls;
+                    }
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        let mut bound_urls = Vec::new();
+        transport.process().unwrap();
+        endpoint.process(&mut bound_urls).unwrap();
+        bound_urls
+    }
+
+    #[test]
+    fn test_websocket_transport_ipv6_and_multiple_interfaces() {
+        let networkid_address: Address = "wss-ipv6-network-id.holo.host".into();
+
+        let mut transport1 = GhostTransportWebsocket::new(
+            "fake_machine_id1".into(),
+            TlsConfig::Unencrypted,
+            networkid_address.clone(),
+        );
+        let mut t1_endpoint = transport1
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("twss_to_child1")
+            .build::<Vec<Lib3hUri>>();
+
+        let mut transport2 = GhostTransportWebsocket::new(
+            "fake_machine_id2".into(),
+            TlsConfig::Unencrypted,
+            networkid_address.clone(),
+        );
+        let mut t2_endpoint = transport2
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("twss_to_child2")
+            .build::<Vec<Lib3hUri>>();
+
+        // transport1 listens on both loopback interfaces
+        let v6_urls = bind_urls(&mut transport1, &mut t1_endpoint, "wss://[::1]:0");
+        assert_eq!(1, v6_urls.len());
+        assert_eq!(Some("[::1]".to_string()), v6_urls[0].hostname());
+        assert_ne!(Some(0), v6_urls[0].port());
+        let all_urls = bind_urls(&mut transport1, &mut t1_endpoint, "wss://127.0.0.1:0");
+        assert_eq!(2, all_urls.len());
+        assert_eq!(v6_urls[0], all_urls[0]);
+        assert_eq!(all_urls, transport1.bound_urls());
+        assert_eq!(Some(v6_urls[0].clone()), transport1.bound_url());
+
+        bind_urls(&mut transport2, &mut t2_endpoint, "wss://[::1]:0");
+
+        // transport1 is reachable on each of them
+        let mut user_data = Vec::new();
+        for (url, expected_from) in all_urls
+            .into_iter()
+            .zip(vec!["\\[::1\\]", "127\\.0\\.0\\.1"])
+        {
+            t2_endpoint
+                .publish(
+                    Span::fixme(),
+                    RequestToChild::create_send_message(url, b"test message".to_vec().into()),
+                )
+                .unwrap();
+            let expected = format!(
+                "ReceivedData \\{{ uri: Lib3hUri\\(\"wss://{}:\\d+/\"\\), payload: \"test message\" \\}}",
+                expected_from
+            );
+            wait_for_message!(
+                vec![&mut transport1, &mut transport2],
+                t1_endpoint,
+                user_data,
+                &expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_websocket_transport_pinned_certificates() {
+        let networkid_address: Address = "wss-pinning-network-id.holo.host".into();
+        let crypto = lib3h_sodium::SodiumCryptoSystem::new();
+
+        let (machine_id1, cert1) = ephemeral_certificate(&crypto);
+        let mut transport1 = GhostTransportWebsocket::new(
+            machine_id1.clone(),
+            TlsConfig::PinnedCertificate {
+                certificate: cert1,
+                require_client_cert: false,
+            },
+            networkid_address.clone(),
+        );
+        let mut t1_endpoint: GhostTransportWebsocketEndpointContextParent<Option<String>> =
+            transport1
+                .take_parent_endpoint()
+                .expect("exists")
+                .as_context_endpoint_builder()
+                .request_id_prefix("twss_to_child1")
+                .build::<Option<String>>();
+
+        let (machine_id2, cert2) = ephemeral_certificate(&crypto);
+        let mut transport2 = GhostTransportWebsocket::new(
+            machine_id2.clone(),
+            TlsConfig::PinnedCertificate {
+                certificate: cert2,
+                require_client_cert: false,
+            },
+            networkid_address.clone(),
+        );
+        let mut t2_endpoint = transport2
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("twss_to_child2")
+            .build::<Option<String>>();
+
+        // a server whose certificate binding does not verify must be refused
+        let mut transport3 = GhostTransportWebsocket::new(
+            "fake_machine_id3".into(),
+            TlsConfig::PinnedCertificate {
+                certificate: forged_certificate(&crypto),
+                require_client_cert: false,
+            },
+            networkid_address.clone(),
+        );
+        let mut t3_endpoint = transport3
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("twss_to_child3")
+            .build::<Option<String>>();
+
+        let init_address: Lib3hUri = lib3h_protocol::uri::Builder::with_raw_url("wss://127.0.0.1/")
+            .unwrap()
+            .with_port(4024)
+            .build();
+        let (_is_match, _address1) =
+            wait_for_bind_result!(transport1, t1_endpoint, init_address.clone());
+        let (_is_match, address2) =
+            wait_for_bind_result!(transport2, t2_endpoint, init_address.clone());
+        let (_is_match, address3) = wait_for_bind_result!(transport3, t3_endpoint, init_address);
+
+        // a server proving another TransportId than the one we connect to must be refused
+        let mut wrong_address2 = address2.clone();
+        wrong_address2.set_agent_id(&machine_id1);
+        t1_endpoint
+            .request(
+                Span::fixme(),
+                RequestToChild::create_send_message(wrong_address2, b"wrong peer".to_vec().into()),
+                // never sent, even once we are connected to the right TransportId
+                Box::new(|_: &mut _, r| {
+                    assert!(format!("{:?}", r).contains("expected"));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t1_endpoint,
+            None,
+            "ErrorOccured .*expected"
+        );
+
+        let mut address2 = address2;
+        address2.set_agent_id(&machine_id2);
+        t1_endpoint
+            .request(
+                Span::fixme(),
+                RequestToChild::create_send_message(address2, b"test message".to_vec().into()),
+                Box::new(|_: &mut _, r| {
+                    assert_eq!("Response(Ok(SendMessageSuccess))", &format!("{:?}", r));
+                    Ok(())
+                }),
+            )
+            .unwrap();
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t2_endpoint,
+            None,
+            "ReceivedData \\{ uri: Lib3hUri\\(\"wss://127\\.0\\.0\\.1:\\d+/\"\\), payload: \"test message\" \\}"
+        );
+
+        t1_endpoint
+            .publish(
+                Span::fixme(),
+                RequestToChild::create_send_message(address3, b"test message".to_vec().into()),
+            )
+            .unwrap();
+        wait_for_message!(
+            vec![&mut transport1, &mut transport3],
+            t1_endpoint,
+            None,
+            "ErrorOccured .*certificate is not bound to TransportId"
+        );
+    }
+
+    #[test]
+    fn test_websocket_transport_mutual_tls() {
+        let networkid_address: Address = "wss-mutual-tls-network-id.holo.host".into();
+        let crypto = lib3h_sodium::SodiumCryptoSystem::new();
+
+        // server requiring client certificates
+        let (machine_id1, cert1) = ephemeral_certificate(&crypto);
+        let mut transport1 = GhostTransportWebsocket::new(
+            machine_id1.clone(),
+            TlsConfig::PinnedCertificate {
+                certificate: cert1,
+                require_client_cert: true,
+            },
+            networkid_address.clone(),
+        );
+        let mut t1_endpoint: GhostTransportWebsocketEndpointContextParent<Option<String>> =
+            transport1
+                .take_parent_endpoint()
+                .expect("exists")
+                .as_context_endpoint_builder()
+                .request_id_prefix("twss_to_child1")
+                .build::<Option<String>>();
+
+        // client presenting its pinned certificate
+        let (machine_id2, cert2) = ephemeral_certificate(&crypto);
+        let mut transport2 = GhostTransportWebsocket::new(
+            machine_id2.clone(),
+            TlsConfig::PinnedCertificate {
+                certificate: cert2,
+                require_client_cert: false,
+            },
+            networkid_address.clone(),
+        );
+        let mut t2_endpoint = transport2
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("twss_to_child2")
+            .build::<Option<String>>();
+
+        // client without a certificate
+        let mut transport3 = GhostTransportWebsocket::new(
+            "fake_machine_id3".into(),
+            TlsConfig::FakeServer,
+            networkid_address.clone(),
+        );
+        let mut t3_endpoint = transport3
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("twss_to_child3")
+            .build::<Option<String>>();
+
+        let init_address: Lib3hUri = lib3h_protocol::uri::Builder::with_raw_url("wss://127.0.0.1/")
+            .unwrap()
+            .with_port(4124)
+            .build();
+        let (_is_match, address1) =
+            wait_for_bind_result!(transport1, t1_endpoint, init_address.clone());
+        let (_is_match, _address2) =
+            wait_for_bind_result!(transport2, t2_endpoint, init_address.clone());
+        let (_is_match, _address3) =
+            wait_for_bind_result!(transport3, t3_endpoint, init_address.clone());
+
+        t2_endpoint
+            .publish(
+                Span::fixme(),
+                RequestToChild::create_send_message(
+                    address1.clone(),
+                    b"test message".to_vec().into(),
+                ),
+            )
+            .unwrap();
+        let expected = format!(
+            "IncomingConnection \\{{ uri: Lib3hUri\\(\"wss://127\\.0\\.0\\.1:\\d+/\"\\), verified_id: Some\\(HashString\\(\"{}\"\\)\\) \\}}",
+            machine_id2
+        );
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t1_endpoint,
+            None,
+            &expected
+        );
+        // and the client learns the server's TransportId
+        let expected = format!(
+            "ConnectionVerified \\{{ uri: Lib3hUri\\(\"wss://127\\.0\\.0\\.1:\\d+/\"\\), verified_id: HashString\\(\"{}\"\\) \\}}",
+            machine_id1
+        );
+        wait_for_message!(
+            vec![&mut transport1, &mut transport2],
+            t2_endpoint,
+            None,
+            &expected
+        );
+
+        t3_endpoint
+            .publish(
+                Span::fixme(),
+                RequestToChild::create_send_message(
+                    address1.clone(),
+                    b"test message".to_vec().into(),
+                ),
+            )
+            .unwrap();
+        wait_for_message!(
+            vec![&mut transport1, &mut transport3],
+            t1_endpoint,
+            None,
+            "ErrorOccured"
+        );
+
+        // client presenting a certificate whose binding does not verify
+        let mut transport4 = GhostTransportWebsocket::new(
+            "fake_machine_id4".into(),
+            TlsConfig::PinnedCertificate {
+                certificate: forged_certificate(&crypto),
+                require_client_cert: false,
+            },
+            networkid_address.clone(),
+        );
+        let mut t4_endpoint = transport4
+            .take_parent_endpoint()
+            .expect("exists")
+            .as_context_endpoint_builder()
+            .request_id_prefix("twss_to_child4")
+            .build::<Option<String>>();
+        let (_is_match, _address4) = wait_for_bind_result!(transport4, t4_endpoint, init_address);
+        t4_endpoint
+            .publish(
+                Span::fixme(),
+                RequestToChild::create_send_message(address1, b"test message".to_vec().into()),
+            )
+            .unwrap();
+        // refused by the tls handshake itself
+        wait_for_message!(
+            vec![&mut transport1, &mut transport4],
+            t1_endpoint,
+            None,
+            "ErrorOccured .*certificate verify failed"
+        );
     }
 
     #[test]
@@ -675,7 +1196,7 @@ mod tests {
                     // parent should see the bind event
                     assert_eq!(
                         format!(
-                            "Response(Ok(Bind(BindResultData {{ bound_url: Lib3hUri(\"wss://127.0.0.1:{}/\") }})))",
+                            "Response(Ok(Bind(BindResultData {{ bound_url: Lib3hUri(\"wss://127.0.0.1:{0}/\"), bound_urls: [Lib3hUri(\"wss://127.0.0.1:{0}/\")] }})))",
                             port1.clone(),
                         ),
                         format!("{:?}", r)
@@ -700,7 +1221,7 @@ mod tests {
                     // parent should see the bind event
                     assert_eq!(
                         &format!(
-                            "Response(Ok(Bind(BindResultData {{ bound_url: Lib3hUri(\"wss://127.0.0.1:{}/\") }})))",
+                            "Response(Ok(Bind(BindResultData {{ bound_url: Lib3hUri(\"wss://127.0.0.1:{0}/\"), bound_urls: [Lib3hUri(\"wss://127.0.0.1:{0}/\")] }})))",
                             port2.clone(),
                         ),
                         &format!("{:?}", r)
@@ -19,7 +19,7 @@ static FAKE_PASS: &'static str = "hello";
 
 // -- some internal types for readability -- //
 
-type TlsConnectResult<T> = Result<TlsStream<T>, native_tls::HandshakeError<T>>;
+type TlsConnectResult<T> = Result<TlsStream<T>, openssl::ssl::HandshakeError<T>>;
 type WsHandshakeError<T> =
     tungstenite::handshake::HandshakeError<tungstenite::handshake::client::ClientHandshake<T>>;
 type WsConnectResult<T> =
@@ -40,11 +40,11 @@ type WssSrvHandshakeError<T> = tungstenite::handshake::HandshakeError<
     >,
 >;
 type WssSrvAcceptResult<T> = Result<WssStream<T>, WssSrvHandshakeError<T>>;
-type TlsMidHandshake<T> = native_tls::MidHandshakeTlsStream<BaseStream<T>>;
+type TlsMidHandshake<T> = openssl::ssl::MidHandshakeSslStream<BaseStream<T>>;
 
 type BaseStream<T> = T;
-type TlsSrvMidHandshake<T> = native_tls::MidHandshakeTlsStream<BaseStream<T>>;
-type TlsStream<T> = native_tls::TlsStream<BaseStream<T>>;
+type TlsSrvMidHandshake<T> = openssl::ssl::MidHandshakeSslStream<BaseStream<T>>;
+type TlsStream<T> = openssl::ssl::SslStream<BaseStream<T>>;
 type WsMidHandshake<T> = tungstenite::handshake::MidHandshake<tungstenite::ClientHandshake<T>>;
 type WsSrvMidHandshake<T> = tungstenite::handshake::MidHandshake<
     tungstenite::ServerHandshake<T, tungstenite::handshake::server::NoCallback>,
@@ -1,13 +1,19 @@
 use crate::transport::{
     error::{TransportError, TransportResult},
     websocket::{
-        tls::TlsConfig, wss_info::WssInfo, BaseStream, SocketMap, TlsConnectResult,
-        TlsMidHandshake, TlsSrvMidHandshake, TlsStream, WsConnectResult, WsMidHandshake,
-        WsSrvAcceptResult, WsSrvMidHandshake, WsStream, WssConnectResult, WssMidHandshake,
-        WssSrvAcceptResult, WssSrvMidHandshake, WssStream, FAKE_PASS, FAKE_PKCS12,
+        tls::{verify_pinned_certificate, TlsCertificate, TlsConfig},
+        wss_info::WssInfo,
+        BaseStream, SocketMap, TlsConnectResult, TlsMidHandshake, TlsSrvMidHandshake, TlsStream,
+        WsConnectResult, WsMidHandshake, WsSrvAcceptResult, WsSrvMidHandshake, WsStream,
+        WssConnectResult, WssMidHandshake, WssSrvAcceptResult, WssSrvMidHandshake, WssStream,
+        FAKE_PASS, FAKE_PKCS12,
     },
 };
-use lib3h_protocol::{uri::Lib3hUri, DidWork};
+use lib3h_protocol::{uri::Lib3hUri, Address, DidWork};
+use openssl::{
+    pkcs12::{ParsedPkcs12_2, Pkcs12},
+    ssl::{HandshakeError, SslAcceptor, SslConnector, SslContextBuilder, SslMethod, SslVerifyMode},
+};
 use std::{
     io::{Read, Write},
     sync::{Arc, Mutex},
@@ -54,12 +60,15 @@ pub enum StreamEvent {
     ErrorOccured(Url, TransportError),
     /// an outgoing connection has been established
     ConnectResult(Url, String),
-    /// we have received an incoming connection
-    IncomingConnectionEstablished(Url),
+    /// we have received an incoming connection,
+    /// with the TransportId proven by its client certificate, if any
+    IncomingConnectionEstablished(Url, Option<Address>),
     /// We have received data from a connection
     ReceivedData(Url, Vec<u8>),
     /// A connection closed for whatever reason
     ConnectionClosed(Url),
+    /// A heartbeat ping was answered after this long
+    RoundTrip(Url, std::time::Duration),
 }
 
 /// A factory callback for generating base streams of type T
@@ -72,8 +81,9 @@ lazy_static! {
 /// A function that produces accepted sockets of type R wrapped in a TransportInfo
 pub type Acceptor<T> = Box<dyn FnMut() -> TransportResult<WssInfo<T>>>;
 
-/// A function that binds to a url and produces sockt acceptors of type T
-pub type Bind<T> = Box<dyn FnMut(&Url) -> TransportResult<Acceptor<T>>>;
+/// A function that binds to a url and produces sockt acceptors of type T,
+/// along with the urls the new listener can be reached at
+pub type Bind<T> = Box<dyn FnMut(&Url) -> TransportResult<(Acceptor<T>, Vec<Url>)>>;
 
 /// A "Transport" implementation based off the websocket protocol
 /// any rust io Read/Write stream should be able to serve as the base
@@ -83,7 +93,7 @@ pub struct StreamManager<T: Read + Write + std::fmt::Debug> {
     stream_sockets: SocketMap<T>,
     event_queue: Vec<StreamEvent>,
     bind: Bind<T>,
-    acceptor: TransportResult<Acceptor<T>>,
+    acceptors: Vec<Acceptor<T>>,
 }
 
 impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
@@ -94,12 +104,13 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             stream_sockets: std::collections::HashMap::new(),
             event_queue: Vec::new(),
             bind,
-            acceptor: Err(TransportError::new("acceptor not initialized".into())),
+            acceptors: Vec::new(),
         }
     }
 
-    /// connect to a remote websocket service
-    pub fn connect(&mut self, uri: &Url) -> TransportResult<()> {
+    /// connect to a remote websocket service,
+    /// whose pinned certificate must prove `expected_id` if given
+    pub fn connect(&mut self, uri: &Url, expected_id: Option<Address>) -> TransportResult<()> {
         let host_port = format!(
             "{}:{}",
             uri.host_str()
@@ -108,14 +119,31 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                 .ok_or_else(|| TransportError::new("bad connect port".into()))?,
         );
         let socket = (self.stream_factory)(&host_port)?;
-        let info = WssInfo::client(uri.clone(), socket);
+        let mut info = WssInfo::client(uri.clone(), socket);
+        info.expected_id = expected_id;
         self.stream_sockets.insert(uri.clone().into(), info);
         Ok(())
     }
 
+    /// Present `certificate` to the connections we make or accept from now on,
+    /// in place of our pinned certificate
+    pub fn set_certificate(&mut self, certificate: TlsCertificate) -> TransportResult<()> {
+        match &mut self.tls_config {
+            TlsConfig::PinnedCertificate {
+                certificate: current,
+                ..
+            } => {
+                *current = certificate;
+                Ok(())
+            }
+            _ => Err(TransportError::new(
+                "only a pinned certificate can be replaced".into(),
+            )),
+        }
+    }
+
     /// close a currently tracked connection
-    #[allow(dead_code)]
-    fn close(&mut self, uri: &Url) -> TransportResult<()> {
+    pub fn close(&mut self, uri: &Url) -> TransportResult<()> {
         if let Some(mut info) = self.stream_sockets.remove(uri) {
             info.close()?;
         }
@@ -123,7 +151,6 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
     }
 
     /// close all currently tracked connections
-    #[allow(dead_code)]
     pub fn close_all(&mut self) -> TransportResult<()> {
         let mut errors: Vec<TransportError> = Vec::new();
 
@@ -188,12 +215,24 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
         })
     }
 
-    pub fn bind(&mut self, url: &Url) -> TransportResult<Url> {
-        let acceptor = (self.bind)(&url.clone());
-        acceptor.map(|acceptor| {
-            self.acceptor = Ok(acceptor);
-            url.clone()
-        })
+    /// Start listening on `url`, in addition to any previous bindings.
+    /// Returns the urls this listener can be reached at.
+    pub fn bind(&mut self, url: &Url) -> TransportResult<Vec<Url>> {
+        let (acceptor, bound_urls) = (self.bind)(&url.clone())?;
+        self.acceptors.push(acceptor);
+        Ok(bound_urls)
+    }
+
+    /// Stop listening on every url we were bound to
+    pub fn unbind_all(&mut self) {
+        self.acceptors.clear();
+    }
+
+    /// the TransportId proven by the remote of this connection, if any
+    pub fn verified_id(&self, url: &Url) -> Option<Address> {
+        self.stream_sockets
+            .get(url)
+            .and_then(|info| info.verified_id.clone())
     }
 
     pub fn connection_status(&self, url: &Url) -> ConnectionStatus {
@@ -212,27 +251,25 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
     // -- private -- //
 
     fn priv_process_accept(&mut self) -> DidWork {
-        match &mut self.acceptor {
-            Err(err) => {
-                warn!("acceptor in error state: {:?}", err);
-                false
-            }
-            Ok(acceptor) => (acceptor)()
-                .map(move |wss_info| {
+        let mut did_work = false;
+        for acceptor in self.acceptors.iter_mut() {
+            match (acceptor)() {
+                Ok(wss_info) => {
                     let _insert_result = self
                         .stream_sockets
                         .insert(wss_info.url.clone().into(), wss_info);
-                    true
-                })
-                .unwrap_or_else(|err| {
+                    did_work = true;
+                }
+                Err(err) => {
                     if !err.is_ignorable() {
                         // TODO: handle these actual errors, and probably this is where the unbinding
                         // would be detectable.
                         panic!("Error when attempting to accept connections: {:?}", err);
                     }
-                    false
-                }),
+                }
+            }
         }
+        did_work
     }
 
     // see if any work needs to be done on our stream sockets
@@ -262,6 +299,10 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                 if let WebsocketStreamState::ReadyWs(socket) = &mut info.stateful_socket {
                     socket.write_message(tungstenite::Message::Ping(vec![]))?;
                 }
+                // time the round trip from the first unanswered ping
+                if info.ping_sent.is_none() {
+                    info.ping_sent = Some(std::time::Instant::now());
+                }
             } else if info.last_msg.elapsed().as_millis() as usize > DEFAULT_HEARTBEAT_WAIT_MS {
                 self.event_queue
                     .push(StreamEvent::ConnectionClosed(info.url));
@@ -307,13 +348,17 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                         )?;
                     }
                     _ => {
-                        let connector = native_tls::TlsConnector::builder()
-                            .danger_accept_invalid_certs(true)
-                            .danger_accept_invalid_hostnames(true)
-                            .build()
-                            .expect("failed to build TlsConnector");
-                        info.stateful_socket =
-                            self.priv_tls_handshake(connector.connect(info.url.as_str(), socket))?;
+                        let connector = self
+                            .priv_tls_connector()?
+                            .configure()?
+                            .use_server_name_indication(false)
+                            .verify_hostname(false);
+                        let host = info.url.host_str().unwrap_or_default().to_string();
+                        info.stateful_socket = self.priv_tls_handshake(
+                            &info.expected_id,
+                            &mut info.verified_id,
+                            connector.connect(&host, socket),
+                        )?;
                     }
                 }
                 Ok(())
@@ -326,27 +371,22 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                         self.priv_ws_srv_handshake(&info.url, tungstenite::accept(socket))?;
                     return Ok(());
                 }
-                let ident = match &self.tls_config {
-                    TlsConfig::Unencrypted => unimplemented!(),
-                    TlsConfig::FakeServer => {
-                        native_tls::Identity::from_pkcs12(FAKE_PKCS12, FAKE_PASS)?
-                    }
-                    TlsConfig::SuppliedCertificate(cert) => {
-                        native_tls::Identity::from_pkcs12(&cert.pkcs12_data, &cert.passphrase)?
-                    }
-                };
-                let acceptor = native_tls::TlsAcceptor::builder(ident)
-                    .build()
-                    .expect("failed to build TlsAcceptor");
-                info.stateful_socket = self.priv_tls_srv_handshake(acceptor.accept(socket))?;
+                let acceptor = self.priv_tls_acceptor()?;
+                info.stateful_socket =
+                    self.priv_tls_srv_handshake(&mut info.verified_id, acceptor.accept(socket))?;
                 Ok(())
             }
             WebsocketStreamState::TlsMidHandshake(socket) => {
-                info.stateful_socket = self.priv_tls_handshake(socket.handshake())?;
+                info.stateful_socket = self.priv_tls_handshake(
+                    &info.expected_id,
+                    &mut info.verified_id,
+                    socket.handshake(),
+                )?;
                 Ok(())
             }
             WebsocketStreamState::TlsSrvMidHandshake(socket) => {
-                info.stateful_socket = self.priv_tls_srv_handshake(socket.handshake())?;
+                info.stateful_socket =
+                    self.priv_tls_srv_handshake(&mut info.verified_id, socket.handshake())?;
                 Ok(())
             }
             WebsocketStreamState::TlsReady(socket) => {
@@ -362,8 +402,11 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             WebsocketStreamState::TlsSrvReady(socket) => {
                 info.last_msg = std::time::Instant::now();
                 *did_work = true;
-                info.stateful_socket =
-                    self.priv_wss_srv_handshake(&info.url, tungstenite::accept(socket))?;
+                info.stateful_socket = self.priv_wss_srv_handshake(
+                    &info.url,
+                    &info.verified_id,
+                    tungstenite::accept(socket),
+                )?;
                 Ok(())
             }
             WebsocketStreamState::WsMidHandshake(socket) => {
@@ -382,7 +425,7 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             }
             WebsocketStreamState::WssSrvMidHandshake(socket) => {
                 info.stateful_socket =
-                    self.priv_wss_srv_handshake(&info.url, socket.handshake())?;
+                    self.priv_wss_srv_handshake(&info.url, &info.verified_id, socket.handshake())?;
                 Ok(())
             }
             WebsocketStreamState::ReadyWs(mut socket) => {
@@ -406,6 +449,15 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                         let qmsg = match msg {
                             tungstenite::Message::Text(s) => Some(s.into_bytes()),
                             tungstenite::Message::Binary(b) => Some(b),
+                            tungstenite::Message::Pong(_) => {
+                                if let Some(ping_sent) = info.ping_sent.take() {
+                                    self.event_queue.push(StreamEvent::RoundTrip(
+                                        info.url.clone(),
+                                        ping_sent.elapsed(),
+                                    ));
+                                }
+                                None
+                            }
                             _ => None,
                         };
 
@@ -439,6 +491,15 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
                         let qmsg = match msg {
                             tungstenite::Message::Text(s) => Some(s.into_bytes()),
                             tungstenite::Message::Binary(b) => Some(b),
+                            tungstenite::Message::Pong(_) => {
+                                if let Some(ping_sent) = info.ping_sent.take() {
+                                    self.event_queue.push(StreamEvent::RoundTrip(
+                                        info.url.clone(),
+                                        ping_sent.elapsed(),
+                                    ));
+                                }
+                                None
+                            }
                             _ => None,
                         };
 
@@ -454,32 +515,134 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
         }
     }
 
+    // our certificate and key, if we have one to present
+    fn priv_tls_identity(&self) -> TransportResult<Option<ParsedPkcs12_2>> {
+        match &self.tls_config {
+            TlsConfig::Unencrypted => Ok(None),
+            TlsConfig::FakeServer => Ok(Some(Pkcs12::from_der(FAKE_PKCS12)?.parse2(FAKE_PASS)?)),
+            TlsConfig::SuppliedCertificate(cert)
+            | TlsConfig::PinnedCertificate {
+                certificate: cert, ..
+            } => Ok(Some(cert.parse()?)),
+            TlsConfig::Ephemeral { .. } => Err(TransportError::new(
+                "ephemeral certificate has not been generated".into(),
+            )),
+        }
+    }
+
+    // remote certificates are self-signed, so we never verify the chain;
+    // with a pinned certificate we check the TransportId binding instead,
+    // and present our own certificate for mutual authentication
+    fn priv_tls_connector(&self) -> TransportResult<SslConnector> {
+        let mut builder = SslConnector::builder(SslMethod::tls())?;
+        builder.set_verify(SslVerifyMode::NONE);
+        if let TlsConfig::PinnedCertificate { .. } = &self.tls_config {
+            if let Some(identity) = self.priv_tls_identity()? {
+                priv_set_identity(&mut builder, identity)?;
+            }
+        }
+        Ok(builder.build())
+    }
+
+    fn priv_tls_acceptor(&self) -> TransportResult<SslAcceptor> {
+        let mut builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
+        let identity = self
+            .priv_tls_identity()?
+            .ok_or_else(|| TransportError::new("no certificate to accept tls with".into()))?;
+        priv_set_identity(&mut builder, identity)?;
+        if let TlsConfig::PinnedCertificate {
+            require_client_cert,
+            ..
+        } = &self.tls_config
+        {
+            let mut mode = SslVerifyMode::PEER;
+            if *require_client_cert {
+                mode |= SslVerifyMode::FAIL_IF_NO_PEER_CERT;
+            }
+            // client certificates are self-signed too: instead of the chain,
+            // the handshake requires a valid TransportId binding
+            builder.set_verify_callback(mode, |_preverify_ok, ctx| {
+                ctx.error_depth() > 0
+                    || ctx
+                        .current_cert()
+                        .and_then(|cert| cert.to_der().ok())
+                        .map_or(false, |der| verify_pinned_certificate(&der).is_ok())
+            });
+        }
+        Ok(builder.build())
+    }
+
     // process tls handshaking
     fn priv_tls_handshake(
         &mut self,
+        expected_id: &Option<Address>,
+        verified_id: &mut Option<Address>,
         res: TlsConnectResult<T>,
     ) -> TransportResult<WebsocketStreamState<T>> {
         match res {
-            Err(native_tls::HandshakeError::WouldBlock(socket)) => {
+            Err(HandshakeError::WouldBlock(socket)) => {
                 Ok(WebsocketStreamState::TlsMidHandshake(socket))
             }
             Err(e) => Err(e.into()),
-            Ok(socket) => Ok(WebsocketStreamState::TlsReady(socket)),
+            Ok(socket) => {
+                if let TlsConfig::PinnedCertificate { .. } = &self.tls_config {
+                    let cert = socket.ssl().peer_certificate().ok_or_else(|| {
+                        TransportError::new("remote did not present a certificate".into())
+                    })?;
+                    let transport_id = verify_pinned_certificate(&cert.to_der()?)?;
+                    if let Some(expected_id) = expected_id {
+                        if *expected_id != transport_id {
+                            return Err(TransportError::new(format!(
+                                "remote certificate is pinned to TransportId {}, expected {}",
+                                transport_id, expected_id
+                            )));
+                        }
+                    }
+                    debug!("remote certificate pinned to TransportId {}", transport_id);
+                    *verified_id = Some(transport_id);
+                }
+                Ok(WebsocketStreamState::TlsReady(socket))
+            }
         }
     }
 
     // process tls handshaking
     fn priv_tls_srv_handshake(
         &mut self,
+        verified_id: &mut Option<Address>,
         res: TlsConnectResult<T>,
     ) -> TransportResult<WebsocketStreamState<T>> {
         trace!("[t] processing tls connect result: {:?}", res);
         match res {
-            Err(native_tls::HandshakeError::WouldBlock(socket)) => {
+            Err(HandshakeError::WouldBlock(socket)) => {
                 Ok(WebsocketStreamState::TlsSrvMidHandshake(socket))
             }
             Err(e) => Err(e.into()),
-            Ok(socket) => Ok(WebsocketStreamState::TlsSrvReady(socket)),
+            Ok(socket) => {
+                if let TlsConfig::PinnedCertificate {
+                    require_client_cert,
+                    ..
+                } = &self.tls_config
+                {
+                    match socket.ssl().peer_certificate() {
+                        Some(cert) => {
+                            let transport_id = verify_pinned_certificate(&cert.to_der()?)?;
+                            debug!(
+                                "client certificate verified for TransportId {}",
+                                transport_id
+                            );
+                            *verified_id = Some(transport_id);
+                        }
+                        None if *require_client_cert => {
+                            return Err(TransportError::new(
+                                "client did not present a certificate".into(),
+                            ));
+                        }
+                        None => (),
+                    }
+                }
+                Ok(WebsocketStreamState::TlsSrvReady(socket))
+            }
         }
     }
 
@@ -540,7 +703,10 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             Err(e) => Err(e.into()),
             Ok(socket) => {
                 self.event_queue
-                    .push(StreamEvent::IncomingConnectionEstablished(url.clone()));
+                    .push(StreamEvent::IncomingConnectionEstablished(
+                        url.clone(),
+                        None,
+                    ));
                 Ok(WebsocketStreamState::ReadyWs(Box::new(socket)))
             }
         }
@@ -550,6 +716,7 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
     fn priv_wss_srv_handshake(
         &mut self,
         url: &Url,
+        verified_id: &Option<Address>,
         res: WssSrvAcceptResult<T>,
     ) -> TransportResult<WebsocketStreamState<T>> {
         match res {
@@ -559,9 +726,29 @@ impl<T: Read + Write + std::fmt::Debug> StreamManager<T> {
             Err(e) => Err(e.into()),
             Ok(socket) => {
                 self.event_queue
-                    .push(StreamEvent::IncomingConnectionEstablished(url.clone()));
+                    .push(StreamEvent::IncomingConnectionEstablished(
+                        url.clone(),
+                        verified_id.clone(),
+                    ));
                 Ok(WebsocketStreamState::ReadyWss(Box::new(socket)))
             }
         }
     }
 }
+
+// load our key and certificate chain into an ssl context
+fn priv_set_identity(
+    builder: &mut SslContextBuilder,
+    identity: ParsedPkcs12_2,
+) -> TransportResult<()> {
+    if let Some(pkey) = identity.pkey {
+        builder.set_private_key(&pkey)?;
+    }
+    if let Some(cert) = identity.cert {
+        builder.set_certificate(&cert)?;
+    }
+    for cert in identity.ca.into_iter().flatten() {
+        builder.add_extra_chain_cert(cert)?;
+    }
+    Ok(())
+}
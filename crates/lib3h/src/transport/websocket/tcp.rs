@@ -10,7 +10,8 @@ use crate::transport::{
     },
 };
 
-use std::net::{TcpListener, TcpStream};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
 
 impl StreamManager<std::net::TcpStream> {
     /// convenience constructor for creating a websocket "Transport"
@@ -28,62 +29,182 @@ impl StreamManager<std::net::TcpStream> {
         )
     }
 
-    fn tcp_bind(url: &url::Url) -> TransportResult<Acceptor<TcpStream>> {
-        // TODO return transport result rather than expect()
-        let host = url.host_str().expect("host name must be supplied");
-        let port = url.port().unwrap_or(80); // TODO default or error here?
-        let formatted_url = format!("{}:{}", host, port);
-        trace!("websocket tcp_bind with url: {}", formatted_url);
-        TcpListener::bind(formatted_url)
-            .map_err(|err| err.into())
-            .and_then(move |listener: TcpListener| {
-                listener
-                    .set_nonblocking(true)
-                    .map_err(|err| {
-                        error!("transport_wss::tcp listener error: {:?}", err);
+    fn tcp_bind(url: &url::Url) -> TransportResult<(Acceptor<TcpStream>, Vec<url::Url>)> {
+        let address = Self::tcp_bind_address(url)?;
+        trace!("websocket tcp_bind with address: {}", address);
+        let listener = Self::tcp_listen(&address)?;
+        listener.set_nonblocking(true).map_err(|err| {
+            error!("transport_wss::tcp listener error: {:?}", err);
+            TransportError::from(err)
+        })?;
+        let bound_urls = Self::tcp_bound_urls(url, &listener.local_addr()?)?;
+        let acceptor: Acceptor<TcpStream> = Box::new(move || {
+            listener
+                .accept()
+                .map_err(|err| match err.kind() {
+                    std::io::ErrorKind::WouldBlock => {
+                        TransportError::new_kind(ErrorKind::Ignore(err.to_string()))
+                    }
+                    _ => {
+                        error!("transport_wss::tcp accept error: {:?}", err);
                         err.into()
-                    })
-                    .map(|()| {
-                        let acceptor: Acceptor<TcpStream> = Box::new(move || {
-                            listener
-                                .accept()
-                                .map_err(|err| match err.kind() {
-                                    std::io::ErrorKind::WouldBlock => {
-                                        TransportError::new_kind(ErrorKind::Ignore(err.to_string()))
-                                    }
-                                    _ => {
-                                        error!("transport_wss::tcp accept error: {:?}", err);
-                                        err.into()
-                                    }
-                                })
-                                .and_then(|(tcp_stream, socket_address)| {
-                                    tcp_stream.set_nonblocking(true)?;
-                                    let v4_socket_address = format!(
-                                        "wss://{}:{}",
-                                        socket_address.ip(),
-                                        socket_address.port()
-                                    );
+                    }
+                })
+                .and_then(|(tcp_stream, socket_address)| {
+                    tcp_stream.set_nonblocking(true)?;
+                    trace!("transport_wss::tcp socket_address: {}", socket_address);
+                    url::Url::parse(&format!("wss://{}", unmap_ipv4(&socket_address)))
+                        .map(|url| {
+                            trace!("transport_wss::tcp accepted for url {}", url.clone());
+                            WssInfo::server(url, tcp_stream)
+                        })
+                        .map_err(|err| {
+                            error!("transport_wss::tcp url error: {:?}", err);
+                            err.into()
+                        })
+                })
+        });
+        Ok((acceptor, bound_urls))
+    }
+
+    /// resolve the socket address a bind spec refers to
+    fn tcp_bind_address(url: &url::Url) -> TransportResult<SocketAddr> {
+        let port = url.port().unwrap_or(80); // TODO default or error here?
+        match url.host() {
+            Some(url::Host::Ipv4(ip)) => Ok(SocketAddr::new(IpAddr::V4(ip), port)),
+            Some(url::Host::Ipv6(ip)) => Ok(SocketAddr::new(IpAddr::V6(ip), port)),
+            Some(url::Host::Domain(domain)) => (domain, port)
+                .to_socket_addrs()?
+                .next()
+                .ok_or_else(|| TransportError::new(format!("cannot resolve {}", domain))),
+            None => Err(TransportError::new(format!(
+                "host name must be supplied: {}",
+                url
+            ))),
+        }
+    }
+
+    /// Listen on `address`.
+    /// The IPv6 wildcard address `[::]` is bound dual-stack,
+    /// so it accepts IPv4 connections too.
+    fn tcp_listen(address: &SocketAddr) -> TransportResult<TcpListener> {
+        let socket = match address {
+            SocketAddr::V4(_) => {
+                Socket::new(Domain::ipv4(), Type::stream(), Some(Protocol::tcp()))?
+            }
+            SocketAddr::V6(_) => {
+                let socket = Socket::new(Domain::ipv6(), Type::stream(), Some(Protocol::tcp()))?;
+                // must be set before binding
+                socket.set_only_v6(!address.ip().is_unspecified())?;
+                socket
+            }
+        };
+        // same as std::net::TcpListener::bind
+        #[cfg(unix)]
+        socket.set_reuse_address(true)?;
+        socket.bind(&(*address).into())?;
+        socket.listen(128)?;
+        Ok(socket.into_tcp_listener())
+    }
+
+    /// The urls a listener bound with `spec` can be reached at.
+    /// The actual port is filled in, and a wildcard address is expanded
+    /// to the addresses of all network interfaces, loopback last.
+    fn tcp_bound_urls(spec: &url::Url, local_addr: &SocketAddr) -> TransportResult<Vec<url::Url>> {
+        let mut ips = Vec::new();
+        if local_addr.ip().is_unspecified() {
+            let dual_stack = local_addr.is_ipv6();
+            for interface in get_if_addrs::get_if_addrs()? {
+                match interface.ip() {
+                    IpAddr::V4(ip) => ips.push(IpAddr::V4(ip)),
+                    // link-local addresses need a scope id we can't put in a url
+                    IpAddr::V6(ip) if dual_stack && ip.segments()[0] & 0xffc0 != 0xfe80 => {
+                        ips.push(IpAddr::V6(ip))
+                    }
+                    IpAddr::V6(_) => (),
+                }
+            }
+            ips.sort_by_key(|ip| ip.is_loopback());
+        }
+        let mut bound_urls = Vec::new();
+        if ips.is_empty() {
+            let mut url = spec.clone();
+            url.set_port(Some(local_addr.port()))
+                .map_err(|_| TransportError::new(format!("cannot set port of {}", spec)))?;
+            bound_urls.push(url);
+        }
+        for ip in ips {
+            let mut url = spec.clone();
+            url.set_ip_host(ip)
+                .and_then(|()| url.set_port(Some(local_addr.port())))
+                .map_err(|_| TransportError::new(format!("cannot set address of {}", spec)))?;
+            bound_urls.push(url);
+        }
+        Ok(bound_urls)
+    }
+}
+
+/// Connections accepted on a dual-stack listener report IPv4 peers
+/// as IPv4-mapped IPv6 addresses, turn those back into plain IPv4.
+fn unmap_ipv4(address: &SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V6(v6) if v6.ip().segments()[..6] == [0, 0, 0, 0, 0, 0xffff] => v6
+            .ip()
+            .to_ipv4()
+            .map(|ip| SocketAddr::new(IpAddr::V4(ip), v6.port()))
+            .unwrap_or(*address),
+        _ => *address,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unmap_ipv4() {
+        let mapped: SocketAddr = "[::ffff:10.1.2.3]:1234".parse().unwrap();
+        assert_eq!("10.1.2.3:1234", unmap_ipv4(&mapped).to_string());
+        let v6: SocketAddr = "[fd00::1]:1234".parse().unwrap();
+        assert_eq!(v6, unmap_ipv4(&v6));
+    }
+
+    /// some hosts (and containers) have no IPv6 at all
+    fn ipv6_available() -> bool {
+        TcpListener::bind("[::1]:0").is_ok()
+    }
+
+    #[test]
+    fn test_tcp_bind_resolves_port() {
+        if !ipv6_available() {
+            println!("skipping test_tcp_bind_resolves_port: no IPv6");
+            return;
+        }
+        let spec = url::Url::parse("wss://[::1]:0").unwrap();
+        let (_acceptor, bound_urls) = StreamManager::tcp_bind(&spec).unwrap();
+        assert_eq!(1, bound_urls.len());
+        assert_eq!(
+            Some(url::Host::Ipv6("::1".parse().unwrap())),
+            bound_urls[0].host()
+        );
+        assert_ne!(Some(0), bound_urls[0].port());
+    }
 
-                                    trace!(
-                                        "transport_wss::tcp v4 socket_address: {}",
-                                        v4_socket_address
-                                    );
-                                    url::Url::parse(v4_socket_address.as_str())
-                                        .map(|url| {
-                                            trace!(
-                                                "transport_wss::tcp accepted for url {}",
-                                                url.clone()
-                                            );
-                                            WssInfo::server(url, tcp_stream)
-                                        })
-                                        .map_err(|err| {
-                                            error!("transport_wss::tcp url error: {:?}", err);
-                                            err.into()
-                                        })
-                                })
-                        });
-                        acceptor
-                    })
-            })
+    #[test]
+    fn test_tcp_bind_wildcard_lists_interfaces() {
+        if !ipv6_available() {
+            println!("skipping test_tcp_bind_wildcard_lists_interfaces: no IPv6");
+            return;
+        }
+        let spec = url::Url::parse("wss://[::]:0").unwrap();
+        let (_acceptor, bound_urls) = StreamManager::tcp_bind(&spec).unwrap();
+        assert!(!bound_urls.is_empty());
+        for url in bound_urls.iter() {
+            assert!(url.host_str() != Some("[::]"));
+        }
+        // dual-stack: the IPv4 loopback is among the interfaces
+        assert!(bound_urls
+            .iter()
+            .any(|url| url.host_str() == Some("127.0.0.1")));
     }
 }
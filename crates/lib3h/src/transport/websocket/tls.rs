@@ -1,12 +1,261 @@
+use crate::{
+    private_file,
+    transport::error::{TransportError, TransportResult},
+};
+use lib3h_protocol::Address;
+use openssl::{
+    asn1::Asn1Time,
+    bn::{BigNum, MsbOption},
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkcs12::{ParsedPkcs12_2, Pkcs12},
+    pkey::{Id, PKey},
+    sign::Verifier,
+    x509::{extension::SubjectAlternativeName, X509NameBuilder, X509},
+};
+use std::path::Path;
+
+/// file name of the ephemeral certificate within the engine's work_dir
+const EPHEMERAL_CERT_FILE: &str = "ephemeral_tls.p12";
+
+/// how long an ephemeral certificate stays valid
+const EPHEMERAL_CERT_DAYS: u32 = 30;
+
+/// subject alternative name scheme carrying the TransportId binding
+const BINDING_URI_PREFIX: &str = "transportid:";
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct TlsCertificate {
     pub(in crate::transport::websocket) pkcs12_data: Vec<u8>,
     pub(in crate::transport::websocket) passphrase: String,
 }
 
+impl TlsCertificate {
+    /// Generate a fresh self-signed certificate for `transport_id`,
+    /// write it into `work_dir` (readable by us only) and load it back from there.
+    /// Without a `work_dir`, it is only kept in memory.
+    /// `sign` must produce a signature with the TransportId secret key,
+    /// it is used to bind the certificate fingerprint to the TransportId.
+    pub fn build_ephemeral<F>(
+        work_dir: &Path,
+        transport_id: &Address,
+        sign: F,
+    ) -> TransportResult<Self>
+    where
+        F: FnOnce(&[u8]) -> TransportResult<Vec<u8>>,
+    {
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+        let pkey = PKey::from_ec_key(EcKey::generate(&group)?)?;
+        let signature = sign(&key_fingerprint(&pkey.public_key_to_der()?))?;
+
+        let mut name = X509NameBuilder::new()?;
+        name.append_entry_by_nid(Nid::COMMONNAME, &transport_id.to_string())?;
+        let name = name.build();
+        let mut serial = BigNum::new()?;
+        serial.rand(64, MsbOption::MAYBE_ZERO, false)?;
+        let serial = serial.to_asn1_integer()?;
+        let not_before = Asn1Time::days_from_now(0)?;
+        let not_after = Asn1Time::days_from_now(EPHEMERAL_CERT_DAYS)?;
+
+        let mut builder = X509::builder()?;
+        builder.set_version(2)?;
+        builder.set_serial_number(&serial)?;
+        builder.set_subject_name(&name)?;
+        builder.set_issuer_name(&name)?;
+        builder.set_pubkey(&pkey)?;
+        builder.set_not_before(&not_before)?;
+        builder.set_not_after(&not_after)?;
+        let san = SubjectAlternativeName::new()
+            .uri(&format!(
+                "{}{}?sig={}",
+                BINDING_URI_PREFIX,
+                transport_id,
+                to_hex(&signature)
+            ))
+            .build(&builder.x509v3_context(None, None))?;
+        builder.append_extension(san)?;
+        builder.sign(&pkey, MessageDigest::sha256())?;
+
+        let pkcs12 = Pkcs12::builder()
+            .name(&transport_id.to_string())
+            .pkey(&pkey)
+            .cert(&builder.build())
+            .build2("")?;
+        let pkcs12_data = pkcs12.to_der()?;
+        if work_dir.as_os_str().is_empty() {
+            return Ok(Self {
+                pkcs12_data,
+                passphrase: String::new(),
+            });
+        }
+        std::fs::create_dir_all(work_dir)?;
+        let path = work_dir.join(EPHEMERAL_CERT_FILE);
+        private_file::write(&path, &pkcs12_data)?;
+        Self::load(&path, "")
+    }
+
+    /// Load a PKCS12 archive from disk
+    pub fn load(path: &Path, passphrase: &str) -> TransportResult<Self> {
+        Ok(Self {
+            pkcs12_data: std::fs::read(path)?,
+            passphrase: passphrase.to_string(),
+        })
+    }
+
+    /// Decrypt the key and certificate chain
+    pub(in crate::transport::websocket) fn parse(&self) -> TransportResult<ParsedPkcs12_2> {
+        Ok(Pkcs12::from_der(&self.pkcs12_data)?.parse2(&self.passphrase)?)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum TlsConfig {
     Unencrypted,
     FakeServer,
     SuppliedCertificate(TlsCertificate),
+    /// Generate a fresh self-signed certificate for this node at startup,
+    /// stored in the engine's work_dir and bound to our TransportId.
+    /// The engine replaces this with `PinnedCertificate` before building the transport.
+    Ephemeral {
+        #[serde(default)]
+        require_client_cert: bool,
+    },
+    /// A certificate carrying a TransportId binding (see `TlsCertificate::build_ephemeral`).
+    /// Remote servers must present a valid binding too. We also present this
+    /// certificate as a client, and verify the client certificates we receive;
+    /// with `require_client_cert` clients without one are refused.
+    PinnedCertificate {
+        certificate: TlsCertificate,
+        #[serde(default)]
+        require_client_cert: bool,
+    },
+}
+
+/// Check that a DER encoded certificate carries a binding signed by the
+/// TransportId it claims, and return that TransportId.
+pub fn verify_pinned_certificate(cert_der: &[u8]) -> TransportResult<Address> {
+    let cert = X509::from_der(cert_der)?;
+    let binding = cert
+        .subject_alt_names()
+        .into_iter()
+        .flatten()
+        .filter_map(|name| name.uri().map(|uri| uri.to_string()))
+        .find(|uri| uri.starts_with(BINDING_URI_PREFIX))
+        .ok_or_else(|| TransportError::new("certificate has no TransportId binding".into()))?;
+    let mut split = binding[BINDING_URI_PREFIX.len()..].splitn(2, "?sig=");
+    let transport_id = split.next().unwrap_or_default().to_string();
+    let signature = split
+        .next()
+        .and_then(from_hex)
+        .ok_or_else(|| TransportError::new(format!("bad TransportId binding: {}", binding)))?;
+
+    let public_key = hcid::HcidEncoding::with_kind("hcm0")
+        .and_then(|hcm0| hcm0.decode(&transport_id))
+        .map_err(|e| TransportError::new(format!("bad TransportId {}: {:?}", transport_id, e)))?;
+    let public_key = PKey::public_key_from_raw_bytes(&public_key, Id::ED25519)?;
+    let fingerprint = key_fingerprint(&cert.public_key()?.public_key_to_der()?);
+    if !Verifier::new_without_digest(&public_key)?.verify_oneshot(&signature, &fingerprint)? {
+        return Err(TransportError::new(format!(
+            "certificate is not bound to TransportId {}",
+            transport_id
+        )));
+    }
+    Ok(transport_id.into())
+}
+
+/// sha256 over the DER encoded SubjectPublicKeyInfo
+fn key_fingerprint(public_key_der: &[u8]) -> Vec<u8> {
+    openssl::sha::sha256(public_key_der).to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    s.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)
+                .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::engine::TransportKeys;
+    use lib3h_crypto_api::CryptoSystem;
+    use lib3h_sodium::SodiumCryptoSystem;
+
+    /// Build an ephemeral certificate in a temp dir, signed by fresh TransportKeys
+    pub fn ephemeral_certificate(crypto: &dyn CryptoSystem) -> (Address, TlsCertificate) {
+        let keys = TransportKeys::new(crypto).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let cert = TlsCertificate::build_ephemeral(dir.path(), &keys.transport_id, |data| {
+            Ok(keys.sign(crypto, data)?)
+        })
+        .unwrap();
+        (keys.transport_id, cert)
+    }
+
+    /// Build a certificate claiming a TransportId, but bound by someone else's key
+    pub fn forged_certificate(crypto: &dyn CryptoSystem) -> TlsCertificate {
+        let keys = TransportKeys::new(crypto).unwrap();
+        let other_keys = TransportKeys::new(crypto).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        TlsCertificate::build_ephemeral(dir.path(), &keys.transport_id, |data| {
+            Ok(other_keys.sign(crypto, data)?)
+        })
+        .unwrap()
+    }
+
+    fn cert_der(cert: &TlsCertificate) -> Vec<u8> {
+        cert.parse().unwrap().cert.unwrap().to_der().unwrap()
+    }
+
+    #[test]
+    fn test_ephemeral_certificate_is_bound_to_transport_id() {
+        let crypto = SodiumCryptoSystem::new();
+        let (transport_id, cert) = ephemeral_certificate(&crypto);
+        assert_eq!(
+            transport_id,
+            verify_pinned_certificate(&cert_der(&cert)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_ephemeral_certificate_without_work_dir() {
+        let crypto = SodiumCryptoSystem::new();
+        let keys = TransportKeys::new(&crypto).unwrap();
+        let cert = TlsCertificate::build_ephemeral(Path::new(""), &keys.transport_id, |data| {
+            Ok(keys.sign(&crypto, data)?)
+        })
+        .unwrap();
+        // kept in memory, not written to the current directory
+        assert!(!Path::new(EPHEMERAL_CERT_FILE).exists());
+        assert_eq!(
+            keys.transport_id,
+            verify_pinned_certificate(&cert_der(&cert)).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_binding_signed_by_other_key_is_rejected() {
+        let cert = forged_certificate(&SodiumCryptoSystem::new());
+        assert!(verify_pinned_certificate(&cert_der(&cert)).is_err());
+    }
+
+    #[test]
+    fn test_hex_roundtrip() {
+        let bytes = vec![0u8, 1, 0xab, 0xff];
+        assert_eq!("0001abff", to_hex(&bytes));
+        assert_eq!(Some(bytes), from_hex("0001abff"));
+        assert_eq!(None, from_hex("0g"));
+        assert_eq!(None, from_hex("abc"));
+    }
 }
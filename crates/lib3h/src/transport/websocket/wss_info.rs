@@ -1,4 +1,5 @@
 use crate::transport::websocket::{streams::WebsocketStreamState, BaseStream, TransportResult};
+use lib3h_protocol::Address;
 
 /// Represents an individual connection
 #[derive(Debug)]
@@ -6,7 +7,13 @@ pub struct WssInfo<T: std::io::Read + std::io::Write + std::fmt::Debug> {
     pub(in crate::transport::websocket) request_id: String,
     pub(in crate::transport::websocket) url: url::Url,
     pub(in crate::transport::websocket) last_msg: std::time::Instant,
+    /// when the heartbeat ping still waiting for its pong was sent
+    pub(in crate::transport::websocket) ping_sent: Option<std::time::Instant>,
     pub(in crate::transport::websocket) stateful_socket: WebsocketStreamState<T>,
+    /// TransportId proven by the remote's pinned certificate
+    pub(in crate::transport::websocket) verified_id: Option<Address>,
+    /// TransportId we connected to, the remote's certificate must prove it
+    pub(in crate::transport::websocket) expected_id: Option<Address>,
 }
 
 impl<T: std::io::Read + std::io::Write + std::fmt::Debug> WssInfo<T> {
@@ -16,6 +23,11 @@ impl<T: std::io::Read + std::io::Write + std::fmt::Debug> WssInfo<T> {
             socket.close(None)?;
             socket.write_pending()?;
         }
+        if let WebsocketStreamState::ReadyWs(socket) = &mut self.stateful_socket {
+            socket.write_message(tungstenite::Message::Close(None))?;
+            socket.close(None)?;
+            socket.write_pending()?;
+        }
         self.stateful_socket = WebsocketStreamState::None;
         Ok(())
     }
@@ -26,10 +38,13 @@ impl<T: std::io::Read + std::io::Write + std::fmt::Debug> WssInfo<T> {
             request_id: "".to_string(),
             url,
             last_msg: std::time::Instant::now(),
+            ping_sent: None,
             stateful_socket: match is_server {
                 false => WebsocketStreamState::Connecting(socket),
                 true => WebsocketStreamState::ConnectingSrv(socket),
             },
+            verified_id: None,
+            expected_id: None,
         }
     }
 
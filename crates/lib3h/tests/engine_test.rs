@@ -18,8 +18,11 @@ use lib3h_ghost_actor::{wait1_for_callback, wait1_for_messages};
 
 use holochain_tracing::Span;
 use lib3h::{
-    dht::mirror_dht::MirrorDht,
-    engine::{EngineConfig, GhostEngine, TransportConfig},
+    dht::{dht_config::DEFAULT_TOMBSTONE_RETENTION_MS, mirror_dht::MirrorDht},
+    engine::{
+        DirectMessageConfig, EngineConfig, GhostEngine, PublishConfig, StreamConfig,
+        TransportConfig,
+    },
     transport::websocket::tls::TlsConfig,
 };
 
@@ -69,9 +72,17 @@ fn basic_setup_mock_bootstrap<'engine>(
         work_dir: PathBuf::new(),
         log_level: 'd',
         bind_url: Lib3hUri::with_memory(name),
+        extra_bind_urls: Vec::new(),
         dht_gossip_interval: 100,
         dht_timeout_threshold: 1000,
+        dht_tombstone_retention: DEFAULT_TOMBSTONE_RETENTION_MS,
         dht_custom_config: vec![],
+        network_policy: NetworkPolicyData::default(),
+        publish: PublishConfig::default(),
+        direct_message: DirectMessageConfig::default(),
+        stream: StreamConfig::default(),
+        transport_seed: None,
+        transport_rotations: Vec::new(),
     };
     let engine = GhostEngine::new(
         Span::fixme(),
@@ -101,9 +112,17 @@ fn basic_setup_wss<'engine>(name: &str) -> GhostEngine<'engine> {
         work_dir: PathBuf::new(),
         log_level: 'd',
         bind_url: Url::parse("wss://127.0.0.1:64519").unwrap().into(),
+        extra_bind_urls: Vec::new(),
         dht_gossip_interval: 200,
         dht_timeout_threshold: 2000,
+        dht_tombstone_retention: DEFAULT_TOMBSTONE_RETENTION_MS,
         dht_custom_config: vec![],
+        network_policy: NetworkPolicyData::default(),
+        publish: PublishConfig::default(),
+        direct_message: DirectMessageConfig::default(),
+        stream: StreamConfig::default(),
+        transport_seed: None,
+        transport_rotations: Vec::new(),
     };
     let engine = GhostEngine::new(
         Span::fixme(),
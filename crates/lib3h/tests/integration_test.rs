@@ -20,12 +20,15 @@ mod test_suites;
 
 use holochain_tracing::Span;
 use lib3h::{
-    dht::mirror_dht::MirrorDht,
-    engine::{ghost_engine_wrapper::WrappedGhostLib3h, EngineConfig, GhostEngine, TransportConfig},
+    dht::{dht_config::DEFAULT_TOMBSTONE_RETENTION_MS, mirror_dht::MirrorDht},
+    engine::{
+        ghost_engine_wrapper::WrappedGhostLib3h, DirectMessageConfig, EngineConfig, GhostEngine,
+        PublishConfig, StreamConfig, TransportConfig,
+    },
     error::Lib3hResult,
     transport::websocket::tls::TlsConfig,
 };
-use lib3h_protocol::{uri::Lib3hUri, Address};
+use lib3h_protocol::{data_types::NetworkPolicyData, uri::Lib3hUri, Address};
 use node_mock::NodeMock;
 use std::path::PathBuf;
 use test_suites::{
@@ -127,9 +130,17 @@ fn setup_memory_node(name: &str, agent_id_arg: Address, fn_name: &str) -> NodeMo
         work_dir: PathBuf::new(),
         log_level: 'd',
         bind_url: Lib3hUri::with_memory(format!("{}/{}", fn_name, name).as_str()),
+        extra_bind_urls: Vec::new(),
         dht_gossip_interval: 500,
         dht_timeout_threshold: 3005,
+        dht_tombstone_retention: DEFAULT_TOMBSTONE_RETENTION_MS,
         dht_custom_config: vec![],
+        network_policy: NetworkPolicyData::default(),
+        publish: PublishConfig::default(),
+        direct_message: DirectMessageConfig::default(),
+        stream: StreamConfig::default(),
+        transport_seed: None,
+        transport_rotations: Vec::new(),
     };
     NodeMock::new_with_config(name, agent_id_arg, config, construct_mock_engine)
 }
@@ -144,7 +155,7 @@ fn setup_wss_node(
     let port = generate_port();
     let protocol = match tls_config {
         TlsConfig::Unencrypted => "ws",
-        TlsConfig::SuppliedCertificate(_) | TlsConfig::FakeServer => "wss",
+        _ => "wss",
     };
     let bind_url = Url::parse(format!("{}://127.0.0.1:{}/{}", protocol, port, fn_name).as_str())
         .expect("invalid web socket url")
@@ -157,9 +168,17 @@ fn setup_wss_node(
         work_dir: PathBuf::new(),
         log_level: 'd',
         bind_url,
+        extra_bind_urls: Vec::new(),
         dht_gossip_interval: 500,
         dht_timeout_threshold: 3005,
+        dht_tombstone_retention: DEFAULT_TOMBSTONE_RETENTION_MS,
         dht_custom_config: vec![],
+        network_policy: NetworkPolicyData::default(),
+        publish: PublishConfig::default(),
+        direct_message: DirectMessageConfig::default(),
+        stream: StreamConfig::default(),
+        transport_seed: None,
+        transport_rotations: Vec::new(),
     };
     NodeMock::new_with_config(name, agent_id_arg, config, construct_wss_engine)
 }
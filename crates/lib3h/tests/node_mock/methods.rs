@@ -761,6 +761,9 @@ impl NodeMock {
             Lib3hServerProtocol::HandleSendDirectMessage(_msg) => {
                 // no-op
             }
+            Lib3hServerProtocol::SignalReceived(_msg) => {
+                // no-op
+            }
             Lib3hServerProtocol::FetchEntryResult(_msg) => {
                 // no-op
             }
@@ -223,6 +223,7 @@ impl TestTransport {
                 let response = if mockernet.bind(spec.clone()) {
                     Ok(RequestToChildResponse::Bind(BindResultData {
                         bound_url: spec.clone(),
+                        bound_urls: vec![spec.clone()],
                     }))
                 } else {
                     Err(TransportError::new("already bound".to_string()))
@@ -247,6 +248,19 @@ impl TestTransport {
                     msg.respond(response)?;
                 }
             }
+            RequestToChild::GetStats => {
+                msg.respond(Ok(RequestToChildResponse::GetStats(Default::default())))?;
+            }
+            RequestToChild::Shutdown => {
+                // nothing is ever queued
+                msg.respond(Ok(RequestToChildResponse::Shutdown))?;
+            }
+            RequestToChild::Close { .. } => {
+                msg.respond(Ok(RequestToChildResponse::Close))?;
+            }
+            RequestToChild::SetTransportId { .. } => {
+                msg.respond(Ok(RequestToChildResponse::SetTransportId))?;
+            }
         }
         Ok(())
     }
@@ -263,8 +277,13 @@ impl TestTransport {
                             .publish(span, RequestToParent::ReceivedData { uri: from, payload })?;
                     }
                     MockernetEvent::Connection { from } => {
-                        self.endpoint_self
-                            .publish(span, RequestToParent::IncomingConnection { uri: from })?;
+                        self.endpoint_self.publish(
+                            span,
+                            RequestToParent::IncomingConnection {
+                                uri: from,
+                                verified_id: None,
+                            },
+                        )?;
                     }
                     MockernetEvent::Error(err) => {
                         self.endpoint_self.publish(
@@ -326,7 +345,7 @@ fn ghost_transport() {
     .unwrap();
     t1.process(&mut owner).expect("should process");
     assert_eq!(
-        "\"Response(Ok(Bind(BindResultData { bound_url: Lib3hUri(\\\"mocknet://t1/\\\") })))\"",
+        "\"Response(Ok(Bind(BindResultData { bound_url: Lib3hUri(\\\"mocknet://t1/\\\"), bound_urls: [Lib3hUri(\\\"mocknet://t1/\\\")] })))\"",
         format!("{:?}", owner.log[0])
     );
 
@@ -367,7 +386,7 @@ fn ghost_transport() {
     .unwrap();
     t2.process(&mut owner).expect("should process");
     assert_eq!(
-        "\"Response(Ok(Bind(BindResultData { bound_url: Lib3hUri(\\\"mocknet://t2/\\\") })))\"",
+        "\"Response(Ok(Bind(BindResultData { bound_url: Lib3hUri(\\\"mocknet://t2/\\\"), bound_urls: [Lib3hUri(\\\"mocknet://t2/\\\")] })))\"",
         format!("{:?}", owner.log[2])
     );
 
@@ -398,7 +417,7 @@ fn ghost_transport() {
     let mut messages = t2.drain_messages();
     assert_eq!(messages.len(), 2);
     assert_eq!(
-        "IncomingConnection { uri: Lib3hUri(\"mocknet://t1/\") }",
+        "IncomingConnection { uri: Lib3hUri(\"mocknet://t1/\"), verified_id: None }",
         format!("{:?}", messages[0].take_message().expect("exists"))
     );
     assert_eq!(
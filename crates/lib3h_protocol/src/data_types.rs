@@ -245,6 +245,68 @@ pub struct DirectMessageData {
     pub content: Opaque,
 }
 
+/// One-way message to some agents of a space, or all of them
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SignalData {
+    pub space_address: SpaceHash,
+    pub from_agent_id: Address,
+    /// Recipients, every agent of the space if None
+    pub to_agent_id_list: Option<Vec<Address>>,
+    pub content: Opaque,
+}
+
+/// Agents to open a stream between
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct OpenStreamData {
+    pub space_address: SpaceHash,
+    pub from_agent_id: Address,
+    pub to_agent_id: Address,
+}
+
+/// A stream of chunks from one agent to another
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StreamData {
+    pub space_address: SpaceHash,
+    pub stream_id: String,
+    /// The agent that opened the stream and sends the chunks
+    pub from_agent_id: Address,
+    pub to_agent_id: Address,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct StreamChunkData {
+    pub space_address: SpaceHash,
+    pub stream_id: String,
+    pub from_agent_id: Address,
+    pub to_agent_id: Address,
+    /// Position of the chunk in the stream, identifies its acknowledgement
+    pub sequence: u64,
+    pub content: Opaque,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Topics
+//--------------------------------------------------------------------------------------------------
+
+/// An agent's subscription to a topic of a space
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TopicSubscriptionData {
+    pub space_address: SpaceHash,
+    pub agent_id: Address,
+    pub topic: String,
+}
+
+/// A message published to a topic of a space
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TopicMessageData {
+    pub space_address: SpaceHash,
+    pub topic: String,
+    pub from_agent_id: Address,
+    /// The subscriber it is delivered to, None when publishing
+    pub to_agent_id: Option<Address>,
+    pub content: Opaque,
+}
+
 //--------------------------------------------------------------------------------------------------
 // Query
 //--------------------------------------------------------------------------------------------------
@@ -280,6 +342,19 @@ pub struct ProvidedEntryData {
     pub entry: EntryData,
 }
 
+/// Which agents confirmed storing a published entry
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PublishEntryResultData {
+    pub space_address: SpaceHash,
+    pub provider_agent_id: Address,
+    pub entry_address: EntryHash,
+    /// How many confirmations were asked for
+    pub replication_factor: usize,
+    /// Agents of other nodes that stored the entry,
+    /// fewer than `replication_factor` if we gave up waiting
+    pub stored_by: Vec<Address>,
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct StoreEntryAspectData {
     pub request_id: String,
@@ -295,6 +370,19 @@ pub struct DropEntryData {
     pub space_address: SpaceHash,
     pub request_id: String,
     pub entry_address: EntryHash,
+    /// Aspects to drop, the whole entry if None
+    #[serde(default)]
+    pub aspect_address_list: Option<Vec<AspectHash>>,
+}
+
+/// Entry, or some of its aspects, to delete from every holder of a space
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct DeleteEntryData {
+    pub space_address: SpaceHash,
+    pub provider_agent_id: Address,
+    pub entry_address: EntryHash,
+    /// Aspects to delete, the whole entry if None
+    pub aspect_address_list: Option<Vec<AspectHash>>,
 }
 
 //--------------------------------------------------------------------------------------------------
@@ -341,6 +429,169 @@ pub struct EntryListData {
     pub address_map: std::collections::HashMap<EntryHash, Vec<AspectHash>>,
 }
 
+//--------------------------------------------------------------------------------------------------
+// Network policy
+//--------------------------------------------------------------------------------------------------
+
+/// Allow and block lists for one kind of peer identifier.
+/// An empty `allow` list lets everyone in, and `block` always takes precedence.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct PeerFilterData {
+    #[serde(default)]
+    pub allow: Vec<String>,
+    #[serde(default)]
+    pub block: Vec<String>,
+}
+
+/// Agent ids allowed or blocked from a specific space
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SpacePolicyData {
+    pub space_address: SpaceHash,
+    pub agent_ids: PeerFilterData,
+}
+
+/// Membership rules for private networks: which remotes may connect to us
+/// and which agents we accept as peers in a space.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct NetworkPolicyData {
+    /// TransportIds (`HcM...`) of remote nodes
+    #[serde(default)]
+    pub transport_ids: PeerFilterData,
+    /// CIDR ranges of remote ip addresses, e.g. `10.0.0.0/8` or `fd00::/8`
+    #[serde(default)]
+    pub cidrs: PeerFilterData,
+    /// Agent id lists per space
+    #[serde(default)]
+    pub spaces: Vec<SpacePolicyData>,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Transport key rotation
+//--------------------------------------------------------------------------------------------------
+
+/// Seed of the transport signing keypair to rotate to
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct RotateTransportKeyData {
+    pub seed: Opaque,
+}
+
+/// Certificate that a node's TransportId changed: peers holding it treat
+/// `new_transport_id` as the same node as `old_transport_id`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct TransportRotationData {
+    pub old_transport_id: Address,
+    pub new_transport_id: Address,
+    /// When the node rotated its key
+    pub timestamp: u64,
+    /// Signature of `signed_content()` by the old TransportId
+    pub signature: Opaque,
+    /// Signature of `signed_content()` by the new TransportId
+    pub new_signature: Opaque,
+}
+
+impl TransportRotationData {
+    /// The bytes covered by both signatures
+    pub fn signed_content(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        serde::Serialize::serialize(
+            &(
+                &self.old_transport_id,
+                &self.new_transport_id,
+                self.timestamp,
+            ),
+            &mut rmp_serde::Serializer::new(&mut out),
+        )
+        .unwrap();
+        out
+    }
+}
+
+//--------------------------------------------------------------------------------------------------
+// Engine state
+//--------------------------------------------------------------------------------------------------
+
+/// A peer known to the DHT of the network or of a space
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct PeerStateData {
+    pub peer_name: Lib3hUri,
+    pub peer_location: Lib3hUri,
+    /// epoch ms of the latest peer info we received
+    pub timestamp: u64,
+    /// false once we haven't heard from the peer within the DHT timeout threshold
+    pub is_alive: bool,
+}
+
+/// A space one of our agents joined
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct JoinedSpaceData {
+    pub space_address: SpaceHash,
+    pub agent_id: Address,
+}
+
+/// Response to a `GetNetworkState` request
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct NetworkStateData {
+    /// our TransportId uri, and the location we are bound to
+    pub this_peer: PeerStateData,
+    /// remotes that connected to us
+    pub connections: Vec<Lib3hUri>,
+    /// nodes known to the network DHT
+    pub peers: Vec<PeerStateData>,
+    pub joined_spaces: Vec<JoinedSpaceData>,
+}
+
+/// Response to a `GetSpaceState` request
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct SpaceStateData {
+    pub space_address: SpaceHash,
+    pub agent_id: Address,
+    /// agents known to the space DHT
+    pub peers: Vec<PeerStateData>,
+    /// entries we hold for this space
+    pub entry_count: u64,
+    /// aspects we hold across those entries
+    pub aspect_count: u64,
+}
+
+//--------------------------------------------------------------------------------------------------
+// Transport statistics
+//--------------------------------------------------------------------------------------------------
+
+/// Traffic counters of a single connection, or totals over a whole transport
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TrafficStatsData {
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub errors: u64,
+    /// times a connection to the same remote was opened again after closing
+    pub reconnects: u64,
+    /// messages waiting to be sent
+    pub queue_depth: u64,
+    /// epoch ms of the last message sent or received
+    pub last_activity_ms: Option<u64>,
+    /// latest measured round trip time, in ms
+    pub rtt_ms: Option<u64>,
+}
+
+/// Traffic counters of the connection to `uri`
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct ConnectionStatsData {
+    pub uri: Lib3hUri,
+    pub stats: TrafficStatsData,
+}
+
+/// Statistics of a transport: totals since it was created,
+/// and counters of the connections currently open.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct TransportStatsData {
+    pub total: TrafficStatsData,
+    pub connections: Vec<ConnectionStatsData>,
+    /// statistics of the transport a gateway is running on, if any
+    pub inner: Option<Box<TransportStatsData>>,
+}
+
 // ---------- serialization helper for binary data as base 64 ---------- //
 
 mod base64 {
@@ -33,14 +33,61 @@ pub enum ClientToLib3h {
     // -- Direct Messaging -- //
     /// Send a message directly to another agent on the network
     SendDirectMessage(DirectMessageData),
+    /// Send a message to some agents, or all agents of a space, without
+    /// waiting for them to answer. Sent once to each node hosting recipients
+    SendSignal(SignalData),
+    /// Open a stream to another agent, answered with its id once accepted
+    OpenStream(OpenStreamData),
+    /// Send a chunk on a stream we opened. Answered once the recipient's
+    /// client took it; only `EngineConfig::stream.window` chunks can wait
+    /// for their answer at a time
+    SendStreamChunk(StreamChunkData),
+    /// Close a stream, from either end
+    CloseStream(StreamData),
+
+    // -- Topics -- //
+    /// Receive the messages published to a topic of the space
+    Subscribe(TopicSubscriptionData),
+    /// Stop receiving the messages of a topic
+    Unsubscribe(TopicSubscriptionData),
+    /// Send a message to the current subscribers of a topic. Sent once to
+    /// each node hosting subscribers, not retried
+    PublishTopic(TopicMessageData),
 
     // -- Entry -- //
     /// Request an Entry from the dht network
     FetchEntry(FetchEntryData), // NOTE: MAY BE DEPRECATED
-    /// Publish data to the dht (event)
+    /// Publish data to the dht. As a request, answered once enough
+    /// holders confirmed storing it
     PublishEntry(ProvidedEntryData),
     /// Request some info / data from a Entry
     QueryEntry(QueryEntryData),
+    /// Delete some aspects of an entry, or the whole entry, from every
+    /// holder of the space by spreading a signed tombstone
+    DeleteEntry(DeleteEntryData),
+
+    // -- Policy -- //
+    /// Replace the allow/block lists used to filter remote nodes and agents
+    SetNetworkPolicy(NetworkPolicyData),
+
+    // -- Transport key -- //
+    /// Switch our TransportId to the keypair of `seed`, and send the peers a
+    /// certificate signed by the current one that vouches for it. The engine
+    /// keeps using its current TransportId until restarted with the new seed,
+    /// which it writes to the seed file if `EngineConfig::transport_seed` has one
+    RotateTransportKey(RotateTransportKeyData),
+
+    // -- Shutdown -- //
+    /// Leave every space, tell our peers we are going, then close all connections
+    Shutdown,
+
+    // -- Diagnostics -- //
+    /// Request traffic statistics of the network transport
+    GetTransportStats,
+    /// Request our connections, peers and joined spaces
+    GetNetworkState,
+    /// Request the peers and held entries of a space we joined
+    GetSpaceState(SpaceData),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -50,14 +97,48 @@ pub enum ClientToLib3hResponse {
 
     /// the response received from a previous `SendDirectMessage`
     SendDirectMessageResult(DirectMessageData),
+    /// Response to a `SendSignal` request, once it is sent out
+    SendSignalResult,
+    /// Response to an `OpenStream` request, once the recipient accepted it
+    OpenStreamResult(StreamData),
+    /// Response to a `SendStreamChunk` request, once the recipient took the chunk
+    SendStreamChunkResult,
+    /// Response to a `CloseStream` request
+    CloseStreamResult,
+
+    /// Response to a `Subscribe` request
+    SubscribeResult,
+    /// Response to an `Unsubscribe` request
+    UnsubscribeResult,
+    /// Response to a `PublishTopic` request, once it is sent out
+    PublishTopicResult,
 
     /// Response from requesting dht data from the network
     FetchEntryResult(FetchEntryResultData),
     /// Response to a `QueryEntry` request
     QueryEntryResult(QueryEntryResultData),
+    /// Response to a `PublishEntry` request
+    PublishEntryResult(PublishEntryResultData),
+    /// Response to a `DeleteEntry` request, once the tombstone is sent out
+    DeleteEntryResult,
 
     JoinSpaceResult,  // response to the ClientToLib3h::JoinSpace() request, Ok or Err
     LeaveSpaceResult, // response to the ClientToLib3h::LeaveSpace() request, Ok or Err
+
+    SetNetworkPolicyResult, // response to the ClientToLib3h::SetNetworkPolicy() request, Ok or Err
+
+    /// Response to a `RotateTransportKey` request, once the certificate is
+    /// sent out. To be added to `EngineConfig::transport_rotations`
+    RotateTransportKeyResult(TransportRotationData),
+
+    ShutdownResult, // response to the ClientToLib3h::Shutdown() request, once connections are closed
+
+    /// Response to a `GetTransportStats` request
+    GetTransportStatsResult(TransportStatsData),
+    /// Response to a `GetNetworkState` request
+    GetNetworkStateResult(NetworkStateData),
+    /// Response to a `GetSpaceState` request
+    GetSpaceStateResult(SpaceStateData),
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -73,6 +154,19 @@ pub enum Lib3hToClient {
     SendDirectMessageResult(DirectMessageData),
     /// Request to handle a direct message another agent has sent us.
     HandleSendDirectMessage(DirectMessageData),
+    /// Notification of a signal another agent sent us, expects no response
+    SignalReceived(SignalData),
+    /// Request to accept a stream another agent opened to us
+    HandleOpenStream(StreamData),
+    /// Request to take a chunk of an accepted stream
+    HandleStreamChunk(StreamChunkData),
+    /// Notification that a stream was closed by the other end, or timed out
+    StreamClosed(StreamData),
+
+    // -- Topics -- //
+    /// A message published to a topic one of our agents subscribed to,
+    /// expects no response
+    HandleTopicMessage(TopicMessageData),
 
     // -- Entry -- //
     /// Another node, or the network module itself is requesting data from us
@@ -82,6 +176,9 @@ pub enum Lib3hToClient {
     /// Local client does not need to hold that entry anymore.
     /// Local client doesn't 'have to' comply.
     HandleDropEntry(DropEntryData),
+    /// Request to approve another agent's deletion of an entry, or some of
+    /// its aspects. Only its author should delete it: an error refuses it.
+    HandleDeleteEntry(DeleteEntryData),
     /// Request a node to handle a QueryEntry request
     HandleQueryEntry(QueryEntryData),
 
@@ -94,10 +191,18 @@ pub enum Lib3hToClient {
 pub enum Lib3hToClientResponse {
     /// Our response to a direct message from another agent.
     HandleSendDirectMessageResult(DirectMessageData),
+    /// We accept the stream, an error refuses it
+    HandleOpenStreamResult,
+    /// We took the chunk, lets the sender send more
+    HandleStreamChunkResult,
     /// Successful data response for a `HandleFetchEntryData` request
     HandleFetchEntryResult(FetchEntryResultData),
     HandleStoreEntryAspectResult,
+    /// We can't tell whether the aspect got stored, e.g. as a legacy client
+    HandleStoreEntryAspectUnconfirmed,
     HandleDropEntryResult,
+    /// We approve the deletion
+    HandleDeleteEntryResult,
     /// Response to a `HandleQueryEntry` request
     HandleQueryEntryResult(QueryEntryResultData),
     // -- Entry lists -- //
@@ -122,6 +227,9 @@ impl TryFrom<Lib3hClientProtocol> for ClientToLib3h {
             Lib3hClientProtocol::SendDirectMessage(direct_message_data) => {
                 Ok(ClientToLib3h::SendDirectMessage(direct_message_data))
             }
+            Lib3hClientProtocol::SendSignal(signal_data) => {
+                Ok(ClientToLib3h::SendSignal(signal_data))
+            }
             Lib3hClientProtocol::FetchEntry(fetch_entry_data) => {
                 Ok(ClientToLib3h::FetchEntry(fetch_entry_data))
             }
@@ -131,6 +239,7 @@ impl TryFrom<Lib3hClientProtocol> for ClientToLib3h {
             Lib3hClientProtocol::QueryEntry(query_entry_data) => {
                 Ok(ClientToLib3h::QueryEntry(query_entry_data))
             }
+            Lib3hClientProtocol::Shutdown => Ok(ClientToLib3h::Shutdown),
             variant => Err(Lib3hProtocolError::new(ErrorKind::Other(format!(
                 "{:?} can't convert to ClientToLib3h",
                 variant
@@ -184,6 +293,9 @@ impl TryFrom<Lib3hServerProtocol> for Lib3hToClient {
             Lib3hServerProtocol::HandleSendDirectMessage(direct_message_data) => {
                 Ok(Lib3hToClient::HandleSendDirectMessage(direct_message_data))
             }
+            Lib3hServerProtocol::SignalReceived(signal_data) => {
+                Ok(Lib3hToClient::SignalReceived(signal_data))
+            }
             Lib3hServerProtocol::HandleFetchEntry(fetch_entry_data) => {
                 Ok(Lib3hToClient::HandleFetchEntry(fetch_entry_data))
             }
@@ -226,6 +338,7 @@ impl From<Lib3hServerProtocol> for ClientToLib3hResponse {
             Lib3hServerProtocol::Connected(_connected_data) => {
                 ClientToLib3hResponse::BootstrapSuccess
             }
+            Lib3hServerProtocol::Terminated => ClientToLib3hResponse::ShutdownResult,
             variant => panic!("{:?} can't convert to ClientToLib3hResponse", variant),
         }
     }
@@ -244,6 +357,7 @@ impl From<ClientToLib3h> for Lib3hClientProtocol {
             ClientToLib3h::SendDirectMessage(direct_message_data) => {
                 Lib3hClientProtocol::SendDirectMessage(direct_message_data)
             }
+            ClientToLib3h::SendSignal(signal_data) => Lib3hClientProtocol::SendSignal(signal_data),
             ClientToLib3h::FetchEntry(fetch_entry_data) => {
                 Lib3hClientProtocol::FetchEntry(fetch_entry_data)
             }
@@ -253,6 +367,8 @@ impl From<ClientToLib3h> for Lib3hClientProtocol {
             ClientToLib3h::QueryEntry(query_entry_data) => {
                 Lib3hClientProtocol::QueryEntry(query_entry_data)
             }
+            ClientToLib3h::Shutdown => Lib3hClientProtocol::Shutdown,
+            variant => panic!("{:?} can't convert to Lib3hClientProtocol", variant),
         }
     }
 }
@@ -297,6 +413,9 @@ impl From<Lib3hToClient> for Lib3hServerProtocol {
             Lib3hToClient::HandleSendDirectMessage(direct_message_data) => {
                 Lib3hServerProtocol::HandleSendDirectMessage(direct_message_data)
             }
+            Lib3hToClient::SignalReceived(signal_data) => {
+                Lib3hServerProtocol::SignalReceived(signal_data)
+            }
             Lib3hToClient::HandleFetchEntry(fetch_entry_data) => {
                 Lib3hServerProtocol::HandleFetchEntry(fetch_entry_data)
             }
@@ -315,6 +434,7 @@ impl From<Lib3hToClient> for Lib3hServerProtocol {
             Lib3hToClient::HandleGetGossipingEntryList(get_list_data) => {
                 Lib3hServerProtocol::HandleGetGossipingEntryList(get_list_data)
             }
+            variant => panic!("{:?} can't convert to Lib3hServerProtocol", variant),
         }
     }
 }
@@ -338,6 +458,7 @@ impl From<ClientToLib3hResponse> for Lib3hServerProtocol {
                     uri: Lib3hUri::with_undefined(),
                 })
             }
+            ClientToLib3hResponse::ShutdownResult => Lib3hServerProtocol::Terminated,
             variant => panic!("{:?} can't convert to Lib3hServerProtocol", variant),
         }
     }
@@ -373,4 +494,43 @@ mod tests {
         let to_s: Lib3hClientProtocol = to_c.into();
         assert_eq!(to_s, s);
     }
+
+    #[test]
+    fn test_translate_shutdown() {
+        let to_c: ClientToLib3h = Lib3hClientProtocol::Shutdown
+            .try_into()
+            .expect("A ClientToLib3h protocol");
+        assert_eq!(to_c, ClientToLib3h::Shutdown);
+        let to_s: Lib3hClientProtocol = to_c.into();
+        assert_eq!(to_s, Lib3hClientProtocol::Shutdown);
+
+        let response: Lib3hServerProtocol = ClientToLib3hResponse::ShutdownResult.into();
+        assert_eq!(response, Lib3hServerProtocol::Terminated);
+        let response: ClientToLib3hResponse = response.into();
+        assert_eq!(response, ClientToLib3hResponse::ShutdownResult);
+    }
+
+    #[test]
+    fn test_translate_signal() {
+        let signal_data = SignalData {
+            space_address: "space_addr".into(),
+            from_agent_id: "agent_id".into(),
+            to_agent_id_list: None,
+            content: b"typing".to_vec().into(),
+        };
+        let to_c: ClientToLib3h = Lib3hClientProtocol::SendSignal(signal_data.clone())
+            .try_into()
+            .expect("A ClientToLib3h protocol");
+        assert_eq!(to_c, ClientToLib3h::SendSignal(signal_data.clone()));
+        let to_s: Lib3hClientProtocol = to_c.into();
+        assert_eq!(to_s, Lib3hClientProtocol::SendSignal(signal_data.clone()));
+
+        let to_s: Lib3hServerProtocol = Lib3hToClient::SignalReceived(signal_data.clone()).into();
+        assert_eq!(
+            to_s,
+            Lib3hServerProtocol::SignalReceived(signal_data.clone())
+        );
+        let to_c: Lib3hToClient = to_s.try_into().expect("A Lib3hToClient protocol");
+        assert_eq!(to_c, Lib3hToClient::SignalReceived(signal_data));
+    }
 }
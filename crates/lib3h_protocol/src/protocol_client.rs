@@ -30,6 +30,8 @@ pub enum Lib3hClientProtocol {
     SendDirectMessage(DirectMessageData),
     /// Our response to a direct message from another agent.
     HandleSendDirectMessageResult(DirectMessageData),
+    /// Send a message to some agents, or all agents of a space, expecting no response
+    SendSignal(SignalData),
 
     // -- Entry -- //
     /// Request an Entry from the dht network
@@ -29,6 +29,8 @@ pub enum Lib3hServerProtocol {
     SendDirectMessageResult(DirectMessageData),
     /// Request to handle a direct message another agent has sent us.
     HandleSendDirectMessage(DirectMessageData),
+    /// Notification of a signal another agent sent us.
+    SignalReceived(SignalData),
 
     // -- Entry -- //
     /// Response from requesting dht data from the network
@@ -107,7 +107,8 @@ impl Lib3hUri {
         self.0.scheme()
     }
 
-    /// The hostname portion of the url (eg. `127.0.0.1` or `foo.com`), if present.
+    /// The hostname portion of the url (eg. `127.0.0.1`, `[::1]` or `foo.com`), if present.
+    /// IPv6 addresses keep their brackets, so `hostname:port` stays valid.
     pub fn hostname(&self) -> Option<String> {
         self.host().map(|host| host.to_string())
     }
@@ -174,9 +175,14 @@ impl Builder {
         url.try_into().map(|url| Builder { url: url.0 })
     }
 
+    /// Sets the host. IPv6 addresses may be given with or without brackets.
     pub fn with_host(&mut self, host: &str) -> &mut Self {
+        let host = match host.parse::<std::net::Ipv6Addr>() {
+            Ok(ip) => format!("[{}]", ip),
+            Err(_) => host.to_string(),
+        };
         self.url
-            .set_host(Some(host))
+            .set_host(Some(&host))
             .unwrap_or_else(|e| panic!("Error setting host {:?}: {:?}", host, e));
         self
     }
@@ -337,4 +343,36 @@ mod tests {
 
         assert_eq!(url.to_string(), "wss://127.0.0.1:9000/");
     }
+
+    #[test]
+    fn test_uri_ipv6() {
+        let uri = Lib3hUri::try_from("wss://[::1]:9000/").unwrap();
+        assert_eq!(
+            Some(url::Host::Ipv6(std::net::Ipv6Addr::LOCALHOST)),
+            uri.host()
+        );
+        assert_eq!(Some("[::1]".to_string()), uri.hostname());
+        assert_eq!(Some(9000), uri.port());
+
+        let uri = Lib3hUri::try_from("mocknet://[fd00::2]:9000/").unwrap();
+        assert_eq!(Some("[fd00::2]".to_string()), uri.hostname());
+    }
+
+    #[test]
+    fn test_uri_builder_ipv6() {
+        let expected = "wss://[fd00::2]:9000/";
+        for host in &["fd00::2", "[fd00::2]"] {
+            let url = Builder::with_raw_url("wss://127.0.0.1/")
+                .unwrap()
+                .with_host(host)
+                .with_port(9000)
+                .build();
+            assert_eq!(expected, url.to_string());
+        }
+        let url = Builder::with_raw_url(expected)
+            .unwrap()
+            .with_host("127.0.0.1")
+            .build();
+        assert_eq!("wss://127.0.0.1:9000/", url.to_string());
+    }
 }
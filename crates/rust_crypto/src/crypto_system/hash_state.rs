@@ -0,0 +1,65 @@
+//! Incremental hash states, wrapping the sha2 and blake2b_simd hashers.
+
+use lib3h_crypto_api::{Buffer, CryptoError, CryptoResult, HashState};
+use sha2::Digest;
+
+/// incremental sha256 or sha512 hash
+pub struct ShaState<D: Digest + Send>(D);
+
+impl<D: Digest + Send> ShaState<D> {
+    pub fn new() -> Self {
+        ShaState(D::new())
+    }
+}
+
+impl<D: Digest + Send> HashState for ShaState<D> {
+    fn update(&mut self, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+        self.0.update(&*data.read_lock());
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>, hash: &mut Box<dyn Buffer>) -> CryptoResult<()> {
+        if hash.len() != D::output_size() {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        hash.write(0, &self.0.finalize())
+    }
+}
+
+/// incremental (BLAKE2b) generic hash
+pub struct GenericHashState {
+    state: blake2b_simd::State,
+    hash_size: usize,
+}
+
+impl GenericHashState {
+    /// sizes must have been checked by the caller
+    #[allow(clippy::borrowed_box)]
+    pub fn new(hash_size: usize, key: Option<&Box<dyn Buffer>>) -> Self {
+        let mut params = blake2b_simd::Params::new();
+        params.hash_length(hash_size);
+        if let Some(key) = key {
+            params.key(&key.read_lock());
+        }
+        GenericHashState {
+            state: params.to_state(),
+            hash_size,
+        }
+    }
+}
+
+impl HashState for GenericHashState {
+    fn update(&mut self, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+        self.state.update(&data.read_lock());
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>, hash: &mut Box<dyn Buffer>) -> CryptoResult<()> {
+        if hash.len() != self.hash_size {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        hash.write(0, self.state.finalize().as_bytes())
+    }
+}
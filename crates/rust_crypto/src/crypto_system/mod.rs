@@ -0,0 +1,1279 @@
+/// a pure rust implementation of lib3h_crypto_api::CryptoSystem,
+/// interoperable with lib3h_sodium::SodiumCryptoSystem
+///
+/// # Examples
+///
+/// ```
+/// extern crate lib3h_crypto_api;
+/// use lib3h_crypto_api::{Buffer, CryptoSystem};
+///
+/// extern crate lib3h_rust_crypto;
+/// use lib3h_rust_crypto::RustCryptoSystem;
+///
+/// // CryptoSystem is designed to be used through trait-objects like this:
+/// fn test(crypto: Box<dyn CryptoSystem>) {
+///     let mut seed = crypto.buf_new_secure(crypto.sign_seed_bytes());
+///     crypto.randombytes_buf(&mut seed).unwrap();
+///
+///     let mut pubkey: Box<dyn Buffer> =
+///         Box::new(vec![0; crypto.sign_public_key_bytes()]);
+///     let mut seckey = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+///
+///     crypto.sign_seed_keypair(&seed, &mut pubkey, &mut seckey).unwrap();
+///
+///     let mut signature: Box<dyn Buffer> =
+///         Box::new(vec![0; crypto.sign_bytes()]);
+///
+///     let message: Box<dyn Buffer> = Box::new(vec![1, 2, 3, 4]);
+///     let bad_message: Box<dyn Buffer> = Box::new(vec![4, 3, 2, 1]);
+///
+///     crypto.sign(
+///         &mut signature, &message, &seckey).unwrap();
+///
+///     assert!(crypto.sign_verify(
+///         &signature, &message, &pubkey).unwrap());
+///     assert!(!crypto.sign_verify(
+///         &signature, &bad_message, &pubkey).unwrap());
+/// }
+///
+/// fn main() {
+///     let crypto: Box<dyn CryptoSystem> =
+///         Box::new(RustCryptoSystem::new());
+///     test(crypto);
+/// }
+/// ```
+#[derive(Clone)]
+pub struct RustCryptoSystem {
+    pwhash_ops_limit: u64,
+    pwhash_mem_limit: usize,
+    pwhash_alg: i32,
+}
+
+// the pwhash parameters have the values of their libsodium counterparts,
+// so a password hashes the same with lib3h_sodium
+pub const PWHASH_OPSLIMIT_INTERACTIVE: u64 = 2;
+pub const PWHASH_OPSLIMIT_MODERATE: u64 = 3;
+pub const PWHASH_OPSLIMIT_SENSITIVE: u64 = 4;
+
+pub const PWHASH_MEMLIMIT_INTERACTIVE: usize = 67_108_864;
+pub const PWHASH_MEMLIMIT_MODERATE: usize = 268_435_456;
+pub const PWHASH_MEMLIMIT_SENSITIVE: usize = 1_073_741_824;
+
+pub const PWHASH_ALG_ARGON2I13: i32 = 1;
+pub const PWHASH_ALG_ARGON2ID13: i32 = 2;
+
+impl RustCryptoSystem {
+    pub fn new() -> Self {
+        Self {
+            pwhash_ops_limit: PWHASH_OPSLIMIT_SENSITIVE,
+            pwhash_mem_limit: PWHASH_MEMLIMIT_SENSITIVE,
+            pwhash_alg: PWHASH_ALG_ARGON2ID13,
+        }
+    }
+
+    pub fn set_pwhash_opslimit(mut self, opslimit: u64) -> Self {
+        self.pwhash_ops_limit = opslimit;
+        self
+    }
+
+    pub fn set_pwhash_memlimit(mut self, memlimit: usize) -> Self {
+        self.pwhash_mem_limit = memlimit;
+        self
+    }
+
+    pub fn set_pwhash_alg(mut self, alg: i32) -> Self {
+        self.pwhash_alg = alg;
+        self
+    }
+
+    pub fn set_pwhash_interactive(self) -> Self {
+        self.set_pwhash_opslimit(PWHASH_OPSLIMIT_INTERACTIVE)
+            .set_pwhash_memlimit(PWHASH_MEMLIMIT_INTERACTIVE)
+    }
+}
+
+impl Default for RustCryptoSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+use blake2b_simd::Params as Blake2bParams;
+use chacha20poly1305::{
+    aead::{AeadInPlace, NewAead},
+    XChaCha20Poly1305,
+};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use ed25519_dalek::Signer;
+use lib3h_crypto_api::{
+    Buffer, CryptoError, CryptoResult, CryptoSystem, HashState, SecretStreamTag,
+};
+use rand::{rngs::OsRng, RngCore};
+use sha2::Digest;
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+use zeroize::Zeroize;
+
+mod hash_state;
+use hash_state::{GenericHashState, ShaState};
+
+mod sealed_box;
+mod secretstream;
+
+mod secure_buffer;
+pub use secure_buffer::SecureBuffer;
+
+const HASH_SHA256_BYTES: usize = 32;
+const HASH_SHA512_BYTES: usize = 64;
+const GENERIC_HASH_MIN_BYTES: usize = 16;
+const GENERIC_HASH_MAX_BYTES: usize = 64;
+const GENERIC_HASH_KEY_MIN_BYTES: usize = 16;
+const GENERIC_HASH_KEY_MAX_BYTES: usize = 64;
+const PWHASH_SALT_BYTES: usize = 16;
+const KDF_KEY_BYTES: usize = 32;
+const KDF_CONTEXT_BYTES: usize = 8;
+const KDF_MIN_BYTES: usize = 16;
+const KDF_MAX_BYTES: usize = 64;
+const SIGN_SEED_BYTES: usize = 32;
+const SIGN_PUBLIC_KEY_BYTES: usize = 32;
+const SIGN_SECRET_KEY_BYTES: usize = 64;
+const SIGN_BYTES: usize = 64;
+const KX_SEED_BYTES: usize = 32;
+const KX_PUBLIC_KEY_BYTES: usize = 32;
+const KX_SECRET_KEY_BYTES: usize = 32;
+const KX_SESSION_KEY_BYTES: usize = 32;
+const AEAD_NONCE_BYTES: usize = 24;
+const AEAD_AUTH_BYTES: usize = 16;
+const AEAD_SECRET_BYTES: usize = 32;
+
+/// the x25519 secret of a kx keypair, sodium does not clamp it before storing
+fn kx_public_key(public_key: &mut [u8], secret_key: &[u8]) {
+    let mut sk = [0; KX_SECRET_KEY_BYTES];
+    sk.copy_from_slice(secret_key);
+    public_key.copy_from_slice(&x25519(sk, X25519_BASEPOINT_BYTES));
+    sk.zeroize();
+}
+
+/// crypto_kx session keys: the blake2b-512 of the x25519 shared secret
+/// and both public keys, the client receives with the first half
+fn kx_session_keys(
+    secret_key: &[u8],
+    other_public_key: &[u8],
+    client_pk: &[u8],
+    server_pk: &[u8],
+) -> CryptoResult<[u8; 2 * KX_SESSION_KEY_BYTES]> {
+    let mut sk = [0; KX_SECRET_KEY_BYTES];
+    sk.copy_from_slice(secret_key);
+    let mut pk = [0; KX_PUBLIC_KEY_BYTES];
+    pk.copy_from_slice(other_public_key);
+    let mut shared = x25519(sk, pk);
+    sk.zeroize();
+    if shared.iter().all(|b| *b == 0) {
+        return Err(CryptoError::new("low order public key"));
+    }
+    let hash = Blake2bParams::new()
+        .hash_length(2 * KX_SESSION_KEY_BYTES)
+        .to_state()
+        .update(&shared)
+        .update(client_pk)
+        .update(server_pk)
+        .finalize();
+    shared.zeroize();
+    let mut keys = [0; 2 * KX_SESSION_KEY_BYTES];
+    keys.copy_from_slice(hash.as_bytes());
+    Ok(keys)
+}
+
+impl CryptoSystem for RustCryptoSystem {
+    fn box_clone(&self) -> Box<dyn CryptoSystem> {
+        Box::new(self.clone())
+    }
+
+    fn as_crypto_system(&self) -> &dyn CryptoSystem {
+        self
+    }
+
+    fn buf_new_secure(&self, size: usize) -> Box<dyn Buffer> {
+        Box::new(SecureBuffer::new(size))
+    }
+
+    fn randombytes_buf(&self, buffer: &mut Box<dyn Buffer>) -> CryptoResult<()> {
+        let mut buffer = buffer.write_lock();
+        OsRng
+            .try_fill_bytes(&mut buffer)
+            .map_err(|e| CryptoError::Generic(e.to_string()))
+    }
+
+    fn hash_sha256_bytes(&self) -> usize {
+        HASH_SHA256_BYTES
+    }
+    fn hash_sha512_bytes(&self) -> usize {
+        HASH_SHA512_BYTES
+    }
+
+    fn hash_sha256(&self, hash: &mut Box<dyn Buffer>, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+        if hash.len() != self.hash_sha256_bytes() {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        hash.write(0, &sha2::Sha256::digest(&data.read_lock()))
+    }
+
+    fn hash_sha512(&self, hash: &mut Box<dyn Buffer>, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+        if hash.len() != self.hash_sha512_bytes() {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        hash.write(0, &sha2::Sha512::digest(&data.read_lock()))
+    }
+
+    fn hash_sha256_init(&self) -> CryptoResult<Box<dyn HashState>> {
+        Ok(Box::new(ShaState::<sha2::Sha256>::new()))
+    }
+
+    fn hash_sha512_init(&self) -> CryptoResult<Box<dyn HashState>> {
+        Ok(Box::new(ShaState::<sha2::Sha512>::new()))
+    }
+
+    fn generic_hash_min_bytes(&self) -> usize {
+        GENERIC_HASH_MIN_BYTES
+    }
+
+    fn generic_hash_max_bytes(&self) -> usize {
+        GENERIC_HASH_MAX_BYTES
+    }
+
+    fn generic_hash_key_min_bytes(&self) -> usize {
+        GENERIC_HASH_KEY_MIN_BYTES
+    }
+
+    fn generic_hash_key_max_bytes(&self) -> usize {
+        GENERIC_HASH_KEY_MAX_BYTES
+    }
+
+    fn generic_hash(
+        &self,
+        hash: &mut Box<dyn Buffer>,
+        data: &Box<dyn Buffer>,
+        key: Option<&Box<dyn Buffer>>,
+    ) -> CryptoResult<()> {
+        if hash.len() < self.generic_hash_min_bytes() || hash.len() > self.generic_hash_max_bytes()
+        {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        let mut params = Blake2bParams::new();
+        params.hash_length(hash.len());
+        if let Some(key) = key {
+            if key.len() < self.generic_hash_key_min_bytes()
+                || key.len() > self.generic_hash_key_max_bytes()
+            {
+                return Err(CryptoError::BadKeySize);
+            }
+            params.key(&key.read_lock());
+        }
+
+        let out = params.hash(&data.read_lock());
+        hash.write(0, out.as_bytes())
+    }
+
+    fn generic_hash_init(
+        &self,
+        hash_size: usize,
+        key: Option<&Box<dyn Buffer>>,
+    ) -> CryptoResult<Box<dyn HashState>> {
+        if hash_size < self.generic_hash_min_bytes() || hash_size > self.generic_hash_max_bytes() {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        if let Some(key) = key {
+            if key.len() < self.generic_hash_key_min_bytes()
+                || key.len() > self.generic_hash_key_max_bytes()
+            {
+                return Err(CryptoError::BadKeySize);
+            }
+        }
+
+        Ok(Box::new(GenericHashState::new(hash_size, key)))
+    }
+
+    fn pwhash_salt_bytes(&self) -> usize {
+        PWHASH_SALT_BYTES
+    }
+    fn pwhash_bytes(&self) -> usize {
+        32
+    }
+
+    fn pwhash(
+        &self,
+        hash: &mut Box<dyn Buffer>,
+        password: &Box<dyn Buffer>,
+        salt: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if hash.len() != self.pwhash_bytes() {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        if salt.len() != self.pwhash_salt_bytes() {
+            return Err(CryptoError::BadSaltSize);
+        }
+
+        let variant = match self.pwhash_alg {
+            PWHASH_ALG_ARGON2I13 => argon2::Variant::Argon2i,
+            PWHASH_ALG_ARGON2ID13 => argon2::Variant::Argon2id,
+            alg => return Err(CryptoError::Generic(format!("bad pwhash alg {}", alg))),
+        };
+        let config = argon2::Config {
+            variant,
+            version: argon2::Version::Version13,
+            mem_cost: (self.pwhash_mem_limit / 1024) as u32,
+            time_cost: self.pwhash_ops_limit as u32,
+            lanes: 1,
+            thread_mode: argon2::ThreadMode::Sequential,
+            secret: &[],
+            ad: &[],
+            hash_length: hash.len() as u32,
+        };
+
+        let mut out = argon2::hash_raw(&password.read_lock(), &salt.read_lock(), &config).map_err(
+            |e| match e {
+                argon2::Error::MemoryTooLittle | argon2::Error::MemoryTooMuch => {
+                    CryptoError::OutOfMemory
+                }
+                e => CryptoError::Generic(e.to_string()),
+            },
+        )?;
+        let result = hash.write(0, &out);
+        out.zeroize();
+        result
+    }
+
+    fn kdf_key_bytes(&self) -> usize {
+        KDF_KEY_BYTES
+    }
+
+    fn kdf_context_bytes(&self) -> usize {
+        KDF_CONTEXT_BYTES
+    }
+
+    fn kdf_min_bytes(&self) -> usize {
+        KDF_MIN_BYTES
+    }
+
+    fn kdf_max_bytes(&self) -> usize {
+        KDF_MAX_BYTES
+    }
+
+    fn kdf(
+        &self,
+        out_buffer: &mut Box<dyn Buffer>,
+        index: u64,
+        context: &Box<dyn Buffer>,
+        parent: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if out_buffer.len() < self.kdf_min_bytes() || out_buffer.len() > self.kdf_max_bytes() {
+            return Err(CryptoError::BadOutBufferSize);
+        }
+
+        if parent.len() != self.kdf_key_bytes() {
+            return Err(CryptoError::BadParentSize);
+        }
+
+        if context.len() != self.kdf_context_bytes() {
+            return Err(CryptoError::BadContextSize);
+        }
+
+        // crypto_kdf_derive_from_key: blake2b keyed with the parent,
+        // salted with the index and personalized with the context
+        let out = Blake2bParams::new()
+            .hash_length(out_buffer.len())
+            .key(&parent.read_lock())
+            .salt(&index.to_le_bytes())
+            .personal(&context.read_lock())
+            .hash(&[]);
+        out_buffer.write(0, out.as_bytes())
+    }
+
+    fn sign_seed_bytes(&self) -> usize {
+        SIGN_SEED_BYTES
+    }
+    fn sign_public_key_bytes(&self) -> usize {
+        SIGN_PUBLIC_KEY_BYTES
+    }
+    fn sign_secret_key_bytes(&self) -> usize {
+        SIGN_SECRET_KEY_BYTES
+    }
+    fn sign_bytes(&self) -> usize {
+        SIGN_BYTES
+    }
+
+    fn sign_seed_keypair(
+        &self,
+        seed: &Box<dyn Buffer>,
+        public_key: &mut Box<dyn Buffer>,
+        secret_key: &mut Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if seed.len() != self.sign_seed_bytes() {
+            return Err(CryptoError::BadSeedSize);
+        }
+
+        if public_key.len() != self.sign_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if secret_key.len() != self.sign_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let secret = ed25519_dalek::SecretKey::from_bytes(&seed.read_lock())
+            .map_err(|e| CryptoError::Generic(e.to_string()))?;
+        let public = ed25519_dalek::PublicKey::from(&secret);
+
+        // like libsodium, the secret key is the seed followed by the public key
+        public_key.write(0, public.as_bytes())?;
+        secret_key.write(0, secret.as_bytes())?;
+        secret_key.write(SIGN_SEED_BYTES, public.as_bytes())
+    }
+
+    fn sign_keypair(
+        &self,
+        public_key: &mut Box<dyn Buffer>,
+        secret_key: &mut Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        let mut seed = self.buf_new_secure(self.sign_seed_bytes());
+        self.randombytes_buf(&mut seed)?;
+        self.sign_seed_keypair(&seed, public_key, secret_key)
+    }
+
+    fn sign(
+        &self,
+        signature: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        secret_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if signature.len() != self.sign_bytes() {
+            return Err(CryptoError::BadSignatureSize);
+        }
+
+        if secret_key.len() != self.sign_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let keypair = ed25519_dalek::Keypair::from_bytes(&secret_key.read_lock())
+            .map_err(|e| CryptoError::Generic(e.to_string()))?;
+        signature.write(0, &keypair.sign(&message.read_lock()).to_bytes())
+    }
+
+    fn sign_verify(
+        &self,
+        signature: &Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        public_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<bool> {
+        if signature.len() != self.sign_bytes() {
+            return Err(CryptoError::BadSignatureSize);
+        }
+
+        if public_key.len() != self.sign_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        let public_key = match ed25519_dalek::PublicKey::from_bytes(&public_key.read_lock()) {
+            Ok(public_key) => public_key,
+            Err(_) => return Ok(false),
+        };
+        let signature = match ed25519_dalek::Signature::from_bytes(&signature.read_lock()) {
+            Ok(signature) => signature,
+            Err(_) => return Ok(false),
+        };
+        // strict like libsodium: reject small order keys and signature points
+        Ok(public_key
+            .verify_strict(&message.read_lock(), &signature)
+            .is_ok())
+    }
+
+    fn kx_seed_bytes(&self) -> usize {
+        KX_SEED_BYTES
+    }
+    fn kx_public_key_bytes(&self) -> usize {
+        KX_PUBLIC_KEY_BYTES
+    }
+    fn kx_secret_key_bytes(&self) -> usize {
+        KX_SECRET_KEY_BYTES
+    }
+    fn kx_session_key_bytes(&self) -> usize {
+        KX_SESSION_KEY_BYTES
+    }
+
+    fn kx_seed_keypair(
+        &self,
+        seed: &Box<dyn Buffer>,
+        public_key: &mut Box<dyn Buffer>,
+        secret_key: &mut Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if seed.len() != self.kx_seed_bytes() {
+            return Err(CryptoError::BadSeedSize);
+        }
+
+        if public_key.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if secret_key.len() != self.kx_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        // crypto_kx_seed_keypair: the secret key is the blake2b of the seed
+        let mut secret = [0; KX_SECRET_KEY_BYTES];
+        secret.copy_from_slice(
+            Blake2bParams::new()
+                .hash_length(KX_SECRET_KEY_BYTES)
+                .hash(&seed.read_lock())
+                .as_bytes(),
+        );
+        kx_public_key(&mut public_key.write_lock(), &secret);
+        let result = secret_key.write(0, &secret);
+        secret.zeroize();
+        result
+    }
+
+    fn kx_keypair(
+        &self,
+        public_key: &mut Box<dyn Buffer>,
+        secret_key: &mut Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if public_key.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if secret_key.len() != self.kx_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        self.randombytes_buf(secret_key)?;
+        kx_public_key(&mut public_key.write_lock(), &secret_key.read_lock());
+        Ok(())
+    }
+
+    fn kx_client_session_keys(
+        &self,
+        client_rx: &mut Box<dyn Buffer>,
+        client_tx: &mut Box<dyn Buffer>,
+        client_pk: &Box<dyn Buffer>,
+        client_sk: &Box<dyn Buffer>,
+        server_pk: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if client_rx.len() != self.kx_session_key_bytes() {
+            return Err(CryptoError::BadRxSessionKeySize);
+        }
+
+        if client_tx.len() != self.kx_session_key_bytes() {
+            return Err(CryptoError::BadTxSessionKeySize);
+        }
+
+        if client_pk.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if client_sk.len() != self.kx_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        if server_pk.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        let server_pk = server_pk.read_lock();
+        let mut keys = kx_session_keys(
+            &client_sk.read_lock(),
+            &server_pk,
+            &client_pk.read_lock(),
+            &server_pk,
+        )?;
+        client_rx.write(0, &keys[..KX_SESSION_KEY_BYTES])?;
+        client_tx.write(0, &keys[KX_SESSION_KEY_BYTES..])?;
+        keys.zeroize();
+        Ok(())
+    }
+
+    fn kx_server_session_keys(
+        &self,
+        server_rx: &mut Box<dyn Buffer>,
+        server_tx: &mut Box<dyn Buffer>,
+        server_pk: &Box<dyn Buffer>,
+        server_sk: &Box<dyn Buffer>,
+        client_pk: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if server_rx.len() != self.kx_session_key_bytes() {
+            return Err(CryptoError::BadRxSessionKeySize);
+        }
+
+        if server_tx.len() != self.kx_session_key_bytes() {
+            return Err(CryptoError::BadTxSessionKeySize);
+        }
+
+        if server_pk.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if server_sk.len() != self.kx_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        if client_pk.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        let client_pk = client_pk.read_lock();
+        let mut keys = kx_session_keys(
+            &server_sk.read_lock(),
+            &client_pk,
+            &client_pk,
+            &server_pk.read_lock(),
+        )?;
+        server_rx.write(0, &keys[KX_SESSION_KEY_BYTES..])?;
+        server_tx.write(0, &keys[..KX_SESSION_KEY_BYTES])?;
+        keys.zeroize();
+        Ok(())
+    }
+
+    fn sign_to_kx_public_key(
+        &self,
+        kx_public_key: &mut Box<dyn Buffer>,
+        sign_public_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if kx_public_key.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if sign_public_key.len() != self.sign_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        // like libsodium, refuse the keys outside of the main subgroup
+        let point = CompressedEdwardsY::from_slice(&sign_public_key.read_lock())
+            .decompress()
+            .filter(|point| !point.is_small_order() && point.is_torsion_free())
+            .ok_or_else(|| CryptoError::new("not a valid ed25519 public key"))?;
+        kx_public_key.write(0, point.to_montgomery().as_bytes())
+    }
+
+    fn sign_to_kx_secret_key(
+        &self,
+        kx_secret_key: &mut Box<dyn Buffer>,
+        sign_secret_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if kx_secret_key.len() != self.kx_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        if sign_secret_key.len() != self.sign_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        // the clamped first half of the sha512 of the seed, like ed25519 does
+        let mut hash = sha2::Sha512::digest(&sign_secret_key.read_lock()[..SIGN_SEED_BYTES]);
+        hash[0] &= 248;
+        hash[31] &= 127;
+        hash[31] |= 64;
+        let result = kx_secret_key.write(0, &hash[..KX_SECRET_KEY_BYTES]);
+        hash.as_mut_slice().zeroize();
+        result
+    }
+
+    fn aead_nonce_bytes(&self) -> usize {
+        AEAD_NONCE_BYTES
+    }
+
+    fn aead_auth_bytes(&self) -> usize {
+        AEAD_AUTH_BYTES
+    }
+
+    fn aead_secret_bytes(&self) -> usize {
+        AEAD_SECRET_BYTES
+    }
+
+    fn aead_encrypt(
+        &self,
+        cipher: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+        nonce: &Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if cipher.len() != message.len() + self.aead_auth_bytes() {
+            return Err(CryptoError::BadCipherSize);
+        }
+
+        if nonce.len() != self.aead_nonce_bytes() {
+            return Err(CryptoError::BadNonceSize);
+        }
+
+        if secret.len() != self.aead_secret_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let my_adata_locker;
+        let mut my_adata: &[u8] = &[];
+        if let Some(adata) = adata {
+            my_adata_locker = adata.read_lock();
+            my_adata = &my_adata_locker;
+        }
+
+        let aead = XChaCha20Poly1305::new(secret.read_lock()[..].into());
+        let mut cipher = cipher.write_lock();
+        let (encrypted, tag) = cipher.split_at_mut(message.len());
+        encrypted.copy_from_slice(&message.read_lock());
+        let computed_tag = aead
+            .encrypt_in_place_detached(nonce.read_lock()[..].into(), my_adata, encrypted)
+            .map_err(|_| CryptoError::new("aead encrypt fail"))?;
+        tag.copy_from_slice(&computed_tag);
+
+        Ok(())
+    }
+
+    fn aead_decrypt(
+        &self,
+        message: &mut Box<dyn Buffer>,
+        cipher: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+        nonce: &Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if cipher.len() < self.aead_auth_bytes()
+            || message.len() != cipher.len() - self.aead_auth_bytes()
+        {
+            return Err(CryptoError::BadMessageSize);
+        }
+
+        if nonce.len() != self.aead_nonce_bytes() {
+            return Err(CryptoError::BadNonceSize);
+        }
+
+        if secret.len() != self.aead_secret_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let my_adata_locker;
+        let mut my_adata: &[u8] = &[];
+        if let Some(adata) = adata {
+            my_adata_locker = adata.read_lock();
+            my_adata = &my_adata_locker;
+        }
+
+        let aead = XChaCha20Poly1305::new(secret.read_lock()[..].into());
+        let cipher = cipher.read_lock();
+        let (encrypted, tag) = cipher.split_at(message.len());
+        let mut decrypted = encrypted.to_vec();
+        let result = aead
+            .decrypt_in_place_detached(
+                nonce.read_lock()[..].into(),
+                my_adata,
+                &mut decrypted,
+                tag.into(),
+            )
+            .map_err(|_| CryptoError::CouldNotDecrypt)
+            .and_then(|_| message.write(0, &decrypted));
+        decrypted.zeroize();
+        result
+    }
+
+    fn secretstream_header_bytes(&self) -> usize {
+        secretstream::HEADER_BYTES
+    }
+
+    fn secretstream_auth_bytes(&self) -> usize {
+        secretstream::AUTH_BYTES
+    }
+
+    fn secretstream_secret_bytes(&self) -> usize {
+        secretstream::KEY_BYTES
+    }
+
+    fn secretstream_state_bytes(&self) -> usize {
+        secretstream::STATE_BYTES
+    }
+
+    fn secretstream_init_push(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        header: &mut Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if header.len() != self.secretstream_header_bytes() {
+            return Err(CryptoError::BadHeaderSize);
+        }
+
+        if secret.len() != self.secretstream_secret_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        self.randombytes_buf(header)?;
+        secretstream::init(
+            &mut state.write_lock(),
+            &header.read_lock(),
+            &secret.read_lock(),
+        );
+
+        Ok(())
+    }
+
+    fn secretstream_push(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        cipher: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+        tag: SecretStreamTag,
+    ) -> CryptoResult<()> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if cipher.len() != message.len() + self.secretstream_auth_bytes() {
+            return Err(CryptoError::BadCipherSize);
+        }
+
+        let my_adata_locker;
+        let mut my_adata: &[u8] = &[];
+        if let Some(adata) = adata {
+            my_adata_locker = adata.read_lock();
+            my_adata = &my_adata_locker;
+        }
+
+        let tag = match tag {
+            SecretStreamTag::Message => secretstream::TAG_MESSAGE,
+            SecretStreamTag::Push => secretstream::TAG_PUSH,
+            SecretStreamTag::Rekey => secretstream::TAG_REKEY,
+            SecretStreamTag::Final => secretstream::TAG_FINAL,
+        };
+
+        secretstream::push(
+            &mut state.write_lock(),
+            &mut cipher.write_lock(),
+            &message.read_lock(),
+            my_adata,
+            tag,
+        );
+
+        Ok(())
+    }
+
+    fn secretstream_init_pull(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        header: &Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if header.len() != self.secretstream_header_bytes() {
+            return Err(CryptoError::BadHeaderSize);
+        }
+
+        if secret.len() != self.secretstream_secret_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        secretstream::init(
+            &mut state.write_lock(),
+            &header.read_lock(),
+            &secret.read_lock(),
+        );
+
+        Ok(())
+    }
+
+    fn secretstream_pull(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        message: &mut Box<dyn Buffer>,
+        cipher: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+    ) -> CryptoResult<SecretStreamTag> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if cipher.len() < self.secretstream_auth_bytes()
+            || message.len() != cipher.len() - self.secretstream_auth_bytes()
+        {
+            return Err(CryptoError::BadMessageSize);
+        }
+
+        let my_adata_locker;
+        let mut my_adata: &[u8] = &[];
+        if let Some(adata) = adata {
+            my_adata_locker = adata.read_lock();
+            my_adata = &my_adata_locker;
+        }
+
+        let tag = secretstream::pull(
+            &mut state.write_lock(),
+            &mut message.write_lock(),
+            &cipher.read_lock(),
+            my_adata,
+        )?;
+
+        match tag {
+            secretstream::TAG_MESSAGE => Ok(SecretStreamTag::Message),
+            secretstream::TAG_PUSH => Ok(SecretStreamTag::Push),
+            secretstream::TAG_REKEY => Ok(SecretStreamTag::Rekey),
+            secretstream::TAG_FINAL => Ok(SecretStreamTag::Final),
+            _ => Err(CryptoError::CouldNotDecrypt),
+        }
+    }
+
+    fn sealed_box_auth_bytes(&self) -> usize {
+        sealed_box::AUTH_BYTES
+    }
+
+    fn sealed_box_encrypt(
+        &self,
+        cipher: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        public_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if cipher.len() != message.len() + self.sealed_box_auth_bytes() {
+            return Err(CryptoError::BadCipherSize);
+        }
+
+        if public_key.len() != sealed_box::PUBLIC_KEY_BYTES {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        sealed_box::seal(
+            &mut cipher.write_lock(),
+            &message.read_lock(),
+            &public_key.read_lock(),
+        )
+    }
+
+    fn sealed_box_decrypt(
+        &self,
+        message: &mut Box<dyn Buffer>,
+        cipher: &Box<dyn Buffer>,
+        public_key: &Box<dyn Buffer>,
+        secret_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if cipher.len() < self.sealed_box_auth_bytes()
+            || message.len() != cipher.len() - self.sealed_box_auth_bytes()
+        {
+            return Err(CryptoError::BadMessageSize);
+        }
+
+        if public_key.len() != sealed_box::PUBLIC_KEY_BYTES {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if secret_key.len() != sealed_box::SECRET_KEY_BYTES {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        sealed_box::open(
+            &mut message.write_lock(),
+            &cipher.read_lock(),
+            &public_key.read_lock(),
+            &secret_key.read_lock(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use curve25519_dalek::constants::EIGHT_TORSION;
+    use lib3h_crypto_api::crypto_system_test;
+    use lib3h_sodium::SodiumCryptoSystem;
+
+    fn rust_crypto() -> Box<dyn CryptoSystem> {
+        Box::new(RustCryptoSystem::new().set_pwhash_interactive())
+    }
+
+    fn sodium() -> Box<dyn CryptoSystem> {
+        Box::new(SodiumCryptoSystem::new().set_pwhash_interactive())
+    }
+
+    fn random_buf(crypto: &Box<dyn CryptoSystem>, size: usize) -> Box<dyn Buffer> {
+        let mut buf: Box<dyn Buffer> = Box::new(vec![0; size]);
+        crypto.randombytes_buf(&mut buf).unwrap();
+        buf
+    }
+
+    fn assert_same_bytes(a: &Box<dyn Buffer>, b: &Box<dyn Buffer>) {
+        assert_eq!(&*a.read_lock(), &*b.read_lock());
+    }
+
+    #[test]
+    fn rust_crypto_should_pass_crypto_system_full_suite() {
+        crypto_system_test::full_suite(rust_crypto());
+    }
+
+    #[test]
+    fn rust_crypto_should_hash_like_sodium() {
+        let (rust, sodium) = (rust_crypto(), sodium());
+        let data = random_buf(&rust, 100);
+        let key = random_buf(&rust, 32);
+
+        let mut rust_hash: Box<dyn Buffer> = Box::new(vec![0; rust.hash_sha512_bytes()]);
+        let mut sodium_hash: Box<dyn Buffer> = Box::new(vec![0; sodium.hash_sha512_bytes()]);
+        rust.hash_sha512(&mut rust_hash, &data).unwrap();
+        sodium.hash_sha512(&mut sodium_hash, &data).unwrap();
+        assert_same_bytes(&rust_hash, &sodium_hash);
+
+        let mut rust_hash: Box<dyn Buffer> = Box::new(vec![0; 24]);
+        let mut sodium_hash: Box<dyn Buffer> = Box::new(vec![0; 24]);
+        rust.generic_hash(&mut rust_hash, &data, Some(&key))
+            .unwrap();
+        sodium
+            .generic_hash(&mut sodium_hash, &data, Some(&key))
+            .unwrap();
+        assert_same_bytes(&rust_hash, &sodium_hash);
+
+        let salt = random_buf(&rust, rust.pwhash_salt_bytes());
+        let mut rust_hash: Box<dyn Buffer> = Box::new(vec![0; rust.pwhash_bytes()]);
+        let mut sodium_hash: Box<dyn Buffer> = Box::new(vec![0; sodium.pwhash_bytes()]);
+        rust.pwhash(&mut rust_hash, &data, &salt).unwrap();
+        sodium.pwhash(&mut sodium_hash, &data, &salt).unwrap();
+        assert_same_bytes(&rust_hash, &sodium_hash);
+    }
+
+    #[test]
+    fn rust_crypto_should_kdf_derive_like_sodium() {
+        let crypto = rust_crypto();
+        let ctx: Box<dyn Buffer> = Box::new(vec![1; crypto.kdf_context_bytes()]);
+        let root: Box<dyn Buffer> = Box::new(vec![0; crypto.kdf_key_bytes()]);
+        let mut out: Box<dyn Buffer> = Box::new(vec![0; crypto.kdf_min_bytes()]);
+        crypto.kdf(&mut out, 1, &ctx, &root).unwrap();
+        // the vector of sodium_should_kdf_derive_as_expected
+        assert_eq!(
+            "[163, 55, 238, 63, 149, 30, 99, 242, 9, 249, 55, 237, 48, 207, 230, 249]",
+            format!("{:?}", &*out.read_lock()),
+        );
+    }
+
+    #[test]
+    fn rust_crypto_should_sign_like_sodium() {
+        let (rust, sodium) = (rust_crypto(), sodium());
+        let seed = random_buf(&rust, rust.sign_seed_bytes());
+        let message = random_buf(&rust, 42);
+
+        let mut rust_pk: Box<dyn Buffer> = Box::new(vec![0; rust.sign_public_key_bytes()]);
+        let mut rust_sk: Box<dyn Buffer> = Box::new(vec![0; rust.sign_secret_key_bytes()]);
+        let mut sodium_pk: Box<dyn Buffer> = Box::new(vec![0; sodium.sign_public_key_bytes()]);
+        let mut sodium_sk: Box<dyn Buffer> = Box::new(vec![0; sodium.sign_secret_key_bytes()]);
+        rust.sign_seed_keypair(&seed, &mut rust_pk, &mut rust_sk)
+            .unwrap();
+        sodium
+            .sign_seed_keypair(&seed, &mut sodium_pk, &mut sodium_sk)
+            .unwrap();
+        assert_same_bytes(&rust_pk, &sodium_pk);
+        assert_same_bytes(&rust_sk, &sodium_sk);
+
+        let mut rust_sig: Box<dyn Buffer> = Box::new(vec![0; rust.sign_bytes()]);
+        let mut sodium_sig: Box<dyn Buffer> = Box::new(vec![0; sodium.sign_bytes()]);
+        rust.sign(&mut rust_sig, &message, &rust_sk).unwrap();
+        sodium.sign(&mut sodium_sig, &message, &sodium_sk).unwrap();
+        assert_same_bytes(&rust_sig, &sodium_sig);
+        assert!(sodium.sign_verify(&rust_sig, &message, &rust_pk).unwrap());
+        assert!(rust.sign_verify(&sodium_sig, &message, &sodium_pk).unwrap());
+
+        let mut rust_kx_pk: Box<dyn Buffer> = Box::new(vec![0; rust.kx_public_key_bytes()]);
+        let mut rust_kx_sk: Box<dyn Buffer> = Box::new(vec![0; rust.kx_secret_key_bytes()]);
+        let mut sodium_kx_pk: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_public_key_bytes()]);
+        let mut sodium_kx_sk: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_secret_key_bytes()]);
+        rust.sign_to_kx_public_key(&mut rust_kx_pk, &rust_pk)
+            .unwrap();
+        rust.sign_to_kx_secret_key(&mut rust_kx_sk, &rust_sk)
+            .unwrap();
+        sodium
+            .sign_to_kx_public_key(&mut sodium_kx_pk, &sodium_pk)
+            .unwrap();
+        sodium
+            .sign_to_kx_secret_key(&mut sodium_kx_sk, &sodium_sk)
+            .unwrap();
+        assert_same_bytes(&rust_kx_pk, &sodium_kx_pk);
+        assert_same_bytes(&rust_kx_sk, &sodium_kx_sk);
+    }
+
+    #[test]
+    fn rust_crypto_should_batch_verify_like_sodium() {
+        let (rust, sodium) = (rust_crypto(), sodium());
+        let mut pk: Box<dyn Buffer> = Box::new(vec![0; rust.sign_public_key_bytes()]);
+        let mut sk: Box<dyn Buffer> = Box::new(vec![0; rust.sign_secret_key_bytes()]);
+        rust.sign_keypair(&mut pk, &mut sk).unwrap();
+        let message = random_buf(&rust, 42);
+        let mut sig: Box<dyn Buffer> = Box::new(vec![0; rust.sign_bytes()]);
+        rust.sign(&mut sig, &message, &sk).unwrap();
+
+        // small order points, and a signature of another message
+        let mut identity = vec![0; 32];
+        identity[0] = 1;
+        let identity_pk: Box<dyn Buffer> = Box::new(identity.clone());
+        let mut identity_r_sig: Box<dyn Buffer> = Box::new(sig.read_lock().to_vec());
+        identity_r_sig.write(0, &identity).unwrap();
+        let mut other_sig: Box<dyn Buffer> = Box::new(vec![0; rust.sign_bytes()]);
+        rust.sign(&mut other_sig, &random_buf(&rust, 42), &sk)
+            .unwrap();
+        // R with a torsion component, an ed25519 batch equation accepts it 1 in 8 times
+        let r = CompressedEdwardsY::from_slice(&sig.read_lock()[..32])
+            .decompress()
+            .unwrap();
+        let torsion_r = (r + EIGHT_TORSION[1]).compress();
+        let mut torsion_r_sig: Box<dyn Buffer> = Box::new(sig.read_lock().to_vec());
+        torsion_r_sig.write(0, torsion_r.as_bytes()).unwrap();
+
+        let items = [
+            (&sig, &message, &pk),
+            (&identity_r_sig, &message, &pk),
+            (&sig, &message, &identity_pk),
+            (&other_sig, &message, &pk),
+            (&torsion_r_sig, &message, &pk),
+            (&sig, &message, &pk),
+        ];
+        let results = rust.sign_verify_batch(&items);
+        for ((signature, message, public_key), result) in items.iter().zip(results) {
+            assert_eq!(
+                sodium.sign_verify(signature, message, public_key).unwrap(),
+                result.unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn rust_crypto_should_kx_like_sodium() {
+        let (rust, sodium) = (rust_crypto(), sodium());
+        let seed = random_buf(&rust, rust.kx_seed_bytes());
+
+        let mut c_pk: Box<dyn Buffer> = Box::new(vec![0; rust.kx_public_key_bytes()]);
+        let mut c_sk: Box<dyn Buffer> = Box::new(vec![0; rust.kx_secret_key_bytes()]);
+        let mut sodium_c_pk: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_public_key_bytes()]);
+        let mut sodium_c_sk: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_secret_key_bytes()]);
+        rust.kx_seed_keypair(&seed, &mut c_pk, &mut c_sk).unwrap();
+        sodium
+            .kx_seed_keypair(&seed, &mut sodium_c_pk, &mut sodium_c_sk)
+            .unwrap();
+        assert_same_bytes(&c_pk, &sodium_c_pk);
+        assert_same_bytes(&c_sk, &sodium_c_sk);
+
+        let mut s_pk: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_public_key_bytes()]);
+        let mut s_sk: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_secret_key_bytes()]);
+        sodium.kx_keypair(&mut s_pk, &mut s_sk).unwrap();
+
+        let mut c_rx: Box<dyn Buffer> = Box::new(vec![0; rust.kx_session_key_bytes()]);
+        let mut c_tx: Box<dyn Buffer> = Box::new(vec![0; rust.kx_session_key_bytes()]);
+        let mut s_rx: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_session_key_bytes()]);
+        let mut s_tx: Box<dyn Buffer> = Box::new(vec![0; sodium.kx_session_key_bytes()]);
+        rust.kx_client_session_keys(&mut c_rx, &mut c_tx, &c_pk, &c_sk, &s_pk)
+            .unwrap();
+        sodium
+            .kx_server_session_keys(&mut s_rx, &mut s_tx, &s_pk, &s_sk, &c_pk)
+            .unwrap();
+        assert_same_bytes(&c_rx, &s_tx);
+        assert_same_bytes(&c_tx, &s_rx);
+
+        rust.kx_server_session_keys(&mut c_rx, &mut c_tx, &s_pk, &s_sk, &c_pk)
+            .unwrap();
+        assert_same_bytes(&c_rx, &s_rx);
+        assert_same_bytes(&c_tx, &s_tx);
+    }
+
+    #[test]
+    fn rust_crypto_should_aead_like_sodium() {
+        let (rust, sodium) = (rust_crypto(), sodium());
+        let message = random_buf(&rust, 42);
+        let adata = random_buf(&rust, 16);
+        let nonce = random_buf(&rust, rust.aead_nonce_bytes());
+        let secret = random_buf(&rust, rust.aead_secret_bytes());
+
+        let mut rust_cipher: Box<dyn Buffer> =
+            Box::new(vec![0; message.len() + rust.aead_auth_bytes()]);
+        let mut sodium_cipher: Box<dyn Buffer> =
+            Box::new(vec![0; message.len() + sodium.aead_auth_bytes()]);
+        rust.aead_encrypt(&mut rust_cipher, &message, Some(&adata), &nonce, &secret)
+            .unwrap();
+        sodium
+            .aead_encrypt(&mut sodium_cipher, &message, Some(&adata), &nonce, &secret)
+            .unwrap();
+        assert_same_bytes(&rust_cipher, &sodium_cipher);
+
+        let mut decrypted: Box<dyn Buffer> = Box::new(vec![0; message.len()]);
+        rust.aead_decrypt(
+            &mut decrypted,
+            &sodium_cipher,
+            Some(&adata),
+            &nonce,
+            &secret,
+        )
+        .unwrap();
+        assert_same_bytes(&decrypted, &message);
+    }
+
+    #[test]
+    fn rust_crypto_should_interoperate_with_sodium_secretstreams() {
+        let (rust, sodium) = (rust_crypto(), sodium());
+        let secret = random_buf(&rust, rust.secretstream_secret_bytes());
+        let adata = random_buf(&rust, 5);
+        let tags = [
+            SecretStreamTag::Message,
+            SecretStreamTag::Rekey,
+            SecretStreamTag::Push,
+            SecretStreamTag::Final,
+        ];
+
+        for (pusher, puller) in &[(&rust, &sodium), (&sodium, &rust)] {
+            let mut push_state = pusher.buf_new_secure(pusher.secretstream_state_bytes());
+            let mut pull_state = puller.buf_new_secure(puller.secretstream_state_bytes());
+            let mut header: Box<dyn Buffer> = Box::new(vec![0; pusher.secretstream_header_bytes()]);
+            pusher
+                .secretstream_init_push(&mut push_state, &mut header, &secret)
+                .unwrap();
+            puller
+                .secretstream_init_pull(&mut pull_state, &header, &secret)
+                .unwrap();
+            for (i, tag) in tags.iter().enumerate() {
+                let message = random_buf(&rust, 7 + 13 * i);
+                let adata = if i == 1 { Some(&adata) } else { None };
+                let mut cipher: Box<dyn Buffer> =
+                    Box::new(vec![0; message.len() + pusher.secretstream_auth_bytes()]);
+                pusher
+                    .secretstream_push(&mut push_state, &mut cipher, &message, adata, *tag)
+                    .unwrap();
+                let mut pulled: Box<dyn Buffer> = Box::new(vec![0; message.len()]);
+                assert_eq!(
+                    Ok(*tag),
+                    puller.secretstream_pull(&mut pull_state, &mut pulled, &cipher, adata)
+                );
+                assert_same_bytes(&pulled, &message);
+            }
+        }
+    }
+
+    #[test]
+    fn rust_crypto_should_interoperate_with_sodium_sealed_boxes() {
+        let (rust, sodium) = (rust_crypto(), sodium());
+        let message = random_buf(&rust, 42);
+
+        for (sealer, opener) in &[(&rust, &sodium), (&sodium, &rust)] {
+            let mut pk: Box<dyn Buffer> = Box::new(vec![0; opener.kx_public_key_bytes()]);
+            let mut sk = opener.buf_new_secure(opener.kx_secret_key_bytes());
+            opener.kx_keypair(&mut pk, &mut sk).unwrap();
+
+            let mut cipher: Box<dyn Buffer> =
+                Box::new(vec![0; message.len() + sealer.sealed_box_auth_bytes()]);
+            sealer
+                .sealed_box_encrypt(&mut cipher, &message, &pk)
+                .unwrap();
+            let mut opened: Box<dyn Buffer> = Box::new(vec![0; message.len()]);
+            opener
+                .sealed_box_decrypt(&mut opened, &cipher, &pk, &sk)
+                .unwrap();
+            assert_same_bytes(&opened, &message);
+        }
+    }
+}
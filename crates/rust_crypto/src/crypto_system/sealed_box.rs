@@ -0,0 +1,105 @@
+//! The libsodium crypto_box_seal construction, so sealed boxes are
+//! interoperable with lib3h_sodium: an ephemeral x25519 public key,
+//! then the crypto_box (xsalsa20poly1305) of the message from the ephemeral
+//! secret key, with the blake2b of both public keys as nonce.
+
+use blake2b_simd::Params;
+use lib3h_crypto_api::{CryptoError, CryptoResult};
+use poly1305::{universal_hash::NewUniversalHash, Poly1305};
+use rand::{rngs::OsRng, RngCore};
+use salsa20::{
+    cipher::{NewStreamCipher, SyncStreamCipher},
+    hsalsa20, XSalsa20,
+};
+use subtle::ConstantTimeEq;
+use x25519_dalek::{x25519, X25519_BASEPOINT_BYTES};
+use zeroize::Zeroize;
+
+pub const PUBLIC_KEY_BYTES: usize = 32;
+pub const SECRET_KEY_BYTES: usize = 32;
+pub const AUTH_BYTES: usize = PUBLIC_KEY_BYTES + MAC_BYTES;
+
+const MAC_BYTES: usize = 16;
+const NONCE_BYTES: usize = 24;
+
+/// Seal `message` into `cipher` for the owner of `public_key`
+pub fn seal(cipher: &mut [u8], message: &[u8], public_key: &[u8]) -> CryptoResult<()> {
+    let mut ephemeral_sk = [0; SECRET_KEY_BYTES];
+    OsRng.fill_bytes(&mut ephemeral_sk);
+    let ephemeral_pk = x25519(ephemeral_sk, X25519_BASEPOINT_BYTES);
+    let key = shared_key(&ephemeral_sk, public_key);
+    ephemeral_sk.zeroize();
+    let mut key = key?;
+    let nonce = nonce(&ephemeral_pk, public_key);
+
+    let (header, encrypted) = cipher.split_at_mut(AUTH_BYTES);
+    let (header_pk, header_mac) = header.split_at_mut(PUBLIC_KEY_BYTES);
+    header_pk.copy_from_slice(&ephemeral_pk);
+    let mut stream = XSalsa20::new(&key.into(), &nonce.into());
+    key.zeroize();
+    let mut mac_key = [0; 32];
+    stream.apply_keystream(&mut mac_key);
+    encrypted.copy_from_slice(message);
+    stream.apply_keystream(encrypted);
+    header_mac.copy_from_slice(
+        &Poly1305::new(&mac_key.into())
+            .compute_unpadded(encrypted)
+            .into_bytes(),
+    );
+    Ok(())
+}
+
+/// Open `cipher` sealed for our keypair into `message`
+pub fn open(
+    message: &mut [u8],
+    cipher: &[u8],
+    public_key: &[u8],
+    secret_key: &[u8],
+) -> CryptoResult<()> {
+    let (header, encrypted) = cipher.split_at(AUTH_BYTES);
+    let (ephemeral_pk, mac) = header.split_at(PUBLIC_KEY_BYTES);
+    let mut key = shared_key(secret_key, ephemeral_pk).map_err(|_| CryptoError::CouldNotDecrypt)?;
+    let nonce = nonce(ephemeral_pk, public_key);
+
+    let mut stream = XSalsa20::new(&key.into(), &nonce.into());
+    key.zeroize();
+    let mut mac_key = [0; 32];
+    stream.apply_keystream(&mut mac_key);
+    let expected_mac = Poly1305::new(&mac_key.into()).compute_unpadded(encrypted);
+    if !bool::from(expected_mac.into_bytes().ct_eq(mac)) {
+        return Err(CryptoError::CouldNotDecrypt);
+    }
+    message.copy_from_slice(encrypted);
+    stream.apply_keystream(message);
+    Ok(())
+}
+
+/// crypto_box_beforenm: the hsalsa20 of the x25519 shared secret
+fn shared_key(secret_key: &[u8], public_key: &[u8]) -> CryptoResult<[u8; 32]> {
+    let mut sk = [0; SECRET_KEY_BYTES];
+    sk.copy_from_slice(secret_key);
+    let mut pk = [0; PUBLIC_KEY_BYTES];
+    pk.copy_from_slice(public_key);
+    let mut shared = x25519(sk, pk);
+    sk.zeroize();
+    if shared.iter().all(|b| *b == 0) {
+        return Err(CryptoError::new("low order public key"));
+    }
+    let mut key = [0; 32];
+    key.copy_from_slice(&hsalsa20(&shared.into(), &Default::default()));
+    shared.zeroize();
+    Ok(key)
+}
+
+/// blake2b of the ephemeral and the recipient public keys
+fn nonce(ephemeral_pk: &[u8], public_key: &[u8]) -> [u8; NONCE_BYTES] {
+    let hash = Params::new()
+        .hash_length(NONCE_BYTES)
+        .to_state()
+        .update(ephemeral_pk)
+        .update(public_key)
+        .finalize();
+    let mut nonce = [0; NONCE_BYTES];
+    nonce.copy_from_slice(hash.as_bytes());
+    nonce
+}
@@ -0,0 +1,133 @@
+//! The libsodium crypto_secretstream_xchacha20poly1305 construction,
+//! so streams are interoperable with lib3h_sodium.
+//! The state is serialized like the libsodium one:
+//! key (32 bytes), nonce (4 bytes counter + 8 bytes inonce), 8 bytes padding.
+
+use chacha20::{
+    cipher::{NewCipher, StreamCipher, StreamCipherSeek},
+    hchacha, ChaCha20, Key, Nonce, R20,
+};
+use lib3h_crypto_api::{CryptoError, CryptoResult};
+use poly1305::{universal_hash::NewUniversalHash, Poly1305};
+use subtle::ConstantTimeEq;
+
+pub const KEY_BYTES: usize = 32;
+pub const HEADER_BYTES: usize = 24;
+pub const AUTH_BYTES: usize = 17;
+pub const STATE_BYTES: usize = 52;
+
+pub const TAG_MESSAGE: u8 = 0;
+pub const TAG_PUSH: u8 = 1;
+pub const TAG_REKEY: u8 = 2;
+pub const TAG_FINAL: u8 = TAG_PUSH | TAG_REKEY;
+
+const COUNTER_BYTES: usize = 4;
+const INONCE_BYTES: usize = 8;
+const NONCE_BYTES: usize = COUNTER_BYTES + INONCE_BYTES;
+const MAC_BYTES: usize = 16;
+const BLOCK_BYTES: usize = 64;
+
+/// Start a stream from `secret` and the random `header`
+pub fn init(state: &mut [u8], header: &[u8], secret: &[u8]) {
+    let key = hchacha::<R20>(Key::from_slice(secret), header[..16].into());
+    state.iter_mut().for_each(|b| *b = 0);
+    state[..KEY_BYTES].copy_from_slice(&key);
+    reset_counter(state);
+    state[KEY_BYTES + COUNTER_BYTES..KEY_BYTES + NONCE_BYTES].copy_from_slice(&header[16..]);
+}
+
+/// Encrypt `message` into `cipher`: the encrypted tag, the encrypted message, the mac
+pub fn push(state: &mut [u8], cipher: &mut [u8], message: &[u8], adata: &[u8], tag: u8) {
+    let mut block = [0; BLOCK_BYTES];
+    block[0] = tag;
+    xor_keystream(state, 1, &mut block);
+    cipher[0] = block[0];
+    let (encrypted, mac) = cipher[1..].split_at_mut(message.len());
+    encrypted.copy_from_slice(message);
+    xor_keystream(state, 2, encrypted);
+    mac.copy_from_slice(&compute_mac(state, adata, &block, encrypted));
+    next_nonce(state, mac, tag);
+}
+
+/// Decrypt `cipher` into `message`, returning the tag it was pushed with
+pub fn pull(state: &mut [u8], message: &mut [u8], cipher: &[u8], adata: &[u8]) -> CryptoResult<u8> {
+    let mut block = [0; BLOCK_BYTES];
+    block[0] = cipher[0];
+    xor_keystream(state, 1, &mut block);
+    let tag = block[0];
+    block[0] = cipher[0];
+    let (encrypted, mac) = cipher[1..].split_at(message.len());
+    let expected_mac = compute_mac(state, adata, &block, encrypted);
+    if !bool::from(expected_mac.ct_eq(mac)) {
+        return Err(CryptoError::CouldNotDecrypt);
+    }
+    message.copy_from_slice(encrypted);
+    xor_keystream(state, 2, message);
+    next_nonce(state, mac, tag);
+    Ok(tag)
+}
+
+/// Poly1305 of the adata, the tag block and the encrypted message,
+/// keyed with the first block of the keystream.
+/// The message padding is `mlen & 0xf` like in libsodium (not `-mlen & 0xf`)
+fn compute_mac(state: &[u8], adata: &[u8], block: &[u8], encrypted: &[u8]) -> [u8; MAC_BYTES] {
+    let mut mac_key = [0; 32];
+    xor_keystream(state, 0, &mut mac_key);
+    let mut data = Vec::with_capacity(adata.len() + block.len() + encrypted.len() + 48);
+    data.extend_from_slice(adata);
+    data.resize(data.len() + (0x10 - adata.len() % 0x10) % 0x10, 0);
+    data.extend_from_slice(block);
+    data.extend_from_slice(encrypted);
+    data.resize(data.len() + encrypted.len() % 0x10, 0);
+    data.extend_from_slice(&(adata.len() as u64).to_le_bytes());
+    data.extend_from_slice(&((BLOCK_BYTES + encrypted.len()) as u64).to_le_bytes());
+    let mut mac = [0; MAC_BYTES];
+    mac.copy_from_slice(
+        &Poly1305::new(&mac_key.into())
+            .compute_unpadded(&data)
+            .into_bytes(),
+    );
+    mac
+}
+
+/// Mix the mac into the inonce, then increment the counter,
+/// rekeying when asked to or when the counter wraps
+fn next_nonce(state: &mut [u8], mac: &[u8], tag: u8) {
+    let inonce = &mut state[KEY_BYTES + COUNTER_BYTES..KEY_BYTES + NONCE_BYTES];
+    inonce.iter_mut().zip(mac.iter()).for_each(|(n, m)| *n ^= m);
+    let counter = &mut state[KEY_BYTES..KEY_BYTES + COUNTER_BYTES];
+    let mut counter_bytes = [0; COUNTER_BYTES];
+    counter_bytes.copy_from_slice(counter);
+    let next_counter = u32::from_le_bytes(counter_bytes).wrapping_add(1);
+    counter.copy_from_slice(&next_counter.to_le_bytes());
+    if tag & TAG_REKEY != 0 || next_counter == 0 {
+        rekey(state);
+    }
+}
+
+/// Replace the key and inonce by their encryption with the stream
+fn rekey(state: &mut [u8]) {
+    let mut key_and_inonce = [0; KEY_BYTES + INONCE_BYTES];
+    key_and_inonce[..KEY_BYTES].copy_from_slice(&state[..KEY_BYTES]);
+    key_and_inonce[KEY_BYTES..]
+        .copy_from_slice(&state[KEY_BYTES + COUNTER_BYTES..KEY_BYTES + NONCE_BYTES]);
+    xor_keystream(state, 0, &mut key_and_inonce);
+    state[..KEY_BYTES].copy_from_slice(&key_and_inonce[..KEY_BYTES]);
+    state[KEY_BYTES + COUNTER_BYTES..KEY_BYTES + NONCE_BYTES]
+        .copy_from_slice(&key_and_inonce[KEY_BYTES..]);
+    reset_counter(state);
+}
+
+fn reset_counter(state: &mut [u8]) {
+    state[KEY_BYTES..KEY_BYTES + COUNTER_BYTES].copy_from_slice(&1_u32.to_le_bytes());
+}
+
+/// Xor `data` with the ChaCha20 keystream of the state, from block `block_counter`
+fn xor_keystream(state: &[u8], block_counter: u64, data: &mut [u8]) {
+    let mut stream = ChaCha20::new(
+        Key::from_slice(&state[..KEY_BYTES]),
+        Nonce::from_slice(&state[KEY_BYTES..KEY_BYTES + NONCE_BYTES]),
+    );
+    stream.seek(block_counter * BLOCK_BYTES as u64);
+    stream.apply_keystream(data);
+}
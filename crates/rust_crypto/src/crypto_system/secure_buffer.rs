@@ -0,0 +1,119 @@
+use lib3h_crypto_api::{Buffer, ProtectState};
+use zeroize::Zeroize;
+
+/// A secret buffer implementation of lib3h_crypto_api::Buffer
+/// zeroing its memory when dropped.
+/// Without libsodium the memory is neither mlocked nor mprotected,
+/// the protect state is only tracked to catch access outside of the lockers.
+pub struct SecureBuffer {
+    b: Box<[u8]>,
+    p: std::cell::RefCell<ProtectState>,
+}
+
+impl Drop for SecureBuffer {
+    fn drop(&mut self) {
+        self.b.zeroize();
+    }
+}
+
+impl Clone for SecureBuffer {
+    fn clone(&self) -> Self {
+        let mut out = SecureBuffer::new(self.b.len());
+        out.write(0, &self.read_lock())
+            .expect("could not write new");
+        out
+    }
+}
+
+impl std::fmt::Debug for SecureBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self.p.borrow() {
+            ProtectState::NoAccess => write!(f, "SecureBuffer( {:?} )", "<NO_ACCESS>"),
+            _ => write!(f, "SecureBuffer( {:?} )", &self.b),
+        }
+    }
+}
+
+impl std::ops::Deref for SecureBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        if *self.p.borrow() == ProtectState::NoAccess {
+            panic!("Deref, but state is NoAccess");
+        }
+        &self.b
+    }
+}
+
+impl std::ops::DerefMut for SecureBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        if *self.p.borrow() != ProtectState::ReadWrite {
+            panic!("DerefMut, but state is not ReadWrite");
+        }
+        &mut self.b
+    }
+}
+
+impl SecureBuffer {
+    pub fn new(size: usize) -> Self {
+        SecureBuffer {
+            b: vec![0; size].into_boxed_slice(),
+            p: std::cell::RefCell::new(ProtectState::NoAccess),
+        }
+    }
+}
+
+impl Buffer for SecureBuffer {
+    fn box_clone(&self) -> Box<dyn Buffer> {
+        Box::new(self.clone())
+    }
+
+    fn as_buffer(&self) -> &dyn Buffer {
+        self
+    }
+
+    fn as_buffer_mut(&mut self) -> &mut dyn Buffer {
+        &mut *self
+    }
+
+    fn len(&self) -> usize {
+        self.b.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.b.is_empty()
+    }
+
+    fn set_no_access(&self) {
+        if *self.p.borrow() == ProtectState::NoAccess {
+            panic!("already no access... bad logic");
+        }
+        *self.p.borrow_mut() = ProtectState::NoAccess;
+    }
+
+    fn set_readable(&self) {
+        if *self.p.borrow() != ProtectState::NoAccess {
+            panic!("not no access... bad logic");
+        }
+        *self.p.borrow_mut() = ProtectState::ReadOnly;
+    }
+
+    fn set_writable(&self) {
+        if *self.p.borrow() != ProtectState::NoAccess {
+            panic!("not no access... bad logic");
+        }
+        *self.p.borrow_mut() = ProtectState::ReadWrite;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "Deref, but state is NoAccess")]
+    fn it_denies_access_outside_of_lockers() {
+        let b = SecureBuffer::new(8);
+        let _ = b[0];
+    }
+}
@@ -0,0 +1,24 @@
+//! lib3h pure rust implementation of lib3h_crypto_api::CryptoSystem,
+//! interoperable with lib3h_sodium without building the native libsodium
+
+extern crate argon2;
+extern crate blake2b_simd;
+extern crate chacha20;
+extern crate chacha20poly1305;
+extern crate curve25519_dalek;
+extern crate ed25519_dalek;
+extern crate lib3h_crypto_api;
+extern crate poly1305;
+extern crate rand;
+extern crate salsa20;
+extern crate sha2;
+extern crate subtle;
+extern crate x25519_dalek;
+extern crate zeroize;
+
+mod crypto_system;
+pub use crypto_system::{
+    RustCryptoSystem, SecureBuffer, PWHASH_ALG_ARGON2I13, PWHASH_ALG_ARGON2ID13,
+    PWHASH_MEMLIMIT_INTERACTIVE, PWHASH_MEMLIMIT_MODERATE, PWHASH_MEMLIMIT_SENSITIVE,
+    PWHASH_OPSLIMIT_INTERACTIVE, PWHASH_OPSLIMIT_MODERATE, PWHASH_OPSLIMIT_SENSITIVE,
+};
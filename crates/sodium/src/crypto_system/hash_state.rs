@@ -0,0 +1,182 @@
+//! Incremental hash states, the libsodium state structs need an alignment
+//! a Buffer can't promise, so they are kept in their own allocation
+//! and zeroed on drop.
+
+use lib3h_crypto_api::{Buffer, CryptoError, CryptoResult, HashState};
+
+/// incremental sha256 hash
+pub struct Sha256State(Box<rust_sodium_sys::crypto_hash_sha256_state>);
+
+impl Sha256State {
+    pub fn new() -> CryptoResult<Self> {
+        let mut state: Box<rust_sodium_sys::crypto_hash_sha256_state> =
+            Box::new(unsafe { std::mem::zeroed() });
+        unsafe {
+            if rust_sodium_sys::crypto_hash_sha256_init(&mut *state) != 0 as libc::c_int {
+                return Err(CryptoError::Generic("libsodium fail".to_string()));
+            }
+        }
+        Ok(Sha256State(state))
+    }
+}
+
+impl HashState for Sha256State {
+    fn update(&mut self, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+        unsafe {
+            let data = data.read_lock();
+            rust_sodium_sys::crypto_hash_sha256_update(
+                &mut *self.0,
+                raw_ptr_char_immut!(data),
+                data.len() as libc::c_ulonglong,
+            );
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>, hash: &mut Box<dyn Buffer>) -> CryptoResult<()> {
+        if hash.len() != rust_sodium_sys::crypto_hash_sha256_BYTES as usize {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        unsafe {
+            let mut hash = hash.write_lock();
+            rust_sodium_sys::crypto_hash_sha256_final(&mut *self.0, raw_ptr_char!(hash));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Sha256State {
+    fn drop(&mut self) {
+        unsafe {
+            rust_sodium_sys::sodium_memzero(
+                &mut *self.0 as *mut _ as *mut libc::c_void,
+                std::mem::size_of::<rust_sodium_sys::crypto_hash_sha256_state>(),
+            );
+        }
+    }
+}
+
+/// incremental sha512 hash
+pub struct Sha512State(Box<rust_sodium_sys::crypto_hash_sha512_state>);
+
+impl Sha512State {
+    pub fn new() -> CryptoResult<Self> {
+        let mut state: Box<rust_sodium_sys::crypto_hash_sha512_state> =
+            Box::new(unsafe { std::mem::zeroed() });
+        unsafe {
+            if rust_sodium_sys::crypto_hash_sha512_init(&mut *state) != 0 as libc::c_int {
+                return Err(CryptoError::Generic("libsodium fail".to_string()));
+            }
+        }
+        Ok(Sha512State(state))
+    }
+}
+
+impl HashState for Sha512State {
+    fn update(&mut self, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+        unsafe {
+            let data = data.read_lock();
+            rust_sodium_sys::crypto_hash_sha512_update(
+                &mut *self.0,
+                raw_ptr_char_immut!(data),
+                data.len() as libc::c_ulonglong,
+            );
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>, hash: &mut Box<dyn Buffer>) -> CryptoResult<()> {
+        if hash.len() != rust_sodium_sys::crypto_hash_sha512_BYTES as usize {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        unsafe {
+            let mut hash = hash.write_lock();
+            rust_sodium_sys::crypto_hash_sha512_final(&mut *self.0, raw_ptr_char!(hash));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Sha512State {
+    fn drop(&mut self) {
+        unsafe {
+            rust_sodium_sys::sodium_memzero(
+                &mut *self.0 as *mut _ as *mut libc::c_void,
+                std::mem::size_of::<rust_sodium_sys::crypto_hash_sha512_state>(),
+            );
+        }
+    }
+}
+
+/// incremental (BLAKE2b) generic hash
+pub struct GenericHashState {
+    state: Box<rust_sodium_sys::crypto_generichash_state>,
+    hash_size: usize,
+}
+
+impl GenericHashState {
+    /// sizes must have been checked by the caller
+    pub fn new(hash_size: usize, key: Option<&Box<dyn Buffer>>) -> CryptoResult<Self> {
+        let my_key_locker;
+        let mut my_key = std::ptr::null();
+        let mut my_key_len = 0 as usize;
+        if let Some(key) = key {
+            my_key_locker = key.read_lock();
+            my_key = raw_ptr_char_immut!(my_key_locker);
+            my_key_len = my_key_locker.len() as usize;
+        }
+
+        let mut state = Box::new(rust_sodium_sys::crypto_generichash_state::default());
+        unsafe {
+            if rust_sodium_sys::crypto_generichash_init(&mut *state, my_key, my_key_len, hash_size)
+                != 0 as libc::c_int
+            {
+                return Err(CryptoError::Generic("libsodium fail".to_string()));
+            }
+        }
+        Ok(GenericHashState { state, hash_size })
+    }
+}
+
+impl HashState for GenericHashState {
+    fn update(&mut self, data: &Box<dyn Buffer>) -> CryptoResult<()> {
+        unsafe {
+            let data = data.read_lock();
+            rust_sodium_sys::crypto_generichash_update(
+                &mut *self.state,
+                raw_ptr_char_immut!(data),
+                data.len() as libc::c_ulonglong,
+            );
+        }
+        Ok(())
+    }
+
+    fn finalize(mut self: Box<Self>, hash: &mut Box<dyn Buffer>) -> CryptoResult<()> {
+        if hash.len() != self.hash_size {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        unsafe {
+            let mut hash = hash.write_lock();
+            rust_sodium_sys::crypto_generichash_final(
+                &mut *self.state,
+                raw_ptr_char!(hash),
+                hash.len() as usize,
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for GenericHashState {
+    fn drop(&mut self) {
+        unsafe {
+            rust_sodium_sys::sodium_memzero(
+                &mut *self.state as *mut _ as *mut libc::c_void,
+                std::mem::size_of::<rust_sodium_sys::crypto_generichash_state>(),
+            );
+        }
+    }
+}
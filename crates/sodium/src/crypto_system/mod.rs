@@ -49,6 +49,7 @@ pub struct SodiumCryptoSystem {
     pwhash_ops_limit: libc::c_ulonglong,
     pwhash_mem_limit: usize,
     pwhash_alg: libc::c_int,
+    secure_pool: Option<SecurePool>,
 }
 
 pub const PWHASH_OPSLIMIT_INTERACTIVE: libc::c_ulonglong =
@@ -79,6 +80,7 @@ impl SodiumCryptoSystem {
             pwhash_ops_limit: PWHASH_OPSLIMIT_SENSITIVE,
             pwhash_mem_limit: PWHASH_MEMLIMIT_SENSITIVE,
             pwhash_alg: PWHASH_ALG_ARGON2ID13,
+            secure_pool: None,
         }
     }
 
@@ -97,17 +99,42 @@ impl SodiumCryptoSystem {
         self
     }
 
+    /// take the secure and scratch buffers from a pool of `limit` bytes
+    /// of locked memory instead of allocating each of them,
+    /// see `set_secure_pool`
+    pub fn set_secure_pool_limit(self, limit: usize) -> Self {
+        self.set_secure_pool(SecurePool::new(limit))
+    }
+
+    /// take the secure and scratch buffers from `pool`, shared with the
+    /// clones of this system. Pooled buffers are mlocked but never
+    /// mprotected, and once the pool is full buffers are allocated
+    /// on their own again.
+    pub fn set_secure_pool(mut self, pool: SecurePool) -> Self {
+        self.secure_pool = Some(pool);
+        self
+    }
+
     pub fn set_pwhash_interactive(mut self) -> Self {
         self.set_pwhash_opslimit(PWHASH_OPSLIMIT_INTERACTIVE)
             .set_pwhash_memlimit(PWHASH_MEMLIMIT_INTERACTIVE)
     }
 }
 
-use lib3h_crypto_api::{Buffer, CryptoError, CryptoResult, CryptoSystem};
+use lib3h_crypto_api::{
+    Buffer, CryptoError, CryptoResult, CryptoSystem, HashState, SecretStreamTag,
+};
+
+mod hash_state;
+use hash_state::{GenericHashState, Sha256State, Sha512State};
 
 mod secure_buffer;
 pub use secure_buffer::SecureBuffer;
 
+mod secure_pool;
+pub(crate) use secure_pool::PoolSlot;
+pub use secure_pool::{PooledBuffer, SecurePool};
+
 impl CryptoSystem for SodiumCryptoSystem {
     fn box_clone(&self) -> Box<dyn CryptoSystem> {
         Box::new(self.clone())
@@ -118,7 +145,23 @@ impl CryptoSystem for SodiumCryptoSystem {
     }
 
     fn buf_new_secure(&self, size: usize) -> Box<dyn Buffer> {
-        Box::new(SecureBuffer::new(size))
+        match &self.secure_pool {
+            Some(pool) => match pool.buf_new(size) {
+                Ok(buf) => Box::new(buf),
+                Err(_) => Box::new(SecureBuffer::new(size)),
+            },
+            None => Box::new(SecureBuffer::new(size)),
+        }
+    }
+
+    fn buf_new_scratch(&self, size: usize) -> Box<dyn Buffer> {
+        match &self.secure_pool {
+            Some(pool) => match pool.buf_new(size) {
+                Ok(buf) => Box::new(buf),
+                Err(_) => Box::new(SecureBuffer::new_unprotected(size)),
+            },
+            None => Box::new(SecureBuffer::new_unprotected(size)),
+        }
     }
 
     fn randombytes_buf(&self, buffer: &mut Box<dyn Buffer>) -> CryptoResult<()> {
@@ -175,6 +218,14 @@ impl CryptoSystem for SodiumCryptoSystem {
         Ok(())
     }
 
+    fn hash_sha256_init(&self) -> CryptoResult<Box<dyn HashState>> {
+        Ok(Box::new(Sha256State::new()?))
+    }
+
+    fn hash_sha512_init(&self) -> CryptoResult<Box<dyn HashState>> {
+        Ok(Box::new(Sha512State::new()?))
+    }
+
     fn generic_hash_min_bytes(&self) -> usize {
         rust_sodium_sys::crypto_generichash_BYTES_MIN as usize
     }
@@ -234,6 +285,25 @@ impl CryptoSystem for SodiumCryptoSystem {
         Ok(())
     }
 
+    fn generic_hash_init(
+        &self,
+        hash_size: usize,
+        key: Option<&Box<dyn Buffer>>,
+    ) -> CryptoResult<Box<dyn HashState>> {
+        if hash_size < self.generic_hash_min_bytes() || hash_size > self.generic_hash_max_bytes() {
+            return Err(CryptoError::BadHashSize);
+        }
+
+        if key.is_some()
+            && (key.unwrap().len() < self.generic_hash_key_min_bytes()
+                || key.unwrap().len() > self.generic_hash_key_max_bytes())
+        {
+            return Err(CryptoError::BadKeySize);
+        }
+
+        Ok(Box::new(GenericHashState::new(hash_size, key)?))
+    }
+
     fn pwhash_salt_bytes(&self) -> usize {
         rust_sodium_sys::crypto_pwhash_SALTBYTES as usize
     }
@@ -459,6 +529,9 @@ impl CryptoSystem for SodiumCryptoSystem {
             return Err(CryptoError::BadPublicKeySize);
         }
 
+        let signature = signature.read_lock();
+        let message = message.read_lock();
+        let public_key = public_key.read_lock();
         Ok(unsafe {
             rust_sodium_sys::crypto_sign_verify_detached(
                 raw_ptr_char_immut!(signature),
@@ -645,6 +718,66 @@ impl CryptoSystem for SodiumCryptoSystem {
         Ok(())
     }
 
+    fn sign_to_kx_public_key(
+        &self,
+        kx_public_key: &mut Box<dyn Buffer>,
+        sign_public_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if kx_public_key.len() != self.kx_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if sign_public_key.len() != self.sign_public_key_bytes() {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        let mut kx_public_key = kx_public_key.write_lock();
+        let sign_public_key = sign_public_key.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_sign_ed25519_pk_to_curve25519(
+                raw_ptr_char!(kx_public_key),
+                raw_ptr_char_immut!(sign_public_key),
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::Generic(
+                    "not a valid ed25519 public key".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sign_to_kx_secret_key(
+        &self,
+        kx_secret_key: &mut Box<dyn Buffer>,
+        sign_secret_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if kx_secret_key.len() != self.kx_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        if sign_secret_key.len() != self.sign_secret_key_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let mut kx_secret_key = kx_secret_key.write_lock();
+        let sign_secret_key = sign_secret_key.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_sign_ed25519_sk_to_curve25519(
+                raw_ptr_char!(kx_secret_key),
+                raw_ptr_char_immut!(sign_secret_key),
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::Generic("libsodium fail".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
     fn aead_nonce_bytes(&self) -> usize {
         rust_sodium_sys::crypto_aead_xchacha20poly1305_ietf_NPUBBYTES as usize
     }
@@ -765,6 +898,300 @@ impl CryptoSystem for SodiumCryptoSystem {
 
         Ok(())
     }
+
+    fn secretstream_header_bytes(&self) -> usize {
+        rust_sodium_sys::crypto_secretstream_xchacha20poly1305_HEADERBYTES as usize
+    }
+
+    fn secretstream_auth_bytes(&self) -> usize {
+        rust_sodium_sys::crypto_secretstream_xchacha20poly1305_ABYTES as usize
+    }
+
+    fn secretstream_secret_bytes(&self) -> usize {
+        rust_sodium_sys::crypto_secretstream_xchacha20poly1305_KEYBYTES as usize
+    }
+
+    fn secretstream_state_bytes(&self) -> usize {
+        std::mem::size_of::<rust_sodium_sys::crypto_secretstream_xchacha20poly1305_state>()
+    }
+
+    fn secretstream_init_push(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        header: &mut Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if header.len() != self.secretstream_header_bytes() {
+            return Err(CryptoError::BadHeaderSize);
+        }
+
+        if secret.len() != self.secretstream_secret_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let mut state = state.write_lock();
+        let mut header = header.write_lock();
+        let secret = secret.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_secretstream_xchacha20poly1305_init_push(
+                raw_ptr_state!(state),
+                raw_ptr_char!(header),
+                raw_ptr_char_immut!(secret),
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::Generic("libsodium fail".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn secretstream_push(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        cipher: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+        tag: SecretStreamTag,
+    ) -> CryptoResult<()> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if cipher.len() != message.len() + self.secretstream_auth_bytes() {
+            return Err(CryptoError::BadCipherSize);
+        }
+
+        let my_adata_locker;
+        let mut my_adata = std::ptr::null();
+        let mut my_ad_len = 0 as libc::c_ulonglong;
+        if let Some(adata) = adata {
+            my_adata_locker = adata.read_lock();
+            my_adata = raw_ptr_char_immut!(my_adata_locker);
+            my_ad_len = my_adata_locker.len() as libc::c_ulonglong;
+        }
+
+        let tag = match tag {
+            SecretStreamTag::Message => {
+                rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_MESSAGE
+            }
+            SecretStreamTag::Push => {
+                rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_PUSH
+            }
+            SecretStreamTag::Rekey => {
+                rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_REKEY
+            }
+            SecretStreamTag::Final => {
+                rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_FINAL
+            }
+        };
+
+        let mut state = state.write_lock();
+        let mut cipher = cipher.write_lock();
+        let message = message.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_secretstream_xchacha20poly1305_push(
+                raw_ptr_state!(state),
+                raw_ptr_char!(cipher),
+                std::ptr::null_mut(),
+                raw_ptr_char_immut!(message),
+                message.len() as libc::c_ulonglong,
+                my_adata,
+                my_ad_len,
+                tag as libc::c_uchar,
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::Generic("libsodium fail".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn secretstream_init_pull(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        header: &Box<dyn Buffer>,
+        secret: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if header.len() != self.secretstream_header_bytes() {
+            return Err(CryptoError::BadHeaderSize);
+        }
+
+        if secret.len() != self.secretstream_secret_bytes() {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let mut state = state.write_lock();
+        let header = header.read_lock();
+        let secret = secret.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_secretstream_xchacha20poly1305_init_pull(
+                raw_ptr_state!(state),
+                raw_ptr_char_immut!(header),
+                raw_ptr_char_immut!(secret),
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::BadHeaderSize);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn secretstream_pull(
+        &self,
+        state: &mut Box<dyn Buffer>,
+        message: &mut Box<dyn Buffer>,
+        cipher: &Box<dyn Buffer>,
+        adata: Option<&Box<dyn Buffer>>,
+    ) -> CryptoResult<SecretStreamTag> {
+        if state.len() != self.secretstream_state_bytes() {
+            return Err(CryptoError::BadStateSize);
+        }
+
+        if cipher.len() < self.secretstream_auth_bytes()
+            || message.len() != cipher.len() - self.secretstream_auth_bytes()
+        {
+            return Err(CryptoError::BadMessageSize);
+        }
+
+        let my_adata_locker;
+        let mut my_adata = std::ptr::null();
+        let mut my_ad_len = 0 as libc::c_ulonglong;
+        if let Some(adata) = adata {
+            my_adata_locker = adata.read_lock();
+            my_adata = raw_ptr_char_immut!(my_adata_locker);
+            my_ad_len = my_adata_locker.len() as libc::c_ulonglong;
+        }
+
+        let mut tag = 0 as libc::c_uchar;
+        let mut state = state.write_lock();
+        let mut message = message.write_lock();
+        let cipher = cipher.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_secretstream_xchacha20poly1305_pull(
+                raw_ptr_state!(state),
+                raw_ptr_char!(message),
+                std::ptr::null_mut(),
+                &mut tag,
+                raw_ptr_char_immut!(cipher),
+                cipher.len() as libc::c_ulonglong,
+                my_adata,
+                my_ad_len,
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::CouldNotDecrypt);
+            }
+        }
+
+        match tag as u32 {
+            rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_MESSAGE => {
+                Ok(SecretStreamTag::Message)
+            }
+            rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_PUSH => {
+                Ok(SecretStreamTag::Push)
+            }
+            rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_REKEY => {
+                Ok(SecretStreamTag::Rekey)
+            }
+            rust_sodium_sys::crypto_secretstream_xchacha20poly1305_TAG_FINAL => {
+                Ok(SecretStreamTag::Final)
+            }
+            _ => Err(CryptoError::CouldNotDecrypt),
+        }
+    }
+
+    fn sealed_box_auth_bytes(&self) -> usize {
+        rust_sodium_sys::crypto_box_SEALBYTES as usize
+    }
+
+    fn sealed_box_encrypt(
+        &self,
+        cipher: &mut Box<dyn Buffer>,
+        message: &Box<dyn Buffer>,
+        public_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if cipher.len() != message.len() + self.sealed_box_auth_bytes() {
+            return Err(CryptoError::BadCipherSize);
+        }
+
+        if public_key.len() != rust_sodium_sys::crypto_box_PUBLICKEYBYTES as usize {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        let mut cipher = cipher.write_lock();
+        let message = message.read_lock();
+        let public_key = public_key.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_box_seal(
+                raw_ptr_char!(cipher),
+                raw_ptr_char_immut!(message),
+                message.len() as libc::c_ulonglong,
+                raw_ptr_char_immut!(public_key),
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::Generic("libsodium fail".to_string()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sealed_box_decrypt(
+        &self,
+        message: &mut Box<dyn Buffer>,
+        cipher: &Box<dyn Buffer>,
+        public_key: &Box<dyn Buffer>,
+        secret_key: &Box<dyn Buffer>,
+    ) -> CryptoResult<()> {
+        if cipher.len() < self.sealed_box_auth_bytes()
+            || message.len() != cipher.len() - self.sealed_box_auth_bytes()
+        {
+            return Err(CryptoError::BadMessageSize);
+        }
+
+        if public_key.len() != rust_sodium_sys::crypto_box_PUBLICKEYBYTES as usize {
+            return Err(CryptoError::BadPublicKeySize);
+        }
+
+        if secret_key.len() != rust_sodium_sys::crypto_box_SECRETKEYBYTES as usize {
+            return Err(CryptoError::BadSecretKeySize);
+        }
+
+        let mut message = message.write_lock();
+        let cipher = cipher.read_lock();
+        let public_key = public_key.read_lock();
+        let secret_key = secret_key.read_lock();
+
+        unsafe {
+            if rust_sodium_sys::crypto_box_seal_open(
+                raw_ptr_char!(message),
+                raw_ptr_char_immut!(cipher),
+                cipher.len() as libc::c_ulonglong,
+                raw_ptr_char_immut!(public_key),
+                raw_ptr_char_immut!(secret_key),
+            ) != 0 as libc::c_int
+            {
+                return Err(CryptoError::CouldNotDecrypt);
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -779,6 +1206,92 @@ mod test {
         crypto_system_test::full_suite(crypto);
     }
 
+    #[test]
+    fn sodium_with_secure_pool_should_pass_crypto_system_full_suite() {
+        let crypto: Box<dyn CryptoSystem> = Box::new(
+            SodiumCryptoSystem::new()
+                .set_pwhash_interactive()
+                .set_secure_pool_limit(64 * 1024),
+        );
+        crypto_system_test::full_suite(crypto);
+    }
+
+    #[test]
+    fn sodium_should_take_secure_buffers_from_the_pool() {
+        let pool = SecurePool::new(4096);
+        let crypto = SodiumCryptoSystem::new().set_secure_pool(pool.clone());
+        let sk = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        assert_eq!(64, pool.used());
+        let scratch = crypto.buf_new_scratch(100);
+        assert_eq!(64 + 128, pool.used());
+        drop(sk);
+        drop(scratch);
+        assert_eq!(0, pool.used());
+    }
+
+    #[test]
+    fn sodium_should_allocate_secure_buffers_when_the_pool_is_full() {
+        let pool = SecurePool::new(64);
+        let crypto = SodiumCryptoSystem::new().set_secure_pool(pool.clone());
+        let sk = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        assert_eq!(64, pool.used());
+
+        let mut seed = crypto.buf_new_secure(crypto.sign_seed_bytes());
+        let mut scratch = crypto.buf_new_scratch(100);
+        crypto.randombytes_buf(&mut seed).unwrap();
+        scratch.write(0, &[42; 100]).unwrap();
+        assert_eq!(&[42; 100][..], &*scratch.read_lock());
+        let clone = sk.box_clone();
+        assert_eq!(&*sk.read_lock(), &*clone.read_lock());
+        assert_eq!(64, pool.used());
+    }
+
+    #[test]
+    fn sodium_should_sign_verify_secure_buffers() {
+        let crypto: Box<dyn CryptoSystem> =
+            Box::new(SodiumCryptoSystem::new().set_pwhash_interactive());
+        let mut pk = crypto.buf_new_secure(crypto.sign_public_key_bytes());
+        let mut sk = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        crypto.sign_keypair(&mut pk, &mut sk).unwrap();
+        let mut msg = crypto.buf_new_secure(64);
+        crypto.randombytes_buf(&mut msg).unwrap();
+        let mut sig = crypto.buf_new_secure(crypto.sign_bytes());
+        crypto.sign(&mut sig, &msg, &sk).unwrap();
+        assert!(crypto.sign_verify(&sig, &msg, &pk).unwrap());
+    }
+
+    #[test]
+    fn sodium_should_convert_sign_keys_to_matching_kx_keys() {
+        let crypto: Box<dyn CryptoSystem> =
+            Box::new(SodiumCryptoSystem::new().set_pwhash_interactive());
+
+        let mut sign_pk: Box<dyn Buffer> = Box::new(vec![0; crypto.sign_public_key_bytes()]);
+        let mut sign_sk = crypto.buf_new_secure(crypto.sign_secret_key_bytes());
+        crypto.sign_keypair(&mut sign_pk, &mut sign_sk).unwrap();
+        let mut c_pk: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_public_key_bytes()]);
+        let mut c_sk = crypto.buf_new_secure(crypto.kx_secret_key_bytes());
+        crypto.sign_to_kx_public_key(&mut c_pk, &sign_pk).unwrap();
+        crypto.sign_to_kx_secret_key(&mut c_sk, &sign_sk).unwrap();
+
+        let mut s_pk: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_public_key_bytes()]);
+        let mut s_sk = crypto.buf_new_secure(crypto.kx_secret_key_bytes());
+        crypto.kx_keypair(&mut s_pk, &mut s_sk).unwrap();
+
+        // the converted keys are a valid kx keypair: both ends agree
+        let mut c_rx: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_session_key_bytes()]);
+        let mut c_tx: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_session_key_bytes()]);
+        let mut s_rx: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_session_key_bytes()]);
+        let mut s_tx: Box<dyn Buffer> = Box::new(vec![0; crypto.kx_session_key_bytes()]);
+        crypto
+            .kx_client_session_keys(&mut c_rx, &mut c_tx, &c_pk, &c_sk, &s_pk)
+            .unwrap();
+        crypto
+            .kx_server_session_keys(&mut s_rx, &mut s_tx, &s_pk, &s_sk, &c_pk)
+            .unwrap();
+        assert_eq!(&format!("{:?}", c_rx), &format!("{:?}", s_tx));
+        assert_eq!(&format!("{:?}", c_tx), &format!("{:?}", s_rx));
+    }
+
     #[test]
     fn sodium_should_kdf_derive_as_expected() {
         let crypto: Box<dyn CryptoSystem> =
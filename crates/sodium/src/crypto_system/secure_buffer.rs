@@ -9,6 +9,8 @@ pub struct SecureBuffer {
     z: *mut c_void,
     s: usize,
     p: std::cell::RefCell<ProtectState>,
+    // false for scratch buffers, whose lockers skip the mprotect calls
+    m: bool,
 }
 
 unsafe impl Send for SecureBuffer {}
@@ -23,7 +25,7 @@ impl Drop for SecureBuffer {
 
 impl Clone for SecureBuffer {
     fn clone(&self) -> Self {
-        let mut out = SecureBuffer::new(self.s);
+        let mut out = SecureBuffer::alloc(self.s, self.m);
         out.write(0, &self.read_lock())
             .expect("could not write new");
         out
@@ -61,6 +63,17 @@ impl std::ops::DerefMut for SecureBuffer {
 
 impl SecureBuffer {
     pub fn new(size: usize) -> Self {
+        SecureBuffer::alloc(size, true)
+    }
+
+    /// a buffer for short-lived scratch data, still mlocked and guarded,
+    /// but readable and writable for its whole life: locking it only tracks
+    /// the protect state, without the cost of the mprotect calls
+    pub fn new_unprotected(size: usize) -> Self {
+        SecureBuffer::alloc(size, false)
+    }
+
+    fn alloc(size: usize, mprotect: bool) -> Self {
         check_init();
         let z = unsafe {
             // sodium_malloc requires memory-aligned sizes,
@@ -71,7 +84,9 @@ impl SecureBuffer {
                 panic!("sodium_malloc could not allocate");
             }
             rust_sodium_sys::sodium_memzero(z, align_size);
-            rust_sodium_sys::sodium_mprotect_noaccess(z);
+            if mprotect {
+                rust_sodium_sys::sodium_mprotect_noaccess(z);
+            }
             z
         };
 
@@ -79,6 +94,7 @@ impl SecureBuffer {
             z,
             s: size,
             p: std::cell::RefCell::new(ProtectState::NoAccess),
+            m: mprotect,
         }
     }
 }
@@ -108,8 +124,10 @@ impl Buffer for SecureBuffer {
         if *self.p.borrow() == ProtectState::NoAccess {
             panic!("already no access... bad logic");
         }
-        unsafe {
-            rust_sodium_sys::sodium_mprotect_noaccess(self.z);
+        if self.m {
+            unsafe {
+                rust_sodium_sys::sodium_mprotect_noaccess(self.z);
+            }
         }
         *self.p.borrow_mut() = ProtectState::NoAccess;
     }
@@ -118,8 +136,10 @@ impl Buffer for SecureBuffer {
         if *self.p.borrow() != ProtectState::NoAccess {
             panic!("not no access... bad logic");
         }
-        unsafe {
-            rust_sodium_sys::sodium_mprotect_readonly(self.z);
+        if self.m {
+            unsafe {
+                rust_sodium_sys::sodium_mprotect_readonly(self.z);
+            }
         }
         *self.p.borrow_mut() = ProtectState::ReadOnly;
     }
@@ -128,8 +148,10 @@ impl Buffer for SecureBuffer {
         if *self.p.borrow() != ProtectState::NoAccess {
             panic!("not no access... bad logic");
         }
-        unsafe {
-            rust_sodium_sys::sodium_mprotect_readwrite(self.z);
+        if self.m {
+            unsafe {
+                rust_sodium_sys::sodium_mprotect_readwrite(self.z);
+            }
         }
         *self.p.borrow_mut() = ProtectState::ReadWrite;
     }
@@ -154,4 +176,11 @@ mod tests {
             assert_eq!(1, w.len());
         }
     }
+
+    #[test]
+    #[should_panic]
+    fn it_tracks_unprotected_access() {
+        let b = SecureBuffer::new_unprotected(8);
+        let _ = b[0];
+    }
 }
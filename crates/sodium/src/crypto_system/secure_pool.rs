@@ -0,0 +1,311 @@
+use lib3h_crypto_api::{Buffer, CryptoError, CryptoResult, ProtectState};
+
+use crate::check_init;
+use libc::c_void;
+use std::sync::{Arc, Mutex};
+
+/// the pool is carved in units of the smallest size class
+const UNIT: usize = 32;
+
+/// slots up to this size are rounded up to a power of two (the size classes),
+/// bigger ones to a multiple of it
+const MAX_CLASS: usize = 4096;
+
+/// size of the slot holding a `size` bytes buffer
+fn slot_size(size: usize) -> usize {
+    if size <= MAX_CLASS {
+        std::cmp::max(size, UNIT).next_power_of_two()
+    } else {
+        (size + MAX_CLASS - 1) / MAX_CLASS * MAX_CLASS
+    }
+}
+
+/// the locked region, and which of its units are taken
+struct PoolRegion {
+    z: *mut c_void,
+    taken: Vec<bool>,
+    // bytes of the region in slots
+    used: usize,
+}
+
+unsafe impl Send for PoolRegion {}
+
+impl Drop for PoolRegion {
+    fn drop(&mut self) {
+        unsafe {
+            rust_sodium_sys::sodium_free(self.z);
+        }
+    }
+}
+
+impl PoolRegion {
+    /// offset of a free slot of `size` bytes, aligned to its size class,
+    /// if the pool has room for it
+    fn take(&mut self, size: usize) -> Option<usize> {
+        let units = size / UNIT;
+        let align = std::cmp::min(units, MAX_CLASS / UNIT);
+        let mut start = 0;
+        while start + units <= self.taken.len() {
+            match self.taken[start..start + units].iter().rposition(|t| *t) {
+                Some(i) => start = (start + i + align) / align * align,
+                None => {
+                    for t in &mut self.taken[start..start + units] {
+                        *t = true;
+                    }
+                    self.used += size;
+                    return Some(start * UNIT);
+                }
+            }
+        }
+        None
+    }
+
+    /// return the slot of `size` bytes at `offset`
+    fn give(&mut self, offset: usize, size: usize) {
+        for t in &mut self.taken[offset / UNIT..(offset + size) / UNIT] {
+            *t = false;
+        }
+        self.used -= size;
+    }
+}
+
+/// A pool of secure memory:
+/// a single sodium_malloc region (mlocked, between guard pages) holding
+/// slots of power of two size classes, which are zeroed when returned and
+/// can then be reused by any size class.
+/// The region is never mprotected, so locking its buffers is free.
+/// Clones share the same pool.
+#[derive(Clone)]
+pub struct SecurePool(Arc<Mutex<PoolRegion>>);
+
+impl SecurePool {
+    /// create a pool of `limit` bytes of locked memory
+    pub fn new(limit: usize) -> Self {
+        check_init();
+        // keep the slots aligned, whatever the guard page offset
+        let size = (limit + 63) & !63;
+        let z = unsafe {
+            let z = rust_sodium_sys::sodium_malloc(size);
+            if z.is_null() {
+                panic!("sodium_malloc could not allocate");
+            }
+            rust_sodium_sys::sodium_memzero(z, size);
+            z
+        };
+        SecurePool(Arc::new(Mutex::new(PoolRegion {
+            z,
+            taken: vec![false; size / UNIT],
+            used: 0,
+        })))
+    }
+
+    /// bytes of locked memory of the pool
+    pub fn limit(&self) -> usize {
+        self.region().taken.len() * UNIT
+    }
+
+    /// bytes of the pool held by live buffers, rounded up to their slots
+    pub fn used(&self) -> usize {
+        self.region().used
+    }
+
+    /// a `size` bytes buffer from the pool,
+    /// CryptoError::OutOfMemory if the pool has no room for it
+    pub fn buf_new(&self, size: usize) -> CryptoResult<PooledBuffer> {
+        Ok(PooledBuffer {
+            slot: self.slot(size)?,
+            s: size,
+            p: std::cell::RefCell::new(ProtectState::NoAccess),
+        })
+    }
+
+    /// a slot holding `size` bytes
+    pub(crate) fn slot(&self, size: usize) -> CryptoResult<PoolSlot> {
+        let size = slot_size(size);
+        let mut region = self.region();
+        let offset = region.take(size).ok_or(CryptoError::OutOfMemory)?;
+        Ok(PoolSlot {
+            pool: self.clone(),
+            z: unsafe { (region.z as *mut u8).add(offset) },
+            offset,
+            size,
+        })
+    }
+
+    fn region(&self) -> std::sync::MutexGuard<PoolRegion> {
+        self.0.lock().expect("pool mutex poisoned")
+    }
+}
+
+/// A slot of a SecurePool, zeroed and returned to the pool on drop
+pub(crate) struct PoolSlot {
+    pool: SecurePool,
+    z: *mut u8,
+    offset: usize,
+    size: usize,
+}
+
+unsafe impl Send for PoolSlot {}
+
+impl PoolSlot {
+    pub(crate) fn as_ptr(&self) -> *mut u8 {
+        self.z
+    }
+}
+
+impl Drop for PoolSlot {
+    fn drop(&mut self) {
+        unsafe {
+            rust_sodium_sys::sodium_memzero(self.z as *mut c_void, self.size);
+        }
+        if let Ok(mut region) = self.pool.0.lock() {
+            region.give(self.offset, self.size);
+        }
+    }
+}
+
+/// A lib3h_crypto_api::Buffer in a slot of a SecurePool
+pub struct PooledBuffer {
+    slot: PoolSlot,
+    s: usize,
+    p: std::cell::RefCell<ProtectState>,
+}
+
+impl std::fmt::Debug for PooledBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self.p.borrow() {
+            ProtectState::NoAccess => write!(f, "PooledBuffer( {:?} )", "<NO_ACCESS>"),
+            _ => write!(f, "PooledBuffer( {:?} )", &**self),
+        }
+    }
+}
+
+impl std::ops::Deref for PooledBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        if *self.p.borrow() == ProtectState::NoAccess {
+            panic!("Deref, but state is NoAccess");
+        }
+        unsafe { std::slice::from_raw_parts(self.slot.as_ptr(), self.s) }
+    }
+}
+
+impl std::ops::DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        if *self.p.borrow() != ProtectState::ReadWrite {
+            panic!("DerefMut, but state is not ReadWrite");
+        }
+        unsafe { std::slice::from_raw_parts_mut(self.slot.as_ptr(), self.s) }
+    }
+}
+
+impl Buffer for PooledBuffer {
+    fn box_clone(&self) -> Box<dyn Buffer> {
+        let mut out: Box<dyn Buffer> = match self.slot.pool.buf_new(self.s) {
+            Ok(buf) => Box::new(buf),
+            Err(_) => Box::new(super::SecureBuffer::new(self.s)),
+        };
+        out.write(0, &self.read_lock())
+            .expect("could not write new");
+        out
+    }
+
+    fn as_buffer(&self) -> &dyn Buffer {
+        &*self
+    }
+
+    fn as_buffer_mut(&mut self) -> &mut dyn Buffer {
+        &mut *self
+    }
+
+    fn len(&self) -> usize {
+        self.s
+    }
+
+    fn is_empty(&self) -> bool {
+        self.s == 0
+    }
+
+    fn set_no_access(&self) {
+        if *self.p.borrow() == ProtectState::NoAccess {
+            panic!("already no access... bad logic");
+        }
+        *self.p.borrow_mut() = ProtectState::NoAccess;
+    }
+
+    fn set_readable(&self) {
+        if *self.p.borrow() != ProtectState::NoAccess {
+            panic!("not no access... bad logic");
+        }
+        *self.p.borrow_mut() = ProtectState::ReadOnly;
+    }
+
+    fn set_writable(&self) {
+        if *self.p.borrow() != ProtectState::NoAccess {
+            panic!("not no access... bad logic");
+        }
+        *self.p.borrow_mut() = ProtectState::ReadWrite;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reuses_zeroed_slots_across_size_classes() {
+        let pool = SecurePool::new(64);
+        assert_eq!(64, pool.limit());
+        let mut b = pool.buf_new(40).unwrap();
+        assert_eq!(40, b.len());
+        assert_eq!(64, pool.used());
+        b.write(0, &[42; 40]).unwrap();
+        let z = b.read_lock().as_ptr();
+        drop(b);
+        assert_eq!(0, pool.used());
+
+        let b1 = pool.buf_new(20).unwrap();
+        let b2 = pool.buf_new(32).unwrap();
+        assert_eq!(64, pool.used());
+        assert_eq!(z, b1.read_lock().as_ptr());
+        assert_eq!(&[0; 20][..], &*b1.read_lock());
+        assert_eq!(&[0; 32][..], &*b2.read_lock());
+    }
+
+    #[test]
+    fn it_pools_big_buffers_in_whole_blocks() {
+        let pool = SecurePool::new(3 * MAX_CLASS);
+        let big = pool.buf_new(5000).unwrap();
+        assert_eq!(5000, big.len());
+        assert_eq!(2 * MAX_CLASS, pool.used());
+        let _small = pool.buf_new(8).unwrap();
+        assert_eq!(2 * MAX_CLASS + UNIT, pool.used());
+        drop(big);
+        assert_eq!(UNIT, pool.used());
+    }
+
+    #[test]
+    fn it_errors_when_full() {
+        let pool = SecurePool::new(128);
+        let _b1 = pool.buf_new(32).unwrap();
+        let b2 = pool.buf_new(32).unwrap();
+        let _b3 = pool.buf_new(64).unwrap();
+        assert_eq!(128, pool.used());
+        assert_eq!(CryptoError::OutOfMemory, pool.buf_new(1).unwrap_err());
+
+        // the free unit is not aligned for a 64 bytes slot
+        drop(b2);
+        assert_eq!(CryptoError::OutOfMemory, pool.buf_new(64).unwrap_err());
+        assert!(pool.buf_new(5000).is_err());
+        assert_eq!(96, pool.used());
+    }
+
+    #[test]
+    #[should_panic]
+    fn it_denies_access_outside_of_lockers() {
+        let pool = SecurePool::new(64);
+        let b = pool.buf_new(8).unwrap();
+        let _ = b[0];
+    }
+}
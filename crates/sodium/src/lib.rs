@@ -41,6 +41,13 @@ macro_rules! raw_ptr_char_immut {
     };
 }
 
+/// make invoking ffi functions taking a secretstream state more readable
+macro_rules! raw_ptr_state {
+    ($name: ident) => {
+        $name.as_mut_ptr() as *mut rust_sodium_sys::crypto_secretstream_xchacha20poly1305_state
+    };
+}
+
 /// make invoking ffi functions taking SecBuf references more readable
 macro_rules! raw_ptr_ichar_immut {
     ($name: ident) => {
@@ -59,4 +66,4 @@ pub mod secbuf_util;
 pub mod sign;
 
 mod crypto_system;
-pub use crypto_system::{SecureBuffer, SodiumCryptoSystem};
+pub use crypto_system::{PooledBuffer, SecureBuffer, SecurePool, SodiumCryptoSystem};
@@ -4,8 +4,15 @@ use libc::c_void;
 use std::ops::{Deref, DerefMut};
 
 use super::check_init;
+use crate::crypto_system::PoolSlot;
+use crate::SecurePool;
 use lib3h_crypto_api::CryptoError;
-use std::isize;
+use std::{isize, sync::Mutex};
+
+lazy_static! {
+    /// the pool the SecBuf::with_secure buffers are taken from, if any
+    static ref SECURE_POOL: Mutex<Option<SecurePool>> = Mutex::new(None);
+}
 
 /// a trait for structures that can be used as a backing store for SecBuf
 pub trait Bufferable: Send {
@@ -129,6 +136,57 @@ impl Drop for SodiumBuf {
     }
 }
 
+/// this is a secure buffer in a slot of a SecurePool,
+/// mlocked but never mprotected
+struct PoolBuf {
+    slot: PoolSlot,
+    s: usize,
+}
+
+impl PoolBuf {
+    /// a SodiumBuf instead if the pool is full
+    /// warning: funky sizes may result in mis-alignment
+    fn with_pool(pool: &SecurePool, s: usize) -> Box<Bufferable> {
+        if s % 8 != 0 {
+            panic!("bad buffer size: {}, disallowing this for safety", s);
+        }
+        match pool.slot(s) {
+            Ok(slot) => Box::new(PoolBuf { slot, s }),
+            Err(_) => SodiumBuf::new(s),
+        }
+    }
+}
+
+impl Bufferable for PoolBuf {
+    fn new(s: usize) -> Box<Bufferable> {
+        let pool = SECURE_POOL.lock().unwrap().clone();
+        PoolBuf::with_pool(&pool.expect("no secure pool"), s)
+    }
+
+    fn from_string(s: String) -> Box<Bufferable> {
+        let b = s.into_bytes().into_boxed_slice();
+        Box::new(RustBuf { b })
+    }
+
+    fn len(&self) -> usize {
+        self.s
+    }
+
+    fn readable(&self) {}
+
+    fn writable(&mut self) {}
+
+    fn noaccess(&self) {}
+
+    fn ref_(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.slot.as_ptr(), self.s) }
+    }
+
+    fn ref_mut(&mut self) -> &mut [u8] {
+        unsafe { std::slice::from_raw_parts_mut(self.slot.as_ptr(), self.s) }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq)]
 enum SecurityType {
     Insecure,
@@ -204,15 +262,26 @@ impl SecBuf {
     }
 
     /// create a new SecBuf backed by secure memory (for things like private keys)
+    /// taken from the secure pool if one is set and has room for it
     /// warning: funky sizes may result in mis-alignment
     pub fn with_secure(s: usize) -> Self {
+        let pool = SECURE_POOL.lock().unwrap().clone();
         SecBuf {
             t: SecurityType::Secure,
-            b: SodiumBuf::new(s),
+            b: match pool {
+                Some(pool) => PoolBuf::with_pool(&pool, s),
+                None => SodiumBuf::new(s),
+            },
             p: ProtectState::NoAccess,
         }
     }
 
+    /// take the secure SecBufs from `pool` (mlocked but never mprotected)
+    /// instead of allocating each of them, or stop with `None`
+    pub fn set_secure_pool(pool: Option<SecurePool>) {
+        *SECURE_POOL.lock().unwrap() = pool;
+    }
+
     pub fn with_insecure_from_string(s: String) -> Self {
         SecBuf {
             t: SecurityType::Insecure,
@@ -399,6 +468,49 @@ mod tests {
         }
     }
 
+    #[test]
+    fn it_should_take_secure_from_the_pool() {
+        let pool = SecurePool::new(64);
+        let mut b = SecBuf {
+            t: SecurityType::Secure,
+            b: PoolBuf::with_pool(&pool, 16),
+            p: ProtectState::NoAccess,
+        };
+        assert_eq!(32, pool.used());
+
+        {
+            let mut b = b.write_lock();
+            b[0] = 12;
+        }
+        {
+            let b = b.read_lock();
+            assert_eq!(b[0], 12);
+        }
+        drop(b);
+        assert_eq!(0, pool.used());
+    }
+
+    #[test]
+    fn it_should_allocate_secure_when_the_pool_is_full() {
+        let pool = SecurePool::new(64);
+        let _full = PoolBuf::with_pool(&pool, 64);
+        let mut b = SecBuf {
+            t: SecurityType::Secure,
+            b: PoolBuf::with_pool(&pool, 16),
+            p: ProtectState::NoAccess,
+        };
+        assert_eq!(64, pool.used());
+
+        {
+            let mut b = b.write_lock();
+            b[0] = 12;
+        }
+        {
+            let b = b.read_lock();
+            assert_eq!(b[0], 12);
+        }
+    }
+
     #[test]
     fn it_should_clone_insecure() {
         let mut b = SecBuf::with_insecure(16);
@@ -64,6 +64,13 @@ pub fn dump_lib3h_protocol_as_json_for_n3h() {
         },
     ));
 
+    test_client(Lib3hClientProtocol::SendSignal(SignalData {
+        space_address: "adr".to_string().into(),
+        from_agent_id: "aid".to_string().into(),
+        to_agent_id_list: Some(vec!["aid".to_string().into()]),
+        content: b"yo".to_vec().into(),
+    }));
+
     test_client(Lib3hClientProtocol::FetchEntry(FetchEntryData {
         space_address: "adr".to_string().into(),
         entry_address: "adr".to_string().into(),
@@ -195,6 +202,13 @@ pub fn dump_lib3h_protocol_as_json_for_n3h() {
         },
     ));
 
+    test_server(Lib3hServerProtocol::SignalReceived(SignalData {
+        space_address: "adr".to_string().into(),
+        from_agent_id: "aid".to_string().into(),
+        to_agent_id_list: Some(vec!["aid".to_string().into()]),
+        content: b"yo".to_vec().into(),
+    }));
+
     test_server(Lib3hServerProtocol::FetchEntryResult(
         FetchEntryResultData {
             space_address: "adr".to_string().into(),
@@ -239,6 +253,7 @@ pub fn dump_lib3h_protocol_as_json_for_n3h() {
         space_address: "adr".to_string().into(),
         request_id: "rid".to_string(),
         entry_address: "adr".to_string().into(),
+        aspect_address_list: None,
     }));
 
     test_server(Lib3hServerProtocol::HandleQueryEntry(QueryEntryData {
@@ -14,6 +14,7 @@ for crate in \
  lib3h_protocol \
  mdns \
  p2p_protocol \
+ rust_crypto \
  sodium \
  zombie_actor
 do
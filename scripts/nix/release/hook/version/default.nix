@@ -11,6 +11,7 @@ for dep in \
  lib3h_protocol \
  lib3h_mdns \
  lib3h_p2p_protocol \
+ lib3h_rust_crypto \
  lib3h_sodium \
  lib3h_zombie_actor
 do